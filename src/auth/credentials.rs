@@ -1,44 +1,156 @@
 use crate::error::Error;
 use crate::onepassword::OnePasswordClient;
+use crate::secret::Secret;
 use std::sync::Arc;
-use tokio::runtime::Runtime;
+use tokio::runtime::Handle;
 
 pub trait CredentialManager: Send + Sync {
-    fn get(&self, key: &str) -> Result<String, Error>;
+    fn get(&self, key: &str) -> Result<Secret, Error>;
 }
 
+/// Credentials from `DNS_UPDATE_<KEY>` environment variables, e.g.
+/// `DNS_UPDATE_LINODE_TOKEN`.
+pub struct EnvCredentialManager;
+
+impl EnvCredentialManager {
+    pub fn variable(key: &str) -> String {
+        format!("DNS_UPDATE_{}", key.to_uppercase())
+    }
+}
+
+impl CredentialManager for EnvCredentialManager {
+    fn get(&self, key: &str) -> Result<Secret, Error> {
+        let variable = Self::variable(key);
+        std::env::var(&variable)
+            .map(Secret::new)
+            .map_err(|_| Error::CredentialError(format!("{variable} is not set")))
+    }
+}
+
+/// Tries each manager in order, returning the first credential found.
+pub struct FallbackCredentialManager {
+    managers: Vec<Arc<dyn CredentialManager>>,
+}
+
+impl FallbackCredentialManager {
+    pub fn new(managers: Vec<Arc<dyn CredentialManager>>) -> Self {
+        Self { managers }
+    }
+}
+
+impl CredentialManager for FallbackCredentialManager {
+    fn get(&self, key: &str) -> Result<Secret, Error> {
+        let mut errors = Vec::new();
+        for manager in &self.managers {
+            match manager.get(key) {
+                Ok(value) => return Ok(value),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Err(Error::CredentialError(format!(
+            "{key} unavailable: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+/// Credentials stored as a single field of a 1Password item:
+/// (key, item, field).
+const ITEM_FIELDS: &[(&str, &str, &str)] = &[
+    ("namecheap_api_key", "Namecheap", "api_key"),
+    ("linode_token", "Linode", "token"),
+    ("vultr_api_key", "Vultr", "api_key"),
+    ("duckdns_token", "DuckDNS", "token"),
+    ("noip_username", "No-IP", "username"),
+    ("noip_password", "No-IP", "password"),
+    ("dynu_username", "Dynu", "username"),
+    ("dynu_password", "Dynu", "password"),
+    ("npm_email", "Nginx Proxy Manager", "username"),
+    ("npm_password", "Nginx Proxy Manager", "password"),
+];
+
 /// 1Password-based credential provider
 pub struct OnePasswordCredentialManager {
     client: Arc<OnePasswordClient>,
-    rt: Runtime,
 }
 
 impl OnePasswordCredentialManager {
     pub fn new(client: Arc<OnePasswordClient>) -> Self {
-        let rt = Runtime::new().expect("Failed to create Tokio runtime");
-        Self { client, rt }
+        Self { client }
+    }
+
+    // `get` is synchronous but called from async code; block the current
+    // worker on the CLI call rather than nesting a second runtime.
+    fn block_on<F: std::future::Future>(&self, fut: F) -> F::Output {
+        tokio::task::block_in_place(|| Handle::current().block_on(fut))
     }
 }
 
 impl CredentialManager for OnePasswordCredentialManager {
-    fn get(&self, key: &str) -> Result<String, Error> {
+    fn get(&self, key: &str) -> Result<Secret, Error> {
         match key {
             "nextdns_email" => self
-                .rt
                 .block_on(self.client.get_nextdns_credentials())
-                .map(|c| c.email)
-                .map_err(|e| Error::CredentialError(e.to_string())),
+                .map(|c| Secret::new(c.email))
+                .map_err(Error::from),
             "nextdns_password" => self
-                .rt
                 .block_on(self.client.get_nextdns_credentials())
                 .map(|c| c.password)
-                .map_err(|e| Error::CredentialError(e.to_string())),
+                .map_err(Error::from),
             "nextdns_profile_id" => self
-                .rt
                 .block_on(self.client.get_nextdns_credentials())
-                .map(|c| c.id)
-                .map_err(|e| Error::CredentialError(e.to_string())),
-            _ => Err(Error::CredentialError(format!("Unknown key: {key}"))),
+                .map(|c| Secret::new(c.id))
+                .map_err(Error::from),
+            _ => {
+                let (_, item, field) = ITEM_FIELDS
+                    .iter()
+                    .find(|(k, _, _)| *k == key)
+                    .ok_or_else(|| Error::CredentialError(format!("Unknown key: {key}")))?;
+                self.block_on(self.client.get_field(item, field))
+                    .map(Secret::new)
+                    .map_err(Error::from)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct MapCredentialManager(HashMap<&'static str, &'static str>);
+
+    impl CredentialManager for MapCredentialManager {
+        fn get(&self, key: &str) -> Result<Secret, Error> {
+            self.0
+                .get(key)
+                .map(|v| Secret::new(*v))
+                .ok_or_else(|| Error::CredentialError(format!("no {key}")))
         }
     }
+
+    #[test]
+    fn test_fallback_tries_managers_in_order() {
+        let first = MapCredentialManager(HashMap::from([("linode_token", "first")]));
+        let second = MapCredentialManager(HashMap::from([
+            ("linode_token", "second"),
+            ("vultr_api_key", "vultr"),
+        ]));
+        let creds = FallbackCredentialManager::new(vec![Arc::new(first), Arc::new(second)]);
+        assert_eq!(creds.get("linode_token").unwrap().expose(), "first");
+        assert_eq!(creds.get("vultr_api_key").unwrap().expose(), "vultr");
+        let Err(Error::CredentialError(message)) = creds.get("duckdns_token") else {
+            panic!("expected a credential error");
+        };
+        assert!(message.starts_with("duckdns_token unavailable"));
+    }
+
+    #[test]
+    fn test_env_variable_names() {
+        assert_eq!(
+            EnvCredentialManager::variable("linode_token"),
+            "DNS_UPDATE_LINODE_TOKEN"
+        );
+    }
 }
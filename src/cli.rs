@@ -0,0 +1,807 @@
+//! Command-line interface definitions.
+
+use clap::{Args, Parser, Subcommand};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::core::canary::CanarySize;
+use crate::core::record::DNSRecordType;
+use crate::core::removal::RemovalCheck;
+use crate::report::{DiffFormat, ReportFormat};
+use crate::service::ServiceManager;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Synchronize DNS records across providers")]
+pub struct Cli {
+    /// Path to the TOML configuration file
+    #[arg(long, short, global = true, env = "DNS_UPDATE_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Use the settings of this profile of the configuration file
+    #[arg(long, global = true, env = "DNS_UPDATE_PROFILE")]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Synchronize providers with the desired records
+    Update {
+        /// Read records from a file instead of 1Password
+        #[arg(long, short)]
+        file: Option<PathBuf>,
+
+        /// Only synchronize the named provider
+        #[arg(long, short)]
+        provider: Option<String>,
+
+        #[command(flatten)]
+        tags: TagFilter,
+
+        /// Give up on provider calls after this long, e.g. `120s` or `5m`
+        #[arg(long, value_parser = parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Take over the lock of a run that is gone without releasing it
+        #[arg(long)]
+        force_unlock: bool,
+
+        /// Apply even if the changes exceed the configured limits
+        #[arg(long)]
+        allow_large: bool,
+
+        /// Sync even if the desired records are empty, deleting every
+        /// record in scope
+        #[arg(long)]
+        allow_empty: bool,
+
+        /// How to print the end-of-run statistics
+        #[arg(long, value_enum, default_value_t)]
+        report: ReportFormat,
+
+        /// Only print failures, e.g. when run from cron
+        #[arg(long, short)]
+        quiet: bool,
+
+        #[command(flatten)]
+        canary: CanaryArgs,
+
+        #[command(flatten)]
+        removals: RemovalArgs,
+    },
+    /// Show the changes `update` would make without applying them
+    Plan {
+        /// Read records from a file instead of 1Password
+        #[arg(long, short)]
+        file: Option<PathBuf>,
+
+        /// Only plan the named provider
+        #[arg(long, short)]
+        provider: Option<String>,
+
+        #[command(flatten)]
+        tags: TagFilter,
+
+        /// Give up on provider calls after this long, e.g. `120s` or `5m`
+        #[arg(long, value_parser = parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Plan even if the desired records are empty, deleting every
+        /// record in scope
+        #[arg(long)]
+        allow_empty: bool,
+
+        /// How to print the changes
+        #[arg(long, value_enum, default_value_t)]
+        diff_format: DiffFormat,
+
+        /// Never color the output; also honored through `NO_COLOR`
+        #[arg(long)]
+        no_color: bool,
+
+        /// Also save the changes to this file, for `apply --plan`
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Apply a plan saved by `plan --out`, unless the records of a provider
+    /// changed since
+    Apply {
+        /// Plan file written by `plan --out`
+        #[arg(long)]
+        plan: PathBuf,
+
+        /// Give up on provider calls after this long, e.g. `120s` or `5m`
+        #[arg(long, value_parser = parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Take over the lock of a run that is gone without releasing it
+        #[arg(long)]
+        force_unlock: bool,
+
+        /// Apply even if the changes exceed the configured limits
+        #[arg(long)]
+        allow_large: bool,
+
+        /// How to print the end-of-run statistics
+        #[arg(long, value_enum, default_value_t)]
+        report: ReportFormat,
+
+        /// Only print failures
+        #[arg(long, short)]
+        quiet: bool,
+    },
+    /// Point dynamic DNS hostnames at new addresses
+    Ddns {
+        /// Address to publish; repeat for IPv4 and IPv6
+        #[arg(long, required_unless_present = "ip_from")]
+        ip: Vec<IpAddr>,
+
+        /// Publish the address a local interface has in this subnet, e.g.
+        /// `10.0.0.0/24`; repeat for several
+        #[arg(long, value_name = "CIDR")]
+        ip_from: Vec<IpNet>,
+
+        /// Only update the named dynamic DNS entry
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+    /// Create and remove ACME DNS-01 challenge records, e.g. from certbot
+    /// or lego hooks
+    Acme {
+        #[command(subcommand)]
+        command: AcmeCommand,
+    },
+    /// List the records two providers disagree on, without desired
+    /// records, e.g. to check a migration from one to the other
+    Compare {
+        /// Provider to compare
+        #[arg(long)]
+        from: String,
+
+        /// Provider to compare it with
+        #[arg(long)]
+        to: String,
+
+        /// Give up on provider calls after this long, e.g. `120s` or `5m`
+        #[arg(long, value_parser = parse_duration)]
+        timeout: Option<Duration>,
+    },
+    /// Copy the records of one provider to another
+    Migrate {
+        /// Provider to copy records from
+        #[arg(long)]
+        from: String,
+
+        /// Provider to copy them to
+        #[arg(long)]
+        to: String,
+
+        /// Only copy records whose name matches this pattern, e.g.
+        /// `*.lab.example.com`; repeat for several
+        #[arg(long = "name", value_name = "PATTERN")]
+        names: Vec<String>,
+
+        /// Only copy records of this type; repeat for several
+        #[arg(long = "type", value_name = "TYPE")]
+        types: Vec<DNSRecordType>,
+
+        /// Delete the records from the source once the target holds them
+        #[arg(long)]
+        delete_source: bool,
+
+        /// Print the changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Give up on provider calls after this long, e.g. `120s` or `5m`
+        #[arg(long, value_parser = parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Take over the lock of a run that is gone without releasing it
+        #[arg(long)]
+        force_unlock: bool,
+
+        /// Apply even if the changes exceed the configured limits
+        #[arg(long)]
+        allow_large: bool,
+
+        /// How to print the end-of-run statistics
+        #[arg(long, value_enum, default_value_t)]
+        report: ReportFormat,
+
+        /// Only print failures
+        #[arg(long, short)]
+        quiet: bool,
+    },
+    /// Combine records files, e.g. to compose the desired records from
+    /// fragments
+    Records {
+        #[command(subcommand)]
+        command: RecordsCommand,
+    },
+    /// Keep providers in sync, reconciling on an interval
+    Daemon(DaemonArgs),
+    /// Serve the external-dns webhook provider API for one provider
+    Webhook {
+        /// Provider external-dns manages records in
+        #[arg(long, short)]
+        provider: String,
+
+        /// Address to listen on
+        #[arg(long, default_value = crate::webhook::DEFAULT_LISTEN)]
+        listen: SocketAddr,
+    },
+    /// Inspect configured providers
+    Providers {
+        #[command(subcommand)]
+        command: ProvidersCommand,
+    },
+    /// Run the daemon as a systemd or launchd service
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
+    },
+    /// Work with the configuration file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the JSON Schema of the configuration file
+    Schema,
+    /// Check the configuration file, listing every mistake in it
+    Check,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RecordsCommand {
+    /// Records of either file
+    Union(RecordFiles),
+    /// Records of the first file that the second also has
+    Intersect(RecordFiles),
+    /// Records of the first file that the second does not have
+    Subtract(RecordFiles),
+}
+
+#[derive(Args, Debug)]
+pub struct RecordFiles {
+    /// Rewrites file, or a file in one of the imported formats
+    pub first: PathBuf,
+
+    /// The file combined with the first
+    pub second: PathBuf,
+
+    /// Write the resulting rewrites file here instead of printing it
+    #[arg(long, short)]
+    pub out: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub tags: TagFilter,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProvidersCommand {
+    /// List configured providers
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ServiceCommand {
+    /// Register the daemon with the service manager and start it
+    Install {
+        #[command(flatten)]
+        target: ServiceTarget,
+
+        /// Copy this environment variable into the service definition;
+        /// repeat for several
+        #[arg(long = "env", value_name = "NAME")]
+        env: Vec<String>,
+
+        /// Print the service definition instead of installing it
+        #[arg(long)]
+        print: bool,
+
+        #[command(flatten)]
+        daemon: Box<DaemonArgs>,
+    },
+    /// Stop the daemon and remove it from the service manager
+    Uninstall {
+        #[command(flatten)]
+        target: ServiceTarget,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct ServiceTarget {
+    /// Service manager; systemd on Linux and launchd on macOS by default
+    #[arg(long, value_enum)]
+    pub manager: Option<ServiceManager>,
+
+    /// Install for the current user instead of system-wide
+    #[arg(long)]
+    pub user: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AcmeCommand {
+    /// Publish a challenge record and wait until it resolves
+    Present {
+        #[command(flatten)]
+        challenge: Challenge,
+
+        /// How long to wait for the record to resolve; `0` skips the check
+        #[arg(long, value_parser = parse_duration, default_value = "2m")]
+        wait: Duration,
+
+        /// Check propagation against this DNS server instead of the
+        /// configured resolver; repeat for several
+        #[arg(long)]
+        resolver: Vec<IpAddr>,
+    },
+    /// Remove a challenge record
+    Cleanup {
+        #[command(flatten)]
+        challenge: Challenge,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DaemonArgs {
+    /// Read records from a file instead of 1Password
+    #[arg(long, short)]
+    pub file: Option<PathBuf>,
+
+    /// Only synchronize the named provider
+    #[arg(long, short)]
+    pub provider: Option<String>,
+
+    #[command(flatten)]
+    pub tags: TagFilter,
+
+    /// Time between runs, e.g. `5m`
+    #[arg(long, value_parser = parse_duration, default_value = "5m")]
+    pub interval: Duration,
+
+    /// Give up on a run's provider calls after this long
+    #[arg(long, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Sync even if the desired records are empty, deleting every record
+    /// in scope
+    #[arg(long)]
+    pub allow_empty: bool,
+
+    /// Serve the control API on this address, e.g. `127.0.0.1:8889`
+    #[arg(long)]
+    pub control: Option<SocketAddr>,
+
+    /// Require this bearer token on control API requests; needed to serve
+    /// it on an address other than loopback
+    #[arg(
+        long,
+        env = "DNS_UPDATE_CONTROL_TOKEN",
+        hide_env_values = true,
+        requires = "control"
+    )]
+    pub control_token: Option<String>,
+
+    /// Log the events received through the control API to this file
+    #[arg(long)]
+    pub journal: Option<PathBuf>,
+
+    /// Apply the journaled events again on startup, restoring added hosts
+    #[arg(long, requires = "journal")]
+    pub replay: bool,
+}
+
+impl DaemonArgs {
+    /// The `daemon` arguments reproducing these options.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(file) = &self.file {
+            args.extend(["--file".to_string(), file.display().to_string()]);
+        }
+        if let Some(provider) = &self.provider {
+            args.extend(["--provider".to_string(), provider.clone()]);
+        }
+        args.extend(self.tags.to_args());
+        args.extend(["--interval".to_string(), format_duration(self.interval)]);
+        if let Some(timeout) = self.timeout {
+            args.extend(["--timeout".to_string(), format_duration(timeout)]);
+        }
+        if self.allow_empty {
+            args.push("--allow-empty".to_string());
+        }
+        // The control token is left out, to be passed in the environment
+        // rather than show up in the process list
+        if let Some(control) = self.control {
+            args.extend(["--control".to_string(), control.to_string()]);
+        }
+        if let Some(journal) = &self.journal {
+            args.extend(["--journal".to_string(), journal.display().to_string()]);
+        }
+        if self.replay {
+            args.push("--replay".to_string());
+        }
+        args
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CanaryArgs {
+    /// Apply this many changes of each provider first, e.g. `5` or `10%`,
+    /// and only apply the rest once they resolve
+    #[arg(long)]
+    pub canary: Option<CanarySize>,
+
+    /// Check canary records against this DNS server instead of the
+    /// configured resolver; repeat for several
+    #[arg(long, requires = "canary")]
+    pub canary_resolver: Vec<IpAddr>,
+
+    /// How long canary records may take to resolve
+    #[arg(long, value_parser = parse_duration, default_value = "2m")]
+    pub canary_timeout: Duration,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RemovalArgs {
+    /// Before deleting records, look up whether managed CNAMEs still
+    /// resolve through them, and warn about or block such deletions
+    #[arg(long, value_enum)]
+    pub check_removals: Option<RemovalCheck>,
+
+    /// Look dependent records up through this DNS server instead of the
+    /// configured resolver; repeat for several
+    #[arg(long, requires = "check_removals")]
+    pub removal_resolver: Vec<IpAddr>,
+}
+
+/// Which records to sync, by the `@tag` words of their lines.
+#[derive(Args, Debug, Clone, Default)]
+pub struct TagFilter {
+    /// Only sync untagged records and those with one of these tags,
+    /// e.g. `home,vpn`
+    #[arg(long, value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// Only sync records with one of these tags, leaving out untagged ones
+    #[arg(long, value_delimiter = ',')]
+    pub only_tag: Vec<String>,
+
+    /// Leave out records with one of these tags
+    #[arg(long, value_delimiter = ',')]
+    pub skip_tag: Vec<String>,
+}
+
+impl TagFilter {
+    /// Whether a record with `tags` is synced. Untagged records belong to
+    /// every network, but not to an `--only-tag` subset.
+    pub fn selects(&self, tags: &[&str]) -> bool {
+        let any_of = |selected: &[String]| tags.iter().any(|t| selected.iter().any(|s| s == t));
+        let network = tags.is_empty() || self.tags.is_empty() || any_of(&self.tags);
+        let subset = self.only_tag.is_empty() || any_of(&self.only_tag);
+        network && subset && !any_of(&self.skip_tag)
+    }
+
+    /// The arguments reproducing this filter.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (flag, tags) in [
+            ("--tags", &self.tags),
+            ("--only-tag", &self.only_tag),
+            ("--skip-tag", &self.skip_tag),
+        ] {
+            if !tags.is_empty() {
+                args.extend([flag.to_string(), tags.join(",")]);
+            }
+        }
+        args
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Challenge {
+    /// Provider holding the zone of the domain
+    #[arg(long, short)]
+    pub provider: String,
+
+    /// Domain being validated
+    #[arg(long, env = "CERTBOT_DOMAIN")]
+    pub domain: String,
+
+    /// Validation token to publish
+    #[arg(long, env = "CERTBOT_VALIDATION")]
+    pub value: String,
+}
+
+/// Parse a duration such as `90`, `90s`, `500ms`, `5m` or `1h`; plain numbers
+/// are seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration: {s}"))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "" | "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        "h" => Ok(Duration::from_secs(value * 3600)),
+        _ => Err(format!("invalid duration unit: {unit}")),
+    }
+}
+
+// The inverse of `parse_duration`
+fn format_duration(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_update_with_file() {
+        let cli = Cli::parse_from(["dns-update", "update", "--file", "records.txt"]);
+        match cli.command {
+            Command::Update { file, provider, .. } => {
+                assert_eq!(file, Some(PathBuf::from("records.txt")));
+                assert_eq!(provider, None);
+            }
+            _ => panic!("expected update command"),
+        }
+    }
+
+    #[test]
+    fn test_allow_large_leaves_locks_alone() {
+        let cli = Cli::parse_from(["dns-update", "update", "--allow-large"]);
+        match cli.command {
+            Command::Update {
+                force_unlock,
+                allow_large,
+                ..
+            } => assert!(allow_large && !force_unlock),
+            _ => panic!("expected update command"),
+        }
+        assert!(Cli::try_parse_from(["dns-update", "update", "--force"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_check_removals() {
+        let cli = Cli::parse_from([
+            "dns-update",
+            "update",
+            "--check-removals",
+            "block",
+            "--removal-resolver",
+            "10.0.0.53",
+        ]);
+        match cli.command {
+            Command::Update { removals, .. } => {
+                assert_eq!(removals.check_removals, Some(RemovalCheck::Block));
+                assert_eq!(removals.removal_resolver.len(), 1);
+            }
+            _ => panic!("expected update command"),
+        }
+        assert!(
+            Cli::try_parse_from(["dns-update", "update", "--removal-resolver", "10.0.0.53"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_daemon_args_round_trip() {
+        let cli = Cli::parse_from([
+            "dns-update",
+            "daemon",
+            "--tags",
+            "home,vpn",
+            "--interval",
+            "90s",
+            "--timeout",
+            "1500ms",
+            "--allow-empty",
+            "--control",
+            "127.0.0.1:8889",
+        ]);
+        let Command::Daemon(daemon) = cli.command else {
+            panic!("expected daemon command");
+        };
+        let args = daemon.to_args();
+        assert_eq!(
+            args,
+            [
+                "--tags",
+                "home,vpn",
+                "--interval",
+                "90s",
+                "--timeout",
+                "1500ms",
+                "--allow-empty",
+                "--control",
+                "127.0.0.1:8889"
+            ]
+        );
+        let again = Cli::parse_from(
+            ["dns-update", "daemon"]
+                .into_iter()
+                .chain(args.iter().map(String::as_str)),
+        );
+        let Command::Daemon(again) = again.command else {
+            panic!("expected daemon command");
+        };
+        assert_eq!(again.to_args(), args);
+    }
+
+    #[test]
+    fn test_parse_ddns_addresses() {
+        let cli = Cli::parse_from(["dns-update", "ddns", "--ip", "1.2.3.4", "--ip", "::1"]);
+        match cli.command {
+            Command::Ddns { ip, ip_from, name } => {
+                assert_eq!(ip.len(), 2);
+                assert!(ip[1].is_ipv6());
+                assert!(ip_from.is_empty());
+                assert_eq!(name, None);
+            }
+            _ => panic!("expected ddns command"),
+        }
+        assert!(Cli::try_parse_from(["dns-update", "ddns", "--ip", "nope"]).is_err());
+        assert!(Cli::try_parse_from(["dns-update", "ddns"]).is_err());
+        let cli = Cli::parse_from(["dns-update", "ddns", "--ip-from", "10.0.0.0/24"]);
+        assert!(matches!(cli.command, Command::Ddns { ip_from, .. } if ip_from.len() == 1));
+    }
+
+    #[test]
+    fn test_parse_migrate_filter() {
+        let cli = Cli::parse_from([
+            "dns-update",
+            "migrate",
+            "--from",
+            "nextdns",
+            "--to",
+            "edge",
+            "--type",
+            "A",
+            "--type",
+            "AAAA",
+        ]);
+        let Command::Migrate { types, names, .. } = cli.command else {
+            panic!("expected migrate command");
+        };
+        assert_eq!(types, [DNSRecordType::A, DNSRecordType::AAAA]);
+        assert!(names.is_empty());
+        let unknown = [
+            "dns-update",
+            "migrate",
+            "--from",
+            "a",
+            "--to",
+            "b",
+            "--type",
+            "MX",
+        ];
+        assert!(Cli::try_parse_from(unknown).is_err());
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        let cli = Cli::parse_from(["dns-update", "update", "--timeout", "2m"]);
+        match cli.command {
+            Command::Update { timeout, .. } => assert_eq!(timeout, Some(Duration::from_secs(120))),
+            _ => panic!("expected update command"),
+        }
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let cli = Cli::parse_from(["dns-update", "update", "--tags", "home,vpn"]);
+        match cli.command {
+            Command::Update { tags, .. } => assert_eq!(tags.tags, vec!["home", "vpn"]),
+            _ => panic!("expected update command"),
+        }
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let cli = Cli::parse_from([
+            "dns-update",
+            "plan",
+            "--only-tag",
+            "media",
+            "--skip-tag",
+            "critical",
+        ]);
+        let Command::Plan { tags, .. } = cli.command else {
+            panic!("expected plan command");
+        };
+        assert!(tags.selects(&["media"]));
+        assert!(!tags.selects(&[]));
+        assert!(!tags.selects(&["media", "critical"]));
+        assert!(!tags.selects(&["home"]));
+        assert_eq!(
+            tags.to_args(),
+            ["--only-tag", "media", "--skip-tag", "critical"]
+        );
+
+        let skip = TagFilter {
+            skip_tag: vec!["critical".to_string()],
+            ..TagFilter::default()
+        };
+        assert!(skip.selects(&[]));
+        assert!(skip.selects(&["media"]));
+        assert!(!skip.selects(&["critical"]));
+    }
+
+    #[test]
+    fn test_parse_plan_format() {
+        let cli = Cli::parse_from(["dns-update", "plan", "--diff-format", "json", "--no-color"]);
+        match cli.command {
+            Command::Plan {
+                diff_format,
+                no_color,
+                ..
+            } => {
+                assert_eq!(diff_format, DiffFormat::Json);
+                assert!(no_color);
+            }
+            _ => panic!("expected plan command"),
+        }
+        assert!(Cli::try_parse_from(["dns-update", "plan", "--diff-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_acme_present() {
+        let cli = Cli::parse_from([
+            "dns-update",
+            "acme",
+            "present",
+            "--provider",
+            "linode",
+            "--domain",
+            "example.com",
+            "--value",
+            "token",
+            "--resolver",
+            "1.1.1.1",
+        ]);
+        match cli.command {
+            Command::Acme {
+                command:
+                    AcmeCommand::Present {
+                        challenge,
+                        wait,
+                        resolver,
+                    },
+            } => {
+                assert_eq!(challenge.provider, "linode");
+                assert_eq!(challenge.domain, "example.com");
+                assert_eq!(wait, Duration::from_secs(120));
+                assert_eq!(resolver.len(), 1);
+            }
+            _ => panic!("expected acme present command"),
+        }
+    }
+}
@@ -0,0 +1,719 @@
+//! Configuration file loading.
+//!
+//! Without a configuration file the tool behaves as before: a single NextDNS
+//! provider whose credentials come from 1Password.
+
+use ipnet::IpNet;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::core::cache::CacheConfig;
+use crate::core::checkpoint::CheckpointConfig;
+use crate::core::conflict::Conflicts;
+use crate::core::http::HttpConfig;
+use crate::core::limits::Limits;
+use crate::core::normalize::Normalize;
+use crate::core::ownership::Ownership;
+use crate::core::protect::Protect;
+use crate::core::resolver::LookupConfig;
+use crate::core::scope::Scope;
+use crate::core::template::PublicIpConfig;
+use crate::daemon::caddy::CaddyConfig;
+use crate::daemon::mdns::MdnsConfig;
+use crate::daemon::nginx_proxy_manager::NginxProxyManagerConfig;
+use crate::daemon::traefik::TraefikConfig;
+use crate::encrypted::DecryptionConfig;
+use crate::error::Error;
+use crate::providers::nextdns::types::NextDNSSettings;
+use crate::schema;
+use crate::signature::SignatureConfig;
+
+pub const DEFAULT_VAULT: &str = "Applications";
+pub const DEFAULT_NEXTDNS_API_URL: &str = "https://api.nextdns.io";
+pub const DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE: u32 = 120;
+pub const DEFAULT_NAMECHEAP_API_URL: &str = "https://api.namecheap.com/xml.response";
+pub const DEFAULT_LINODE_API_URL: &str = "https://api.linode.com/v4";
+pub const DEFAULT_VULTR_API_URL: &str = "https://api.vultr.com/v2";
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// 1Password vault holding credentials and DNS rewrites
+    #[serde(default = "default_vault")]
+    pub vault: String,
+
+    /// Providers keyed by a user-chosen name
+    #[serde(default = "default_providers")]
+    pub providers: BTreeMap<String, ProviderConfig>,
+
+    /// Proxy, certificate and timeout settings for API providers
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Only records whose names match one of these patterns are synchronized
+    #[serde(default)]
+    pub scope: Scope,
+
+    /// Dynamic DNS services updated by `dns-update ddns`
+    #[serde(default)]
+    pub ddns: BTreeMap<String, DdnsConfig>,
+
+    /// Local cache of provider listings; disabled when omitted
+    pub cache: Option<CacheConfig>,
+
+    /// Chunked applies that resume after an interruption; disabled when
+    /// omitted
+    pub checkpoint: Option<CheckpointConfig>,
+
+    /// Directory holding run locks; the system temporary directory when omitted
+    pub lock_dir: Option<PathBuf>,
+
+    /// Last-known-good copy of the 1Password rewrites, refreshed after each
+    /// fetch and read when 1Password is unavailable
+    pub rewrites_fallback: Option<PathBuf>,
+
+    /// Keys for desired-records files encrypted with SOPS or age
+    #[serde(default)]
+    pub decryption: DecryptionConfig,
+
+    /// Key the desired records must be signed with; unsigned records are
+    /// refused. Not checked when omitted
+    pub signature: Option<SignatureConfig>,
+
+    /// CNAME records with these names are published as the A and AAAA
+    /// records of their target; disabled when omitted
+    pub flatten: Option<Scope>,
+
+    /// Where records are looked up, e.g. by canary checks and CNAME flattening
+    #[serde(default)]
+    pub resolver: LookupConfig,
+
+    /// Where the public addresses filling in `{{ public_ipv4 }}` and
+    /// `{{ public_ipv6 }}` in the desired records are looked up
+    #[serde(default)]
+    pub public_ip: PublicIpConfig,
+
+    /// Rules applied to the desired records before planning
+    #[serde(default)]
+    pub normalize: Normalize,
+
+    /// What to do when a desired record conflicts with a provider record of
+    /// another type, like an A record with a CNAME
+    #[serde(default)]
+    pub conflicts: Conflicts,
+
+    /// Records that are never deleted or modified, whatever the desired
+    /// records say
+    #[serde(default)]
+    pub protect: Protect,
+
+    /// How much one run may change at a provider before it needs `--force`
+    #[serde(default)]
+    pub limits: Limits,
+
+    /// Traefik instance whose routers the daemon adds hosts for; disabled
+    /// when omitted
+    pub traefik: Option<TraefikConfig>,
+
+    /// Caddy server whose routes the daemon adds hosts for; disabled when
+    /// omitted
+    pub caddy: Option<CaddyConfig>,
+
+    /// Nginx Proxy Manager instance whose proxy hosts the daemon adds hosts
+    /// for; disabled when omitted
+    pub nginx_proxy_manager: Option<NginxProxyManagerConfig>,
+
+    /// Also advertise the desired records on the LAN over mDNS while the
+    /// daemon runs; disabled when omitted
+    pub mdns: Option<MdnsConfig>,
+
+    /// Named sets of settings selected with `--profile`, each replacing the
+    /// top-level settings of the same name
+    #[serde(default, rename = "profile")]
+    #[schemars(with = "BTreeMap<String, serde_json::Map<String, serde_json::Value>>")]
+    pub profiles: BTreeMap<String, toml::Table>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ProviderConfig {
+    #[serde(rename = "nextdns")]
+    NextDNS {
+        /// Profile ID; read from 1Password when omitted
+        profile_id: Option<String>,
+        #[serde(default = "default_nextdns_api_url")]
+        api_url: String,
+        /// API requests allowed per minute, shared by all endpoints
+        #[serde(default = "default_nextdns_requests_per_minute")]
+        requests_per_minute: u32,
+        /// Profile settings managed along with the rewrites
+        #[serde(default)]
+        settings: NextDNSSettings,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+    /// Namecheap; the API key is read from 1Password
+    Namecheap {
+        /// Registered domain, e.g. `example.com`
+        domain: String,
+        api_user: String,
+        /// Account owning the domain; defaults to `api_user`
+        username: Option<String>,
+        /// Whitelisted IP address requests are made from
+        client_ip: String,
+        #[serde(default = "default_namecheap_api_url")]
+        api_url: String,
+        /// How records created by this tool are recognized; `txt` markers
+        /// by default, `zone` if the tool manages the whole zone
+        #[serde(default)]
+        ownership: Ownership,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+    /// Linode Domains; the API token is read from 1Password
+    Linode {
+        /// Zone name, e.g. `example.com`
+        domain: String,
+        #[serde(default = "default_linode_api_url")]
+        api_url: String,
+        /// How records created by this tool are recognized; `txt` markers
+        /// by default, `zone` if the tool manages the whole zone
+        #[serde(default)]
+        ownership: Ownership,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+    /// Vultr DNS; the API key is read from 1Password
+    Vultr {
+        /// Zone name, e.g. `example.com`
+        domain: String,
+        #[serde(default = "default_vultr_api_url")]
+        api_url: String,
+        /// How records created by this tool are recognized; `txt` markers
+        /// by default, `zone` if the tool manages the whole zone
+        #[serde(default)]
+        ownership: Ownership,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+    /// hosts(5) file, also usable as a dnsmasq `addn-hosts` file
+    Hosts {
+        path: PathBuf,
+        reload: Option<ReloadConfig>,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+    /// dnsmasq configuration snippet with `address=` and `cname=` lines
+    Dnsmasq {
+        path: PathBuf,
+        reload: Option<ReloadConfig>,
+        /// Publish PTR records for A and AAAA records in these prefixes
+        #[serde(default)]
+        #[schemars(with = "Vec<String>")]
+        reverse: Vec<IpNet>,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+    /// Unbound configuration snippet with `local-data:` lines
+    Unbound {
+        path: PathBuf,
+        reload: Option<ReloadConfig>,
+        /// Publish PTR records for A and AAAA records in these prefixes
+        #[serde(default)]
+        #[schemars(with = "Vec<String>")]
+        reverse: Vec<IpNet>,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+    /// Zone file for CoreDNS's `file` plugin
+    Zone {
+        path: PathBuf,
+        /// Zone origin, e.g. `lab.example.com`
+        zone: String,
+        /// Primary nameserver for the SOA and NS records; `ns.<zone>` by default
+        nameserver: Option<String>,
+        /// TTL for records without one
+        ttl: Option<u32>,
+        reload: Option<ReloadConfig>,
+        /// Publish PTR records for A and AAAA records in these prefixes
+        #[serde(default)]
+        #[schemars(with = "Vec<String>")]
+        reverse: Vec<IpNet>,
+        /// Only compare with the desired records, reporting drift without
+        /// changing anything
+        #[serde(default)]
+        read_only: bool,
+    },
+}
+
+/// Update-URL style dynamic DNS services; credentials come from 1Password.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum DdnsConfig {
+    #[serde(rename = "duckdns")]
+    DuckDNS {
+        /// Subdomain, with or without the `.duckdns.org` suffix
+        hostname: String,
+        #[serde(default = "default_duckdns_update_url")]
+        update_url: String,
+    },
+    #[serde(rename = "noip")]
+    NoIP {
+        hostname: String,
+        #[serde(default = "default_noip_update_url")]
+        update_url: String,
+    },
+    Dynu {
+        hostname: String,
+        #[serde(default = "default_dynu_update_url")]
+        update_url: String,
+    },
+}
+
+/// How to make a resolver pick up a rewritten file.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ReloadConfig {
+    /// Send SIGHUP to the process whose PID is stored in `pid_file`
+    Signal { pid_file: PathBuf },
+    /// Run a command, e.g. `["unbound-control", "reload"]`
+    Command { command: Vec<String> },
+}
+
+impl ProviderConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProviderConfig::NextDNS { .. } => "nextdns",
+            ProviderConfig::Namecheap { .. } => "namecheap",
+            ProviderConfig::Linode { .. } => "linode",
+            ProviderConfig::Vultr { .. } => "vultr",
+            ProviderConfig::Hosts { .. } => "hosts",
+            ProviderConfig::Dnsmasq { .. } => "dnsmasq",
+            ProviderConfig::Unbound { .. } => "unbound",
+            ProviderConfig::Zone { .. } => "zone",
+        }
+    }
+
+    /// Whether runs only report drift at this provider.
+    pub fn read_only(&self) -> bool {
+        match self {
+            ProviderConfig::NextDNS { read_only, .. }
+            | ProviderConfig::Namecheap { read_only, .. }
+            | ProviderConfig::Linode { read_only, .. }
+            | ProviderConfig::Vultr { read_only, .. }
+            | ProviderConfig::Hosts { read_only, .. }
+            | ProviderConfig::Dnsmasq { read_only, .. }
+            | ProviderConfig::Unbound { read_only, .. }
+            | ProviderConfig::Zone { read_only, .. } => *read_only,
+        }
+    }
+
+    /// Prefixes whose addresses get PTR records at this provider.
+    pub fn reverse(&self) -> &[IpNet] {
+        match self {
+            ProviderConfig::Dnsmasq { reverse, .. }
+            | ProviderConfig::Unbound { reverse, .. }
+            | ProviderConfig::Zone { reverse, .. } => reverse,
+            _ => &[],
+        }
+    }
+}
+
+impl DdnsConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DdnsConfig::DuckDNS { .. } => "duckdns",
+            DdnsConfig::NoIP { .. } => "noip",
+            DdnsConfig::Dynu { .. } => "dynu",
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path, profile: Option<&str>) -> Result<Self, Error> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| Error::InvalidInput(format!("{}: {e}", path.display())))?;
+        match profile {
+            Some(profile) => Self::parse_profile(&raw, profile),
+            None => Self::parse(&raw),
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, Error> {
+        schema::validate(raw)?;
+        let config: Config = toml::from_str(raw).map_err(|e| Error::InvalidInput(e.to_string()))?;
+        Ok(config.with_default_providers())
+    }
+
+    /// Parse `raw` with the settings of the named profile in place of the
+    /// top-level settings of the same name.
+    pub fn parse_profile(raw: &str, profile: &str) -> Result<Self, Error> {
+        // Parsed as is first, so mistakes outside the profile point at their line
+        let mut config = Self::parse(raw)?;
+        let settings = config
+            .profiles
+            .remove(profile)
+            .ok_or_else(|| Error::InvalidInput(format!("Unknown profile: {profile}")))?;
+        let mut table: toml::Table =
+            toml::from_str(raw).map_err(|e| Error::InvalidInput(e.to_string()))?;
+        table.remove("profile");
+        table.extend(settings);
+        let config: Config = table
+            .try_into()
+            .map_err(|e| Error::InvalidInput(format!("profile {profile}: {e}")))?;
+        Ok(config.with_default_providers())
+    }
+
+    fn with_default_providers(mut self) -> Self {
+        if self.providers.is_empty() {
+            self.providers = default_providers();
+        }
+        self
+    }
+
+    pub fn lock_dir(&self) -> PathBuf {
+        self.lock_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            vault: default_vault(),
+            providers: default_providers(),
+            http: HttpConfig::default(),
+            scope: Scope::default(),
+            ddns: BTreeMap::new(),
+            cache: None,
+            checkpoint: None,
+            lock_dir: None,
+            rewrites_fallback: None,
+            decryption: DecryptionConfig::default(),
+            signature: None,
+            flatten: None,
+            resolver: LookupConfig::default(),
+            public_ip: PublicIpConfig::default(),
+            normalize: Normalize::default(),
+            conflicts: Conflicts::default(),
+            protect: Protect::default(),
+            limits: Limits::default(),
+            traefik: None,
+            caddy: None,
+            nginx_proxy_manager: None,
+            mdns: None,
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_vault() -> String {
+    DEFAULT_VAULT.to_string()
+}
+
+fn default_nextdns_requests_per_minute() -> u32 {
+    DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE
+}
+
+fn default_nextdns_api_url() -> String {
+    DEFAULT_NEXTDNS_API_URL.to_string()
+}
+
+fn default_namecheap_api_url() -> String {
+    DEFAULT_NAMECHEAP_API_URL.to_string()
+}
+
+fn default_linode_api_url() -> String {
+    DEFAULT_LINODE_API_URL.to_string()
+}
+
+fn default_vultr_api_url() -> String {
+    DEFAULT_VULTR_API_URL.to_string()
+}
+
+fn default_duckdns_update_url() -> String {
+    crate::providers::dyndns::DUCKDNS_UPDATE_URL.to_string()
+}
+
+fn default_noip_update_url() -> String {
+    crate::providers::dyndns::NOIP_UPDATE_URL.to_string()
+}
+
+fn default_dynu_update_url() -> String {
+    crate::providers::dyndns::DYNU_UPDATE_URL.to_string()
+}
+
+fn default_providers() -> BTreeMap<String, ProviderConfig> {
+    BTreeMap::from([(
+        "nextdns".to_string(),
+        ProviderConfig::NextDNS {
+            profile_id: None,
+            api_url: default_nextdns_api_url(),
+            requests_per_minute: default_nextdns_requests_per_minute(),
+            settings: NextDNSSettings::default(),
+            read_only: false,
+        },
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::conflict::ConflictPolicy;
+    use crate::secret::Secret;
+
+    #[test]
+    fn test_default_config_uses_nextdns() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.vault, DEFAULT_VAULT);
+        assert_eq!(config.providers.len(), 1);
+        assert_eq!(config.providers["nextdns"].kind(), "nextdns");
+    }
+
+    #[test]
+    fn test_profiles_replace_top_level_settings() {
+        let raw = r#"
+            vault = "Homelab"
+            lock_dir = "/run/dns-update"
+
+            [providers.lan]
+            type = "hosts"
+            path = "/etc/hosts.d/homelab"
+
+            [profile.work]
+            vault = "Work"
+
+            [profile.work.providers.office]
+            type = "dnsmasq"
+            path = "/etc/dnsmasq.d/office.conf"
+        "#;
+        let config = Config::parse(raw).unwrap();
+        assert_eq!(config.vault, "Homelab");
+        assert!(config.providers.contains_key("lan"));
+
+        let work = Config::parse_profile(raw, "work").unwrap();
+        assert_eq!(work.vault, "Work");
+        assert_eq!(work.lock_dir, Some(PathBuf::from("/run/dns-update")));
+        assert_eq!(work.providers.keys().collect::<Vec<_>>(), ["office"]);
+        assert!(work.profiles.is_empty());
+
+        assert!(Config::parse_profile(raw, "home").is_err());
+        let bad = format!("{raw}\n[profile.bad]\nvault = 1\n");
+        assert!(Config::parse_profile(&bad, "bad").is_err());
+    }
+
+    #[test]
+    fn test_parse_multiple_providers() {
+        let raw = r#"
+            vault = "Homelab"
+
+            [providers.cloud]
+            type = "nextdns"
+            profile_id = "abc123"
+
+            [providers.lan]
+            type = "hosts"
+            path = "/etc/hosts.d/homelab"
+        "#;
+        let config = Config::parse(raw).unwrap();
+        assert_eq!(config.vault, "Homelab");
+        match &config.providers["cloud"] {
+            ProviderConfig::NextDNS {
+                profile_id,
+                api_url,
+                requests_per_minute,
+                settings,
+                read_only,
+            } => {
+                assert!(!read_only);
+                assert_eq!(profile_id.as_deref(), Some("abc123"));
+                assert_eq!(api_url, DEFAULT_NEXTDNS_API_URL);
+                assert_eq!(*requests_per_minute, DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE);
+                assert_eq!(*settings, NextDNSSettings::default());
+            }
+            other => panic!("unexpected provider: {other:?}"),
+        }
+        assert_eq!(config.providers["lan"].kind(), "hosts");
+    }
+
+    #[test]
+    fn test_parse_reload_options() {
+        let raw = r#"
+            [providers.dnsmasq]
+            type = "dnsmasq"
+            path = "/etc/dnsmasq.d/homelab.conf"
+            reload = { pid_file = "/run/dnsmasq.pid" }
+
+            [providers.unbound]
+            type = "unbound"
+            path = "/etc/unbound/unbound.conf.d/homelab.conf"
+            reload = { command = ["unbound-control", "reload"] }
+        "#;
+        let config = Config::parse(raw).unwrap();
+        assert!(matches!(
+            &config.providers["dnsmasq"],
+            ProviderConfig::Dnsmasq {
+                reload: Some(ReloadConfig::Signal { .. }),
+                ..
+            }
+        ));
+        assert!(matches!(
+            &config.providers["unbound"],
+            ProviderConfig::Unbound {
+                reload: Some(ReloadConfig::Command { command }),
+                ..
+            } if command == &["unbound-control", "reload"]
+        ));
+    }
+
+    #[test]
+    fn test_parse_ddns() {
+        let raw = r#"
+            [ddns.home]
+            type = "duckdns"
+            hostname = "myhome"
+
+            [ddns.backup]
+            type = "noip"
+            hostname = "home.ddns.net"
+        "#;
+        let config = Config::parse(raw).unwrap();
+        assert_eq!(config.ddns["home"].kind(), "duckdns");
+        match &config.ddns["backup"] {
+            DdnsConfig::NoIP {
+                hostname,
+                update_url,
+            } => {
+                assert_eq!(hostname, "home.ddns.net");
+                assert_eq!(update_url, crate::providers::dyndns::NOIP_UPDATE_URL);
+            }
+            other => panic!("unexpected ddns config: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields_rejected() {
+        let raw = r#"
+            [providers.lan]
+            type = "hosts"
+            pathh = "/tmp/hosts"
+        "#;
+        assert!(matches!(Config::parse(raw), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        let config = Config::parse(r#"scope = ["*.lab.example.com"]"#).unwrap();
+        assert!(config.scope.contains("nas.lab.example.com"));
+        assert!(!config.scope.contains("www.example.com"));
+        assert!(Config::parse(r#"scope = [""]"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_conflicts() {
+        let raw = r#"
+            [conflicts]
+            policy = "replace"
+            fail = ["*.prod.example.com"]
+        "#;
+        let conflicts = Config::parse(raw).unwrap().conflicts;
+        assert_eq!(conflicts.policy, ConflictPolicy::Replace);
+        assert_eq!(
+            conflicts.policy_for("db.prod.example.com"),
+            ConflictPolicy::Fail
+        );
+        assert!(
+            Config::parse(
+                "[conflicts]
+policy = \"merge\""
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_http() {
+        let raw = r#"
+            [http]
+            proxy = "http://proxy.lan:3128"
+            timeout = 10
+        "#;
+        let config = Config::parse(raw).unwrap();
+        assert_eq!(
+            config.http.proxy.as_ref().map(Secret::expose),
+            Some("http://proxy.lan:3128")
+        );
+        assert_eq!(config.http.timeout, Some(10));
+        assert!(Config::parse("[http]\nproxi = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_cache() {
+        assert!(Config::parse("").unwrap().cache.is_none());
+        let config = Config::parse("[cache]\npath = \"/var/cache/dns-update.db\"").unwrap();
+        let cache = config.cache.unwrap();
+        assert_eq!(cache.path, PathBuf::from("/var/cache/dns-update.db"));
+        assert_eq!(
+            cache.max_age,
+            crate::core::cache::DEFAULT_CACHE_MAX_AGE_SECS
+        );
+    }
+
+    #[test]
+    fn test_parse_lock_dir() {
+        assert_eq!(Config::parse("").unwrap().lock_dir(), std::env::temp_dir());
+        let config = Config::parse("lock_dir = \"/run/dns-update\"").unwrap();
+        assert_eq!(config.lock_dir(), PathBuf::from("/run/dns-update"));
+    }
+
+    #[test]
+    fn test_parse_rewrites_fallback() {
+        assert!(Config::parse("").unwrap().rewrites_fallback.is_none());
+        let config =
+            Config::parse("rewrites_fallback = \"/var/lib/dns-update/rewrites.txt\"").unwrap();
+        assert_eq!(
+            config.rewrites_fallback,
+            Some(PathBuf::from("/var/lib/dns-update/rewrites.txt"))
+        );
+    }
+
+    #[test]
+    fn test_parse_flatten() {
+        assert!(Config::parse("").unwrap().flatten.is_none());
+        let config = Config::parse("flatten = [\"example.com\"]").unwrap();
+        let flatten = config.flatten.unwrap();
+        assert!(flatten.contains("example.com."));
+        assert!(!flatten.contains("www.example.com"));
+    }
+
+    #[test]
+    fn test_parse_reverse_prefixes() {
+        let raw = r#"
+            [providers.reverse]
+            type = "zone"
+            path = "/etc/coredns/db.0.0.10.in-addr.arpa"
+            zone = "0.0.10.in-addr.arpa"
+            reverse = ["10.0.0.0/24"]
+        "#;
+        let config = Config::parse(raw).unwrap();
+        let reverse = config.providers["reverse"].reverse();
+        assert_eq!(reverse.len(), 1);
+        assert!(reverse[0].contains(&"10.0.0.2".parse::<std::net::IpAddr>().unwrap()));
+        assert!(Config::parse("[providers.r]\ntype = \"zone\"\npath = \"x\"\nzone = \"z\"\nreverse = [\"10.0.0.0/33\"]").is_err());
+    }
+}
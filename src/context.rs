@@ -0,0 +1,47 @@
+//! What a run was doing when it failed, added to errors on their way back
+//! to `main` so the printed message can be acted on without a second run:
+//!
+//! ```text
+//! invalid name: bad<name>
+//!   while parsing line 42
+//!   while reading records from rewrites.txt
+//! ```
+
+use std::fmt::Display;
+
+pub trait Context<T> {
+    /// Add `what` was being done to the error, e.g. `listing records of lan`.
+    fn context(self, what: impl Display) -> Result<T, String>;
+
+    /// `context`, with the description only built on failure.
+    fn with_context<D: Display>(self, what: impl FnOnce() -> D) -> Result<T, String>;
+}
+
+impl<T, E: Display> Context<T> for Result<T, E> {
+    fn context(self, what: impl Display) -> Result<T, String> {
+        self.map_err(|e| format!("{e}\n  while {what}"))
+    }
+
+    fn with_context<D: Display>(self, what: impl FnOnce() -> D) -> Result<T, String> {
+        self.map_err(|e| format!("{e}\n  while {}", what()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_is_stacked_innermost_first() {
+        let failed: Result<(), &str> = Err("invalid name: bad<name>");
+        let e = failed
+            .context("parsing line 42")
+            .with_context(|| format!("reading records from {}", "rewrites.txt"))
+            .unwrap_err();
+        assert_eq!(
+            e,
+            "invalid name: bad<name>\n  while parsing line 42\n  while reading records from rewrites.txt"
+        );
+        assert_eq!(Ok::<_, String>(1).context("listing records"), Ok(1));
+    }
+}
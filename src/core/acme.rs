@@ -0,0 +1,122 @@
+//! ACME DNS-01 challenge records and propagation checks.
+
+use hickory_resolver::TokioResolver;
+use hickory_resolver::proto::rr::RData;
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::core::record::DNSRecord;
+use crate::core::resolver::{self, LookupConfig};
+use crate::error::Error;
+
+pub const CHALLENGE_LABEL: &str = "_acme-challenge";
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Name of the challenge record for `domain`; wildcard certificates are
+/// validated at the parent name.
+pub fn challenge_name(domain: &str) -> String {
+    let domain = domain.trim_end_matches('.');
+    let domain = domain.strip_prefix("*.").unwrap_or(domain);
+    format!("{CHALLENGE_LABEL}.{domain}")
+}
+
+pub fn challenge_record(domain: &str, value: &str) -> DNSRecord {
+    DNSRecord::txt(challenge_name(domain), value)
+}
+
+/// Looks up TXT records, through the given servers or the system resolver.
+pub struct TxtLookup {
+    resolver: TokioResolver,
+}
+
+impl TxtLookup {
+    pub fn new(servers: &[IpAddr], config: &LookupConfig) -> Result<Self, Error> {
+        let resolver = resolver::build(servers, config)?;
+        Ok(Self { resolver })
+    }
+
+    /// The TXT values published at `name`; missing names have none.
+    pub async fn values(&self, name: &str) -> Vec<String> {
+        // Every poll must see fresh answers, not a cached NXDOMAIN
+        self.resolver.clear_cache();
+        let Ok(lookup) = self.resolver.txt_lookup(format!("{name}.")).await else {
+            return Vec::new();
+        };
+        lookup
+            .answers()
+            .iter()
+            .filter_map(|record| match &record.data {
+                RData::TXT(txt) => Some(
+                    txt.txt_data
+                        .iter()
+                        .map(|part| String::from_utf8_lossy(part))
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Poll `values` until it includes `value`, giving up after `timeout`.
+pub async fn wait_for<F, Fut>(values: F, value: &str, timeout: Duration) -> Result<(), Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Vec<String>>,
+{
+    let poll = async {
+        loop {
+            if values().await.iter().any(|v| v == value) {
+                return;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    };
+    tokio::time::timeout(timeout, poll).await.map_err(|_| {
+        Error::Other(format!(
+            "challenge record not visible after {}s",
+            timeout.as_secs()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_challenge_name() {
+        assert_eq!(challenge_name("example.com"), "_acme-challenge.example.com");
+        assert_eq!(
+            challenge_name("*.lab.example.com."),
+            "_acme-challenge.lab.example.com"
+        );
+        let record = challenge_record("www.example.com", "token");
+        assert_eq!(record.record_type, DNSRecordType::TXT);
+        assert_eq!(record.value, "token");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_for_polls_until_visible() {
+        let polls = AtomicUsize::new(0);
+        let values = || async {
+            match polls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => vec!["stale".to_string()],
+                _ => vec!["stale".to_string(), "token".to_string()],
+            }
+        };
+        wait_for(values, "token", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(polls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_for_times_out() {
+        let result = wait_for(|| async { Vec::new() }, "token", Duration::from_secs(30)).await;
+        assert!(matches!(result, Err(Error::Other(_))));
+    }
+}
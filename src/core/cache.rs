@@ -0,0 +1,629 @@
+//! Local SQLite cache of provider listings.
+//!
+//! A cached listing, and the names and types the provider reports as owned,
+//! are served until they are older than `max_age`; changes applied through
+//! the cache are written to it as well, so repeated syncs only re-list a
+//! provider when the cache expires or an operation fails.
+
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension, params};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::core::ownership::{Owned, Ownership};
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
+use crate::core::record::{DNSRecord, normalize_hostname};
+use crate::core::settings::SettingChange;
+use crate::error::Error;
+
+pub const DEFAULT_CACHE_MAX_AGE_SECS: u64 = 300;
+
+/// The `[cache]` configuration section.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// SQLite database file, created if missing
+    pub path: PathBuf,
+    /// Seconds after which a provider is listed again
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+}
+
+fn default_max_age() -> u64 {
+    DEFAULT_CACHE_MAX_AGE_SECS
+}
+
+#[derive(Clone)]
+pub struct RecordCache {
+    conn: Arc<Mutex<Connection>>,
+}
+
+fn cache_error(e: rusqlite::Error) -> Error {
+    Error::Other(format!("record cache: {e}"))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl RecordCache {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        Self::init(Connection::open(path).map_err(cache_error)?)
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::init(Connection::open_in_memory().map_err(cache_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, Error> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS listings (
+                 provider TEXT PRIMARY KEY,
+                 fetched_at INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS records (
+                 provider TEXT NOT NULL,
+                 record_type TEXT NOT NULL,
+                 name TEXT NOT NULL,
+                 value TEXT NOT NULL,
+                 ttl INTEGER,
+                 comment TEXT
+             );
+             CREATE INDEX IF NOT EXISTS records_provider ON records (provider);
+             CREATE TABLE IF NOT EXISTS owned_listings (
+                 provider TEXT PRIMARY KEY,
+                 fetched_at INTEGER NOT NULL,
+                 everything INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS owned (
+                 provider TEXT NOT NULL,
+                 name TEXT NOT NULL,
+                 record_type TEXT NOT NULL,
+                 PRIMARY KEY (provider, name, record_type)
+             );",
+        )
+        .map_err(cache_error)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn with_conn<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    ) -> Result<T, Error> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|_| Error::Other("record cache lock poisoned".to_string()))?;
+        f(&mut conn).map_err(cache_error)
+    }
+
+    /// The cached listing of `provider`, unless it is missing or older than `max_age`.
+    pub fn get(&self, provider: &str, max_age: Duration) -> Result<Option<Vec<DNSRecord>>, Error> {
+        self.with_conn(|conn| {
+            let fetched_at: Option<i64> = conn
+                .query_row(
+                    "SELECT fetched_at FROM listings WHERE provider = ?1",
+                    params![provider],
+                    |row| row.get(0),
+                )
+                .or_else(|e| match e {
+                    rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                    e => Err(e),
+                })?;
+            match fetched_at {
+                Some(at) if now() - at <= max_age.as_secs() as i64 => {}
+                _ => return Ok(None),
+            }
+            let mut stmt = conn.prepare(
+                "SELECT record_type, name, value, ttl, comment FROM records WHERE provider = ?1 ORDER BY rowid",
+            )?;
+            let rows = stmt.query_map(params![provider], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<u32>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?;
+            let mut records = Vec::new();
+            for row in rows {
+                let (record_type, name, value, ttl, comment) = row?;
+                // Entries of types this version does not know are dropped
+                if let Ok(record_type) = record_type.parse() {
+                    records.push(DNSRecord {
+                        record_type,
+                        name,
+                        value,
+                        ttl,
+                        comment,
+                    });
+                }
+            }
+            Ok(Some(records))
+        })
+    }
+
+    /// Replace the listing of `provider`.
+    pub fn store(&self, provider: &str, records: &[DNSRecord]) -> Result<(), Error> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM records WHERE provider = ?1", params![provider])?;
+            for record in records {
+                insert(&tx, provider, record)?;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO listings (provider, fetched_at) VALUES (?1, ?2)",
+                params![provider, now()],
+            )?;
+            tx.commit()
+        })
+    }
+
+    /// Add `record`, unless a record with the same data is cached already,
+    /// as when the provider skipped a duplicate add. It is claimed in the
+    /// owned names, if they are cached.
+    pub fn add(&self, provider: &str, record: &DNSRecord) -> Result<(), Error> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            for rowid in matching_rows(&tx, provider, record, DNSRecord::same_data)? {
+                tx.execute("DELETE FROM records WHERE rowid = ?1", params![rowid])?;
+            }
+            insert(&tx, provider, record)?;
+            if Ownership::Txt.marks(record) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO owned (provider, name, record_type)
+                     SELECT provider, ?2, ?3 FROM owned_listings
+                     WHERE provider = ?1 AND everything = 0",
+                    params![
+                        provider,
+                        normalize_hostname(&record.name),
+                        record.record_type.as_str()
+                    ],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Replace the value of the record with the same name and type.
+    pub fn update(&self, provider: &str, record: &DNSRecord) -> Result<(), Error> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            for rowid in matching_rows(&tx, provider, record, DNSRecord::same_name)? {
+                tx.execute(
+                    "UPDATE records SET name = ?2, value = ?3, ttl = ?4, comment = ?5
+                     WHERE rowid = ?1",
+                    params![rowid, record.name, record.value, record.ttl, record.comment],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Remove `record`, and its name and type from the owned names once no
+    /// record is left under them. Without a cached listing to tell, the
+    /// owned names are listed again.
+    pub fn remove(&self, provider: &str, record: &DNSRecord) -> Result<(), Error> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            for rowid in matching_rows(&tx, provider, record, DNSRecord::same_data)? {
+                tx.execute("DELETE FROM records WHERE rowid = ?1", params![rowid])?;
+            }
+            let listed = tx
+                .query_row(
+                    "SELECT 1 FROM listings WHERE provider = ?1",
+                    params![provider],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if !listed {
+                tx.execute(
+                    "DELETE FROM owned_listings WHERE provider = ?1",
+                    params![provider],
+                )?;
+            } else if matching_rows(&tx, provider, record, DNSRecord::same_name)?.is_empty() {
+                tx.execute(
+                    "DELETE FROM owned WHERE provider = ?1 AND name = ?2 AND record_type = ?3",
+                    params![
+                        provider,
+                        normalize_hostname(&record.name),
+                        record.record_type.as_str()
+                    ],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// The cached owned names of `provider`, unless they are missing or
+    /// older than `max_age`. `Some(None)` is a cached answer that every
+    /// record is owned.
+    pub fn get_owned(
+        &self,
+        provider: &str,
+        max_age: Duration,
+    ) -> Result<Option<Option<Owned>>, Error> {
+        self.with_conn(|conn| {
+            let listing: Option<(i64, bool)> = conn
+                .query_row(
+                    "SELECT fetched_at, everything FROM owned_listings WHERE provider = ?1",
+                    params![provider],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            match listing {
+                Some((at, everything)) if now() - at <= max_age.as_secs() as i64 => {
+                    if everything {
+                        return Ok(Some(None));
+                    }
+                }
+                _ => return Ok(None),
+            }
+            let mut stmt =
+                conn.prepare("SELECT name, record_type FROM owned WHERE provider = ?1")?;
+            let rows = stmt.query_map(params![provider], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut owned = Owned::new();
+            for row in rows {
+                let (name, record_type) = row?;
+                if let Ok(record_type) = record_type.parse() {
+                    owned.insert((name, record_type));
+                }
+            }
+            Ok(Some(Some(owned)))
+        })
+    }
+
+    /// Replace the owned names of `provider`; `None` if every record is owned.
+    pub fn store_owned(&self, provider: &str, owned: Option<&Owned>) -> Result<(), Error> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM owned WHERE provider = ?1", params![provider])?;
+            for (name, record_type) in owned.into_iter().flatten() {
+                tx.execute(
+                    "INSERT OR IGNORE INTO owned (provider, name, record_type) VALUES (?1, ?2, ?3)",
+                    params![provider, name, record_type.as_str()],
+                )?;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO owned_listings (provider, fetched_at, everything)
+                 VALUES (?1, ?2, ?3)",
+                params![provider, now(), owned.is_none()],
+            )?;
+            tx.commit()
+        })
+    }
+
+    /// Forget the listing of `provider`, so the next sync lists it again.
+    pub fn invalidate(&self, provider: &str) -> Result<(), Error> {
+        self.with_conn(|conn| {
+            for table in ["listings", "records", "owned_listings", "owned"] {
+                conn.execute(
+                    &format!("DELETE FROM {table} WHERE provider = ?1"),
+                    params![provider],
+                )?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// The rows of `provider` holding a record of the type of `record` that
+// `matches` it, whatever the spelling of names and values
+fn matching_rows(
+    conn: &Connection,
+    provider: &str,
+    record: &DNSRecord,
+    matches: fn(&DNSRecord, &DNSRecord) -> bool,
+) -> rusqlite::Result<Vec<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT rowid, name, value FROM records WHERE provider = ?1 AND record_type = ?2",
+    )?;
+    let rows = stmt.query_map(params![provider, record.record_type.as_str()], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    let mut found = Vec::new();
+    for row in rows {
+        let (rowid, name, value) = row?;
+        let cached = DNSRecord::new(record.record_type.clone(), name, value);
+        if matches(&cached, record) {
+            found.push(rowid);
+        }
+    }
+    Ok(found)
+}
+
+fn insert(conn: &Connection, provider: &str, record: &DNSRecord) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT INTO records (provider, record_type, name, value, ttl, comment)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            provider,
+            record.record_type.as_str(),
+            record.name,
+            record.value,
+            record.ttl,
+            record.comment
+        ],
+    )
+}
+
+/// A provider whose listing is served from a `RecordCache`.
+pub struct CachedProvider {
+    key: String,
+    inner: Arc<dyn DNSProvider>,
+    cache: RecordCache,
+    max_age: Duration,
+}
+
+impl CachedProvider {
+    /// Wrap `inner`, caching its listing under `key`, the configured provider name.
+    pub fn new(
+        key: &str,
+        inner: Arc<dyn DNSProvider>,
+        cache: RecordCache,
+        max_age: Duration,
+    ) -> Self {
+        Self {
+            key: key.to_string(),
+            inner,
+            cache,
+            max_age,
+        }
+    }
+
+    /// Keep the cache in line with the outcome of a change. A failed change
+    /// leaves the provider state unknown, so the listing is dropped.
+    fn after_change(
+        &self,
+        result: Result<(), Error>,
+        update: impl FnOnce() -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        match result {
+            Ok(()) => update(),
+            Err(e) => {
+                self.cache.invalidate(&self.key)?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DNSProvider for CachedProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+        if let Some(records) = self.cache.get(&self.key, self.max_age)? {
+            return Ok(records);
+        }
+        let records = self.inner.list_records().await?;
+        self.cache.store(&self.key, &records)?;
+        Ok(records)
+    }
+
+    async fn owned_records(&self) -> Result<Option<Owned>, Error> {
+        if let Some(owned) = self.cache.get_owned(&self.key, self.max_age)? {
+            return Ok(owned);
+        }
+        let owned = self.inner.owned_records().await?;
+        self.cache.store_owned(&self.key, owned.as_ref())?;
+        Ok(owned)
+    }
+
+    async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let result = self.inner.add_record(record.clone()).await;
+        self.after_change(result, || self.cache.add(&self.key, &record))
+    }
+
+    async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let result = self.inner.update_record(record.clone()).await;
+        self.after_change(result, || self.cache.update(&self.key, &record))
+    }
+
+    async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let result = self.inner.delete_record(record.clone()).await;
+        self.after_change(result, || self.cache.remove(&self.key, &record))
+    }
+
+    async fn estimate(&self, changes: usize) -> Option<Duration> {
+        self.inner.estimate(changes).await
+    }
+
+    async fn flush(&self) -> Result<(), Error> {
+        let result = self.inner.flush().await;
+        self.after_change(result, || Ok(()))
+    }
+
+    async fn plan_settings(&self) -> Result<Vec<SettingChange>, Error> {
+        self.inner.plan_settings().await
+    }
+
+    async fn apply_setting(&self, change: &SettingChange) -> Result<(), Error> {
+        self.inner.apply_setting(change).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingProvider {
+        records: Mutex<Vec<DNSRecord>>,
+        lists: AtomicUsize,
+        owned_lists: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl DNSProvider for CountingProvider {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+            self.lists.fetch_add(1, Ordering::SeqCst);
+            Ok(self.records.lock().unwrap().clone())
+        }
+
+        async fn owned_records(&self) -> Result<Option<Owned>, Error> {
+            self.owned_lists.fetch_add(1, Ordering::SeqCst);
+            let records = self.records.lock().unwrap();
+            Ok(Some(
+                records
+                    .iter()
+                    .map(|r| (r.name.clone(), r.record_type.clone()))
+                    .collect(),
+            ))
+        }
+
+        async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+            self.records.lock().unwrap().push(record);
+            Ok(())
+        }
+
+        async fn update_record(&self, _record: DNSRecord) -> Result<(), Error> {
+            Err(Error::NotFound("Record not found".to_string()))
+        }
+
+        async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+            self.records.lock().unwrap().retain(|r| r != &record);
+            Ok(())
+        }
+    }
+
+    fn record(name: &str) -> DNSRecord {
+        DNSRecord {
+            record_type: DNSRecordType::A,
+            name: name.to_string(),
+            value: "10.0.0.1".to_string(),
+            ttl: Some(300),
+            comment: None,
+        }
+    }
+
+    fn setup(max_age: Duration) -> (Arc<CountingProvider>, CachedProvider) {
+        let inner = Arc::new(CountingProvider {
+            records: Mutex::new(vec![record("a.lan")]),
+            lists: AtomicUsize::new(0),
+            owned_lists: AtomicUsize::new(0),
+        });
+        let cache = RecordCache::in_memory().unwrap();
+        let cached = CachedProvider::new("lan", inner.clone(), cache, max_age);
+        (inner, cached)
+    }
+
+    #[tokio::test]
+    async fn test_listing_is_served_from_cache() {
+        let (inner, cached) = setup(Duration::from_secs(300));
+        assert_eq!(cached.list_records().await.unwrap(), vec![record("a.lan")]);
+        cached.add_record(record("b.lan")).await.unwrap();
+        cached.delete_record(record("a.lan")).await.unwrap();
+        assert_eq!(cached.list_records().await.unwrap(), vec![record("b.lan")]);
+        assert_eq!(inner.lists.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_adds_are_cached_once() {
+        let (_, cached) = setup(Duration::from_secs(300));
+        cached.list_records().await.unwrap();
+        cached.add_record(record("b.lan")).await.unwrap();
+        // The same record again, spelled differently, as a re-run does
+        // after the provider skipped it as a duplicate
+        cached.add_record(record("B.lan.")).await.unwrap();
+        assert_eq!(
+            cached.list_records().await.unwrap(),
+            vec![record("a.lan"), record("B.lan.")]
+        );
+        let moved = DNSRecord {
+            value: "10.0.0.2".to_string(),
+            ..record("b.lan")
+        };
+        cached.cache.update("lan", &moved).unwrap();
+        assert_eq!(
+            cached.list_records().await.unwrap(),
+            vec![record("a.lan"), moved]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_owned_records_are_cached() {
+        let (inner, cached) = setup(Duration::from_secs(300));
+        cached.list_records().await.unwrap();
+        let owned = |names: &[&str]| -> Owned {
+            names
+                .iter()
+                .map(|name| (name.to_string(), DNSRecordType::A))
+                .collect()
+        };
+        assert_eq!(
+            cached.owned_records().await.unwrap(),
+            Some(owned(&["a.lan"]))
+        );
+        cached.add_record(record("b.lan")).await.unwrap();
+        assert_eq!(
+            cached.owned_records().await.unwrap(),
+            Some(owned(&["a.lan", "b.lan"]))
+        );
+        cached.delete_record(record("a.lan")).await.unwrap();
+        assert_eq!(
+            cached.owned_records().await.unwrap(),
+            Some(owned(&["b.lan"]))
+        );
+        assert_eq!(inner.owned_lists.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failures_invalidate_listing() {
+        let (inner, cached) = setup(Duration::from_secs(300));
+        cached.list_records().await.unwrap();
+        assert!(cached.update_record(record("a.lan")).await.is_err());
+        cached.list_records().await.unwrap();
+        assert_eq!(inner.lists.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_store_replaces_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.db");
+        let cache = RecordCache::open(&path).unwrap();
+        cache.store("lan", &[record("a.lan")]).unwrap();
+        let commented = DNSRecord {
+            comment: Some("from ansible".to_string()),
+            ..record("b.lan")
+        };
+        cache
+            .store("lan", std::slice::from_ref(&commented))
+            .unwrap();
+        cache.store("other", &[record("c.lan")]).unwrap();
+        drop(cache);
+
+        let cache = RecordCache::open(&path).unwrap();
+        let max_age = Duration::from_secs(60);
+        assert_eq!(cache.get("lan", max_age).unwrap(), Some(vec![commented]));
+        assert_eq!(cache.get("missing", max_age).unwrap(), None);
+    }
+}
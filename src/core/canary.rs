@@ -0,0 +1,244 @@
+//! Canary applies: apply the first changes of a plan, check that they
+//! resolve, and only then apply the rest.
+//!
+//! A systematic mistake in a large plan, like a wrong address for a whole
+//! subnet, then breaks a handful of names instead of all of them.
+
+use hickory_resolver::TokioResolver;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use std::future::Future;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::core::diff::Change;
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+use crate::core::resolver::{self, LookupConfig};
+use crate::error::Error;
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How many changes of each provider go first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CanarySize {
+    Count(usize),
+    Percent(f64),
+}
+
+impl CanarySize {
+    /// The number of canary changes out of `total`; at least one.
+    pub fn of(self, total: usize) -> usize {
+        let size = match self {
+            CanarySize::Count(count) => count,
+            CanarySize::Percent(percent) => (total as f64 * percent / 100.0).ceil() as usize,
+        };
+        size.clamp(1, total.max(1))
+    }
+}
+
+impl FromStr for CanarySize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid canary size: {s} (expected e.g. `5` or `10%`)");
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(percent) if percent > 0.0 && percent <= 100.0 => {
+                    Ok(CanarySize::Percent(percent))
+                }
+                _ => Err(invalid()),
+            },
+            None => match s.trim().parse::<usize>() {
+                Ok(count) if count > 0 => Ok(CanarySize::Count(count)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+/// The records a canary can be checked by: added and updated records of
+/// the types resolvers answer for. Removals are not checked, as resolvers
+/// may keep answering from their cache.
+pub fn checked_records(changes: &[Change]) -> Vec<DNSRecord> {
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::Add(record) | Change::Update(record) => Some(record),
+            Change::Remove(_) => None,
+        })
+        .filter(|record| {
+            matches!(
+                record.record_type,
+                DNSRecordType::A | DNSRecordType::AAAA | DNSRecordType::CNAME | DNSRecordType::TXT
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+/// Looks up the values published for a record, through the given servers
+/// or the system resolver.
+pub struct RecordLookup {
+    resolver: TokioResolver,
+}
+
+impl RecordLookup {
+    pub fn new(servers: &[IpAddr], config: &LookupConfig) -> Result<Self, Error> {
+        Ok(Self {
+            resolver: resolver::build(servers, config)?,
+        })
+    }
+
+    /// The values published at the name and type of `record`; missing
+    /// names have none.
+    pub async fn values(&self, record: &DNSRecord) -> Vec<String> {
+        let record_type = match record.record_type {
+            DNSRecordType::A => RecordType::A,
+            DNSRecordType::AAAA => RecordType::AAAA,
+            DNSRecordType::CNAME => RecordType::CNAME,
+            DNSRecordType::TXT => RecordType::TXT,
+            _ => return Vec::new(),
+        };
+        // Every poll must see fresh answers, not a cached NXDOMAIN
+        self.resolver.clear_cache();
+        let name = format!("{}.", record.name.trim_end_matches('.'));
+        let Ok(lookup) = self.resolver.lookup(name, record_type).await else {
+            return Vec::new();
+        };
+        lookup
+            .answers()
+            .iter()
+            .filter_map(|answer| match &answer.data {
+                RData::A(a) => Some(a.to_string()),
+                RData::AAAA(aaaa) => Some(aaaa.to_string()),
+                RData::CNAME(cname) => Some(cname.to_string()),
+                RData::TXT(txt) => Some(
+                    txt.txt_data
+                        .iter()
+                        .map(|part| String::from_utf8_lossy(part))
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// Whether a looked up value is the record's value
+fn same_value(record: &DNSRecord, value: &str) -> bool {
+    match record.record_type {
+        DNSRecordType::A | DNSRecordType::AAAA => {
+            match (record.value.parse::<IpAddr>(), value.parse::<IpAddr>()) {
+                (Ok(expected), Ok(found)) => expected == found,
+                _ => false,
+            }
+        }
+        DNSRecordType::CNAME => normalize_hostname(&record.value) == normalize_hostname(value),
+        _ => record.value == value,
+    }
+}
+
+/// Poll `values` until every record of `records` resolves to its value,
+/// giving up after `timeout` with the records that still don't.
+pub async fn verify<F, Fut>(
+    records: &[DNSRecord],
+    values: F,
+    timeout: Duration,
+) -> Result<(), Error>
+where
+    F: Fn(DNSRecord) -> Fut,
+    Fut: Future<Output = Vec<String>>,
+{
+    let mut pending: Vec<&DNSRecord> = records.iter().collect();
+    let poll = async {
+        loop {
+            let mut still_pending = Vec::new();
+            for record in pending.iter().copied() {
+                let found = values(record.clone()).await;
+                if !found.iter().any(|v| same_value(record, v)) {
+                    still_pending.push(record);
+                }
+            }
+            pending = still_pending;
+            if pending.is_empty() {
+                return;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    };
+    if tokio::time::timeout(timeout, poll).await.is_ok() {
+        return Ok(());
+    }
+    let names: Vec<String> = pending.iter().map(|r| r.to_string()).collect();
+    Err(Error::Other(format!(
+        "canary records not resolving after {}s: {}",
+        timeout.as_secs(),
+        names.join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn a(name: &str, last: u8) -> DNSRecord {
+        DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_canary_size() {
+        assert_eq!("5".parse(), Ok(CanarySize::Count(5)));
+        assert_eq!("10%".parse(), Ok(CanarySize::Percent(10.0)));
+        assert!("0".parse::<CanarySize>().is_err());
+        assert!("150%".parse::<CanarySize>().is_err());
+        assert_eq!(CanarySize::Count(5).of(3), 3);
+        assert_eq!(CanarySize::Percent(10.0).of(25), 3);
+        assert_eq!(CanarySize::Percent(1.0).of(10), 1);
+    }
+
+    #[test]
+    fn test_checked_records_leave_out_removals() {
+        let changes = [
+            Change::Add(a("new.example.com", 1)),
+            Change::Remove(a("old.example.com", 2)),
+        ];
+        assert_eq!(checked_records(&changes), vec![a("new.example.com", 1)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_verify_polls_until_resolving() {
+        let polls = AtomicUsize::new(0);
+        let values = |_: DNSRecord| async {
+            match polls.fetch_add(1, Ordering::SeqCst) {
+                0 => vec!["10.0.0.9".to_string()],
+                _ => vec!["10.0.0.1".to_string()],
+            }
+        };
+        verify(&[a("nas.example.com", 1)], values, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(polls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_verify_reports_records_not_resolving() {
+        let records = [a("good.example.com", 1), a("bad.example.com", 2)];
+        let values = |record: DNSRecord| {
+            let found = if record.name == "good.example.com" {
+                vec!["10.0.0.1".to_string()]
+            } else {
+                Vec::new()
+            };
+            async move { found }
+        };
+        let err = verify(&records, values, Duration::from_secs(30))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::Other(ref msg) if msg.ends_with("30s: bad.example.com A 10.0.0.2")),
+            "{err:?}"
+        );
+    }
+}
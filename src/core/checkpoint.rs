@@ -0,0 +1,150 @@
+//! Progress of large applies, so an interrupted run resumes where it stopped.
+//!
+//! Changes are applied in chunks. After each chunk is flushed, the changes
+//! still to apply are written to the checkpoint file; the next run of the
+//! same desired records applies those instead of planning again.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::core::diff::Change;
+use crate::core::record::DNSRecord;
+use crate::error::Error;
+use crate::providers::file::write_atomic;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 100;
+
+/// The `[checkpoint]` configuration section.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CheckpointConfig {
+    /// JSON file holding the changes not yet applied, per provider
+    pub path: PathBuf,
+    /// Changes applied between checkpoints
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+}
+
+fn default_chunk_size() -> usize {
+    DEFAULT_CHUNK_SIZE
+}
+
+/// The unfinished apply of one provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Fingerprint of the desired records the changes were planned for
+    pub desired: String,
+    /// Changes not applied yet, in order
+    pub remaining: Vec<Change>,
+}
+
+/// Fingerprint of a set of desired records, independent of their order.
+pub fn fingerprint(records: &[DNSRecord]) -> String {
+    let mut lines: Vec<String> = records.iter().map(|r| r.to_string()).collect();
+    lines.sort();
+    // FNV-1a, which unlike std's hasher is stable across releases
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in lines.join("\n").bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+pub struct CheckpointFile {
+    path: PathBuf,
+    chunk_size: usize,
+}
+
+fn checkpoint_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::Other(format!("checkpoint {}: {e}", path.display()))
+}
+
+impl CheckpointFile {
+    pub fn new(config: &CheckpointConfig) -> Self {
+        Self {
+            path: config.path.clone(),
+            chunk_size: config.chunk_size.max(1),
+        }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn read(&self) -> Result<BTreeMap<String, Checkpoint>, Error> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => {
+                serde_json::from_str(&contents).map_err(|e| checkpoint_error(&self.path, e))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(checkpoint_error(&self.path, e)),
+        }
+    }
+
+    /// The unfinished apply of `provider`, if any.
+    pub fn load(&self, provider: &str) -> Result<Option<Checkpoint>, Error> {
+        Ok(self.read()?.remove(provider))
+    }
+
+    /// Record the changes of `provider` still to apply; none clears it.
+    pub fn save(&self, provider: &str, checkpoint: Option<Checkpoint>) -> Result<(), Error> {
+        let mut checkpoints = self.read()?;
+        match checkpoint {
+            Some(checkpoint) if !checkpoint.remaining.is_empty() => {
+                checkpoints.insert(provider.to_string(), checkpoint);
+            }
+            _ => {
+                if checkpoints.remove(provider).is_none() {
+                    return Ok(());
+                }
+            }
+        }
+        let contents = serde_json::to_string_pretty(&checkpoints)
+            .map_err(|e| checkpoint_error(&self.path, e))?;
+        write_atomic(&self.path, &contents).map_err(|e| checkpoint_error(&self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn a(name: &str) -> DNSRecord {
+        DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, 1))
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_order() {
+        let records = [a("a.lan"), a("b.lan")];
+        let reversed = [a("b.lan"), a("a.lan")];
+        assert_eq!(fingerprint(&records), fingerprint(&reversed));
+        assert_ne!(fingerprint(&records), fingerprint(&[a("a.lan")]));
+    }
+
+    #[test]
+    fn test_save_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = CheckpointFile::new(&CheckpointConfig {
+            path: dir.path().join("checkpoint.json"),
+            chunk_size: 0,
+        });
+        assert_eq!(file.chunk_size(), 1);
+        assert_eq!(file.load("lan").unwrap(), None);
+
+        let checkpoint = Checkpoint {
+            desired: fingerprint(&[a("a.lan")]),
+            remaining: vec![Change::Add(a("a.lan")), Change::Remove(a("b.lan"))],
+        };
+        file.save("lan", Some(checkpoint.clone())).unwrap();
+        file.save("wan", Some(checkpoint.clone())).unwrap();
+        assert_eq!(file.load("lan").unwrap(), Some(checkpoint.clone()));
+
+        file.save("lan", None).unwrap();
+        assert_eq!(file.load("lan").unwrap(), None);
+        assert_eq!(file.load("wan").unwrap(), Some(checkpoint));
+    }
+}
@@ -0,0 +1,128 @@
+//! Comparing the records of two providers, without desired records, e.g. to
+//! check a migration from one to the other.
+
+use std::collections::BTreeMap;
+
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+
+/// How the records of two providers differ. Records are matched by type and
+/// name, and compared by value; TTLs and comments are left out.
+#[derive(Debug, Default, PartialEq)]
+pub struct Comparison {
+    /// Records of a name and type the second provider has none of
+    pub only_from: Vec<DNSRecord>,
+    /// Records of a name and type the first provider has none of
+    pub only_to: Vec<DNSRecord>,
+    /// Records of a name and type both providers hold, with other values
+    pub different: Vec<(Vec<DNSRecord>, Vec<DNSRecord>)>,
+}
+
+impl Comparison {
+    pub fn is_empty(&self) -> bool {
+        self.only_from.is_empty() && self.only_to.is_empty() && self.different.is_empty()
+    }
+
+    /// One line per difference, naming the providers `from` and `to`.
+    pub fn lines(&self, from: &str, to: &str) -> Vec<String> {
+        let values = |records: &[DNSRecord]| {
+            records
+                .iter()
+                .map(|r| r.value.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let only_from = self
+            .only_from
+            .iter()
+            .map(|record| format!("only in {from}: {record}"));
+        let only_to = self
+            .only_to
+            .iter()
+            .map(|record| format!("only in {to}: {record}"));
+        let different = self.different.iter().map(|(a, b)| {
+            format!(
+                "different: {} {}: {from} has {}, {to} has {}",
+                a[0].name,
+                a[0].record_type,
+                values(a),
+                values(b)
+            )
+        });
+        only_from.chain(only_to).chain(different).collect()
+    }
+}
+
+pub fn compare(from: &[DNSRecord], to: &[DNSRecord]) -> Comparison {
+    let mut names: BTreeMap<(String, DNSRecordType), (Vec<DNSRecord>, Vec<DNSRecord>)> =
+        BTreeMap::new();
+    for record in from {
+        let key = (normalize_hostname(&record.name), record.record_type.clone());
+        names.entry(key).or_default().0.push(record.clone());
+    }
+    for record in to {
+        let key = (normalize_hostname(&record.name), record.record_type.clone());
+        names.entry(key).or_default().1.push(record.clone());
+    }
+
+    let mut comparison = Comparison::default();
+    for (a, b) in names.into_values() {
+        if a.is_empty() {
+            comparison.only_to.extend(b);
+        } else if b.is_empty() {
+            comparison.only_from.extend(a);
+        } else if values(&a) != values(&b) {
+            comparison.different.push((a, b));
+        }
+    }
+    comparison
+}
+
+// The distinct values at a name, in canonical form
+fn values(records: &[DNSRecord]) -> Vec<String> {
+    let mut values: Vec<String> = records.iter().map(|r| r.normalized_value()).collect();
+    values.sort();
+    values.dedup();
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn a(name: &str, last: u8) -> DNSRecord {
+        DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_compare() {
+        let from = [
+            a("nas.example.com", 2),
+            a("printer.example.com", 3),
+            a("www.example.com", 4),
+            a("www.example.com", 5),
+        ];
+        let to = [
+            a("NAS.example.com.", 2).ttl(60),
+            a("www.example.com", 5),
+            a("www.example.com", 6),
+            DNSRecord::cname("printer.example.com", "nas.example.com"),
+        ];
+        let comparison = compare(&from, &to);
+        assert_eq!(comparison.only_from, [a("printer.example.com", 3)]);
+        assert_eq!(
+            comparison.only_to,
+            [DNSRecord::cname("printer.example.com", "nas.example.com")]
+        );
+        assert_eq!(
+            comparison.lines("nextdns", "adguard"),
+            [
+                "only in nextdns: printer.example.com A 10.0.0.3",
+                "only in adguard: printer.example.com CNAME nas.example.com",
+                "different: www.example.com A: nextdns has 10.0.0.4, 10.0.0.5, \
+                 adguard has 10.0.0.5, 10.0.0.6",
+            ]
+        );
+        assert!(compare(&from, &from).is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+//! What to do when a desired record and a record held by a provider cannot
+//! exist side by side, like an A record and a CNAME at the same name.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+use crate::core::scope::Scope;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Remove the conflicting record before adding the desired one
+    Replace,
+    /// Leave the conflicting record and skip the desired one with a warning
+    #[default]
+    Skip,
+    /// Fail the provider's sync
+    Fail,
+}
+
+/// The `[conflicts]` configuration section: a policy for all names, and
+/// name patterns that use another one.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Conflicts {
+    pub policy: ConflictPolicy,
+    pub replace: Scope,
+    pub skip: Scope,
+    pub fail: Scope,
+}
+
+impl Conflicts {
+    /// The policy for conflicts at `name`; `fail` patterns take precedence
+    /// over `replace` patterns, and those over `skip` patterns.
+    pub fn policy_for(&self, name: &str) -> ConflictPolicy {
+        [
+            (&self.fail, ConflictPolicy::Fail),
+            (&self.replace, ConflictPolicy::Replace),
+            (&self.skip, ConflictPolicy::Skip),
+        ]
+        .into_iter()
+        .find(|(names, _)| !names.is_empty() && names.contains(name))
+        .map_or(self.policy, |(_, policy)| policy)
+    }
+}
+
+/// Whether `a` and `b` cannot both exist: a CNAME shares its name with no
+/// record of another type.
+pub fn conflicting(a: &DNSRecord, b: &DNSRecord) -> bool {
+    a.record_type != b.record_type
+        && (a.record_type == DNSRecordType::CNAME || b.record_type == DNSRecordType::CNAME)
+        && normalize_hostname(&a.name) == normalize_hostname(&b.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_policy_for() {
+        let conflicts = Conflicts {
+            policy: ConflictPolicy::Replace,
+            fail: Scope::new(vec!["*.prod.example.com".to_string()]).unwrap(),
+            ..Conflicts::default()
+        };
+        assert_eq!(
+            conflicts.policy_for("db.prod.example.com"),
+            ConflictPolicy::Fail
+        );
+        assert_eq!(
+            conflicts.policy_for("nas.lab.example.com"),
+            ConflictPolicy::Replace
+        );
+        assert_eq!(
+            Conflicts::default().policy_for("nas.lab.example.com"),
+            ConflictPolicy::Skip
+        );
+    }
+
+    #[test]
+    fn test_conflicting() {
+        let a = DNSRecord::a("nas.example.com", Ipv4Addr::new(10, 0, 0, 1));
+        let cname = DNSRecord::cname("NAS.example.com.", "files.example.com");
+        let txt = DNSRecord::txt("nas.example.com", "owner");
+        assert!(conflicting(&a, &cname));
+        assert!(conflicting(&cname, &txt));
+        assert!(!conflicting(&a, &txt));
+        assert!(!conflicting(
+            &a,
+            &DNSRecord::cname("www.example.com", "nas")
+        ));
+    }
+}
@@ -0,0 +1,64 @@
+//! IDs tying a run and each change it applies to the provider requests
+//! made for them.
+//!
+//! Every run gets an ID, and every change applied an ID of its own. API
+//! providers send the ID of the current change, or of the run outside of
+//! changes, as `X-Request-Id`, and the IDs appear in failure messages and
+//! the run report, so a failure can be looked up in the provider's logs or
+//! quoted to its support.
+
+use std::future::Future;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Header carrying the ID with each request.
+pub const HEADER: &str = "X-Request-Id";
+
+// Runs never overlap, so one is current at a time
+static RUN: Mutex<Option<String>> = Mutex::new(None);
+
+tokio::task_local! {
+    static CHANGE: String;
+}
+
+pub fn new_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Start a run with a new ID, returning it.
+pub fn start_run() -> String {
+    let id = new_id();
+    *RUN.lock().unwrap_or_else(|e| e.into_inner()) = Some(id.clone());
+    id
+}
+
+/// The ID of the current run.
+pub fn run_id() -> Option<String> {
+    RUN.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Run `f` as the change with ID `id`.
+pub async fn for_change<F: Future>(id: String, f: F) -> F::Output {
+    CHANGE.scope(id, f).await
+}
+
+/// The ID to send with a request: the change being applied, else the run.
+pub fn request_id() -> Option<String> {
+    CHANGE.try_with(Clone::clone).ok().or_else(run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_change_ids_take_precedence_over_the_run() {
+        let run = start_run();
+        assert_eq!(request_id(), Some(run.clone()));
+        let change = new_id();
+        assert_ne!(change, run);
+        let inside = for_change(change.clone(), async { request_id() }).await;
+        assert_eq!(inside, Some(change));
+        assert_eq!(request_id(), Some(run));
+    }
+}
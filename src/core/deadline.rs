@@ -0,0 +1,56 @@
+//! Overall time budget for a run.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A point in time after which provider calls are cancelled. Without a
+/// timeout the deadline never expires.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deadline(Option<Instant>);
+
+/// The operation did not finish before the deadline and was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Deadline {
+    pub fn after(timeout: Option<Duration>) -> Self {
+        Self(timeout.map(|t| Instant::now() + t))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.0.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Run `fut` to completion, or drop it once the deadline passes.
+    pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output, Cancelled> {
+        match self.0 {
+            Some(at) => tokio::time::timeout_at(at, fut)
+                .await
+                .map_err(|_| Cancelled),
+            None => Ok(fut.await),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancels_slow_operations() {
+        let deadline = Deadline::after(Some(Duration::from_secs(5)));
+        assert_eq!(deadline.run(async { 1 }).await, Ok(1));
+        let slow = tokio::time::sleep(Duration::from_secs(10));
+        assert_eq!(deadline.run(slow).await, Err(Cancelled));
+        assert!(deadline.expired());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_timeout() {
+        let deadline = Deadline::default();
+        let slow = tokio::time::sleep(Duration::from_secs(3600));
+        assert_eq!(deadline.run(slow).await, Ok(()));
+        assert!(!deadline.expired());
+    }
+}
@@ -0,0 +1,685 @@
+//! Planning the changes that bring a provider in line with the desired records.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::debug;
+
+use crate::core::conflict::{ConflictPolicy, Conflicts, conflicting};
+use crate::core::ownership::Owned;
+use crate::core::provider::ProviderCapabilities;
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+use crate::core::settings::SettingChange;
+
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub to_add: Vec<DNSRecord>,
+    /// Records whose value changes in place, for providers that support it
+    pub to_update: Vec<DNSRecord>,
+    pub to_remove: Vec<DNSRecord>,
+    /// Records of another type removed to make way for desired records,
+    /// under the `replace` conflict policy
+    pub replaced: Vec<DNSRecord>,
+    /// Desired records the provider cannot hold
+    pub skipped: Vec<Skipped>,
+    /// Desired records conflicting with provider records under the `fail`
+    /// conflict policy; a plan with any must not be applied
+    pub conflicts: Vec<Skipped>,
+    /// Changes to the provider's managed settings, planned separately
+    pub settings: Vec<SettingChange>,
+    /// Changes to protected records, which are never applied
+    pub blocked: Vec<Change>,
+}
+
+/// A single operation of a plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", content = "record", rename_all = "lowercase")]
+pub enum Change {
+    Add(DNSRecord),
+    Update(DNSRecord),
+    Remove(DNSRecord),
+}
+
+impl Change {
+    pub fn record(&self) -> &DNSRecord {
+        match self {
+            Change::Add(record) | Change::Update(record) | Change::Remove(record) => record,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Skipped {
+    pub record: DNSRecord,
+    pub reason: String,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.to_add.is_empty()
+            && self.to_update.is_empty()
+            && self.to_remove.is_empty()
+            && self.replaced.is_empty()
+    }
+
+    /// The changes in the order they are applied: additions first, so names
+    /// never go unresolved while a value is being replaced. Replaced records
+    /// go before that, as providers reject the additions while they exist.
+    /// CNAMEs are added after their targets and removed before them, so no
+    /// managed CNAME dangles between two changes.
+    pub fn changes(&self) -> Vec<Change> {
+        let replaced = self.replaced.iter().cloned().map(Change::Remove);
+        let adds = dependency_order(&self.to_add, Order::TargetsFirst)
+            .into_iter()
+            .map(Change::Add);
+        let updates = self.to_update.iter().cloned().map(Change::Update);
+        let removes = dependency_order(&self.to_remove, Order::CnamesFirst)
+            .into_iter()
+            .map(Change::Remove);
+        replaced.chain(adds).chain(updates).chain(removes).collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Order {
+    TargetsFirst,
+    CnamesFirst,
+}
+
+// The target of a CNAME record, unless it points at its own name
+fn cname_target(record: &DNSRecord) -> Option<String> {
+    let target = normalize_hostname(&record.value);
+    (record.record_type == DNSRecordType::CNAME && target != normalize_hostname(&record.name))
+        .then_some(target)
+}
+
+// `records` with the CNAMEs among them after or before the records of
+// their target's name, chains included; otherwise in their given order.
+// CNAME loops have no such order and are left as given.
+fn dependency_order(records: &[DNSRecord], order: Order) -> Vec<DNSRecord> {
+    let mut pending: Vec<DNSRecord> = records.to_vec();
+    let mut ordered = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let names: HashSet<String> = pending
+            .iter()
+            .map(|r| normalize_hostname(&r.name))
+            .collect();
+        let targets: HashSet<String> = pending.iter().filter_map(cname_target).collect();
+        let (waiting, ready): (Vec<_>, Vec<_>) =
+            pending.into_iter().partition(|record| match order {
+                Order::TargetsFirst => cname_target(record).is_some_and(|t| names.contains(&t)),
+                Order::CnamesFirst => targets.contains(&normalize_hostname(&record.name)),
+            });
+        if ready.is_empty() {
+            ordered.extend(waiting);
+            break;
+        }
+        ordered.extend(ready);
+        pending = waiting;
+    }
+    ordered
+}
+
+// Names and values are compared normalized, so case and trailing dots don't
+// cause churn. A listed record without a TTL (file formats, providers that
+// don't report one) matches a desired record with any TTL.
+fn same_record(a: &DNSRecord, b: &DNSRecord) -> bool {
+    a.same_data(b) && same_ttl(a, b)
+}
+
+fn same_ttl(a: &DNSRecord, b: &DNSRecord) -> bool {
+    a.ttl == b.ttl || a.ttl.is_none() || b.ttl.is_none()
+}
+
+// A record as it is compared, for debug logs
+fn compared(record: &DNSRecord) -> String {
+    let ttl = record.ttl.map_or("none".to_string(), |ttl| ttl.to_string());
+    format!(
+        "{} {:?} {:?} ttl {ttl}",
+        normalize_hostname(&record.name),
+        record.record_type,
+        record.normalized_value()
+    )
+}
+
+// The fields keeping `desired` from matching `current`, a record of the same
+// name and type
+fn differences(desired: &DNSRecord, current: &DNSRecord) -> Vec<String> {
+    let mut fields = Vec::new();
+    let (value, held) = (desired.normalized_value(), current.normalized_value());
+    if value != held {
+        fields.push(format!("value {value:?} != {held:?}"));
+    }
+    if !same_ttl(desired, current) {
+        fields.push(format!("ttl {:?} != {:?}", desired.ttl, current.ttl));
+    }
+    fields
+}
+
+/// Adapt the desired records to what the provider supports, dropping the
+/// ones it cannot hold.
+fn adapt(
+    desired: &[DNSRecord],
+    capabilities: &ProviderCapabilities,
+) -> (Vec<DNSRecord>, Vec<Skipped>) {
+    let mut records = Vec::new();
+    let mut skipped = Vec::new();
+    for record in desired {
+        if !capabilities.record_types.contains(&record.record_type) {
+            skipped.push(Skipped {
+                record: record.clone(),
+                reason: format!("{:?} records are not supported", record.record_type),
+            });
+        } else if !capabilities.wildcards && record.name.starts_with("*.") {
+            skipped.push(Skipped {
+                record: record.clone(),
+                reason: "wildcard names are not supported".to_string(),
+            });
+        } else if !capabilities.ttl {
+            records.push(DNSRecord {
+                ttl: None,
+                ..record.clone()
+            });
+        } else {
+            records.push(record.clone());
+        }
+    }
+    (records, skipped)
+}
+
+/// Compute the changes needed to turn `current` into `desired`. With `owned`
+/// set, only current records it lists are updated or removed. Desired records
+/// that cannot exist next to a current record of another type are handled
+/// by the policy of `conflicts`.
+pub fn plan(
+    desired: &[DNSRecord],
+    current: &[DNSRecord],
+    capabilities: &ProviderCapabilities,
+    owned: Option<&Owned>,
+    conflicts: &Conflicts,
+) -> Plan {
+    let (desired, mut skipped) = adapt(desired, capabilities);
+    // Types that are not synchronized, like ad-hoc TXT records, are left alone
+    let current: Vec<DNSRecord> = current
+        .iter()
+        .filter(|r| capabilities.record_types.contains(&r.record_type))
+        .cloned()
+        .collect();
+    let current = current.as_slice();
+    let is_owned = |r: &DNSRecord| {
+        owned.is_none_or(|owned| owned.contains(&(r.name.clone(), r.record_type.clone())))
+    };
+
+    let mut to_add: Vec<DNSRecord> = Vec::new();
+    let mut replaced: Vec<DNSRecord> = Vec::new();
+    // Conflicting records that stay, as their desired records were not added
+    let mut kept: Vec<DNSRecord> = Vec::new();
+    let mut conflicted = Vec::new();
+    for record in desired.iter() {
+        if let Some(held) = current.iter().find(|c| same_record(c, record)) {
+            debug!("unchanged: {} matches {}", compared(record), compared(held));
+            continue;
+        }
+        // Repeated desired records must not become repeated adds
+        if to_add.iter().any(|a| same_record(a, record)) {
+            continue;
+        }
+        // Adding next to someone else's records would mix values under their name
+        if current.iter().any(|c| c.same_name(record) && !is_owned(c)) {
+            skipped.push(Skipped {
+                record: record.clone(),
+                reason: "name is held by records not managed by dns-update".to_string(),
+            });
+            continue;
+        }
+        // Records of another type that are desired as well are the desired
+        // records' own conflict, not the provider's
+        let conflicts_with: Vec<&DNSRecord> = current
+            .iter()
+            .filter(|c| conflicting(c, record) && !desired.iter().any(|d| d.same_name(c)))
+            .collect();
+        if !conflicts_with.is_empty() {
+            let held: Vec<String> = conflicts_with.iter().map(|c| c.to_string()).collect();
+            let reason = format!("conflicts with {}", held.join(", "));
+            match conflicts.policy_for(&record.name) {
+                ConflictPolicy::Replace if conflicts_with.iter().all(|c| is_owned(c)) => {
+                    for c in conflicts_with {
+                        if !replaced.contains(c) {
+                            replaced.push(c.clone());
+                        }
+                    }
+                }
+                ConflictPolicy::Replace => {
+                    kept.extend(conflicts_with.into_iter().cloned());
+                    skipped.push(Skipped {
+                        record: record.clone(),
+                        reason: format!("{reason}, which dns-update does not manage"),
+                    });
+                    continue;
+                }
+                ConflictPolicy::Skip => {
+                    kept.extend(conflicts_with.into_iter().cloned());
+                    skipped.push(Skipped {
+                        record: record.clone(),
+                        reason,
+                    });
+                    continue;
+                }
+                ConflictPolicy::Fail => {
+                    kept.extend(conflicts_with.into_iter().cloned());
+                    conflicted.push(Skipped {
+                        record: record.clone(),
+                        reason,
+                    });
+                    continue;
+                }
+            }
+        }
+        for held in current.iter().filter(|c| c.same_name(record)) {
+            debug!(
+                "{} differs from {}: {}",
+                compared(record),
+                compared(held),
+                differences(record, held).join(", ")
+            );
+        }
+        to_add.push(record.clone());
+    }
+    let mut to_remove: Vec<_> = current
+        .iter()
+        .filter(|r| is_owned(r) && !desired.iter().any(|d| same_record(d, r)))
+        .filter(|r| !replaced.contains(r) && !kept.contains(r))
+        .cloned()
+        .collect();
+
+    // A single value replacing a single value under the same name and type
+    // can be updated in place instead of added and removed
+    let mut to_update = Vec::new();
+    if capabilities.update_in_place {
+        let unique = |records: &[DNSRecord], r: &DNSRecord| {
+            records.iter().filter(|o| o.same_name(r)).count() == 1
+        };
+        let pairs: Vec<(usize, usize)> = to_add
+            .iter()
+            .enumerate()
+            .filter_map(|(i, add)| {
+                let j = to_remove.iter().position(|rm| rm.same_name(add))?;
+                (unique(&desired, add) && unique(current, &to_remove[j])).then_some((i, j))
+            })
+            .collect();
+        for (i, j) in pairs.into_iter().rev() {
+            to_update.push(to_add.remove(i));
+            to_remove.remove(j);
+        }
+        to_update.reverse();
+    }
+
+    Plan {
+        to_add,
+        to_update,
+        to_remove,
+        replaced,
+        skipped,
+        conflicts: conflicted,
+        settings: Vec::new(),
+        blocked: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+
+    fn record(record_type: DNSRecordType, name: &str, value: &str, ttl: Option<u32>) -> DNSRecord {
+        DNSRecord {
+            record_type,
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_cname_chains_are_ordered() {
+        let www = record(DNSRecordType::CNAME, "www.lan", "web.lan", None);
+        let web = record(DNSRecordType::CNAME, "web.lan", "NAS.lan.", None);
+        let nas = record(DNSRecordType::A, "nas.lan", "10.0.0.2", None);
+        let tv = record(DNSRecordType::A, "tv.lan", "10.0.0.4", None);
+        let plan = Plan {
+            to_add: vec![www.clone(), web.clone(), tv.clone(), nas.clone()],
+            to_remove: vec![nas.clone(), tv.clone(), web.clone(), www.clone()],
+            ..Default::default()
+        };
+        assert_eq!(
+            plan.changes(),
+            [
+                Change::Add(tv.clone()),
+                Change::Add(nas.clone()),
+                Change::Add(web.clone()),
+                Change::Add(www.clone()),
+                Change::Remove(tv),
+                Change::Remove(www),
+                Change::Remove(web),
+                Change::Remove(nas),
+            ]
+        );
+
+        // A loop cannot be ordered, and is kept as it is
+        let a = record(DNSRecordType::CNAME, "a.lan", "b.lan", None);
+        let b = record(DNSRecordType::CNAME, "b.lan", "a.lan", None);
+        assert_eq!(
+            dependency_order(&[a.clone(), b.clone()], Order::TargetsFirst),
+            [a, b]
+        );
+    }
+
+    #[test]
+    fn test_differences() {
+        let desired = record(DNSRecordType::A, "nas.lan", "10.0.0.2", Some(300));
+        let held = record(DNSRecordType::A, "NAS.lan.", "10.0.0.3", Some(600));
+        assert_eq!(
+            differences(&desired, &held),
+            [
+                r#"value "10.0.0.2" != "10.0.0.3""#,
+                "ttl Some(300) != Some(600)"
+            ]
+        );
+        assert_eq!(compared(&held), r#"nas.lan A "10.0.0.3" ttl 600"#);
+        let unknown_ttl = record(DNSRecordType::A, "nas.lan", "10.0.0.2", None);
+        assert!(differences(&desired, &unknown_ttl).is_empty());
+    }
+
+    #[test]
+    fn test_unsynchronized_types_are_left_alone() {
+        let current = vec![
+            record(
+                DNSRecordType::TXT,
+                "_acme-challenge.example.com",
+                "token",
+                None,
+            ),
+            record(DNSRecordType::A, "old.example.com", "10.0.0.1", None),
+        ];
+        let plan = plan(
+            &[],
+            &current,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        assert_eq!(plan.to_remove, vec![current[1].clone()]);
+    }
+
+    #[test]
+    fn test_missing_ttl_matches() {
+        let desired = [record(DNSRecordType::A, "a.com", "1.1.1.1", Some(300))];
+        let current = [record(DNSRecordType::A, "a.com", "1.1.1.1", None)];
+        let plan = plan(
+            &desired,
+            &current,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        assert!(plan.is_empty());
+    }
+
+    #[test]
+    fn test_names_match_normalized() {
+        let desired = [
+            record(DNSRecordType::A, "NAS.lab.example.com", "10.0.0.2", None),
+            record(
+                DNSRecordType::CNAME,
+                "files.lab.example.com",
+                "nas.lab.example.com",
+                None,
+            ),
+        ];
+        let current = [
+            record(DNSRecordType::A, "nas.lab.example.com.", "10.0.0.2", None),
+            record(
+                DNSRecordType::CNAME,
+                "Files.lab.example.com",
+                "NAS.lab.example.com.",
+                None,
+            ),
+        ];
+        let plan = plan(
+            &desired,
+            &current,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        assert!(plan.is_empty());
+    }
+
+    #[test]
+    fn test_addresses_match_in_any_textual_form() {
+        let expanded = record(
+            DNSRecordType::AAAA,
+            "nas.lab.example.com",
+            "2001:DB8:0:0:0:0:0:0001",
+            None,
+        );
+        let desired = [record(
+            DNSRecordType::AAAA,
+            "nas.lab.example.com",
+            "2001:db8::1",
+            None,
+        )];
+        let current = [expanded.clone()];
+        let unchanged = plan(
+            &desired,
+            &current,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        assert!(unchanged.is_empty());
+
+        // Removals keep the text the provider holds
+        let removed = plan(
+            &[],
+            &current,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        assert_eq!(removed.to_remove, vec![expanded]);
+    }
+
+    #[test]
+    fn test_unsupported_records_are_skipped() {
+        let capabilities = ProviderCapabilities {
+            record_types: vec![DNSRecordType::A],
+            wildcards: false,
+            ..Default::default()
+        };
+        let desired = [
+            record(DNSRecordType::A, "a.com", "1.1.1.1", None),
+            record(DNSRecordType::CNAME, "b.com", "a.com", None),
+            record(DNSRecordType::A, "*.c.com", "1.1.1.1", None),
+        ];
+        let plan = plan(&desired, &[], &capabilities, None, &Conflicts::default());
+        assert_eq!(plan.to_add.len(), 1);
+        assert_eq!(plan.skipped.len(), 2);
+        assert!(plan.skipped[0].reason.contains("CNAME"));
+        assert!(plan.skipped[1].reason.contains("wildcard"));
+    }
+
+    #[test]
+    fn test_ttl_dropped_without_ttl_support() {
+        let capabilities = ProviderCapabilities {
+            ttl: false,
+            ..Default::default()
+        };
+        let desired = [record(DNSRecordType::A, "a.com", "1.1.1.1", Some(300))];
+        let current = [record(DNSRecordType::A, "a.com", "1.1.1.1", Some(60))];
+        assert!(
+            plan(
+                &desired,
+                &current,
+                &capabilities,
+                None,
+                &Conflicts::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_update_in_place() {
+        let desired = [
+            record(DNSRecordType::A, "a.com", "2.2.2.2", None),
+            record(DNSRecordType::A, "rr.com", "3.3.3.3", None),
+            record(DNSRecordType::A, "rr.com", "4.4.4.4", None),
+        ];
+        let current = [
+            record(DNSRecordType::A, "a.com", "1.1.1.1", None),
+            record(DNSRecordType::A, "rr.com", "3.3.3.3", None),
+            record(DNSRecordType::A, "rr.com", "5.5.5.5", None),
+        ];
+
+        let without = plan(
+            &desired,
+            &current,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        assert_eq!(without.to_add.len(), 2);
+        assert_eq!(without.to_remove.len(), 2);
+        assert!(without.to_update.is_empty());
+
+        let capabilities = ProviderCapabilities {
+            update_in_place: true,
+            ..Default::default()
+        };
+        let with = plan(
+            &desired,
+            &current,
+            &capabilities,
+            None,
+            &Conflicts::default(),
+        );
+        // Only the single-valued name is updated; round-robin sets are not paired
+        assert_eq!(with.to_update, vec![desired[0].clone()]);
+        assert_eq!(with.to_add, vec![desired[2].clone()]);
+        assert_eq!(with.to_remove, vec![current[2].clone()]);
+    }
+
+    #[test]
+    fn test_only_owned_records_are_removed() {
+        let desired = [
+            record(DNSRecordType::A, "a.com", "1.1.1.1", None),
+            record(DNSRecordType::A, "manual.com", "2.2.2.2", None),
+        ];
+        let current = [
+            record(DNSRecordType::A, "old.com", "1.1.1.1", None),
+            record(DNSRecordType::A, "manual.com", "3.3.3.3", None),
+            record(DNSRecordType::CNAME, "other.com", "a.com", None),
+        ];
+        let owned: Owned = [("old.com".to_string(), DNSRecordType::A)].into();
+        let plan = plan(
+            &desired,
+            &current,
+            &ProviderCapabilities::default(),
+            Some(&owned),
+            &Conflicts::default(),
+        );
+        assert_eq!(plan.to_add, vec![desired[0].clone()]);
+        assert_eq!(plan.to_remove, vec![current[0].clone()]);
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(plan.skipped[0].record, desired[1]);
+    }
+
+    #[test]
+    fn test_duplicate_desired_records_are_added_once() {
+        let desired = [
+            record(DNSRecordType::A, "a.com", "1.1.1.1", Some(300)),
+            record(DNSRecordType::A, "a.com", "1.1.1.1", Some(300)),
+        ];
+        let plan = plan(
+            &desired,
+            &[],
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        assert_eq!(plan.to_add.len(), 1);
+    }
+
+    #[test]
+    fn test_type_conflicts_follow_the_policy() {
+        let desired = [record(
+            DNSRecordType::CNAME,
+            "nas.lab.example.com",
+            "files.lab.example.com",
+            None,
+        )];
+        let current = [
+            record(DNSRecordType::A, "nas.lab.example.com", "10.0.0.2", None),
+            record(DNSRecordType::A, "old.lab.example.com", "10.0.0.9", None),
+        ];
+        let with = |policy| {
+            let conflicts = Conflicts {
+                policy,
+                ..Conflicts::default()
+            };
+            let capabilities = ProviderCapabilities::default();
+            plan(&desired, &current, &capabilities, None, &conflicts)
+        };
+
+        let skipped = with(ConflictPolicy::Skip);
+        assert!(skipped.to_add.is_empty());
+        assert_eq!(skipped.to_remove, vec![current[1].clone()]);
+        assert_eq!(skipped.skipped[0].record, desired[0]);
+        assert!(skipped.skipped[0].reason.contains("conflicts with"));
+
+        // The conflicting record is removed before the desired one is added
+        let replaced = with(ConflictPolicy::Replace);
+        assert_eq!(
+            replaced.changes(),
+            vec![
+                Change::Remove(current[0].clone()),
+                Change::Add(desired[0].clone()),
+                Change::Remove(current[1].clone()),
+            ]
+        );
+
+        let failed = with(ConflictPolicy::Fail);
+        assert!(failed.to_add.is_empty());
+        assert_eq!(failed.conflicts[0].record, desired[0]);
+    }
+
+    #[test]
+    fn test_unmanaged_conflicting_records_are_not_replaced() {
+        let desired = [record(
+            DNSRecordType::A,
+            "www.example.com",
+            "10.0.0.2",
+            None,
+        )];
+        let current = [record(
+            DNSRecordType::CNAME,
+            "www.example.com",
+            "example.com",
+            None,
+        )];
+        let conflicts = Conflicts {
+            policy: ConflictPolicy::Replace,
+            ..Conflicts::default()
+        };
+        let plan = plan(
+            &desired,
+            &current,
+            &ProviderCapabilities::default(),
+            Some(&Owned::default()),
+            &conflicts,
+        );
+        assert!(plan.is_empty());
+        assert!(plan.skipped[0].reason.ends_with("does not manage"));
+    }
+}
@@ -0,0 +1,14 @@
+use crate::error::Error;
+use async_trait::async_trait;
+use std::net::IpAddr;
+
+/// Update-URL style services that can only point a hostname at an address.
+///
+/// These have no notion of a record set, so they are driven by address
+/// changes rather than by the record diff used for `DNSProvider`.
+#[async_trait]
+pub trait DynamicDNSUpdater: Send + Sync {
+    #[allow(dead_code)]
+    fn name(&self) -> &str;
+    async fn set_address(&self, address: IpAddr) -> Result<(), Error>;
+}
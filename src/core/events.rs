@@ -0,0 +1,45 @@
+//! Changes requested of the daemon at runtime, as typed events that can be
+//! logged and replayed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::record::DNSRecord;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Add a host, or renew it when it is already there
+    HostAdded {
+        record: DNSRecord,
+        /// Seconds the host is kept unless added again
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lease: Option<u64>,
+    },
+    /// Forget the added hosts with this name
+    HostRemoved { name: String },
+    /// Start a run now instead of at the next interval
+    ForceReconcile,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_events_serialize_tagged() {
+        let added = Event::HostAdded {
+            record: DNSRecord::a("nas.lab.example.com", Ipv4Addr::new(10, 0, 0, 2)),
+            lease: Some(600),
+        };
+        let value = serde_json::to_value(&added).unwrap();
+        assert_eq!(value["event"], "host_added");
+        assert_eq!(value["record"]["name"], "nas.lab.example.com");
+        assert_eq!(value["lease"], 600);
+        assert_eq!(serde_json::from_value::<Event>(value).unwrap(), added);
+
+        let reconcile: Event = serde_json::from_value(json!({"event": "force_reconcile"})).unwrap();
+        assert_eq!(reconcile, Event::ForceReconcile);
+    }
+}
@@ -0,0 +1,179 @@
+//! CNAME flattening: publish the addresses a CNAME resolves to instead of
+//! the CNAME itself, for apex names and resolvers that mishandle CNAMEs.
+
+use hickory_resolver::TokioResolver;
+use std::future::Future;
+use std::net::IpAddr;
+
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+use crate::core::resolver::{self, LookupConfig};
+use crate::core::scope::Scope;
+use crate::error::Error;
+
+/// CNAME chains longer than this among the desired records are resolved
+/// through DNS instead.
+const MAX_CHAIN: usize = 8;
+
+/// Looks up the A and AAAA addresses of CNAME targets.
+pub struct AddressLookup {
+    resolver: TokioResolver,
+}
+
+impl AddressLookup {
+    pub fn new(servers: &[IpAddr], config: &LookupConfig) -> Result<Self, Error> {
+        Ok(Self {
+            resolver: resolver::build(servers, config)?,
+        })
+    }
+
+    pub async fn addresses(&self, name: String) -> Result<Vec<IpAddr>, Error> {
+        let lookup = self
+            .resolver
+            .lookup_ip(format!("{}.", name.trim_end_matches('.')))
+            .await
+            .map_err(|e| Error::Other(format!("resolving {name}: {e}")))?;
+        Ok(lookup.iter().collect())
+    }
+}
+
+/// Addresses of `name` among the desired records, following CNAMEs.
+fn local_addresses(records: &[DNSRecord], name: &str, depth: usize) -> Vec<IpAddr> {
+    if depth > MAX_CHAIN {
+        return Vec::new();
+    }
+    records
+        .iter()
+        .filter(|r| normalize_hostname(&r.name) == normalize_hostname(name))
+        .flat_map(|r| match r.record_type {
+            DNSRecordType::A | DNSRecordType::AAAA => r.value.parse().ok().into_iter().collect(),
+            DNSRecordType::CNAME => local_addresses(records, &r.value, depth + 1),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Replace the CNAME records whose name is in `names` by A and AAAA records
+/// for the addresses of their target. Targets among `records` resolve to
+/// the desired addresses; others are looked up with `lookup`. CNAMEs that
+/// cannot be resolved are kept.
+pub async fn flatten<F, Fut>(records: Vec<DNSRecord>, names: &Scope, lookup: F) -> Vec<DNSRecord>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<Vec<IpAddr>, Error>>,
+{
+    let mut flattened = Vec::with_capacity(records.len());
+    for record in &records {
+        if record.record_type != DNSRecordType::CNAME || !names.contains(&record.name) {
+            flattened.push(record.clone());
+            continue;
+        }
+        let mut addresses = local_addresses(&records, &record.value, 0);
+        if addresses.is_empty() {
+            match lookup(record.value.clone()).await {
+                Ok(found) => addresses = found,
+                Err(e) => eprintln!("Not flattening {}: {e}", record.name),
+            }
+        }
+        if addresses.is_empty() {
+            flattened.push(record.clone());
+            continue;
+        }
+        addresses.sort();
+        addresses.dedup();
+        flattened.extend(addresses.into_iter().map(|address| {
+            match address {
+                IpAddr::V4(v4) => DNSRecord::a(&record.name, v4),
+                IpAddr::V6(v6) => DNSRecord::aaaa(&record.name, v6),
+            }
+            .ttl(record.ttl)
+            .comment(record.comment.clone())
+        }));
+    }
+    flattened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(record_type: DNSRecordType, name: &str, value: &str) -> DNSRecord {
+        DNSRecord {
+            record_type,
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl: Some(300),
+            comment: None,
+        }
+    }
+
+    async fn no_lookup(name: String) -> Result<Vec<IpAddr>, Error> {
+        Err(Error::NotFound(name))
+    }
+
+    #[tokio::test]
+    async fn test_flatten_through_desired_records() {
+        let records = vec![
+            record(DNSRecordType::A, "nas.lab.example.com", "10.0.0.2"),
+            record(DNSRecordType::AAAA, "nas.lab.example.com", "fd00::2"),
+            record(
+                DNSRecordType::CNAME,
+                "files.lab.example.com",
+                "nas.lab.example.com.",
+            ),
+            record(
+                DNSRecordType::CNAME,
+                "media.lab.example.com",
+                "FILES.lab.example.com",
+            ),
+            record(
+                DNSRecordType::CNAME,
+                "www.lab.example.com",
+                "nas.lab.example.com",
+            ),
+        ];
+        let names = Scope::new(vec!["media.lab.example.com".to_string()]).unwrap();
+        let flattened = flatten(records.clone(), &names, no_lookup).await;
+        assert_eq!(
+            flattened,
+            vec![
+                records[0].clone(),
+                records[1].clone(),
+                records[2].clone(),
+                record(DNSRecordType::A, "media.lab.example.com", "10.0.0.2"),
+                record(DNSRecordType::AAAA, "media.lab.example.com", "fd00::2"),
+                records[4].clone(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flatten_through_lookup() {
+        let records = vec![
+            record(
+                DNSRecordType::CNAME,
+                "example.com",
+                "site.hosting.example.net",
+            ),
+            record(DNSRecordType::CNAME, "old.example.com", "gone.example.net"),
+        ];
+        let names = Scope::new(vec![
+            "example.com".to_string(),
+            "old.example.com".to_string(),
+        ])
+        .unwrap();
+        let lookup = |name: String| async move {
+            match name.as_str() {
+                "site.hosting.example.net" => Ok(vec!["203.0.113.10".parse().unwrap()]),
+                _ => Err(Error::NotFound(name)),
+            }
+        };
+        let flattened = flatten(records.clone(), &names, lookup).await;
+        assert_eq!(
+            flattened,
+            vec![
+                record(DNSRecordType::A, "example.com", "203.0.113.10"),
+                records[1].clone(),
+            ]
+        );
+    }
+}
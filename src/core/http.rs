@@ -0,0 +1,203 @@
+//! HTTP client construction shared by the API providers.
+//!
+//! Proxies set in the configuration take precedence over the `HTTP_PROXY`,
+//! `HTTPS_PROXY` and `NO_PROXY` environment variables, which are honored
+//! otherwise.
+
+use reqwest::{Certificate, ClientBuilder, Proxy, RequestBuilder};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::core::correlation;
+use crate::error::Error;
+use crate::secret::Secret;
+
+pub const DEFAULT_USER_AGENT: &str = concat!("dns-update/", env!("CARGO_PKG_VERSION"));
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// The `[http]` configuration section.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Proxy URL for all requests, e.g. `http://proxy.lan:3128` or
+    /// `socks5h://proxy.lan:1080`; may hold a password
+    #[schemars(with = "Option<String>")]
+    pub proxy: Option<Secret>,
+    /// PEM files with additional trusted root certificates
+    #[serde(default)]
+    pub ca_certificates: Vec<PathBuf>,
+    /// Request timeout in seconds
+    pub timeout: Option<u64>,
+    pub user_agent: Option<String>,
+}
+
+/// Validated HTTP settings, with the certificates loaded.
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    user_agent: String,
+    proxy: Option<Proxy>,
+    root_certificates: Vec<Certificate>,
+    timeout: Duration,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy: None,
+            root_certificates: Vec::new(),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+        }
+    }
+}
+
+impl HttpOptions {
+    pub fn from_config(config: &HttpConfig) -> Result<Self, Error> {
+        let proxy = config
+            .proxy
+            .as_ref()
+            .map(|proxy| parse_proxy(proxy.expose()))
+            .transpose()?;
+        let root_certificates = config
+            .ca_certificates
+            .iter()
+            .map(|path| {
+                let pem = std::fs::read(path)
+                    .map_err(|e| Error::InvalidInput(format!("{}: {e}", path.display())))?;
+                match Certificate::from_pem_bundle(&pem) {
+                    Ok(certificates) if !certificates.is_empty() => Ok(certificates),
+                    Ok(_) => Err(Error::InvalidInput(format!(
+                        "{}: no certificates found",
+                        path.display()
+                    ))),
+                    Err(e) => Err(Error::InvalidInput(format!("{}: {e}", path.display()))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+        Ok(Self {
+            user_agent: config
+                .user_agent
+                .clone()
+                .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+            proxy,
+            root_certificates,
+            timeout: Duration::from_secs(config.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS)),
+        })
+    }
+
+    /// A client builder with these settings applied, for providers to extend.
+    pub fn builder(&self) -> ClientBuilder {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(&self.user_agent)
+            .timeout(self.timeout);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        builder
+    }
+}
+
+/// `builder` with the ID of the current change or run as `X-Request-Id`,
+/// for providers to match the request with.
+pub fn with_request_id(builder: RequestBuilder) -> RequestBuilder {
+    match correlation::request_id() {
+        Some(id) => builder.header(correlation::HEADER, id),
+        None => builder,
+    }
+}
+
+fn parse_proxy(url: &str) -> Result<Proxy, Error> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| Error::InvalidInput(format!("invalid proxy: {e}")))?;
+    if !matches!(
+        parsed.scheme(),
+        "http" | "https" | "socks4" | "socks4a" | "socks5" | "socks5h"
+    ) {
+        return Err(Error::InvalidInput(format!(
+            "unsupported proxy scheme: {}",
+            parsed.scheme()
+        )));
+    }
+    Proxy::all(parsed).map_err(|e| Error::InvalidInput(format!("invalid proxy: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    #[tokio::test]
+    async fn test_user_agent_and_proxy() {
+        let server = MockServer::start_async().await;
+        // The mock server stands in for the proxy: absolute-form requests
+        // for another host still arrive with the original path
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/ping")
+                    .header("User-Agent", DEFAULT_USER_AGENT);
+                then.status(200);
+            })
+            .await;
+        let config = HttpConfig {
+            proxy: Some(server.base_url().into()),
+            ..Default::default()
+        };
+        let client = HttpOptions::from_config(&config)
+            .unwrap()
+            .builder()
+            .build()
+            .unwrap();
+        let response = client
+            .get("http://upstream.invalid/ping")
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let config = HttpConfig {
+            proxy: Some("not a url".into()),
+            ..Default::default()
+        };
+        assert!(HttpOptions::from_config(&config).is_err());
+
+        let config = HttpConfig {
+            proxy: Some("ftp://proxy.lan:21".into()),
+            ..Default::default()
+        };
+        assert!(HttpOptions::from_config(&config).is_err());
+
+        let config = HttpConfig {
+            proxy: Some("socks5h://proxy.lan:1080".into()),
+            ..Default::default()
+        };
+        assert!(HttpOptions::from_config(&config).is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ca.pem");
+        std::fs::write(&path, "not a certificate").unwrap();
+        let config = HttpConfig {
+            ca_certificates: vec![path],
+            ..Default::default()
+        };
+        assert!(HttpOptions::from_config(&config).is_err());
+
+        let config = HttpConfig {
+            ca_certificates: vec![dir.path().join("missing.pem")],
+            ..Default::default()
+        };
+        assert!(HttpOptions::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Addresses of the host's own network interfaces, for publishing the
+//! address a host has on a given network, like its LAN address to an
+//! internal zone, where public-address detection would be wrong.
+
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+use crate::error::Error;
+
+/// For each subnet, the first of `addresses` within it.
+pub fn select(addresses: &[IpAddr], subnets: &[IpNet]) -> Result<Vec<IpAddr>, Error> {
+    subnets
+        .iter()
+        .map(|subnet| {
+            addresses
+                .iter()
+                .copied()
+                .find(|address| subnet.contains(address))
+                .ok_or_else(|| Error::NotFound(format!("no local address in {subnet}")))
+        })
+        .collect()
+}
+
+/// For each subnet, the address a local interface has within it.
+pub fn addresses_in(subnets: &[IpNet]) -> Result<Vec<IpAddr>, Error> {
+    if subnets.is_empty() {
+        return Ok(Vec::new());
+    }
+    let addresses: Vec<IpAddr> = if_addrs::get_if_addrs()
+        .map_err(|e| Error::Other(format!("listing network interfaces: {e}")))?
+        .iter()
+        .map(|interface| interface.ip())
+        .collect();
+    select(&addresses, subnets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let addresses: Vec<IpAddr> = ["127.0.0.1", "192.168.1.20", "10.0.0.5", "fd00::5"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        let subnets: Vec<IpNet> = ["10.0.0.0/24", "fd00::/64"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(
+            select(&addresses, &subnets).unwrap(),
+            vec![addresses[2], addresses[3]]
+        );
+        let missing = ["172.16.0.0/12".parse().unwrap()];
+        assert!(matches!(
+            select(&addresses, &missing),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
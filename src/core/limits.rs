@@ -0,0 +1,87 @@
+//! Limits on how much one sync may change, so a truncated records source
+//! cannot wipe a zone. Plans beyond them are refused unless forced.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::core::diff::Change;
+
+/// The `[limits]` configuration section; no limit applies when omitted.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Most changes applied to one provider in a run
+    pub max_changes: Option<usize>,
+    /// Largest share of a provider's records deleted in a run, from 0 to 1,
+    /// e.g. `0.25` for a quarter
+    pub max_delete_fraction: Option<f64>,
+}
+
+impl Limits {
+    /// Why `changes` to a provider holding `examined` records must not be
+    /// applied without `--force`, if they exceed a limit.
+    pub fn exceeded(&self, changes: &[Change], examined: usize) -> Option<String> {
+        if let Some(max) = self.max_changes
+            && changes.len() > max
+        {
+            return Some(format!(
+                "{} changes exceed max_changes = {max}",
+                changes.len()
+            ));
+        }
+        let deletes = changes
+            .iter()
+            .filter(|change| matches!(change, Change::Remove(_)))
+            .count();
+        // Resumed changes come without a listing to compare with
+        if let Some(max) = self.max_delete_fraction
+            && examined > 0
+            && deletes as f64 / examined as f64 > max
+        {
+            return Some(format!(
+                "deleting {deletes} of {examined} records exceeds max_delete_fraction = {max}"
+            ));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecord;
+    use std::net::Ipv4Addr;
+
+    fn changes(adds: u8, removes: u8) -> Vec<Change> {
+        (0..adds + removes)
+            .map(|i| {
+                let record =
+                    DNSRecord::a(format!("host{i}.example.com"), Ipv4Addr::new(10, 0, 0, i));
+                if i < adds {
+                    Change::Add(record)
+                } else {
+                    Change::Remove(record)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            max_changes: Some(5),
+            max_delete_fraction: Some(0.25),
+        };
+        assert_eq!(limits.exceeded(&changes(3, 2), 8), None);
+        assert_eq!(
+            limits.exceeded(&changes(6, 0), 8).as_deref(),
+            Some("6 changes exceed max_changes = 5")
+        );
+        assert_eq!(
+            limits.exceeded(&changes(0, 3), 8).as_deref(),
+            Some("deleting 3 of 8 records exceeds max_delete_fraction = 0.25")
+        );
+        assert_eq!(limits.exceeded(&changes(0, 3), 0), None);
+        assert_eq!(Limits::default().exceeded(&changes(50, 50), 50), None);
+    }
+}
@@ -0,0 +1,246 @@
+//! Lock files keeping overlapping runs from syncing the same provider.
+//!
+//! Each provider has its own lock, so `--provider` runs only wait on runs
+//! touching the same provider; a full run holds every lock.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::error::Error;
+
+/// Locks older than this are taken over when it cannot be told whether
+/// their process is alive.
+pub const STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// A held lock; released when dropped.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+}
+
+/// Who holds a lock, as written in the lock file.
+#[derive(Debug, PartialEq, Eq)]
+struct Holder {
+    pid: u32,
+    since: u64,
+}
+
+impl Holder {
+    fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        let pid = lines.next()?.trim().parse().ok()?;
+        let since = lines.next()?.trim().parse().ok()?;
+        Some(Self { pid, since })
+    }
+
+    /// Whether the lock is taken over without asking, given whether its
+    /// process is `alive`; only its age counts when that is unknown.
+    fn is_stale(&self, now: u64, alive: Option<bool>) -> bool {
+        match alive {
+            Some(alive) => !alive,
+            None => now.saturating_sub(self.since) > STALE_AFTER.as_secs(),
+        }
+    }
+
+    /// Whether `--force-unlock` may take the lock over: never from a
+    /// process that is still running.
+    fn may_force(&self, alive: Option<bool>) -> bool {
+        alive != Some(true)
+    }
+}
+
+// Whether process `pid` is running; `None` without procfs to tell, as
+// there is no portable liveness check
+fn process_alive(pid: u32) -> Option<bool> {
+    let proc = Path::new("/proc");
+    proc.is_dir().then(|| proc.join(pid.to_string()).exists())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn lock_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Other(format!("lock {}: {e}", path.display()))
+}
+
+impl RunLock {
+    /// Take the lock of provider `name` in `dir`. A stale lock is taken
+    /// over; a live one is an error, unless `force_unlock` is set and its
+    /// process is not running.
+    pub fn acquire(dir: &Path, name: &str, force_unlock: bool) -> Result<Self, Error> {
+        let path = dir.join(format!("dns-update-{name}.lock"));
+        // The lock is written aside and linked into place, so it is never
+        // seen half written
+        let draft = dir.join(format!("dns-update-{name}.lock.{}", std::process::id()));
+        fs::write(&draft, format!("{}\n{}\n", std::process::id(), now()))
+            .map_err(|e| lock_error(&draft, e))?;
+        let acquired = Self::link(&draft, &path, name, force_unlock);
+        let _ = fs::remove_file(&draft);
+        acquired?;
+        Ok(Self { path })
+    }
+
+    fn link(draft: &Path, path: &Path, name: &str, force_unlock: bool) -> Result<(), Error> {
+        // One retry after removing a stale or forced lock
+        for _ in 0..2 {
+            match fs::hard_link(draft, path) {
+                Ok(()) => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(lock_error(path, e)),
+            }
+            let contents = match fs::read_to_string(path) {
+                Ok(contents) => contents,
+                // Released in the meantime
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(lock_error(path, e)),
+            };
+            // An unreadable lock was left behind by an older version
+            match Holder::parse(&contents) {
+                Some(holder) => {
+                    let alive = process_alive(holder.pid);
+                    let stale = holder.is_stale(now(), alive);
+                    if !stale && !force_unlock {
+                        return Err(Error::Other(format!(
+                            "{name} is locked by process {} (see {}); use --force-unlock if it is gone",
+                            holder.pid,
+                            path.display()
+                        )));
+                    }
+                    if !holder.may_force(alive) {
+                        return Err(Error::Other(format!(
+                            "{name} is locked by process {}, which is still running (see {})",
+                            holder.pid,
+                            path.display()
+                        )));
+                    }
+                    warn!("Taking over the lock of {name} from process {}", holder.pid)
+                }
+                None => warn!("Taking over the unreadable lock of {name}"),
+            }
+            // Only the lock just read is removed; if another run took it
+            // over first, its new lock is left alone
+            match fs::read_to_string(path) {
+                Ok(again) if again == contents => {}
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(lock_error(path, e)),
+            }
+            match fs::remove_file(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(lock_error(path, e)),
+            }
+        }
+        Err(Error::Other(format!("{name} is locked by another run")))
+    }
+
+    /// Take the locks of every provider in `names`, or none of them.
+    pub fn acquire_all(
+        dir: &Path,
+        names: &[String],
+        force_unlock: bool,
+    ) -> Result<Vec<Self>, Error> {
+        names
+            .iter()
+            .map(|name| Self::acquire(dir, name, force_unlock))
+            .collect()
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = RunLock::acquire(dir.path(), "nextdns", false).unwrap();
+        assert!(RunLock::acquire(dir.path(), "nextdns", false).is_err());
+        // Other providers are unaffected
+        RunLock::acquire(dir.path(), "zone", false).unwrap();
+        drop(lock);
+        RunLock::acquire(dir.path(), "nextdns", false).unwrap();
+    }
+
+    #[test]
+    fn test_force_unlock_spares_running_holders() {
+        let dir = tempfile::tempdir().unwrap();
+        let _held = RunLock::acquire(dir.path(), "nextdns", false).unwrap();
+        let err = RunLock::acquire(dir.path(), "nextdns", true).unwrap_err();
+        assert!(err.to_string().contains("still running"), "{err}");
+    }
+
+    #[test]
+    fn test_stale_locks_are_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dns-update-nextdns.lock");
+        // No process has this PID
+        fs::write(&path, format!("{}\n{}\n", u32::MAX, now())).unwrap();
+        let lock = RunLock::acquire(dir.path(), "nextdns", false).unwrap();
+        let holder = Holder::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(holder.pid, std::process::id());
+        drop(lock);
+
+        fs::write(&path, "garbage").unwrap();
+        RunLock::acquire(dir.path(), "nextdns", false).unwrap();
+    }
+
+    #[test]
+    fn test_old_locks_of_running_holders_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dns-update-nextdns.lock");
+        fs::write(&path, format!("{}\n0\n", std::process::id())).unwrap();
+        assert!(RunLock::acquire(dir.path(), "nextdns", false).is_err());
+        assert!(RunLock::acquire(dir.path(), "nextdns", true).is_err());
+        assert!(path.exists());
+        // No drafts are left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_acquire_all_releases_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let _held = RunLock::acquire(dir.path(), "zone", false).unwrap();
+        let names = vec!["nextdns".to_string(), "zone".to_string()];
+        assert!(RunLock::acquire_all(dir.path(), &names, false).is_err());
+        assert!(!dir.path().join("dns-update-nextdns.lock").exists());
+    }
+
+    #[test]
+    fn test_holder_staleness() {
+        let holder = Holder::parse("42\n1000\n").unwrap();
+        assert_eq!(
+            holder,
+            Holder {
+                pid: 42,
+                since: 1000
+            }
+        );
+        let alive = Holder {
+            pid: std::process::id(),
+            since: 1000,
+        };
+        assert!(!alive.is_stale(1000 + 60, Some(true)));
+        assert!(alive.is_stale(1000 + 60, Some(false)));
+        assert!(!alive.is_stale(1000 + STALE_AFTER.as_secs() + 1, Some(true)));
+        // Without procfs only age counts, and forcing is up to the user
+        assert!(!alive.is_stale(1000 + 60, None));
+        assert!(alive.is_stale(1000 + STALE_AFTER.as_secs() + 1, None));
+        assert!(alive.may_force(None));
+        assert!(!alive.may_force(Some(true)));
+        assert!(Holder::parse("42").is_none());
+    }
+}
@@ -0,0 +1,101 @@
+//! Copying the records of one provider to another, for `migrate`.
+//!
+//! A migration is planned like a sync whose desired records are the
+//! source's, except that the target keeps its records at names the source
+//! has none of.
+
+use crate::core::diff::{Change, Plan};
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::core::scope::Scope;
+
+/// Which records of the source to copy; every record when empty.
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub names: Scope,
+    pub types: Vec<DNSRecordType>,
+}
+
+impl Filter {
+    pub fn selects(&self, record: &DNSRecord) -> bool {
+        self.names.contains(&record.name)
+            && (self.types.is_empty() || self.types.contains(&record.record_type))
+    }
+}
+
+/// Keep only the removals of `plan` that make way for records of `source`,
+/// such as another value at the same name.
+pub fn keep_target_records(plan: &mut Plan, source: &[DNSRecord]) {
+    plan.to_remove
+        .retain(|removed| source.iter().any(|record| record.same_name(removed)));
+}
+
+/// The removals emptying the source of the records `target` now holds, so
+/// records whose copy failed or was skipped stay where they are.
+pub fn delete_copied(source: &[DNSRecord], target: &[DNSRecord]) -> Vec<Change> {
+    source
+        .iter()
+        .filter(|record| target.iter().any(|copy| copy.same_data(record)))
+        .cloned()
+        .map(Change::Remove)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::conflict::Conflicts;
+    use crate::core::diff;
+    use crate::core::provider::ProviderCapabilities;
+    use std::net::Ipv4Addr;
+
+    fn a(name: &str, last: u8) -> DNSRecord {
+        DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_target_keeps_records_the_source_lacks() {
+        let source = [a("nas.example.com", 2), a("www.example.com", 4)];
+        let target = [a("www.example.com", 9), a("printer.example.com", 3)];
+        let mut plan = diff::plan(
+            &source,
+            &target,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        keep_target_records(&mut plan, &source);
+        assert_eq!(
+            plan.changes(),
+            [
+                Change::Add(a("nas.example.com", 2)),
+                Change::Add(a("www.example.com", 4)),
+                Change::Remove(a("www.example.com", 9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_copied_records_are_deleted() {
+        let source = [a("nas.example.com", 2), a("www.example.com", 4)];
+        let target = [a("NAS.example.com", 2).ttl(60), a("www.example.com", 9)];
+        assert_eq!(
+            delete_copied(&source, &target),
+            [Change::Remove(a("nas.example.com", 2))]
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter {
+            names: Scope::new(vec!["*.lab.example.com".to_string()]).unwrap(),
+            types: vec![DNSRecordType::A],
+        };
+        assert!(filter.selects(&a("nas.lab.example.com", 2)));
+        assert!(!filter.selects(&a("www.example.com", 4)));
+        assert!(!filter.selects(&DNSRecord::cname(
+            "files.lab.example.com",
+            "nas.lab.example.com"
+        )));
+        assert!(Filter::default().selects(&a("www.example.com", 4)));
+    }
+}
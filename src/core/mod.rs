@@ -1,3 +1,34 @@
+pub mod acme;
+pub mod cache;
+pub mod canary;
+pub mod checkpoint;
+pub mod compare;
+pub mod conflict;
+pub mod correlation;
+pub mod deadline;
+pub mod diff;
+pub mod dyndns;
+pub mod events;
+pub mod flatten;
+pub mod http;
+pub mod interfaces;
+pub mod limits;
+pub mod lock;
+pub mod migrate;
+pub mod normalize;
+pub mod ownership;
+pub mod planfile;
+pub mod protect;
 pub mod provider;
+pub mod rdata;
+pub mod read_only;
 pub mod record;
+pub mod record_set;
 pub mod registry;
+pub mod removal;
+pub mod resolver;
+pub mod reverse;
+pub mod scope;
+pub mod settings;
+pub mod stats;
+pub mod template;
@@ -0,0 +1,150 @@
+//! Normalization of the desired records before they are compared with the
+//! records of the providers.
+//!
+//! Records written by hand vary in ways DNS does not care about, like case,
+//! trailing root dots or the spelling of an IPv6 address. Published as
+//! written, each variant is a different value to providers that compare
+//! text, and runs keep adding and removing them.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+use crate::error::Error;
+
+/// The `[normalize]` configuration section. Every rule is on by default;
+/// `@` names are only accepted once `apex` is set.
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Normalize {
+    /// Lowercase names and host name values
+    pub lowercase_names: bool,
+    /// Strip the trailing root dot of names and host name values
+    pub strip_trailing_dots: bool,
+    /// Collapse runs of whitespace in TXT data into one space
+    pub collapse_txt_whitespace: bool,
+    /// Write IPv6 addresses in their shortest form
+    pub canonical_ipv6: bool,
+    /// Zone apex that `@` names stand for, e.g. `example.com`
+    pub apex: Option<String>,
+}
+
+impl Default for Normalize {
+    fn default() -> Self {
+        Self {
+            lowercase_names: true,
+            strip_trailing_dots: true,
+            collapse_txt_whitespace: true,
+            canonical_ipv6: true,
+            apex: None,
+        }
+    }
+}
+
+impl Normalize {
+    fn hostname(&self, name: &str) -> String {
+        let mut name = name.trim().to_string();
+        if self.strip_trailing_dots {
+            name = name.trim_end_matches('.').to_string();
+        }
+        if self.lowercase_names {
+            name = name.to_ascii_lowercase();
+        }
+        name
+    }
+
+    /// `record` with the rules applied.
+    pub fn apply(&self, record: &DNSRecord) -> Result<DNSRecord, Error> {
+        let name = if record.name == "@" {
+            let apex = self.apex.as_deref().ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "{record}: `@` names need an apex in the [normalize] section"
+                ))
+            })?;
+            normalize_hostname(apex)
+        } else {
+            self.hostname(&record.name)
+        };
+        let value = match record.record_type {
+            DNSRecordType::CNAME | DNSRecordType::PTR => self.hostname(&record.value),
+            DNSRecordType::AAAA if self.canonical_ipv6 => record
+                .value
+                .trim()
+                .parse::<std::net::Ipv6Addr>()
+                .map(|address| address.to_string())
+                .unwrap_or_else(|_| record.value.clone()),
+            DNSRecordType::TXT if self.collapse_txt_whitespace => record
+                .value
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => record.value.clone(),
+        };
+        Ok(DNSRecord {
+            name,
+            value,
+            ..record.clone()
+        })
+    }
+
+    pub fn apply_all(&self, records: Vec<DNSRecord>) -> Result<Vec<DNSRecord>, Error> {
+        records.iter().map(|record| self.apply(record)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(record_type: DNSRecordType, name: &str, value: &str) -> DNSRecord {
+        DNSRecord::new(record_type, name, value)
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let rules = Normalize::default();
+        let normalized = |r: DNSRecord| rules.apply(&r).unwrap();
+        assert_eq!(
+            normalized(record(
+                DNSRecordType::CNAME,
+                "WWW.Example.com.",
+                "Host.Example.com."
+            )),
+            record(DNSRecordType::CNAME, "www.example.com", "host.example.com")
+        );
+        assert_eq!(
+            normalized(record(
+                DNSRecordType::AAAA,
+                "nas.example.com",
+                "0:0:0:0:0:0:0:1"
+            ))
+            .value,
+            "::1"
+        );
+        assert_eq!(
+            normalized(record(
+                DNSRecordType::TXT,
+                "example.com",
+                " v=spf1   mx  -all "
+            ))
+            .value,
+            "v=spf1 mx -all"
+        );
+    }
+
+    #[test]
+    fn test_apex_and_disabled_rules() {
+        let apex = record(DNSRecordType::A, "@", "10.0.0.1");
+        assert!(Normalize::default().apply(&apex).is_err());
+
+        let rules = Normalize {
+            lowercase_names: false,
+            collapse_txt_whitespace: false,
+            apex: Some("Example.com.".to_string()),
+            ..Normalize::default()
+        };
+        assert_eq!(rules.apply(&apex).unwrap().name, "example.com");
+        let txt = record(DNSRecordType::TXT, "Mixed.example.com", "a  b");
+        assert_eq!(rules.apply(&txt).unwrap(), txt);
+    }
+}
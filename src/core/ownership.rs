@@ -0,0 +1,119 @@
+//! Ownership markers for zones shared with records managed elsewhere.
+//!
+//! For every name and type it creates, the tool publishes a TXT record at
+//! `_dns-update.<name>` holding `heritage=dns-update,type=<TYPE>`. Records
+//! without a marker are never updated or removed.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use crate::core::record::{DNSRecord, DNSRecordType};
+
+pub const MARKER_LABEL: &str = "_dns-update";
+const WILDCARD_LABEL: &str = "_wildcard";
+const MARKER_PREFIX: &str = "heritage=dns-update,type=";
+
+/// Names and types created by this tool.
+pub type Owned = HashSet<(String, DNSRecordType)>;
+
+/// How records created by this tool are told apart from the rest of a zone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Ownership {
+    /// TXT marker records next to every managed name
+    #[default]
+    Txt,
+    /// The whole zone belongs to this tool
+    Zone,
+}
+
+impl Ownership {
+    /// Whether changes to `record` publish and release a marker. TXT records
+    /// are only created on demand, e.g. for ACME challenges, and are never
+    /// synchronized, so they go without one.
+    pub fn marks(self, record: &DNSRecord) -> bool {
+        self == Ownership::Txt && record.record_type != DNSRecordType::TXT
+    }
+}
+
+/// Name of the marker record for `name`.
+pub fn marker_name(name: &str) -> String {
+    // A wildcard label is only valid in the leftmost position
+    match name.strip_prefix("*.") {
+        Some(rest) => format!("{MARKER_LABEL}.{WILDCARD_LABEL}.{rest}"),
+        None => format!("{MARKER_LABEL}.{name}"),
+    }
+}
+
+/// Name and value of the marker record claiming `record`.
+pub fn marker(record: &DNSRecord) -> (String, String) {
+    (
+        marker_name(&record.name),
+        format!("{MARKER_PREFIX}{}", record.record_type.as_str()),
+    )
+}
+
+/// The name and type claimed by a TXT record, if it is a marker.
+pub fn parse_marker(name: &str, value: &str) -> Option<(String, DNSRecordType)> {
+    let rest = name.strip_prefix(MARKER_LABEL)?.strip_prefix('.')?;
+    let name = match rest
+        .strip_prefix(WILDCARD_LABEL)
+        .and_then(|r| r.strip_prefix('.'))
+    {
+        Some(parent) => format!("*.{parent}"),
+        None => rest.to_string(),
+    };
+    let record_type = value
+        .trim_matches('"')
+        .strip_prefix(MARKER_PREFIX)?
+        .parse()
+        .ok()?;
+    Some((name, record_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str) -> DNSRecord {
+        DNSRecord {
+            record_type: DNSRecordType::AAAA,
+            name: name.to_string(),
+            value: "::1".to_string(),
+            ttl: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_marker_round_trip() {
+        let (name, value) = marker(&record("www.example.com"));
+        assert_eq!(name, "_dns-update.www.example.com");
+        assert_eq!(value, "heritage=dns-update,type=AAAA");
+        assert_eq!(
+            parse_marker(&name, &format!("\"{value}\"")),
+            Some(("www.example.com".to_string(), DNSRecordType::AAAA))
+        );
+    }
+
+    #[test]
+    fn test_wildcard_marker() {
+        let (name, value) = marker(&record("*.example.com"));
+        assert_eq!(name, "_dns-update._wildcard.example.com");
+        assert_eq!(
+            parse_marker(&name, &value),
+            Some(("*.example.com".to_string(), DNSRecordType::AAAA))
+        );
+    }
+
+    #[test]
+    fn test_other_txt_records_are_not_markers() {
+        assert_eq!(parse_marker("example.com", "v=spf1 -all"), None);
+        assert_eq!(parse_marker("_dns-update.example.com", "v=spf1 -all"), None);
+        assert_eq!(
+            parse_marker("_dns-updater.example.com", "heritage=dns-update,type=A"),
+            None
+        );
+    }
+}
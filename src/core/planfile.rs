@@ -0,0 +1,92 @@
+//! Plans saved by `dns-update plan --out` and applied by `dns-update apply`.
+//!
+//! A saved plan holds the changes of each provider along with a
+//! fingerprint of the records the provider held when it was planned. The
+//! changes are only applied as long as the provider still holds those
+//! records, so what gets applied is exactly what was reviewed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::core::checkpoint::fingerprint;
+use crate::core::diff::Change;
+use crate::core::record::DNSRecord;
+use crate::error::Error;
+use crate::providers::file::write_atomic;
+
+/// The planned changes of one provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderPlan {
+    /// Fingerprint of the provider's records the changes were planned against
+    pub state: String,
+    /// Changes in the order they are applied
+    pub changes: Vec<Change>,
+}
+
+impl ProviderPlan {
+    pub fn new(current: &[DNSRecord], changes: Vec<Change>) -> Self {
+        Self {
+            state: fingerprint(current),
+            changes,
+        }
+    }
+
+    /// Whether the provider still holds the records the plan was made for.
+    pub fn matches(&self, current: &[DNSRecord]) -> bool {
+        self.state == fingerprint(current)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlanFile {
+    pub providers: BTreeMap<String, ProviderPlan>,
+}
+
+fn plan_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::Other(format!("plan {}: {e}", path.display()))
+}
+
+impl PlanFile {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|e| plan_error(path, e))?;
+        serde_json::from_str(&contents).map_err(|e| plan_error(path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| plan_error(path, e))?;
+        write_atomic(path, &contents).map_err(|e| plan_error(path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_save_load_and_drift() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+        let current = vec![DNSRecord::a("nas.example.com", Ipv4Addr::new(10, 0, 0, 1))];
+        let desired = DNSRecord::a("nas.example.com", Ipv4Addr::new(10, 0, 0, 2));
+        let planned = ProviderPlan::new(
+            &current,
+            vec![
+                Change::Add(desired.clone()),
+                Change::Remove(current[0].clone()),
+            ],
+        );
+        let plan = PlanFile {
+            providers: BTreeMap::from([("lan".to_string(), planned)]),
+        };
+        plan.save(&path).unwrap();
+        let loaded = PlanFile::load(&path).unwrap();
+        assert_eq!(loaded, plan);
+
+        let lan = &loaded.providers["lan"];
+        assert!(lan.matches(&current));
+        assert!(!lan.matches(&[desired]));
+        assert!(!lan.matches(&[]));
+    }
+}
@@ -0,0 +1,142 @@
+//! Records a sync must never delete or modify, whatever the desired records
+//! say, like the apex or every TXT record.
+//!
+//! Planned changes to protected records are set aside as blocked rather
+//! than dropped, so plans and runs show what the desired records asked for.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::core::conflict::conflicting;
+use crate::core::diff::{Change, Plan};
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::core::scope::Scope;
+
+/// The `[protect]` configuration section. A record is protected when its
+/// name matches one of `names` or its type is one of `types`.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Protect {
+    /// Name patterns such as `example.com` or `*.mail.example.com`
+    pub names: Scope,
+    /// Record types, e.g. `["TXT"]`
+    pub types: Vec<DNSRecordType>,
+}
+
+impl Protect {
+    pub fn covers(&self, record: &DNSRecord) -> bool {
+        // An empty scope matches every name, which is not what no names means
+        (!self.names.is_empty() && self.names.contains(&record.name))
+            || self.types.contains(&record.record_type)
+    }
+
+    /// Move the changes of `plan` that would delete or modify a protected
+    /// record to its blocked changes. Additions are blocked along with the
+    /// removals they were meant to replace, so a blocked change of value
+    /// does not leave the old and new values side by side.
+    pub fn block(&self, plan: &mut Plan) {
+        let mut blocked: Vec<Change> = Vec::new();
+        for record in take_if(&mut plan.to_update, |r| self.covers(r)) {
+            blocked.push(Change::Update(record));
+        }
+        let removed = take_if(&mut plan.to_remove, |r| self.covers(r));
+        let replaced = take_if(&mut plan.replaced, |r| self.covers(r));
+        let replacing = |add: &DNSRecord| {
+            removed.iter().any(|r| r.same_name(add)) || replaced.iter().any(|r| conflicting(r, add))
+        };
+        for record in take_if(&mut plan.to_add, replacing) {
+            blocked.push(Change::Add(record));
+        }
+        blocked.extend(replaced.iter().chain(&removed).cloned().map(Change::Remove));
+        plan.blocked.extend(blocked);
+    }
+}
+
+// Remove the records matching `f` from `records`, returning them
+fn take_if(records: &mut Vec<DNSRecord>, f: impl Fn(&DNSRecord) -> bool) -> Vec<DNSRecord> {
+    let (taken, kept) = std::mem::take(records).into_iter().partition(|r| f(r));
+    *records = kept;
+    taken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::conflict::{ConflictPolicy, Conflicts};
+    use crate::core::diff;
+    use crate::core::provider::ProviderCapabilities;
+    use std::net::Ipv4Addr;
+
+    fn a(name: &str, last: u8) -> DNSRecord {
+        DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    fn protect(names: &[&str], types: Vec<DNSRecordType>) -> Protect {
+        Protect {
+            names: Scope::new(names.iter().map(|n| n.to_string()).collect()).unwrap(),
+            types,
+        }
+    }
+
+    #[test]
+    fn test_changes_to_protected_records_are_blocked() {
+        let current = [
+            a("example.com", 1),
+            a("nas.example.com", 2),
+            a("old.example.com", 3),
+        ];
+        let desired = [
+            a("example.com", 9),
+            a("nas.example.com", 2),
+            a("new.example.com", 4),
+        ];
+        let mut plan = diff::plan(
+            &desired,
+            &current,
+            &ProviderCapabilities::default(),
+            None,
+            &Conflicts::default(),
+        );
+        protect(&["example.com"], Vec::new()).block(&mut plan);
+
+        assert_eq!(plan.to_add, [a("new.example.com", 4)]);
+        assert_eq!(plan.to_remove, [a("old.example.com", 3)]);
+        assert_eq!(
+            plan.blocked,
+            [
+                Change::Add(a("example.com", 9)),
+                Change::Remove(a("example.com", 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocked_replacements_keep_the_conflicting_record() {
+        let txt = DNSRecord {
+            record_type: DNSRecordType::TXT,
+            name: "www.example.com".to_string(),
+            value: "v=spf1 -all".to_string(),
+            ttl: None,
+            comment: None,
+        };
+        let capabilities = ProviderCapabilities {
+            record_types: vec![DNSRecordType::TXT, DNSRecordType::CNAME],
+            ..ProviderCapabilities::default()
+        };
+        let conflicts = Conflicts {
+            policy: ConflictPolicy::Replace,
+            ..Conflicts::default()
+        };
+        let desired = [DNSRecord::cname("www.example.com", "example.com")];
+        let current = vec![txt.clone()];
+        let mut plan = diff::plan(&desired, &current, &capabilities, None, &conflicts);
+        assert_eq!(plan.replaced, current);
+
+        protect(&[], vec![DNSRecordType::TXT]).block(&mut plan);
+        assert!(plan.is_empty());
+        assert_eq!(
+            plan.blocked,
+            [Change::Add(desired[0].clone()), Change::Remove(txt)]
+        );
+    }
+}
@@ -1,14 +1,81 @@
-use crate::core::record::DNSRecord;
+use crate::core::ownership::Owned;
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::core::settings::SettingChange;
 use crate::error::Error;
 use async_trait::async_trait;
+use std::time::Duration;
+
+/// What a provider can store, so plans can be adapted up front instead of
+/// failing with opaque API errors.
+#[derive(Debug, Clone)]
+pub struct ProviderCapabilities {
+    pub record_types: Vec<DNSRecordType>,
+    /// Whether per-record TTLs are stored; if not, desired TTLs are dropped
+    pub ttl: bool,
+    /// Whether names like `*.example.com` are accepted
+    pub wildcards: bool,
+    /// Whether `update_record` can change the value of an existing record
+    pub update_in_place: bool,
+    /// Whether TXT records can be added and deleted on demand, e.g. for
+    /// ACME DNS-01 challenges
+    pub txt: bool,
+}
+
+impl Default for ProviderCapabilities {
+    fn default() -> Self {
+        Self {
+            record_types: vec![DNSRecordType::A, DNSRecordType::AAAA, DNSRecordType::CNAME],
+            ttl: true,
+            wildcards: true,
+            update_in_place: false,
+            txt: false,
+        }
+    }
+}
 
 #[async_trait]
 pub trait DNSProvider: Send + Sync {
     #[allow(dead_code)]
     fn name(&self) -> &str;
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
     async fn list_records(&self) -> Result<Vec<DNSRecord>, Error>;
+
+    /// Names and types created by this tool, for providers whose zone also
+    /// holds records managed elsewhere. `None` means every listed record
+    /// belongs to this tool.
+    async fn owned_records(&self) -> Result<Option<Owned>, Error> {
+        Ok(None)
+    }
+
     async fn add_record(&self, record: DNSRecord) -> Result<(), Error>;
-    #[allow(dead_code)]
     async fn update_record(&self, record: DNSRecord) -> Result<(), Error>;
     async fn delete_record(&self, record: DNSRecord) -> Result<(), Error>;
+
+    /// How long applying `changes` changes is expected to take, for
+    /// providers that throttle requests to stay within an API quota.
+    async fn estimate(&self, _changes: usize) -> Option<Duration> {
+        None
+    }
+
+    /// Persist buffered changes once all records of a run have been applied.
+    async fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Changes bringing the configured settings beyond records, like the
+    /// blocklists of a NextDNS profile, to their desired state.
+    async fn plan_settings(&self) -> Result<Vec<SettingChange>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn apply_setting(&self, change: &SettingChange) -> Result<(), Error> {
+        Err(Error::InvalidInput(format!(
+            "{} does not manage settings: {change}",
+            self.name()
+        )))
+    }
 }
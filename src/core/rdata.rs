@@ -0,0 +1,262 @@
+//! Values of the record types whose data has several fields, read from and
+//! written in their zone file presentation format. Records keep the text
+//! form in `DNSRecord::value`; these types check it and give the canonical
+//! form records are compared by.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::core::record::normalize_hostname;
+
+/// An SSHFP record (RFC 4255): the fingerprint of a host key, e.g.
+/// `4 2 9d9a...` for the SHA-256 fingerprint of an Ed25519 key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sshfp {
+    /// Key algorithm: 1 RSA, 2 DSA, 3 ECDSA, 4 Ed25519, 6 Ed448
+    pub algorithm: u8,
+    /// Fingerprint algorithm: 1 SHA-1, 2 SHA-256
+    pub fingerprint_type: u8,
+    /// Hex digits, lowercase
+    pub fingerprint: String,
+}
+
+impl FromStr for Sshfp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [algorithm, fingerprint_type, fingerprint @ ..] = fields.as_slice() else {
+            return Err(format!("expected `algorithm type fingerprint`: {s}"));
+        };
+        let algorithm = algorithm
+            .parse()
+            .map_err(|_| format!("invalid SSHFP algorithm: {algorithm}"))?;
+        let fingerprint_type = fingerprint_type
+            .parse()
+            .map_err(|_| format!("invalid SSHFP fingerprint type: {fingerprint_type}"))?;
+        // Long fingerprints may be split in groups
+        let fingerprint = fingerprint.concat().to_ascii_lowercase();
+        if fingerprint.is_empty() || !fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid SSHFP fingerprint: {fingerprint}"));
+        }
+        let expected = match fingerprint_type {
+            1 => Some(40),
+            2 => Some(64),
+            _ => None,
+        };
+        if let Some(expected) = expected.filter(|&len| len != fingerprint.len()) {
+            return Err(format!(
+                "SSHFP fingerprint of type {fingerprint_type} has {expected} hex digits, not {}",
+                fingerprint.len()
+            ));
+        }
+        Ok(Self {
+            algorithm,
+            fingerprint_type,
+            fingerprint,
+        })
+    }
+}
+
+impl fmt::Display for Sshfp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.algorithm, self.fingerprint_type, self.fingerprint
+        )
+    }
+}
+
+/// A NAPTR record (RFC 3403), e.g. for ENUM or SIP service discovery:
+/// `100 10 "u" "E2U+sip" "!^.*$!sip:info@example.com!" .`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Naptr {
+    pub order: u16,
+    pub preference: u16,
+    /// Flags like `u` or `s`, lowercase
+    pub flags: String,
+    pub service: String,
+    pub regexp: String,
+    /// Fully qualified with the root dot, or `.` when the regexp applies
+    pub replacement: String,
+}
+
+impl FromStr for Naptr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("expected `order preference flags service regexp replacement`: {s}");
+        let fields = character_strings(s).ok_or_else(invalid)?;
+        let [order, preference, flags, service, regexp, replacement] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        let order = order
+            .parse()
+            .map_err(|_| format!("invalid NAPTR order: {order}"))?;
+        let preference = preference
+            .parse()
+            .map_err(|_| format!("invalid NAPTR preference: {preference}"))?;
+        if !flags.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(format!("invalid NAPTR flags: {flags}"));
+        }
+        let replacement = match normalize_hostname(replacement) {
+            name if name.is_empty() => ".".to_string(),
+            name => format!("{name}."),
+        };
+        if replacement != "." && !regexp.is_empty() {
+            return Err("a NAPTR record has a regexp or a replacement, not both".to_string());
+        }
+        Ok(Self {
+            order,
+            preference,
+            flags: flags.to_ascii_lowercase(),
+            service: service.clone(),
+            regexp: regexp.clone(),
+            replacement,
+        })
+    }
+}
+
+impl fmt::Display for Naptr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.order,
+            self.preference,
+            quoted(&self.flags),
+            quoted(&self.service),
+            quoted(&self.regexp),
+            self.replacement
+        )
+    }
+}
+
+/// The maximum length of one character-string, in bytes.
+const MAX_STRING_LEN: usize = 255;
+
+/// TXT data as quoted character-strings of at most 255 bytes each, as zone
+/// files and DNS messages hold it, so long values like DKIM keys fit:
+/// `"v=DKIM1; k=rsa; p=MIIBIj..." "...IDAQAB"`.
+pub fn txt_strings(value: &str) -> String {
+    let mut strings = Vec::new();
+    let mut rest = value;
+    loop {
+        let mut end = rest.len().min(MAX_STRING_LEN);
+        // Never split a character, whose bytes go in one string
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        strings.push(quoted(&rest[..end]));
+        rest = &rest[end..];
+        if rest.is_empty() {
+            return strings.join(" ");
+        }
+    }
+}
+
+/// TXT data from its character-strings, joined into one value as written
+/// in the desired records. Text that is not quoted is taken as it is.
+pub fn join_txt(data: &str) -> String {
+    match character_strings(data) {
+        Some(strings) if data.trim_start().starts_with('"') => strings.concat(),
+        _ => data.trim().trim_matches('"').to_string(),
+    }
+}
+
+// The fields of `s`: words, or quoted strings that may hold spaces and
+// `\"` escapes
+fn character_strings(s: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = s.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut field = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => field.push(chars.next()?),
+                    c => field.push(c),
+                }
+            }
+            fields.push(field);
+        } else {
+            let mut field = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                field.push(c);
+            }
+            fields.push(field);
+        }
+    }
+    Some(fields)
+}
+
+fn quoted(field: &str) -> String {
+    format!("\"{}\"", field.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "9D9A8E7B6C5D4E3F2A1B0C9D8E7F6A5B4C3D2E1F0A9B8C7D6E5F4A3B2C1D0E9F";
+
+    #[test]
+    fn test_sshfp() {
+        let sshfp: Sshfp = format!("4 2 {SHA256}").parse().unwrap();
+        assert_eq!(sshfp.algorithm, 4);
+        assert_eq!(sshfp.to_string(), format!("4 2 {}", SHA256.to_lowercase()));
+        let split = format!("4  2 {} {}", &SHA256[..32], &SHA256[32..]);
+        assert_eq!(split.parse::<Sshfp>().unwrap(), sshfp);
+        assert!(format!("4 1 {SHA256}").parse::<Sshfp>().is_err());
+        assert!("4 2 not-hex".parse::<Sshfp>().is_err());
+        assert!("ed25519 2 abcd".parse::<Sshfp>().is_err());
+    }
+
+    #[test]
+    fn test_naptr() {
+        let naptr: Naptr = r#"100 10 "U" "E2U+sip" "!^.*$!sip:info@example.com!" ."#
+            .parse()
+            .unwrap();
+        assert_eq!((naptr.order, naptr.preference), (100, 10));
+        assert_eq!(naptr.regexp, "!^.*$!sip:info@example.com!");
+        assert_eq!(
+            naptr.to_string(),
+            r#"100 10 "u" "E2U+sip" "!^.*$!sip:info@example.com!" ."#
+        );
+        let srv: Naptr = r#"10 0 "s" "SIP+D2U" "" _sip._udp.Example.com"#.parse().unwrap();
+        assert_eq!(srv.replacement, "_sip._udp.example.com.");
+        assert_eq!(srv.to_string().parse::<Naptr>().unwrap(), srv);
+        assert!(
+            r#"10 0 "s" "SIP+D2U" "!^.*$!x!" _sip._udp.example.com"#
+                .parse::<Naptr>()
+                .is_err()
+        );
+        assert!(r#"10 0 "s" "SIP+D2U" "" "#.parse::<Naptr>().is_err());
+        assert!(r#"10 0 "s "#.parse::<Naptr>().is_err());
+    }
+
+    #[test]
+    fn test_txt_strings() {
+        assert_eq!(txt_strings("v=spf1 -all"), r#""v=spf1 -all""#);
+        assert_eq!(txt_strings(""), r#""""#);
+        let key = format!("v=DKIM1; k=rsa; p={}", "A".repeat(400));
+        let data = txt_strings(&key);
+        let strings = character_strings(&data).unwrap();
+        assert_eq!(strings.len(), 2);
+        assert_eq!(strings[0].len(), 255);
+        assert_eq!(join_txt(&data), key);
+        // Multi-byte characters are not split across strings
+        let accents = "é".repeat(200);
+        let strings = character_strings(&txt_strings(&accents)).unwrap();
+        assert_eq!(strings[0].len(), 254);
+        assert_eq!(strings.concat(), accents);
+        assert_eq!(join_txt(r#""say \"hi\"""#), r#"say "hi""#);
+        assert_eq!(join_txt("unquoted"), "unquoted");
+    }
+}
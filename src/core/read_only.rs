@@ -0,0 +1,95 @@
+//! Providers configured with `read_only = true`, which runs only compare
+//! with the desired records: what a sync would change is reported as drift
+//! and never applied. Useful while a new provider is not yet trusted.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::core::ownership::Owned;
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
+use crate::core::record::DNSRecord;
+use crate::core::settings::SettingChange;
+use crate::error::Error;
+
+/// A provider whose records and settings can be read but not changed.
+pub struct ReadOnlyProvider {
+    inner: Arc<dyn DNSProvider>,
+}
+
+impl ReadOnlyProvider {
+    pub fn new(inner: Arc<dyn DNSProvider>) -> Self {
+        Self { inner }
+    }
+
+    fn refuse(&self, change: String) -> Result<(), Error> {
+        Err(Error::InvalidInput(format!(
+            "{} is read-only, not applying: {change}",
+            self.inner.name()
+        )))
+    }
+}
+
+#[async_trait]
+impl DNSProvider for ReadOnlyProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+        self.inner.list_records().await
+    }
+
+    async fn owned_records(&self) -> Result<Option<Owned>, Error> {
+        self.inner.owned_records().await
+    }
+
+    async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.refuse(format!("add {record}"))
+    }
+
+    async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.refuse(format!("update {record}"))
+    }
+
+    async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.refuse(format!("delete {record}"))
+    }
+
+    async fn estimate(&self, changes: usize) -> Option<Duration> {
+        self.inner.estimate(changes).await
+    }
+
+    async fn plan_settings(&self) -> Result<Vec<SettingChange>, Error> {
+        self.inner.plan_settings().await
+    }
+
+    async fn apply_setting(&self, change: &SettingChange) -> Result<(), Error> {
+        self.refuse(change.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{InMemoryProvider, Operation};
+    use std::net::Ipv4Addr;
+
+    #[tokio::test]
+    async fn test_changes_never_reach_the_provider() {
+        let record = DNSRecord::a("nas.lab.example.com", Ipv4Addr::new(10, 0, 0, 2));
+        let inner = Arc::new(InMemoryProvider::new(vec![record.clone()]));
+        let provider = ReadOnlyProvider::new(inner.clone());
+
+        assert_eq!(provider.list_records().await.unwrap(), vec![record.clone()]);
+        assert!(provider.add_record(record.clone()).await.is_err());
+        assert!(provider.update_record(record.clone()).await.is_err());
+        assert!(provider.delete_record(record).await.is_err());
+        let operations: Vec<Operation> = inner.calls().iter().map(|c| c.operation).collect();
+        assert_eq!(operations, [Operation::List]);
+    }
+}
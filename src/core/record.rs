@@ -1,17 +1,286 @@
+use idna::AsciiDenyList;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::core::rdata::{Naptr, Sshfp};
+use crate::error::Error;
+
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub enum DNSRecordType {
     A,
     AAAA,
     CNAME,
+    NAPTR,
+    PTR,
+    SSHFP,
+    TXT,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+impl DNSRecordType {
+    /// The type of a record pointing at `value`: A or AAAA for addresses,
+    /// CNAME for anything else.
+    pub fn for_value(value: &str) -> Self {
+        if value.parse::<std::net::Ipv4Addr>().is_ok() {
+            DNSRecordType::A
+        } else if value.parse::<std::net::Ipv6Addr>().is_ok() {
+            DNSRecordType::AAAA
+        } else {
+            DNSRecordType::CNAME
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DNSRecordType::A => "A",
+            DNSRecordType::AAAA => "AAAA",
+            DNSRecordType::CNAME => "CNAME",
+            DNSRecordType::NAPTR => "NAPTR",
+            DNSRecordType::PTR => "PTR",
+            DNSRecordType::SSHFP => "SSHFP",
+            DNSRecordType::TXT => "TXT",
+        }
+    }
+}
+
+impl fmt::Display for DNSRecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DNSRecordType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(DNSRecordType::A),
+            "AAAA" => Ok(DNSRecordType::AAAA),
+            "CNAME" => Ok(DNSRecordType::CNAME),
+            "NAPTR" => Ok(DNSRecordType::NAPTR),
+            "PTR" => Ok(DNSRecordType::PTR),
+            "SSHFP" => Ok(DNSRecordType::SSHFP),
+            "TXT" => Ok(DNSRecordType::TXT),
+            _ => Err(format!("unsupported record type: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct DNSRecord {
     pub record_type: DNSRecordType,
     pub name: String,
     pub value: String,
     pub ttl: Option<u32>,
+    /// Where the record came from, e.g. the trailing comment of its source
+    /// line; written by providers that can store it, never used to match records
+    pub comment: Option<String>,
+}
+
+impl DNSRecord {
+    /// A record without TTL or comment, e.g.
+    /// `DNSRecord::a("nas.lab.example.com", ip).ttl(300)`.
+    pub fn new(
+        record_type: DNSRecordType,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        Self {
+            record_type,
+            name: name.into(),
+            value: value.into(),
+            ttl: None,
+            comment: None,
+        }
+    }
+
+    pub fn a(name: impl Into<String>, address: Ipv4Addr) -> Self {
+        Self::new(DNSRecordType::A, name, address.to_string())
+    }
+
+    pub fn aaaa(name: impl Into<String>, address: Ipv6Addr) -> Self {
+        Self::new(DNSRecordType::AAAA, name, address.to_string())
+    }
+
+    pub fn cname(name: impl Into<String>, target: impl Into<String>) -> Self {
+        Self::new(DNSRecordType::CNAME, name, target)
+    }
+
+    pub fn ptr(name: impl Into<String>, target: impl Into<String>) -> Self {
+        Self::new(DNSRecordType::PTR, name, target)
+    }
+
+    pub fn txt(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self::new(DNSRecordType::TXT, name, text)
+    }
+
+    pub fn ttl(self, ttl: impl Into<Option<u32>>) -> Self {
+        Self {
+            ttl: ttl.into(),
+            ..self
+        }
+    }
+
+    pub fn comment(self, comment: impl Into<Option<String>>) -> Self {
+        Self {
+            comment: comment.into(),
+            ..self
+        }
+    }
+}
+
+/// `name [ttl] TYPE value`, e.g. `www.example.com 300 A 10.0.0.1`; the
+/// comment is left out.
+impl fmt::Display for DNSRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(ttl) = self.ttl {
+            write!(f, " {ttl}")?;
+        }
+        write!(f, " {} {}", self.record_type, self.value)
+    }
+}
+
+impl std::str::FromStr for DNSRecord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected `name [ttl] TYPE value`: {s}");
+        let (name, rest) = s
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(invalid)?;
+        let rest = rest.trim_start();
+        let (ttl, rest) = match rest.split_once(char::is_whitespace) {
+            Some((ttl, rest)) if ttl.bytes().all(|b| b.is_ascii_digit()) => {
+                (Some(ttl.parse().map_err(|_| invalid())?), rest.trim_start())
+            }
+            _ => (None, rest),
+        };
+        let (record_type, value) = rest.split_once(char::is_whitespace).ok_or_else(invalid)?;
+        // TXT data may contain spaces, so the value is the rest of the line
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self::new(record_type.parse()?, name, value).ttl(ttl))
+    }
+}
+
+/// Canonical form of a host name for comparisons: lowercase, without
+/// surrounding whitespace or the trailing root dot.
+pub fn normalize_hostname(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `name` is a usable host name in ASCII form: labels of letters,
+/// digits, `-` and `_`, optionally behind a `*.` wildcard and followed by
+/// the root dot. A numeric last label is rejected, so mistyped addresses
+/// like `10.0.0.256` are not taken for names.
+pub fn is_valid_hostname(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    let name = name.strip_prefix("*.").unwrap_or(name);
+    if name.is_empty() || name.len() > 253 {
+        return false;
+    }
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    };
+    name.split('.').all(valid_label)
+        && !name
+            .rsplit('.')
+            .next()
+            .is_some_and(|last| last.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The ASCII form of a host name as providers expect it, with Unicode
+/// labels converted to punycode (`bücher.example` → `xn--bcher-kva.example`).
+pub fn to_ascii_hostname(name: &str) -> Result<String, Error> {
+    if name.is_ascii() {
+        return Ok(name.to_string());
+    }
+    idna::domain_to_ascii_cow(name.as_bytes(), AsciiDenyList::URL)
+        .map(String::from)
+        .map_err(|_| Error::InvalidInput(format!("invalid host name: {name}")))
+}
+
+/// The Unicode form of a host name, for display. Names with invalid
+/// punycode labels are returned unchanged.
+pub fn to_unicode_hostname(name: &str) -> String {
+    let punycode = name.split('.').any(|label| {
+        label
+            .get(..4)
+            .is_some_and(|p| p.eq_ignore_ascii_case("xn--"))
+    });
+    if !punycode {
+        return name.to_string();
+    }
+    match idna::domain_to_unicode(name) {
+        (unicode, Ok(())) => unicode,
+        (_, Err(_)) => name.to_string(),
+    }
+}
+
+impl DNSRecord {
+    /// The record with its name, and the target of CNAME and PTR records,
+    /// in ASCII form.
+    pub fn to_ascii(&self) -> Result<Self, Error> {
+        let value = match self.record_type {
+            DNSRecordType::CNAME | DNSRecordType::PTR => to_ascii_hostname(&self.value)?,
+            _ => self.value.clone(),
+        };
+        Ok(Self {
+            name: to_ascii_hostname(&self.name)?,
+            value,
+            ..self.clone()
+        })
+    }
+
+    /// The value in canonical form: host names are normalized and addresses
+    /// rewritten in their shortest form, so `2001:DB8::0001` and
+    /// `2001:db8::1` compare equal. TXT data is kept as is.
+    pub fn normalized_value(&self) -> String {
+        match self.record_type {
+            DNSRecordType::A | DNSRecordType::AAAA => self
+                .value
+                .trim()
+                .parse::<std::net::IpAddr>()
+                .map(|address| address.to_string())
+                .unwrap_or_else(|_| self.value.trim().to_string()),
+            DNSRecordType::CNAME | DNSRecordType::PTR => normalize_hostname(&self.value),
+            DNSRecordType::NAPTR => self
+                .value
+                .parse::<Naptr>()
+                .map(|naptr| naptr.to_string())
+                .unwrap_or_else(|_| self.value.trim().to_string()),
+            DNSRecordType::SSHFP => self
+                .value
+                .parse::<Sshfp>()
+                .map(|sshfp| sshfp.to_string())
+                .unwrap_or_else(|_| self.value.trim().to_string()),
+            DNSRecordType::TXT => self.value.clone(),
+        }
+    }
+
+    /// Whether both records are of the same type at the same name.
+    pub fn same_name(&self, other: &DNSRecord) -> bool {
+        self.record_type == other.record_type
+            && normalize_hostname(&self.name) == normalize_hostname(&other.name)
+    }
+
+    /// Whether both records hold the same data, whatever their TTL and
+    /// comment.
+    pub fn same_data(&self, other: &DNSRecord) -> bool {
+        self.same_name(other) && self.normalized_value() == other.normalized_value()
+    }
 }
 
 #[cfg(test)]
@@ -33,6 +302,7 @@ mod tests {
                 name: name.to_string(),
                 value: value.to_string(),
                 ttl: None,
+                comment: None,
             })
         } else if value.parse::<std::net::Ipv6Addr>().is_ok() {
             Ok(DNSRecord {
@@ -40,6 +310,7 @@ mod tests {
                 name: name.to_string(),
                 value: value.to_string(),
                 ttl: None,
+                comment: None,
             })
         } else if value.contains('.') {
             Ok(DNSRecord {
@@ -47,6 +318,7 @@ mod tests {
                 name: name.to_string(),
                 value: value.to_string(),
                 ttl: None,
+                comment: None,
             })
         } else {
             Err("Unknown record type")
@@ -97,12 +369,14 @@ mod tests {
                 name: "a.com".into(),
                 value: "1.1.1.1".into(),
                 ttl: None,
+                comment: None,
             },
             DNSRecord {
                 record_type: DNSRecordType::CNAME,
                 name: "b.com".into(),
                 value: "c.com".into(),
                 ttl: None,
+                comment: None,
             },
         ];
         let new = [
@@ -111,18 +385,21 @@ mod tests {
                 name: "a.com".into(),
                 value: "2.2.2.2".into(),
                 ttl: None,
+                comment: None,
             },
             DNSRecord {
                 record_type: DNSRecordType::CNAME,
                 name: "b.com".into(),
                 value: "c.com".into(),
                 ttl: None,
+                comment: None,
             },
             DNSRecord {
                 record_type: DNSRecordType::AAAA,
                 name: "ipv6.com".into(),
                 value: "2001:db8::1".into(),
                 ttl: None,
+                comment: None,
             },
         ];
         let old_set: HashSet<_> = old.iter().collect();
@@ -134,4 +411,174 @@ mod tests {
         assert_eq!(to_add.len(), 2); // new A for a.com and new AAAA
         assert_eq!(to_remove.len(), 1); // old A for a.com
     }
+
+    #[test]
+    fn test_normalized_comparisons() {
+        assert_eq!(normalize_hostname(" WWW.Example.com. "), "www.example.com");
+        let record = |record_type, name: &str, value: &str| DNSRecord {
+            record_type,
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl: None,
+            comment: None,
+        };
+        assert!(
+            record(DNSRecordType::AAAA, "Host.example.com.", "2001:DB8::0001").same_data(&record(
+                DNSRecordType::AAAA,
+                "host.example.com",
+                "2001:db8::1"
+            ))
+        );
+        assert!(
+            record(
+                DNSRecordType::CNAME,
+                "www.example.com",
+                "Target.example.com."
+            )
+            .same_data(&record(
+                DNSRecordType::CNAME,
+                "WWW.example.com",
+                "target.example.com"
+            ))
+        );
+        assert!(
+            !record(DNSRecordType::TXT, "example.com", "V=spf1").same_data(&record(
+                DNSRecordType::TXT,
+                "example.com",
+                "v=spf1"
+            ))
+        );
+        assert!(
+            !record(DNSRecordType::A, "example.com", "10.0.0.1").same_name(&record(
+                DNSRecordType::AAAA,
+                "example.com",
+                "::1"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_valid_hostnames() {
+        for name in [
+            "example.com",
+            "*.lab.example.com.",
+            "_acme-challenge.example.com",
+            "nas",
+        ] {
+            assert!(is_valid_hostname(name), "{name}");
+        }
+        for name in [
+            "",
+            ".",
+            "10.0.0.256",
+            "1.2.3",
+            "a..example.com",
+            "bad name.example",
+            "*",
+        ] {
+            assert!(!is_valid_hostname(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_idn_round_trip() {
+        for (unicode, ascii) in [
+            ("bücher.example", "xn--bcher-kva.example"),
+            ("*.müller.example.com.", "*.xn--mller-kva.example.com."),
+            ("日本.example", "xn--wgv71a.example"),
+        ] {
+            assert_eq!(to_ascii_hostname(unicode).unwrap(), ascii);
+            assert_eq!(to_unicode_hostname(ascii), unicode);
+        }
+        assert_eq!(
+            to_ascii_hostname("_acme-challenge.Example.com").unwrap(),
+            "_acme-challenge.Example.com"
+        );
+        assert_eq!(to_unicode_hostname("www.example.com"), "www.example.com");
+        assert_eq!(
+            to_unicode_hostname("xn--invalid-.example"),
+            "xn--invalid-.example"
+        );
+        assert!(to_ascii_hostname("bad name.bücher.example").is_err());
+
+        let cname = DNSRecord {
+            record_type: DNSRecordType::CNAME,
+            name: "www.bücher.example".to_string(),
+            value: "bücher.example".to_string(),
+            ttl: None,
+            comment: None,
+        };
+        let ascii = cname.to_ascii().unwrap();
+        assert_eq!(ascii.name, "www.xn--bcher-kva.example");
+        assert_eq!(ascii.value, "xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn test_builder() {
+        let record = DNSRecord::a("nas.lab.example.com", Ipv4Addr::new(10, 0, 0, 2))
+            .ttl(300)
+            .comment("rack 2".to_string());
+        assert_eq!(
+            record,
+            DNSRecord {
+                record_type: DNSRecordType::A,
+                name: "nas.lab.example.com".to_string(),
+                value: "10.0.0.2".to_string(),
+                ttl: Some(300),
+                comment: Some("rack 2".to_string()),
+            }
+        );
+        assert_eq!(
+            DNSRecord::aaaa("nas.lab.example.com", Ipv6Addr::LOCALHOST).value,
+            "::1"
+        );
+        assert_eq!(
+            DNSRecord::cname("www.example.com", "example.com").record_type,
+            DNSRecordType::CNAME
+        );
+    }
+
+    #[test]
+    fn test_display_from_str_round_trip() {
+        let records = [
+            DNSRecord::a("www.example.com", Ipv4Addr::new(10, 0, 0, 1)).ttl(300),
+            DNSRecord::cname("files.example.com", "nas.example.com"),
+            DNSRecord::txt("example.com", "v=spf1 include:example.net -all").ttl(60),
+            DNSRecord::ptr("1.0.0.10.in-addr.arpa", "www.example.com"),
+        ];
+        for record in records {
+            let line = record.to_string();
+            assert_eq!(line.parse::<DNSRecord>().unwrap(), record, "{line}");
+        }
+        assert_eq!(
+            DNSRecord::a("www.example.com", Ipv4Addr::new(10, 0, 0, 1))
+                .ttl(300)
+                .to_string(),
+            "www.example.com 300 A 10.0.0.1"
+        );
+        assert!("www.example.com A".parse::<DNSRecord>().is_err());
+        assert!("www.example.com 300 MX mail".parse::<DNSRecord>().is_err());
+    }
+
+    #[test]
+    fn test_ordering() {
+        let mut records = [
+            DNSRecord::cname("b.example.com", "a.example.com"),
+            DNSRecord::a("b.example.com", Ipv4Addr::new(10, 0, 0, 2)),
+            DNSRecord::a("a.example.com", Ipv4Addr::new(10, 0, 0, 1)),
+        ];
+        records.sort();
+        let names: Vec<_> = records
+            .iter()
+            .map(|r| (r.record_type.as_str(), r.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("A", "a.example.com"),
+                ("A", "b.example.com"),
+                ("CNAME", "b.example.com")
+            ]
+        );
+    }
 }
@@ -0,0 +1,76 @@
+//! Combining records files as sets, so desired records can be composed from
+//! fragments maintained separately, e.g. everything shared minus one site.
+//!
+//! Records are the same when they hold the same data; TTLs and comments
+//! are taken from the first file that has the record.
+
+use crate::core::record::DNSRecord;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOperation {
+    /// Records of either file
+    Union,
+    /// Records of the first file that the second also has
+    Intersect,
+    /// Records of the first file that the second does not have
+    Subtract,
+}
+
+pub fn combine(
+    operation: SetOperation,
+    first: &[DNSRecord],
+    second: &[DNSRecord],
+) -> Vec<DNSRecord> {
+    let in_second = |record: &DNSRecord| second.iter().any(|other| other.same_data(record));
+    let mut records: Vec<DNSRecord> = Vec::new();
+    let selected: Box<dyn Iterator<Item = &DNSRecord>> = match operation {
+        SetOperation::Union => Box::new(first.iter().chain(second)),
+        SetOperation::Intersect => Box::new(first.iter().filter(|r| in_second(r))),
+        SetOperation::Subtract => Box::new(first.iter().filter(|r| !in_second(r))),
+    };
+    for record in selected {
+        if !records.iter().any(|kept| kept.same_data(record)) {
+            records.push(record.clone());
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn a(name: &str, last: u8) -> DNSRecord {
+        DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_combine() {
+        let first = [
+            a("nas.example.com", 2).comment("rack 2".to_string()),
+            a("printer.example.com", 3),
+        ];
+        let second = [
+            a("NAS.example.com.", 2),
+            a("vpn.example.com", 4),
+            a("vpn.example.com", 4),
+        ];
+        assert_eq!(
+            combine(SetOperation::Union, &first, &second),
+            [
+                first[0].clone(),
+                a("printer.example.com", 3),
+                a("vpn.example.com", 4)
+            ]
+        );
+        assert_eq!(
+            combine(SetOperation::Intersect, &first, &second),
+            [first[0].clone()]
+        );
+        assert_eq!(
+            combine(SetOperation::Subtract, &first, &second),
+            [a("printer.example.com", 3)]
+        );
+    }
+}
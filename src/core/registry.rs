@@ -1,30 +1,144 @@
-#[allow(dead_code)]
 use crate::core::provider::DNSProvider;
+use crate::error::Error;
+use futures::future::BoxFuture;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError, RwLock};
 
-#[allow(dead_code)]
+/// Creates a provider when it is first used, e.g. logging in to its API.
+pub type Factory =
+    Box<dyn Fn() -> BoxFuture<'static, Result<Arc<dyn DNSProvider>, Error>> + Send + Sync>;
+
+/// The configured providers by name. Providers registered with a factory
+/// are only created when a run first asks for them, and then kept, so a
+/// daemon syncing one provider never logs in to the others.
 pub struct ProviderRegistry {
-    providers: HashMap<String, Arc<dyn DNSProvider>>,
+    factories: HashMap<String, Factory>,
+    /// Providers created so far
+    providers: RwLock<HashMap<String, Arc<dyn DNSProvider>>>,
 }
 
-#[allow(dead_code)]
 impl ProviderRegistry {
     pub fn new() -> Self {
         ProviderRegistry {
-            providers: HashMap::new(),
+            factories: HashMap::new(),
+            providers: RwLock::new(HashMap::new()),
         }
     }
 
-    pub fn register(&mut self, provider: Arc<dyn DNSProvider>) {
-        self.providers.insert(provider.name().to_string(), provider);
+    /// Register a provider under its configured name, which may differ from
+    /// `DNSProvider::name` when several providers share a type.
+    #[cfg(test)]
+    pub fn register(&mut self, name: &str, provider: Arc<dyn DNSProvider>) {
+        self.providers
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.to_string(), provider);
+    }
+
+    /// Register a provider to be created by `factory` on first use.
+    pub fn register_lazy(&mut self, name: &str, factory: Factory) {
+        self.factories.insert(name.to_string(), factory);
     }
 
+    /// The named provider, if it has been created.
     pub fn get(&self, name: &str) -> Option<Arc<dyn DNSProvider>> {
-        self.providers.get(name).cloned()
+        self.providers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
     }
 
+    /// The named provider, creating it first if needed. A failed creation
+    /// is not kept, so the next call tries again.
+    pub async fn get_or_init(&self, name: &str) -> Result<Arc<dyn DNSProvider>, Error> {
+        if let Some(provider) = self.get(name) {
+            return Ok(provider);
+        }
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| Error::NotFound(format!("provider {name}")))?;
+        // The lock is not held while logging in; when two callers race, the
+        // provider created first is kept
+        let provider = factory().await?;
+        let mut providers = self
+            .providers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(providers
+            .entry(name.to_string())
+            .or_insert(provider)
+            .clone())
+    }
+
+    /// The names of the providers created so far, sorted.
     pub fn list(&self) -> Vec<String> {
-        self.providers.keys().cloned().collect()
+        let providers = self
+            .providers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut names: Vec<String> = providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::InMemoryProvider;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting(created: Arc<AtomicUsize>, fail: bool) -> Factory {
+        Box::new(move || {
+            let created = created.clone();
+            Box::pin(async move {
+                created.fetch_add(1, Ordering::SeqCst);
+                if fail {
+                    return Err(Error::CredentialError("bad token".to_string()));
+                }
+                Ok(Arc::new(InMemoryProvider::default()) as Arc<dyn DNSProvider>)
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_providers_are_created_once_on_first_use() {
+        let lan = Arc::new(AtomicUsize::new(0));
+        let wan = Arc::new(AtomicUsize::new(0));
+        let mut registry = ProviderRegistry::new();
+        registry.register_lazy("lan", counting(lan.clone(), false));
+        registry.register_lazy("wan", counting(wan.clone(), false));
+        assert!(registry.list().is_empty());
+
+        let first = registry.get_or_init("lan").await.unwrap();
+        let second = registry.get_or_init("lan").await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(lan.load(Ordering::SeqCst), 1);
+        assert_eq!(wan.load(Ordering::SeqCst), 0);
+        assert_eq!(registry.list(), ["lan"]);
+        assert!(registry.get_or_init("dmz").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_racing_callers_share_one_provider() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let mut registry = ProviderRegistry::new();
+        registry.register_lazy("lan", counting(created, false));
+        let (first, second) =
+            tokio::join!(registry.get_or_init("lan"), registry.get_or_init("lan"));
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_failed_creation_is_tried_again() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let mut registry = ProviderRegistry::new();
+        registry.register_lazy("lan", counting(created.clone(), true));
+        assert!(registry.get_or_init("lan").await.is_err());
+        assert!(registry.get_or_init("lan").await.is_err());
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+        assert!(registry.get("lan").is_none());
     }
 }
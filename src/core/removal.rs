@@ -0,0 +1,118 @@
+//! Checks of planned removals against the records that depend on them: a
+//! managed CNAME pointing at a name that loses its last record is left
+//! dangling once the removal is applied.
+//!
+//! Dependents are found among the desired records, then looked up through
+//! a resolver, so only CNAMEs that resolve through the name today count.
+
+use clap::ValueEnum;
+use std::future::Future;
+
+use crate::core::diff::Change;
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+
+/// What to do with removals that would break other managed records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RemovalCheck {
+    /// Report them, and apply them anyway
+    Warn,
+    /// Report them, and leave the records in place
+    Block,
+}
+
+/// A planned removal and a record it would break.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub removed: DNSRecord,
+    pub dependent: DNSRecord,
+}
+
+/// The CNAME records of `desired` pointing at the name of `removed`, when
+/// no desired record is left at that name.
+pub fn dependents<'a>(removed: &DNSRecord, desired: &'a [DNSRecord]) -> Vec<&'a DNSRecord> {
+    let name = normalize_hostname(&removed.name);
+    if desired.iter().any(|r| normalize_hostname(&r.name) == name) {
+        return Vec::new();
+    }
+    desired
+        .iter()
+        .filter(|r| r.record_type == DNSRecordType::CNAME && normalize_hostname(&r.value) == name)
+        .collect()
+}
+
+/// The dependencies of the removals among `changes` that are in use, as
+/// shown by `values`, the published values of a record.
+pub async fn live_dependencies<F, Fut>(
+    changes: &[Change],
+    desired: &[DNSRecord],
+    values: F,
+) -> Vec<Dependency>
+where
+    F: Fn(DNSRecord) -> Fut,
+    Fut: Future<Output = Vec<String>>,
+{
+    let mut found = Vec::new();
+    for change in changes {
+        let Change::Remove(removed) = change else {
+            continue;
+        };
+        let name = normalize_hostname(&removed.name);
+        for dependent in dependents(removed, desired) {
+            let published = values(dependent.clone()).await;
+            if published.iter().any(|v| normalize_hostname(v) == name) {
+                found.push(Dependency {
+                    removed: removed.clone(),
+                    dependent: dependent.clone(),
+                });
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_dependents() {
+        let nas = DNSRecord::a("nas.example.com", Ipv4Addr::new(10, 0, 0, 2));
+        let files = DNSRecord::cname("files.example.com", "NAS.example.com.");
+        let media = DNSRecord::cname("media.example.com", "files.example.com");
+        let desired = vec![files.clone(), media.clone()];
+        assert_eq!(dependents(&nas, &desired), [&files]);
+        // Another record is left at the name, so the CNAME still resolves
+        let moved = DNSRecord::a("nas.example.com", Ipv4Addr::new(10, 0, 0, 3));
+        let desired = vec![moved, files];
+        assert!(dependents(&nas, &desired).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_live_dependencies_are_looked_up() {
+        let nas = DNSRecord::a("nas.example.com", Ipv4Addr::new(10, 0, 0, 2));
+        let desired = vec![
+            DNSRecord::cname("files.example.com", "nas.example.com"),
+            DNSRecord::cname("backup.example.com", "nas.example.com"),
+        ];
+        let changes = [
+            Change::Remove(nas.clone()),
+            Change::Add(DNSRecord::a("tv.example.com", Ipv4Addr::new(10, 0, 0, 4))),
+        ];
+        // backup is not published yet, so nothing resolves through it
+        let values = |record: DNSRecord| async move {
+            match record.name.as_str() {
+                "files.example.com" => vec!["nas.example.com.".to_string()],
+                _ => Vec::new(),
+            }
+        };
+        let found = live_dependencies(&changes, &desired, values).await;
+        assert_eq!(
+            found,
+            [Dependency {
+                removed: nas,
+                dependent: desired[0].clone(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! DNS resolver shared by the commands that look records up.
+//!
+//! Lookups go to the system resolver unless servers are given. With a
+//! DNS-over-HTTPS endpoint configured, they go there instead, for networks
+//! where port 53 to public resolvers is blocked or intercepted.
+
+use hickory_resolver::config::{ConnectionConfig, NameServerConfig, ResolverConfig};
+use hickory_resolver::net::runtime::TokioRuntimeProvider;
+use hickory_resolver::{Resolver, TokioResolver};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::Arc;
+
+use crate::error::Error;
+
+/// The `[resolver]` configuration section.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LookupConfig {
+    /// DNS-over-HTTPS endpoint, e.g. `https://cloudflare-dns.com/dns-query`
+    pub doh: Option<String>,
+    /// Addresses of the DoH endpoint, so finding it takes no plain DNS
+    /// lookup; resolved through the system resolver when empty
+    #[serde(default)]
+    pub bootstrap: Vec<IpAddr>,
+}
+
+impl LookupConfig {
+    // One HTTPS name server per address of the endpoint
+    fn doh_servers(&self, url: &str) -> Result<Vec<NameServerConfig>, Error> {
+        let invalid = |e: &dyn std::fmt::Display| Error::InvalidInput(format!("doh {url}: {e}"));
+        let parsed = reqwest::Url::parse(url).map_err(|e| invalid(&e))?;
+        if parsed.scheme() != "https" {
+            return Err(invalid(&"not an https URL"));
+        }
+        let host = parsed.host_str().ok_or_else(|| invalid(&"no host"))?;
+        let port = parsed.port_or_known_default().unwrap_or(443);
+        let addresses = if self.bootstrap.is_empty() {
+            (host, port)
+                .to_socket_addrs()
+                .map_err(|e| invalid(&e))?
+                .map(|address| address.ip())
+                .collect()
+        } else {
+            self.bootstrap.clone()
+        };
+        Ok(addresses
+            .into_iter()
+            .map(|ip| {
+                let mut connection =
+                    ConnectionConfig::https(Arc::from(host), Some(Arc::from(parsed.path())));
+                connection.port = port;
+                NameServerConfig::new(ip, true, vec![connection])
+            })
+            .collect())
+    }
+}
+
+/// A resolver querying `servers`, or when empty the DoH endpoint of
+/// `config`, or the system resolver.
+pub fn build(servers: &[IpAddr], config: &LookupConfig) -> Result<TokioResolver, Error> {
+    let resolver_error =
+        |e: hickory_resolver::net::NetError| Error::Other(format!("resolver: {e}"));
+    let name_servers: Vec<NameServerConfig> = match &config.doh {
+        _ if !servers.is_empty() => servers
+            .iter()
+            .copied()
+            .map(NameServerConfig::udp_and_tcp)
+            .collect(),
+        Some(url) => config.doh_servers(url)?,
+        None => {
+            return TokioResolver::builder_tokio()
+                .map_err(resolver_error)?
+                .build()
+                .map_err(resolver_error);
+        }
+    };
+    Resolver::builder_with_config(
+        ResolverConfig::from_name_servers(name_servers),
+        TokioRuntimeProvider::default(),
+    )
+    .build()
+    .map_err(resolver_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doh_servers() {
+        let config = LookupConfig {
+            doh: Some("https://dns.example.net:8443/resolve".to_string()),
+            bootstrap: vec!["192.0.2.1".parse().unwrap(), "2001:db8::1".parse().unwrap()],
+        };
+        let servers = config.doh_servers(config.doh.as_deref().unwrap()).unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[1].ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(servers[0].connections[0].port, 8443);
+
+        assert!(
+            config
+                .doh_servers("http://dns.example.net/dns-query")
+                .is_err()
+        );
+        assert!(config.doh_servers("not a url").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! PTR records derived from forward A and AAAA records.
+
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+use crate::core::record::{DNSRecord, DNSRecordType};
+
+/// The `in-addr.arpa` or `ip6.arpa` name of an address.
+pub fn reverse_name(address: IpAddr) -> String {
+    match address {
+        IpAddr::V4(v4) => {
+            let [a, b, c, d] = v4.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa")
+        }
+        IpAddr::V6(v6) => {
+            let mut labels: Vec<String> = v6
+                .octets()
+                .iter()
+                .flat_map(|byte| [byte >> 4, byte & 0xf])
+                .map(|nibble| format!("{nibble:x}"))
+                .collect();
+            labels.reverse();
+            format!("{}.ip6.arpa", labels.join("."))
+        }
+    }
+}
+
+/// PTR records for the A and AAAA records whose address lies in one of
+/// `prefixes`, pointing back at the forward name.
+pub fn ptr_records(records: &[DNSRecord], prefixes: &[IpNet]) -> Vec<DNSRecord> {
+    records
+        .iter()
+        .filter(|r| matches!(r.record_type, DNSRecordType::A | DNSRecordType::AAAA))
+        .filter_map(|r| {
+            let address: IpAddr = r.value.parse().ok()?;
+            prefixes
+                .iter()
+                .any(|prefix| prefix.contains(&address))
+                .then(|| {
+                    DNSRecord::ptr(reverse_name(address), r.name.trim_end_matches('.'))
+                        .ttl(r.ttl)
+                        .comment(r.comment.clone())
+                })
+        })
+        .collect()
+}
+
+/// The desired records plus the PTR records derived from them.
+pub fn with_ptr_records(records: &[DNSRecord], prefixes: &[IpNet]) -> Vec<DNSRecord> {
+    let mut all = records.to_vec();
+    all.extend(ptr_records(records, prefixes));
+    all
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(record_type: DNSRecordType, name: &str, value: &str) -> DNSRecord {
+        DNSRecord {
+            record_type,
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl: Some(300),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_reverse_name() {
+        assert_eq!(
+            reverse_name("10.0.0.2".parse().unwrap()),
+            "2.0.0.10.in-addr.arpa"
+        );
+        assert_eq!(
+            reverse_name("2001:db8::1".parse().unwrap()),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+
+    #[test]
+    fn test_ptr_records_only_cover_prefixes() {
+        let records = [
+            record(DNSRecordType::A, "nas.lab.example.com", "10.0.0.2"),
+            record(DNSRecordType::A, "www.example.com", "203.0.113.10"),
+            record(DNSRecordType::AAAA, "nas.lab.example.com", "2001:db8::2"),
+            record(
+                DNSRecordType::CNAME,
+                "files.lab.example.com",
+                "nas.lab.example.com",
+            ),
+        ];
+        let prefixes: Vec<IpNet> = vec!["10.0.0.0/24".parse().unwrap()];
+        assert_eq!(
+            ptr_records(&records, &prefixes),
+            vec![record(
+                DNSRecordType::PTR,
+                "2.0.0.10.in-addr.arpa",
+                "nas.lab.example.com"
+            )]
+        );
+        assert_eq!(with_ptr_records(&records, &prefixes).len(), 5);
+        assert!(ptr_records(&records, &[]).is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+//! Name patterns limiting which records a sync may touch.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::core::record::{DNSRecord, normalize_hostname};
+use crate::error::Error;
+
+/// Name patterns such as `*.lab.example.com`, where `*` matches any run of
+/// characters. An empty scope matches every name.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(try_from = "Vec<String>")]
+pub struct Scope {
+    patterns: Vec<String>,
+}
+
+impl Scope {
+    pub fn new(patterns: Vec<String>) -> Result<Self, Error> {
+        let patterns = patterns
+            .into_iter()
+            .map(|p| normalize_hostname(&p))
+            .collect::<Vec<_>>();
+        if patterns.iter().any(String::is_empty) {
+            return Err(Error::InvalidInput("empty scope pattern".to_string()));
+        }
+        Ok(Self { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        let name = normalize_hostname(name);
+        self.patterns.is_empty() || self.patterns.iter().any(|p| glob_match(p, &name))
+    }
+
+    /// The domains the scope covers, for APIs that filter by domain suffix.
+    /// Patterns with a `*` other than a leading `*.` cannot be expressed and
+    /// are left out.
+    pub fn domains(&self) -> Vec<String> {
+        self.patterns
+            .iter()
+            .map(|p| p.strip_prefix("*.").unwrap_or(p))
+            .filter(|p| !p.contains('*'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Whether `domains` leaves no pattern out, so that a listing filtered
+    /// by those domains holds every record in scope. Never for the empty
+    /// scope, which is not limited to any domain.
+    pub fn covered_by_domains(&self) -> bool {
+        !self.is_empty()
+            && self
+                .patterns
+                .iter()
+                .all(|p| !p.strip_prefix("*.").unwrap_or(p).contains('*'))
+    }
+
+    /// Split records into those inside and outside of the scope.
+    pub fn partition(&self, records: &[DNSRecord]) -> (Vec<DNSRecord>, Vec<DNSRecord>) {
+        records
+            .iter()
+            .cloned()
+            .partition(|r| self.contains(&r.name))
+    }
+}
+
+impl TryFrom<Vec<String>> for Scope {
+    type Error = Error;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        Self::new(patterns)
+    }
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            // Try every split point for the characters `*` consumes
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(patterns: &[&str]) -> Scope {
+        Scope::new(patterns.iter().map(|p| p.to_string()).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_domains() {
+        let scope = Scope::new(vec![
+            "*.lab.example.com".to_string(),
+            "router.example.com".to_string(),
+            "host-*.example.com".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            scope.domains(),
+            vec!["lab.example.com", "router.example.com"]
+        );
+        assert!(!scope.covered_by_domains());
+        assert!(
+            Scope::new(vec!["*.lab.example.com".to_string()])
+                .unwrap()
+                .covered_by_domains()
+        );
+        assert!(!Scope::default().covered_by_domains());
+    }
+
+    #[test]
+    fn test_empty_scope_matches_everything() {
+        assert!(Scope::default().contains("anything.example.com"));
+    }
+
+    #[test]
+    fn test_wildcard_patterns() {
+        let scope = scope(&["*.lab.example.com", "router.example.com."]);
+        assert!(scope.contains("nas.lab.example.com"));
+        assert!(scope.contains("a.b.LAB.example.com"));
+        assert!(scope.contains("router.example.com"));
+        assert!(!scope.contains("lab.example.com"));
+        assert!(!scope.contains("www.example.com"));
+        assert!(!scope.contains("nas.lab.example.com.evil.org"));
+    }
+
+    #[test]
+    fn test_infix_wildcard() {
+        let scope = scope(&["dev-*.example.com"]);
+        assert!(scope.contains("dev-api.example.com"));
+        assert!(!scope.contains("prod-api.example.com"));
+    }
+
+    #[test]
+    fn test_rejects_empty_pattern() {
+        assert!(Scope::new(vec![" ".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Provider settings managed besides the records, like the blocklists of a
+//! NextDNS profile. Each setting is a set of enabled items, planned and
+//! applied the way records are.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Enabling or disabling one item of a setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    /// The set the item belongs to, e.g. `blocklists`
+    pub setting: &'static str,
+    pub item: String,
+    pub enable: bool,
+}
+
+impl SettingChange {
+    pub fn action(&self) -> &'static str {
+        if self.enable { "enable" } else { "disable" }
+    }
+}
+
+impl fmt::Display for SettingChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.action(), self.setting, self.item)
+    }
+}
+
+/// The changes turning the `current` items of `setting` into `desired`:
+/// missing items are enabled first, then extra ones disabled.
+pub fn diff(setting: &'static str, current: &[String], desired: &[String]) -> Vec<SettingChange> {
+    let current: BTreeSet<&String> = current.iter().collect();
+    let desired: BTreeSet<&String> = desired.iter().collect();
+    let change = |item: &String, enable| SettingChange {
+        setting,
+        item: item.clone(),
+        enable,
+    };
+    desired
+        .difference(&current)
+        .map(|item| change(item, true))
+        .chain(current.difference(&desired).map(|item| change(item, false)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let changes = diff(
+            "blocklists",
+            &items(&["nextdns-recommended", "oisd"]),
+            &items(&["oisd", "1hosts-lite", "oisd"]),
+        );
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "enable blocklists 1hosts-lite",
+                "disable blocklists nextdns-recommended"
+            ]
+        );
+        assert!(diff("tlds", &items(&["zip"]), &items(&["zip"])).is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+//! Counts and timings of a sync, for the end-of-run report.
+
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+/// What a sync of one provider did and where its time went.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SyncStats {
+    /// Current records listed within the scope
+    pub examined: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// Desired records left out of the scope or unsupported by the provider
+    pub skipped: usize,
+    /// Changes left unapplied at a read-only provider
+    pub drift: usize,
+    /// Changes refused as they would delete or modify a protected record
+    pub blocked: usize,
+    /// Failed or cancelled changes by category, e.g. `provider` or `cancelled`
+    pub failures: BTreeMap<String, usize>,
+    #[serde(rename = "list_ms", serialize_with = "millis")]
+    pub list_time: Duration,
+    #[serde(rename = "apply_ms", serialize_with = "millis")]
+    pub apply_time: Duration,
+}
+
+impl SyncStats {
+    pub fn fail(&mut self, category: &str) {
+        *self.failures.entry(category.to_string()).or_default() += 1;
+    }
+
+    pub fn failed(&self) -> usize {
+        self.failures.values().sum()
+    }
+}
+
+/// The outcome of syncing one provider.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncOutcome {
+    #[serde(flatten)]
+    pub stats: SyncStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SyncOutcome {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_by_category() {
+        let mut stats = SyncStats::default();
+        stats.fail("provider");
+        stats.fail("provider");
+        stats.fail("cancelled");
+        assert_eq!(stats.failed(), 3);
+        assert_eq!(stats.failures["provider"], 2);
+
+        stats.list_time = Duration::from_millis(1500);
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["list_ms"], 1500);
+        assert_eq!(json["failures"]["cancelled"], 1);
+    }
+}
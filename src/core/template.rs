@@ -0,0 +1,206 @@
+//! Placeholders in the desired records filled in at sync time, so records
+//! following a dynamic address live in the same file as static rewrites:
+//!
+//! ```text
+//! {{ public_ipv4 }} home.example.com
+//! {{ public_ipv6 }} home.example.com
+//! 10.0.0.2 nas.lab.example.com
+//! ```
+//!
+//! The public addresses are asked from an HTTP service that echoes the
+//! address a request comes from, and only when a placeholder uses them.
+
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::core::http::HttpOptions;
+use crate::error::Error;
+
+pub const DEFAULT_PUBLIC_IPV4_URL: &str = "https://api.ipify.org";
+pub const DEFAULT_PUBLIC_IPV6_URL: &str = "https://api6.ipify.org";
+
+/// The `[public_ip]` configuration section.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PublicIpConfig {
+    /// Answers with the public IPv4 address of the request, as plain text
+    #[serde(default = "default_ipv4_url")]
+    pub ipv4_url: String,
+    /// Answers with the public IPv6 address of the request, as plain text
+    #[serde(default = "default_ipv6_url")]
+    pub ipv6_url: String,
+}
+
+impl Default for PublicIpConfig {
+    fn default() -> Self {
+        Self {
+            ipv4_url: default_ipv4_url(),
+            ipv6_url: default_ipv6_url(),
+        }
+    }
+}
+
+fn default_ipv4_url() -> String {
+    DEFAULT_PUBLIC_IPV4_URL.to_string()
+}
+
+fn default_ipv6_url() -> String {
+    DEFAULT_PUBLIC_IPV6_URL.to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Placeholder {
+    PublicIpv4,
+    PublicIpv6,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Result<Self, Error> {
+        match name {
+            "public_ipv4" => Ok(Placeholder::PublicIpv4),
+            "public_ipv6" => Ok(Placeholder::PublicIpv6),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown placeholder {{{{ {name} }}}}"
+            ))),
+        }
+    }
+}
+
+// Each `{{ name }}` of `text`: the text before it, the placeholder, and the
+// text after it
+fn next_placeholder(text: &str) -> Option<Result<(&str, Placeholder, &str), Error>> {
+    let (before, rest) = text.split_once("{{")?;
+    let Some((name, after)) = rest.split_once("}}") else {
+        return Some(Err(Error::InvalidInput(
+            "unclosed placeholder, expected }}".to_string(),
+        )));
+    };
+    Some(Placeholder::parse(name.trim()).map(|placeholder| (before, placeholder, after)))
+}
+
+/// The placeholders `text` uses, failing on unknown ones.
+pub fn placeholders(text: &str) -> Result<Vec<Placeholder>, Error> {
+    let mut used = Vec::new();
+    let mut rest = text;
+    while let Some(found) = next_placeholder(rest) {
+        let (_, placeholder, after) = found?;
+        if !used.contains(&placeholder) {
+            used.push(placeholder);
+        }
+        rest = after;
+    }
+    Ok(used)
+}
+
+/// `text` with its placeholders replaced by their `values`.
+pub fn render(text: &str, values: &BTreeMap<Placeholder, IpAddr>) -> Result<String, Error> {
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(found) = next_placeholder(rest) {
+        let (before, placeholder, after) = found?;
+        let value = values
+            .get(&placeholder)
+            .ok_or_else(|| Error::NotFound(format!("no value for {placeholder:?}")))?;
+        rendered.push_str(before);
+        rendered.push_str(&value.to_string());
+        rest = after;
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Looks up the public addresses of this host.
+pub struct PublicIp {
+    client: Client,
+    config: PublicIpConfig,
+}
+
+impl PublicIp {
+    pub fn new(config: &PublicIpConfig, http: &HttpOptions) -> Result<Self, Error> {
+        Ok(Self {
+            client: http.builder().build()?,
+            config: config.clone(),
+        })
+    }
+
+    /// The value of `placeholder`, checked to be an address of its family.
+    pub async fn lookup(&self, placeholder: Placeholder) -> Result<IpAddr, Error> {
+        let url = match placeholder {
+            Placeholder::PublicIpv4 => &self.config.ipv4_url,
+            Placeholder::PublicIpv6 => &self.config.ipv6_url,
+        };
+        let body = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let address: IpAddr = body.trim().parse().map_err(|_| {
+            Error::ProviderError(format!("{url} answered {:?}, not an address", body.trim()))
+        })?;
+        match (placeholder, address) {
+            (Placeholder::PublicIpv4, IpAddr::V4(_)) | (Placeholder::PublicIpv6, IpAddr::V6(_)) => {
+                Ok(address)
+            }
+            _ => Err(Error::ProviderError(format!(
+                "{url} answered {address}, not an address for {placeholder:?}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    #[test]
+    fn test_render() {
+        let text = "{{ public_ipv4 }} home.example.com\n\
+                    {{public_ipv6}} home.example.com\n\
+                    {{ public_ipv4 }} vpn.example.com\n";
+        assert_eq!(
+            placeholders(text).unwrap(),
+            [Placeholder::PublicIpv4, Placeholder::PublicIpv6]
+        );
+        let values = BTreeMap::from([
+            (Placeholder::PublicIpv4, "203.0.113.7".parse().unwrap()),
+            (Placeholder::PublicIpv6, "2001:db8::7".parse().unwrap()),
+        ]);
+        assert_eq!(
+            render(text, &values).unwrap(),
+            "203.0.113.7 home.example.com\n\
+             2001:db8::7 home.example.com\n\
+             203.0.113.7 vpn.example.com\n"
+        );
+        assert!(placeholders("10.0.0.2 nas.lan").unwrap().is_empty());
+        assert!(placeholders("{{ private_ip }} nas.lan").is_err());
+        assert!(placeholders("{{ public_ipv4 nas.lan").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_checks_the_family() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/v4");
+                then.status(200).body("203.0.113.7\n");
+            })
+            .await;
+        let config = PublicIpConfig {
+            ipv4_url: server.url("/v4"),
+            ipv6_url: server.url("/v4"),
+        };
+        let public = PublicIp::new(&config, &HttpOptions::default()).unwrap();
+        assert_eq!(
+            public.lookup(Placeholder::PublicIpv4).await.unwrap(),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        assert!(public.lookup(Placeholder::PublicIpv6).await.is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Hosts discovered from the configuration of a Caddy server.
+//!
+//! Names come from the `host` matchers of the HTTP routes, read through the
+//! admin API, including routes nested in subroutes.
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::daemon::discovery::{Publish, Source, default_interval};
+use crate::error::Error;
+
+pub const DEFAULT_CADDY_ADMIN_URL: &str = "http://localhost:2019";
+
+/// The `[caddy]` configuration section.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CaddyConfig {
+    #[serde(default = "default_admin_url")]
+    pub admin_url: String,
+    /// Address or name the discovered hosts point to, usually Caddy's own
+    pub target: String,
+    pub ttl: Option<u32>,
+    /// Seconds between polls
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_admin_url() -> String {
+    DEFAULT_CADDY_ADMIN_URL.to_string()
+}
+
+/// The names in the `host` matchers anywhere in `config`.
+fn matched_hosts(config: &Value, hosts: &mut Vec<String>) {
+    match config {
+        Value::Object(object) => {
+            if let Some(Value::Array(matchers)) = object.get("match") {
+                let names = matchers
+                    .iter()
+                    .filter_map(|matcher| matcher.get("host")?.as_array())
+                    .flatten()
+                    .filter_map(Value::as_str);
+                hosts.extend(names.map(str::to_string));
+            }
+            object
+                .values()
+                .for_each(|value| matched_hosts(value, hosts));
+        }
+        Value::Array(values) => values.iter().for_each(|value| matched_hosts(value, hosts)),
+        _ => {}
+    }
+}
+
+#[async_trait]
+impl Source for CaddyConfig {
+    const NAME: &'static str = "Caddy";
+
+    fn publish(&self) -> Publish<'_> {
+        Publish {
+            target: &self.target,
+            ttl: self.ttl,
+            interval: self.interval,
+        }
+    }
+
+    async fn names(&self, client: &reqwest::Client) -> Result<Vec<String>, Error> {
+        let url = format!("{}/config/apps/http", self.admin_url.trim_end_matches('/'));
+        let error = |e: reqwest::Error| Error::Other(format!("caddy: {e}"));
+        let config: Value = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(error)?
+            .json()
+            .await
+            .map_err(error)?;
+        let mut hosts = Vec::new();
+        matched_hosts(&config, &mut hosts);
+        Ok(hosts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::discovery::DEFAULT_POLL_INTERVAL_SECS;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_names_of_routes() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/config/apps/http");
+                then.status(200).json_body(json!({
+                    "servers": {
+                        "srv0": {
+                            "listen": [":443"],
+                            "routes": [
+                                {
+                                    "match": [{"host": ["nas.lab.example.com", "files.lab.example.com"]}],
+                                    "handle": [{
+                                        "handler": "subroute",
+                                        "routes": [{
+                                            "match": [{"host": ["wiki.lab.example.com"]}],
+                                            "handle": [{"handler": "reverse_proxy"}]
+                                        }]
+                                    }],
+                                    "terminal": true
+                                },
+                                {"match": [{"path": ["/health"]}]}
+                            ]
+                        }
+                    }
+                }));
+            })
+            .await;
+        let config = CaddyConfig {
+            admin_url: server.base_url(),
+            target: "10.0.0.10".to_string(),
+            ttl: None,
+            interval: DEFAULT_POLL_INTERVAL_SECS,
+        };
+        let names = config.names(&reqwest::Client::new()).await.unwrap();
+        assert_eq!(
+            names,
+            [
+                "nas.lab.example.com",
+                "files.lab.example.com",
+                "wiki.lab.example.com"
+            ]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Local HTTP API of the daemon.
+//!
+//! - `GET /status`: tracked hosts and the outcome of the last run
+//! - `POST /reconcile`: start a run now
+//! - `GET /hosts`, `POST /hosts`, `DELETE /hosts/{name}`: hosts added at
+//!   runtime, optionally with a lease that must be renewed
+//!
+//! With a token, every request must carry it as `Authorization: Bearer`.
+
+use axum::extract::{Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use crate::core::events::Event;
+use crate::core::rdata::{Naptr, Sshfp};
+use crate::core::record::{DNSRecord, DNSRecordType, is_valid_hostname, to_ascii_hostname};
+use crate::daemon::DaemonState;
+
+/// A host to add; the type follows from the value unless given.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddHost {
+    pub name: String,
+    pub value: String,
+    pub record_type: Option<DNSRecordType>,
+    pub ttl: Option<u32>,
+    /// Seconds the host is kept unless added again
+    pub lease: Option<u64>,
+}
+
+// Refuse hosts a rewrites line would refuse too: invalid names, and
+// values that do not fit the record type
+fn check_host(record: &DNSRecord) -> Result<(), String> {
+    if !is_valid_hostname(&record.name) {
+        return Err(format!("invalid name: {}", record.name));
+    }
+    let value = record.value.as_str();
+    let valid = match record.record_type {
+        DNSRecordType::A => value.parse::<Ipv4Addr>().is_ok(),
+        DNSRecordType::AAAA => value.parse::<Ipv6Addr>().is_ok(),
+        DNSRecordType::CNAME | DNSRecordType::PTR => {
+            is_valid_hostname(value) && !value.starts_with("*.")
+        }
+        DNSRecordType::SSHFP => value.parse::<Sshfp>().is_ok(),
+        DNSRecordType::NAPTR => value.parse::<Naptr>().is_ok(),
+        DNSRecordType::TXT => true,
+    };
+    if !valid {
+        return Err(format!(
+            "invalid {} value: {value}",
+            record.record_type.as_str()
+        ));
+    }
+    Ok(())
+}
+
+async fn status(State(state): State<Arc<DaemonState>>) -> Response {
+    Json(state.status()).into_response()
+}
+
+async fn reconcile(State(state): State<Arc<DaemonState>>) -> StatusCode {
+    state.apply(Event::ForceReconcile);
+    StatusCode::ACCEPTED
+}
+
+async fn list_hosts(State(state): State<Arc<DaemonState>>) -> Response {
+    Json(state.added()).into_response()
+}
+
+async fn add_host(
+    State(state): State<Arc<DaemonState>>,
+    Json(host): Json<AddHost>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let record_type = host
+        .record_type
+        .unwrap_or_else(|| DNSRecordType::for_value(&host.value));
+    let record = DNSRecord::new(record_type, host.name.trim_end_matches('.'), host.value)
+        .ttl(host.ttl)
+        .comment("added through the control API".to_string())
+        .to_ascii()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    check_host(&record).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    state.apply(Event::HostAdded {
+        record,
+        lease: host.lease,
+    });
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn remove_host(
+    State(state): State<Arc<DaemonState>>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    let name = to_ascii_hostname(name.trim_end_matches('.')).unwrap_or(name);
+    if state.apply(Event::HostRemoved { name }) {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn authorize(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match given {
+        Some(given) if same_token(given, &token) => Ok(next.run(request).await),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+// Compared in full, so the time taken gives nothing away
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub fn router(state: Arc<DaemonState>, token: Option<&str>) -> Router {
+    let router = Router::new()
+        .route("/status", get(status))
+        .route("/reconcile", post(reconcile))
+        .route("/hosts", get(list_hosts).post(add_host))
+        .route("/hosts/{name}", delete(remove_host))
+        .with_state(state);
+    match token {
+        Some(token) => router.layer(middleware::from_fn_with_state(Arc::from(token), authorize)),
+        None => router,
+    }
+}
+
+pub async fn serve(
+    listener: TcpListener,
+    state: Arc<DaemonState>,
+    token: Option<&str>,
+) -> std::io::Result<()> {
+    axum::serve(listener, router(state, token)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use std::time::Duration;
+
+    async fn start_with(token: Option<&'static str>) -> (String, Arc<DaemonState>) {
+        let state = Arc::new(DaemonState::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, state.clone(), token));
+        (url, state)
+    }
+
+    async fn start() -> (String, Arc<DaemonState>) {
+        start_with(None).await
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_hosts() {
+        let (url, state) = start().await;
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{url}/hosts"))
+            .json(&json!({"name": "nas.lab.example.com.", "value": "10.0.0.2", "lease": 600}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        // The add asks the loop for a run
+        tokio::time::timeout(Duration::from_secs(1), state.reconcile.notified())
+            .await
+            .unwrap();
+
+        let hosts: Value = reqwest::get(format!("{url}/hosts"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(hosts[0]["name"], "nas.lab.example.com");
+        assert_eq!(hosts[0]["record_type"], "A");
+        assert!(hosts[0]["expires_at"].is_u64());
+
+        let remove = |name: &str| client.delete(format!("{url}/hosts/{name}")).send();
+        assert_eq!(
+            remove("nas.lab.example.com").await.unwrap().status(),
+            StatusCode::ACCEPTED
+        );
+        assert_eq!(
+            remove("nas.lab.example.com").await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_and_reconcile() {
+        let (url, state) = start().await;
+        let status: Value = reqwest::get(format!("{url}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status, json!({"hosts": [], "last_sync": null}));
+
+        let response = reqwest::Client::new()
+            .post(format!("{url}/reconcile"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        tokio::time::timeout(Duration::from_secs(1), state.reconcile.notified())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_invalid_hosts_are_refused() {
+        let (url, state) = start().await;
+        let client = reqwest::Client::new();
+        for host in [
+            json!({"name": "bad<name>.example.com", "value": "10.0.0.2"}),
+            json!({"name": "nas.example.com", "value": "10.0.0.256"}),
+            json!({"name": "nas.example.com", "value": "nas.example.com", "record_type": "A"}),
+            json!({"name": "nas.example.com", "value": "10.0.0.2", "record_type": "AAAA"}),
+            json!({"name": "nas.example.com", "value": "*.example.com"}),
+        ] {
+            let response = client
+                .post(format!("{url}/hosts"))
+                .json(&host)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{host}");
+        }
+        assert!(state.added().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_token_is_required() {
+        let (url, _state) = start_with(Some("s3cret")).await;
+        let client = reqwest::Client::new();
+        let reconcile = |token: Option<&str>| {
+            let request = client.post(format!("{url}/reconcile"));
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+            .send()
+        };
+        for token in [None, Some("wrong"), Some("s3cre")] {
+            assert_eq!(
+                reconcile(token).await.unwrap().status(),
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert_eq!(
+            reconcile(Some("s3cret")).await.unwrap().status(),
+            StatusCode::ACCEPTED
+        );
+    }
+}
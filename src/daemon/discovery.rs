@@ -0,0 +1,194 @@
+//! Hosts discovered from reverse proxies.
+//!
+//! The daemon polls each configured proxy and adds a host for every name it
+//! serves, pointing to the address the proxy is reached at. Hosts whose
+//! name the proxy no longer serves are removed again.
+
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::core::events::Event;
+use crate::core::http::HttpOptions;
+use crate::core::record::{DNSRecord, DNSRecordType, is_valid_hostname, to_ascii_hostname};
+use crate::daemon::DaemonState;
+use crate::error::Error;
+
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 60;
+
+pub fn default_interval() -> u64 {
+    DEFAULT_POLL_INTERVAL_SECS
+}
+
+/// How the hosts of a source are published.
+pub struct Publish<'a> {
+    /// Address or name the hosts point to, usually the proxy's own
+    pub target: &'a str,
+    pub ttl: Option<u32>,
+    /// Seconds between polls
+    pub interval: u64,
+}
+
+/// A reverse proxy the served names are read from.
+#[async_trait]
+pub trait Source: Send + Sync {
+    /// Name of the proxy, e.g. `Traefik`
+    const NAME: &'static str;
+
+    fn publish(&self) -> Publish<'_>;
+
+    /// The names currently served.
+    async fn names(&self, client: &reqwest::Client) -> Result<Vec<String>, Error>;
+}
+
+/// Marks the added hosts that came from `S`, so hosts added through the
+/// control API or by other sources are left alone.
+fn comment<S: Source>() -> String {
+    format!("discovered from {}", S::NAME)
+}
+
+/// The records for the names served by `source`, one per valid name.
+async fn discover<S: Source>(
+    source: &S,
+    client: &reqwest::Client,
+) -> Result<Vec<DNSRecord>, Error> {
+    let names: BTreeSet<String> = source
+        .names(client)
+        .await?
+        .into_iter()
+        .filter_map(|name| match to_ascii_hostname(&name.to_lowercase()) {
+            Ok(ascii) if is_valid_hostname(&ascii) => Some(ascii),
+            _ => {
+                eprintln!("{}: skipping invalid host name {name:?}", S::NAME);
+                None
+            }
+        })
+        .collect();
+    let publish = source.publish();
+    let record_type = DNSRecordType::for_value(publish.target);
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            DNSRecord::new(record_type.clone(), name, publish.target)
+                .ttl(publish.ttl)
+                .comment(comment::<S>())
+        })
+        .collect())
+}
+
+/// Bring the hosts added from `S` in line with `discovered`.
+fn update<S: Source>(state: &DaemonState, discovered: Vec<DNSRecord>) {
+    let comment = comment::<S>();
+    let current: Vec<DNSRecord> = state
+        .added()
+        .into_iter()
+        .map(|host| host.record)
+        .filter(|record| record.comment.as_ref() == Some(&comment))
+        .collect();
+    let kept: BTreeSet<&str> = discovered.iter().map(|r| r.name.as_str()).collect();
+    let gone: BTreeSet<String> = current
+        .iter()
+        .filter(|record| !kept.contains(record.name.as_str()))
+        .map(|record| record.name.clone())
+        .collect();
+    for name in gone {
+        println!("{}: removing {name}", S::NAME);
+        state.apply(Event::HostRemoved { name });
+    }
+    for record in discovered {
+        if !current.contains(&record) {
+            println!("{}: adding {record}", S::NAME);
+            state.apply(Event::HostAdded {
+                record,
+                lease: None,
+            });
+        }
+    }
+}
+
+/// Poll `source` until the daemon exits. A failed poll is reported and
+/// leaves the hosts as they were.
+pub async fn watch<S: Source>(source: S, http: HttpOptions, state: Arc<DaemonState>) {
+    let client = match http.builder().build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}: {e}", S::NAME);
+            return;
+        }
+    };
+    loop {
+        match discover(&source, &client).await {
+            Ok(discovered) => update::<S>(&state, discovered),
+            Err(e) => eprintln!("{e}"),
+        }
+        tokio::time::sleep(Duration::from_secs(source.publish().interval)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A source serving whatever names the test sets.
+    #[derive(Default)]
+    struct Names(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl Source for Names {
+        const NAME: &'static str = "Test";
+
+        fn publish(&self) -> Publish<'_> {
+            Publish {
+                target: "10.0.0.10",
+                ttl: Some(300),
+                interval: DEFAULT_POLL_INTERVAL_SECS,
+            }
+        }
+
+        async fn names(&self, _client: &reqwest::Client) -> Result<Vec<String>, Error> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    fn added_names(state: &DaemonState) -> Vec<String> {
+        state.added().into_iter().map(|h| h.record.name).collect()
+    }
+
+    #[tokio::test]
+    async fn test_discovered_hosts_follow_the_source() {
+        let client = reqwest::Client::new();
+        let source = Names::default();
+        *source.0.lock().unwrap() = vec![
+            "nas.lab.example.com".to_string(),
+            "Wiki.lab.example.com".to_string(),
+            "bad_name;rm.example.com".to_string(),
+        ];
+        let state = DaemonState::default();
+        state.apply(Event::HostAdded {
+            record: DNSRecord::a("printer.lab.example.com", "10.0.0.3".parse().unwrap()),
+            lease: None,
+        });
+
+        update::<Names>(&state, discover(&source, &client).await.unwrap());
+        assert_eq!(
+            added_names(&state),
+            [
+                "printer.lab.example.com",
+                "nas.lab.example.com",
+                "wiki.lab.example.com"
+            ]
+        );
+        let nas = &state.added()[1].record;
+        assert_eq!(nas.record_type, DNSRecordType::A);
+        assert_eq!(nas.ttl, Some(300));
+
+        *source.0.lock().unwrap() = vec!["nas.lab.example.com".to_string()];
+        update::<Names>(&state, discover(&source, &client).await.unwrap());
+        assert_eq!(
+            added_names(&state),
+            ["printer.lab.example.com", "nas.lab.example.com"]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Append-only log of the events applied by the daemon.
+//!
+//! Added hosts only live in memory, so a restarted daemon forgets them.
+//! With a journal, `daemon --replay` applies the logged events again;
+//! since runs only ever converge on the desired records, replaying an event
+//! that already took effect changes nothing.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::core::events::Event;
+use crate::error::Error;
+use crate::providers::file::write_atomic;
+
+/// One line of the journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix timestamp of when the event was applied
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+pub struct Journal {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+fn journal_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::Other(format!("journal {}: {e}", path.display()))
+}
+
+impl Journal {
+    /// Open `path` for appending, creating it if missing.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| journal_error(path, e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn append(&self, entry: &Entry) -> Result<(), Error> {
+        let mut line = serde_json::to_string(entry).map_err(|e| journal_error(&self.path, e))?;
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())
+            .map_err(|e| journal_error(&self.path, e))
+    }
+
+    /// The logged entries, oldest first. Lines that cannot be parsed, like
+    /// one cut short by a crash, are skipped with a warning.
+    pub fn read(&self) -> Result<Vec<Entry>, Error> {
+        let file = File::open(&self.path).map_err(|e| journal_error(&self.path, e))?;
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| journal_error(&self.path, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => eprintln!(
+                    "Skipping line {} of {}: {e}",
+                    number + 1,
+                    self.path.display()
+                ),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Replace the journal with `entries`, so it does not grow with every
+    /// renewed lease.
+    pub fn compact(&self, entries: &[Entry]) -> Result<(), Error> {
+        let mut contents = String::new();
+        for entry in entries {
+            contents
+                .push_str(&serde_json::to_string(entry).map_err(|e| journal_error(&self.path, e))?);
+            contents.push('\n');
+        }
+        let mut file = self.file.lock().unwrap();
+        write_atomic(&self.path, &contents).map_err(|e| journal_error(&self.path, e))?;
+        // The old handle points at the replaced file
+        *file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| journal_error(&self.path, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_read_and_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let journal = Journal::open(&path).unwrap();
+        let removed = Entry {
+            at: 2,
+            event: Event::HostRemoved {
+                name: "nas.lab.example.com".to_string(),
+            },
+        };
+        journal
+            .append(&Entry {
+                at: 1,
+                event: Event::ForceReconcile,
+            })
+            .unwrap();
+        journal.append(&removed).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"at\": 3, \"event\": \"host_ad")
+            .unwrap();
+        assert_eq!(journal.read().unwrap().len(), 2);
+
+        journal.compact(std::slice::from_ref(&removed)).unwrap();
+        journal.append(&removed).unwrap();
+        assert_eq!(journal.read().unwrap(), vec![removed.clone(), removed]);
+    }
+}
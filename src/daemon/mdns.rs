@@ -0,0 +1,150 @@
+//! Advertising the desired A and AAAA records over mDNS.
+//!
+//! Each record is also answered on the LAN as `<first label>.local`, e.g.
+//! `nas.lab.example.com` as `nas.local`, so devices that ignore the local
+//! DNS server still resolve the hosts. The host names are announced through
+//! a `_device-info._tcp` service per host, as mDNS responders only answer
+//! for the hosts of registered services.
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+use crate::core::scope::Scope;
+use crate::error::Error;
+
+const SERVICE_TYPE: &str = "_device-info._tcp.local.";
+
+/// The `[mdns]` configuration section.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct MdnsConfig {
+    /// Only records whose names match one of these patterns are advertised;
+    /// all of them when empty
+    pub names: Scope,
+}
+
+/// The `.local` names to advertise for the A and AAAA records among
+/// `records`, with their addresses.
+fn local_hosts(records: &[DNSRecord], names: &Scope) -> BTreeMap<String, BTreeSet<IpAddr>> {
+    let mut hosts: BTreeMap<String, BTreeSet<IpAddr>> = BTreeMap::new();
+    for record in records {
+        if !matches!(record.record_type, DNSRecordType::A | DNSRecordType::AAAA)
+            || !names.contains(&record.name)
+        {
+            continue;
+        }
+        let name = normalize_hostname(&record.name);
+        let Some(label) = name.split('.').next().filter(|label| *label != "*") else {
+            continue;
+        };
+        if let Ok(address) = record.value.parse() {
+            hosts
+                .entry(format!("{label}.local."))
+                .or_default()
+                .insert(address);
+        }
+    }
+    hosts
+}
+
+/// The mDNS responder and the hosts it currently advertises.
+pub struct Advertiser {
+    daemon: ServiceDaemon,
+    names: Scope,
+    advertised: Mutex<BTreeMap<String, BTreeSet<IpAddr>>>,
+}
+
+fn mdns_error(e: mdns_sd::Error) -> Error {
+    Error::Other(format!("mdns: {e}"))
+}
+
+impl Advertiser {
+    pub fn new(config: &MdnsConfig) -> Result<Self, Error> {
+        Ok(Self {
+            daemon: ServiceDaemon::new().map_err(mdns_error)?,
+            names: config.names.clone(),
+            advertised: Mutex::default(),
+        })
+    }
+
+    // One service per host, named after it
+    fn fullname(host: &str) -> String {
+        let instance = host.trim_end_matches(".local.");
+        format!("{instance}.{SERVICE_TYPE}")
+    }
+
+    /// Advertise the hosts of `records`, withdrawing those no longer among
+    /// them.
+    pub fn advertise(&self, records: &[DNSRecord]) {
+        let desired = local_hosts(records, &self.names);
+        let mut advertised = self.advertised.lock().unwrap();
+        for (host, addresses) in advertised.iter() {
+            if desired.get(host) != Some(addresses)
+                && let Err(e) = self.daemon.unregister(&Self::fullname(host))
+            {
+                eprintln!("mdns: failed to withdraw {host}: {e}");
+            }
+        }
+        advertised.retain(|host, addresses| desired.get(host) == Some(addresses));
+        for (host, addresses) in desired {
+            if advertised.contains_key(&host) {
+                continue;
+            }
+            let instance = host.trim_end_matches(".local.");
+            let addresses: Vec<IpAddr> = addresses.into_iter().collect();
+            let registered =
+                ServiceInfo::new(SERVICE_TYPE, instance, &host, &addresses[..], 0, None)
+                    .and_then(|info| self.daemon.register(info));
+            match registered {
+                Ok(()) => {
+                    advertised.insert(host, addresses.into_iter().collect());
+                }
+                Err(e) => eprintln!("mdns: failed to advertise {host}: {e}"),
+            }
+        }
+    }
+
+    /// Withdraw every host, so they are not answered for after the daemon
+    /// exits.
+    pub fn shutdown(&self) {
+        self.advertise(&[]);
+        if let Err(e) = self.daemon.shutdown() {
+            eprintln!("mdns: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_local_hosts() {
+        let records = vec![
+            DNSRecord::a("nas.lab.example.com", Ipv4Addr::new(10, 0, 0, 2)),
+            DNSRecord::aaaa(
+                "NAS.lab.example.com.",
+                "fd00::2".parse::<Ipv6Addr>().unwrap(),
+            ),
+            DNSRecord::a("printer.office.example.com", Ipv4Addr::new(10, 1, 0, 3)),
+            DNSRecord::a("*.apps.lab.example.com", Ipv4Addr::new(10, 0, 0, 10)),
+            DNSRecord::cname("files.lab.example.com", "nas.lab.example.com"),
+        ];
+        let hosts = local_hosts(&records, &Scope::default());
+        assert_eq!(
+            hosts.keys().collect::<Vec<_>>(),
+            ["nas.local.", "printer.local."]
+        );
+        assert_eq!(hosts["nas.local."].len(), 2);
+
+        let lab = Scope::new(vec!["*.lab.example.com".to_string()]).unwrap();
+        let hosts = local_hosts(&records, &lab);
+        assert_eq!(hosts.keys().collect::<Vec<_>>(), ["nas.local."]);
+    }
+}
@@ -0,0 +1,445 @@
+//! Long-running mode: reconcile on an interval, controlled through an
+//! optional local HTTP API and fed hosts discovered from reverse proxies.
+
+pub mod caddy;
+pub mod control;
+pub mod discovery;
+pub mod journal;
+pub mod mdns;
+pub mod nginx_proxy_manager;
+pub mod traefik;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::Notify;
+
+use crate::cli::TagFilter;
+use crate::config::Config;
+use crate::core::correlation;
+use crate::core::deadline::Deadline;
+use crate::core::events::Event;
+use crate::core::lock::RunLock;
+use crate::core::record::DNSRecord;
+use crate::core::registry::ProviderRegistry;
+use crate::core::stats::SyncOutcome;
+use crate::daemon::journal::{Entry, Journal};
+use crate::daemon::mdns::Advertiser;
+use crate::error::Error;
+use crate::secret::redact;
+
+pub struct DaemonOptions {
+    /// Read records from a file instead of 1Password
+    pub file: Option<PathBuf>,
+    /// Only synchronize the named provider
+    pub only: Option<String>,
+    /// Which records to synchronize by their tags
+    pub tags: TagFilter,
+    pub interval: Duration,
+    /// Time limit of each run
+    pub timeout: Option<Duration>,
+    /// Sync even if the desired records are empty
+    pub allow_empty: bool,
+    /// Kept across runs, so each provider logs in once
+    pub providers: Arc<ProviderRegistry>,
+    /// Also advertises the desired records over mDNS
+    pub mdns: Option<Advertiser>,
+}
+
+/// Outcome of one reconcile run.
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    /// Sent as `X-Request-Id` with the provider requests of the run
+    pub run_id: String,
+    /// Unix timestamps
+    pub started_at: u64,
+    pub finished_at: u64,
+    /// Why the run could not start, e.g. unreadable rewrites
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub providers: BTreeMap<String, ProviderReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderReport {
+    pub ok: bool,
+    /// Counts, timings and the error, if any
+    #[serde(flatten)]
+    pub outcome: SyncOutcome,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    /// Desired records of the last run, including added hosts
+    pub hosts: Vec<DNSRecord>,
+    pub last_sync: Option<SyncReport>,
+}
+
+/// A host added through the control API.
+#[derive(Debug, Clone, Serialize)]
+pub struct AddedHost {
+    #[serde(flatten)]
+    pub record: DNSRecord,
+    /// Unix timestamp after which the host is dropped unless added again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl AddedHost {
+    fn expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// State shared between the reconcile loop and the control API.
+#[derive(Default)]
+pub struct DaemonState {
+    status: Mutex<Status>,
+    /// Hosts added through the control API, kept until their lease lapses
+    /// or the daemon exits
+    added: Mutex<Vec<AddedHost>>,
+    reconcile: Notify,
+    /// Log of applied events, replayed after a restart
+    journal: Option<Journal>,
+}
+
+impl DaemonState {
+    pub fn with_journal(journal: Journal) -> Self {
+        Self {
+            journal: Some(journal),
+            ..Self::default()
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn added(&self) -> Vec<AddedHost> {
+        self.added.lock().unwrap().clone()
+    }
+
+    /// Apply an event, asking for a run if it changed anything; false if
+    /// there was nothing to change.
+    pub fn apply(&self, event: Event) -> bool {
+        if let Some(journal) = &self.journal {
+            let entry = Entry {
+                at: now(),
+                event: event.clone(),
+            };
+            if let Err(e) = journal.append(&entry) {
+                eprintln!("{e}");
+            }
+        }
+        let changed = match event {
+            Event::HostAdded { record, lease } => {
+                self.add_host(record, lease.map(Duration::from_secs));
+                true
+            }
+            Event::HostRemoved { name } => self.remove_host(&name),
+            Event::ForceReconcile => true,
+        };
+        if changed {
+            self.reconcile.notify_one();
+        }
+        changed
+    }
+
+    /// Add a host, or renew it when it is already there. With a `lease`,
+    /// the host must be added again within that time to be kept.
+    fn add_host(&self, record: DNSRecord, lease: Option<Duration>) {
+        let expires_at = lease.map(|lease| now() + lease.as_secs());
+        self.insert_host(AddedHost { record, expires_at });
+    }
+
+    fn insert_host(&self, host: AddedHost) {
+        let mut added = self.added.lock().unwrap();
+        added.retain(|h| {
+            !(h.record.name == host.record.name && h.record.record_type == host.record.record_type)
+        });
+        added.push(host);
+    }
+
+    /// Apply the journaled events again to restore the added hosts, then
+    /// compact the journal to those still added. Leases keep counting
+    /// from when the host was added. Returns the number of replayed events.
+    pub fn replay(&self) -> Result<usize, Error> {
+        let Some(journal) = &self.journal else {
+            return Ok(0);
+        };
+        let entries = journal.read()?;
+        for entry in &entries {
+            match &entry.event {
+                Event::HostAdded { record, lease } => self.insert_host(AddedHost {
+                    record: record.clone(),
+                    expires_at: lease.map(|lease| entry.at + lease),
+                }),
+                Event::HostRemoved { name } => {
+                    self.remove_host(name);
+                }
+                Event::ForceReconcile => {}
+            }
+        }
+        let now = now();
+        self.live_hosts(now);
+        let compacted: Vec<Entry> = self
+            .added()
+            .into_iter()
+            .map(|host| Entry {
+                at: now,
+                event: Event::HostAdded {
+                    lease: host.expires_at.map(|at| at - now),
+                    record: host.record,
+                },
+            })
+            .collect();
+        journal.compact(&compacted)?;
+        Ok(entries.len())
+    }
+
+    /// Forget added hosts named `name`; false if there were none.
+    fn remove_host(&self, name: &str) -> bool {
+        let mut added = self.added.lock().unwrap();
+        let before = added.len();
+        added.retain(|h| h.record.name != name);
+        added.len() != before
+    }
+
+    /// Drop hosts whose lease lapsed by `now`, returning the records still
+    /// desired.
+    fn live_hosts(&self, now: u64) -> Vec<DNSRecord> {
+        let mut added = self.added.lock().unwrap();
+        added.retain(|h| {
+            let expired = h.expired(now);
+            if expired {
+                println!("Lease expired: {}", h.record);
+            }
+            !expired
+        });
+        added.iter().map(|h| h.record.clone()).collect()
+    }
+
+    fn finish(&self, hosts: Vec<DNSRecord>, report: SyncReport) {
+        let mut status = self.status.lock().unwrap();
+        status.hosts = hosts;
+        status.last_sync = Some(report);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+async fn reconcile(config: &Config, options: &DaemonOptions, state: &DaemonState) {
+    let started_at = now();
+    let run_id = correlation::start_run();
+    let deadline = Deadline::after(options.timeout);
+    // Skip the run rather than race a manual `update`
+    let locks = crate::selected_providers(config, options.only.as_deref()).and_then(|names| {
+        RunLock::acquire_all(&config.lock_dir(), &names, false).map_err(|e| e.to_string())
+    });
+    let prepared = match locks {
+        Ok(locks) => crate::prepare(
+            config,
+            options.providers.clone(),
+            options.file.clone(),
+            options.only.clone(),
+            &options.tags,
+            deadline,
+            options.allow_empty,
+        )
+        .await
+        .map(|prepared| (locks, prepared)),
+        Err(e) => Err(e),
+    };
+    let (hosts, report) = match prepared {
+        Ok((_locks, mut prepared)) => {
+            prepared.desired.extend(state.live_hosts(now()));
+            let desired = prepared.desired.clone();
+            let providers = crate::sync_all(config, prepared, deadline, false, false, None, None)
+                .await
+                .into_iter()
+                .map(|(name, outcome)| {
+                    let report = ProviderReport {
+                        ok: outcome.is_ok(),
+                        outcome,
+                    };
+                    (name, report)
+                })
+                .collect();
+            let report = SyncReport {
+                run_id,
+                started_at,
+                finished_at: now(),
+                error: None,
+                providers,
+            };
+            (desired, report)
+        }
+        Err(e) => {
+            let e = redact(&e);
+            eprintln!("{e}");
+            let report = SyncReport {
+                run_id,
+                started_at,
+                finished_at: now(),
+                error: Some(e),
+                providers: BTreeMap::new(),
+            };
+            (state.status().hosts, report)
+        }
+    };
+    state.finish(hosts, report);
+}
+
+/// Reconcile every `interval`, or sooner when asked to, until interrupted
+/// or terminated, as service managers stop it.
+pub async fn run(config: &Config, options: DaemonOptions, state: Arc<DaemonState>) {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => Some(terminate),
+        Err(e) => {
+            eprintln!("Failed to listen for SIGTERM: {e}");
+            None
+        }
+    };
+    loop {
+        reconcile(config, &options, &state).await;
+        if let Some(mdns) = &options.mdns {
+            mdns.advertise(&state.status().hosts);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(options.interval) => {}
+            _ = state.reconcile.notified() => {}
+            _ = tokio::signal::ctrl_c() => break,
+            Some(_) = async { terminate.as_mut()?.recv().await } => break,
+        }
+    }
+    if let Some(mdns) = &options.mdns {
+        mdns.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+
+    fn host(name: &str) -> DNSRecord {
+        DNSRecord {
+            record_type: DNSRecordType::A,
+            name: name.to_string(),
+            value: "10.0.0.2".to_string(),
+            ttl: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_replay_restores_added_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let state = DaemonState::with_journal(Journal::open(&path).unwrap());
+        state.apply(Event::HostAdded {
+            record: host("kept.lab.example.com"),
+            lease: Some(600),
+        });
+        state.apply(Event::HostAdded {
+            record: host("removed.lab.example.com"),
+            lease: None,
+        });
+        state.apply(Event::HostRemoved {
+            name: "removed.lab.example.com".to_string(),
+        });
+        let expires_at = state.added()[0].expires_at;
+
+        let restarted = DaemonState::with_journal(Journal::open(&path).unwrap());
+        assert_eq!(restarted.replay().unwrap(), 3);
+        let added = restarted.added();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].record, host("kept.lab.example.com"));
+        assert_eq!(added[0].expires_at, expires_at);
+
+        // The journal was compacted to the remaining host
+        let again = DaemonState::with_journal(Journal::open(&path).unwrap());
+        assert_eq!(again.replay().unwrap(), 1);
+        assert_eq!(again.added().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_file_edits_between_runs_are_seen() {
+        let dir = tempfile::tempdir().unwrap();
+        let hosts = dir.path().join("hosts");
+        let records = dir.path().join("rewrites.txt");
+        std::fs::write(&records, "10.0.0.2 nas.lab.example.com\n").unwrap();
+        let raw = format!(
+            "lock_dir = {:?}\n[providers.lan]\ntype = \"hosts\"\npath = {:?}\n",
+            dir.path(),
+            hosts
+        );
+        let config = Config::parse(&raw).unwrap();
+        let options = DaemonOptions {
+            file: Some(records),
+            only: None,
+            tags: TagFilter::default(),
+            interval: Duration::from_secs(60),
+            timeout: None,
+            allow_empty: false,
+            providers: Arc::new(crate::provider_registry(&config).unwrap()),
+            mdns: None,
+        };
+        let state = DaemonState::default();
+
+        reconcile(&config, &options, &state).await;
+        assert!(
+            std::fs::read_to_string(&hosts)
+                .unwrap()
+                .contains("nas.lab.example.com")
+        );
+
+        // Someone empties the file; the kept provider must not trust its
+        // copy from the last run
+        std::fs::write(&hosts, "").unwrap();
+        reconcile(&config, &options, &state).await;
+        assert!(state.status().last_sync.unwrap().providers["lan"].ok);
+        assert!(
+            std::fs::read_to_string(&hosts)
+                .unwrap()
+                .contains("nas.lab.example.com")
+        );
+    }
+
+    #[test]
+    fn test_leases_expire_unless_renewed() {
+        let state = DaemonState::default();
+        state.add_host(host("kept.lab.example.com"), None);
+        state.add_host(
+            host("leased.lab.example.com"),
+            Some(Duration::from_secs(60)),
+        );
+        let expires_at = state.added()[1].expires_at.unwrap();
+
+        assert_eq!(state.live_hosts(expires_at - 1).len(), 2);
+        assert_eq!(
+            state.live_hosts(expires_at),
+            vec![host("kept.lab.example.com")]
+        );
+
+        // Adding the host again renews its lease
+        state.add_host(
+            host("leased.lab.example.com"),
+            Some(Duration::from_secs(60)),
+        );
+        state.add_host(
+            host("leased.lab.example.com"),
+            Some(Duration::from_secs(600)),
+        );
+        assert_eq!(state.added().len(), 2);
+        assert_eq!(state.live_hosts(now() + 300).len(), 2);
+    }
+}
@@ -0,0 +1,154 @@
+//! Hosts discovered from the proxy hosts of Nginx Proxy Manager.
+//!
+//! The API takes a token, which is requested with the credentials
+//! `npm_email` and `npm_password` on every poll, as tokens expire.
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::daemon::discovery::{Publish, Source, default_interval};
+use crate::error::Error;
+use crate::secret::Secret;
+
+/// The `[nginx_proxy_manager]` configuration section.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NginxProxyManagerConfig {
+    /// Nginx Proxy Manager API, e.g. `http://npm.lan:81/api`
+    pub api_url: String,
+    /// Address or name the discovered hosts point to, usually the proxy's own
+    pub target: String,
+    pub ttl: Option<u32>,
+    /// Seconds between polls
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+/// An Nginx Proxy Manager instance and the account to read it with.
+pub struct NginxProxyManager {
+    pub config: NginxProxyManagerConfig,
+    pub email: Secret,
+    pub password: Secret,
+}
+
+#[derive(Debug, Deserialize)]
+struct Token {
+    token: Secret,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxyHost {
+    #[serde(default)]
+    domain_names: Vec<String>,
+    /// `1`/`0` in older versions, a boolean in newer ones
+    #[serde(default)]
+    enabled: Value,
+}
+
+impl ProxyHost {
+    fn enabled(&self) -> bool {
+        !matches!(self.enabled, Value::Bool(false)) && self.enabled.as_u64() != Some(0)
+    }
+}
+
+#[async_trait]
+impl Source for NginxProxyManager {
+    const NAME: &'static str = "Nginx Proxy Manager";
+
+    fn publish(&self) -> Publish<'_> {
+        Publish {
+            target: &self.config.target,
+            ttl: self.config.ttl,
+            interval: self.config.interval,
+        }
+    }
+
+    async fn names(&self, client: &reqwest::Client) -> Result<Vec<String>, Error> {
+        let api_url = self.config.api_url.trim_end_matches('/');
+        let error = |e: reqwest::Error| Error::Other(format!("nginx proxy manager: {e}"));
+        let token: Token = client
+            .post(format!("{api_url}/tokens"))
+            .json(&json!({
+                "identity": self.email.expose(),
+                "secret": self.password.expose(),
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(error)?
+            .json()
+            .await
+            .map_err(error)?;
+        let hosts: Vec<ProxyHost> = client
+            .get(format!("{api_url}/nginx/proxy-hosts"))
+            .bearer_auth(token.token.expose())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(error)?
+            .json()
+            .await
+            .map_err(error)?;
+        Ok(hosts
+            .into_iter()
+            .filter(ProxyHost::enabled)
+            .flat_map(|host| host.domain_names)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::discovery::DEFAULT_POLL_INTERVAL_SECS;
+    use httpmock::prelude::*;
+
+    #[tokio::test]
+    async fn test_names_of_enabled_proxy_hosts() {
+        let server = MockServer::start_async().await;
+        let login = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/api/tokens").json_body(
+                    json!({"identity": "admin@example.com", "secret": "changeme-please"}),
+                );
+                then.status(200).json_body(
+                    json!({"token": "eyJhbGciOi.test", "expires": "2030-01-01T00:00:00Z"}),
+                );
+            })
+            .await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/api/nginx/proxy-hosts")
+                    .header("Authorization", "Bearer eyJhbGciOi.test");
+                then.status(200).json_body(json!([
+                    {"id": 1, "domain_names": ["nas.lab.example.com", "files.lab.example.com"], "enabled": 1},
+                    {"id": 2, "domain_names": ["wiki.lab.example.com"], "enabled": true},
+                    {"id": 3, "domain_names": ["old.lab.example.com"], "enabled": 0}
+                ]));
+            })
+            .await;
+        let npm = NginxProxyManager {
+            config: NginxProxyManagerConfig {
+                api_url: server.url("/api"),
+                target: "10.0.0.10".to_string(),
+                ttl: None,
+                interval: DEFAULT_POLL_INTERVAL_SECS,
+            },
+            email: Secret::new("admin@example.com"),
+            password: Secret::new("changeme-please"),
+        };
+        let names = npm.names(&reqwest::Client::new()).await.unwrap();
+        assert_eq!(
+            names,
+            [
+                "nas.lab.example.com",
+                "files.lab.example.com",
+                "wiki.lab.example.com"
+            ]
+        );
+        login.assert_async().await;
+    }
+}
@@ -0,0 +1,140 @@
+//! Hosts discovered from the routers of a Traefik instance.
+//!
+//! Names come from the `Host` rules of the enabled routers, whatever
+//! provider defined them: Docker labels, the file provider or Kubernetes.
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::daemon::discovery::{Publish, Source, default_interval};
+use crate::error::Error;
+
+/// The `[traefik]` configuration section.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TraefikConfig {
+    /// Traefik API, e.g. `http://traefik.lan:8080`
+    pub api_url: String,
+    /// Address or name the discovered hosts point to, usually Traefik's own
+    pub target: String,
+    pub ttl: Option<u32>,
+    /// Seconds between polls
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraefikRouter {
+    #[serde(default)]
+    rule: String,
+    #[serde(default)]
+    status: String,
+}
+
+/// The names matched by the `Host` matchers of a router rule, e.g.
+/// ``Host(`a.example.com`) || Host(`b.example.com`, `c.example.com`)``.
+/// `HostRegexp` and `HostSNI` matchers are not names and are skipped.
+fn rule_hosts(rule: &str) -> Vec<String> {
+    let mut hosts = Vec::new();
+    let mut rest = rule;
+    while let Some(start) = rest.find("Host(") {
+        let preceded_by_word = rest[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric());
+        rest = &rest[start + "Host(".len()..];
+        let Some(end) = rest.find(')') else { break };
+        if !preceded_by_word {
+            hosts.extend(
+                rest[..end]
+                    .split(',')
+                    .map(|arg| arg.trim().trim_matches(['`', '"']).to_string())
+                    .filter(|name| !name.is_empty()),
+            );
+        }
+        rest = &rest[end..];
+    }
+    hosts
+}
+
+#[async_trait]
+impl Source for TraefikConfig {
+    const NAME: &'static str = "Traefik";
+
+    fn publish(&self) -> Publish<'_> {
+        Publish {
+            target: &self.target,
+            ttl: self.ttl,
+            interval: self.interval,
+        }
+    }
+
+    async fn names(&self, client: &reqwest::Client) -> Result<Vec<String>, Error> {
+        let url = format!("{}/api/http/routers", self.api_url.trim_end_matches('/'));
+        let error = |e: reqwest::Error| Error::Other(format!("traefik: {e}"));
+        let routers: Vec<TraefikRouter> = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(error)?
+            .json()
+            .await
+            .map_err(error)?;
+        Ok(routers
+            .iter()
+            .filter(|router| router.status.is_empty() || router.status == "enabled")
+            .flat_map(|router| rule_hosts(&router.rule))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::discovery::DEFAULT_POLL_INTERVAL_SECS;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rule_hosts() {
+        assert_eq!(
+            rule_hosts("Host(`nas.lab.example.com`)"),
+            ["nas.lab.example.com"]
+        );
+        assert_eq!(
+            rule_hosts("Host(`a.example.com`, `b.example.com`) && PathPrefix(`/api`)"),
+            ["a.example.com", "b.example.com"]
+        );
+        assert_eq!(
+            rule_hosts("Host(\"a.example.com\") || HostRegexp(`{sub:[a-z]+}.example.com`)"),
+            ["a.example.com"]
+        );
+        assert!(rule_hosts("HostSNI(`*`)").is_empty());
+        assert!(rule_hosts("PathPrefix(`/`)").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_names_of_enabled_routers() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/api/http/routers");
+                then.status(200).json_body(json!([
+                    {"name": "nas@docker", "rule": "Host(`nas.lab.example.com`)", "status": "enabled"},
+                    {"name": "wiki@file", "rule": "Host(`wiki.lab.example.com`)", "status": "enabled"},
+                    {"name": "old@docker", "rule": "Host(`old.lab.example.com`)", "status": "disabled"}
+                ]));
+            })
+            .await;
+        let config = TraefikConfig {
+            api_url: server.base_url(),
+            target: "10.0.0.10".to_string(),
+            ttl: None,
+            interval: DEFAULT_POLL_INTERVAL_SECS,
+        };
+        let names = config.names(&reqwest::Client::new()).await.unwrap();
+        assert_eq!(names, ["nas.lab.example.com", "wiki.lab.example.com"]);
+    }
+}
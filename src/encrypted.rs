@@ -0,0 +1,192 @@
+//! Desired-records files encrypted with SOPS or age.
+//!
+//! Encrypted files are recognized by their contents and decrypted in
+//! memory, so the records can be kept in a public repository without
+//! exposing internal host names. age files are decrypted natively; SOPS
+//! files through the `sops` CLI, which handles their key groups and MAC.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+use crate::error::Error;
+use crate::secret::redact;
+
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1\n";
+const AGE_ARMOR_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// The `[decryption]` configuration section.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DecryptionConfig {
+    /// age identity file, e.g. `~/.config/sops/age/keys.txt`; SOPS falls
+    /// back to its own key lookup when omitted
+    pub identity: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    Age,
+    Sops,
+}
+
+/// How `contents` are encrypted, if at all.
+pub fn detect(contents: &[u8]) -> Option<Encryption> {
+    let contents = contents.trim_ascii_start();
+    if contents.starts_with(AGE_HEADER) || contents.starts_with(AGE_ARMOR_HEADER) {
+        return Some(Encryption::Age);
+    }
+    // SOPS keeps its metadata under a top-level `sops` key, in JSON or YAML
+    let sops_json = contents.starts_with(b"{")
+        && serde_json::from_slice::<serde_json::Value>(contents)
+            .is_ok_and(|value| value.get("sops").is_some_and(|sops| sops.is_object()));
+    let sops_yaml = contents.split(|&b| b == b'\n').any(|line| line == b"sops:");
+    (sops_json || sops_yaml).then_some(Encryption::Sops)
+}
+
+fn decryption_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::Other(format!("{}: {e}", path.display()))
+}
+
+/// `contents` of the file at `path`, decrypted if encrypted. The `sops` CLI
+/// reads the file itself.
+pub async fn decrypt(
+    path: &Path,
+    contents: Vec<u8>,
+    config: &DecryptionConfig,
+) -> Result<String, Error> {
+    let plaintext = match detect(&contents) {
+        None => contents,
+        Some(Encryption::Age) => {
+            let identity = config.identity.as_deref().ok_or_else(|| {
+                decryption_error(
+                    path,
+                    "age-encrypted, but no identity is set in the [decryption] section",
+                )
+            })?;
+            decrypt_age(&contents, identity).map_err(|e| decryption_error(path, e))?
+        }
+        Some(Encryption::Sops) => decrypt_sops(path, &contents, config)
+            .await
+            .map_err(|e| decryption_error(path, e))?,
+    };
+    String::from_utf8(plaintext).map_err(|e| decryption_error(path, e))
+}
+
+fn decrypt_age(contents: &[u8], identity: &Path) -> Result<Vec<u8>, String> {
+    let identities = age::IdentityFile::from_file(identity.display().to_string())
+        .map_err(|e| format!("identity {}: {e}", identity.display()))?
+        .into_identities()
+        .map_err(|e| format!("identity {}: {e}", identity.display()))?;
+    let decryptor =
+        age::Decryptor::new(age::armor::ArmoredReader::new(contents)).map_err(|e| e.to_string())?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref()))
+        .map_err(|e| e.to_string())?;
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .map_err(|e| e.to_string())?;
+    Ok(plaintext)
+}
+
+// Files encrypted with `sops --encrypt --input-type binary` hold the whole
+// plaintext under one key, which `--output-type binary` restores
+async fn decrypt_sops(
+    path: &Path,
+    contents: &[u8],
+    config: &DecryptionConfig,
+) -> Result<Vec<u8>, String> {
+    let input_type = if contents.trim_ascii_start().starts_with(b"{") {
+        "json"
+    } else {
+        "yaml"
+    };
+    let mut command = Command::new("sops");
+    command
+        .arg("--decrypt")
+        .arg("--input-type")
+        .arg(input_type)
+        .arg("--output-type")
+        .arg("binary")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(identity) = &config.identity {
+        command.env("SOPS_AGE_KEY_FILE", identity);
+    }
+    let output = command
+        .output()
+        .await
+        .map_err(|e| format!("failed to run sops: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "sops: {}",
+            redact(String::from_utf8_lossy(&output.stderr).trim())
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+
+    const REWRITES: &str = "10.0.0.5 nas.lab.example.com\n";
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(REWRITES.as_bytes()), None);
+        assert_eq!(
+            detect(b"age-encryption.org/v1\n-> X25519 abc\n"),
+            Some(Encryption::Age)
+        );
+        assert_eq!(
+            detect(b"-----BEGIN AGE ENCRYPTED FILE-----\nYWdl\n"),
+            Some(Encryption::Age)
+        );
+        assert_eq!(
+            detect(br#"{"data": "ENC[AES256_GCM,data:abc]", "sops": {"mac": "x"}}"#),
+            Some(Encryption::Sops)
+        );
+        assert_eq!(
+            detect(b"data: ENC[AES256_GCM,data:abc]\nsops:\n    mac: x\n"),
+            Some(Encryption::Sops)
+        );
+        assert_eq!(detect(br#"{"sops": "not metadata"}"#), None);
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_age_encrypted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = age::x25519::Identity::generate();
+        let identity = dir.path().join("keys.txt");
+        std::fs::write(&identity, key.to_string().expose_secret()).unwrap();
+        let path = dir.path().join("rewrites.age");
+        let ciphertext = age::encrypt(&key.to_public(), REWRITES.as_bytes()).unwrap();
+
+        let config = DecryptionConfig {
+            identity: Some(identity),
+        };
+        let decrypted = decrypt(&path, ciphertext.clone(), &config).await;
+        assert_eq!(decrypted.unwrap(), REWRITES);
+
+        let err = decrypt(&path, ciphertext, &DecryptionConfig::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no identity"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_plain_file_is_read_as_is() {
+        let path = Path::new("rewrites.txt");
+        let config = DecryptionConfig::default();
+        let decrypted = decrypt(path, REWRITES.into(), &config).await;
+        assert_eq!(decrypted.unwrap(), REWRITES);
+    }
+}
@@ -1,25 +1,138 @@
 use std::fmt;
 
+use thiserror::Error as ThisError;
+
+use crate::onepassword::OnePasswordError;
+use crate::secret::redact;
+
+/// Errors of the crate. Messages are redacted when formatted, as they may
+/// quote requests made with credentials. Errors converted from another
+/// error keep it as their `source`.
 #[allow(clippy::enum_variant_names)]
-#[derive(Debug)]
+#[derive(ThisError)]
 pub enum Error {
+    #[error("Provider error: {}", redact(.0))]
     ProviderError(String),
+    #[error("Credential error: {}", redact(.0))]
     CredentialError(String),
+    #[error("Not found: {}", redact(.0))]
     NotFound(String),
+    #[error("Invalid input: {}", redact(.0))]
     InvalidInput(String),
+    #[error("Other error: {}", redact(.0))]
     Other(String),
+    /// A request to a provider's API that got no response
+    #[error("HTTP error: {}", redact(&.0.to_string()))]
+    Http(#[from] reqwest::Error),
+    #[error("Credential error: {}", redact(&.0.to_string()))]
+    OnePassword(#[from] OnePasswordError),
+    #[error("I/O error: {}", redact(&.0.to_string()))]
+    Io(#[from] std::io::Error),
+    /// A provider's own error kept whole, so callers can inspect it, e.g.
+    /// the status of a rejected request; shown and classified as `error`
+    #[error("{error}")]
+    Provider {
+        error: Box<Error>,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
-impl std::error::Error for Error {}
+impl Error {
+    /// `error`, keeping `source` as the error it was made from.
+    pub fn with_source(
+        error: Error,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Error::Provider {
+            error: Box::new(error),
+            source: Box::new(source),
+        }
+    }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    /// The error as classified, looking through a kept provider error.
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Provider { error, .. } => error.kind(),
+            other => other,
+        }
+    }
+
+    /// Short name of the kind of error, for grouping failures in reports.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Error::ProviderError(_) | Error::Http(_) => "provider",
+            Error::CredentialError(_) | Error::OnePassword(_) => "credential",
+            Error::NotFound(_) => "not_found",
+            Error::InvalidInput(_) => "invalid_input",
+            Error::Other(_) | Error::Io(_) => "other",
+            Error::Provider { error, .. } => error.category(),
+        }
+    }
+
+    /// Code of the error for scripts matching on failures, finer than the
+    /// category. Codes are never reused or renamed.
+    pub fn code(&self) -> &'static str {
         match self {
-            Error::ProviderError(msg) => write!(f, "Provider error: {msg}"),
-            Error::CredentialError(msg) => write!(f, "Credential error: {msg}"),
-            Error::NotFound(msg) => write!(f, "Not found: {msg}"),
-            Error::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
-            Error::Other(msg) => write!(f, "Other error: {msg}"),
+            Error::ProviderError(_) => "E100",
+            Error::Http(_) => "E101",
+            Error::CredentialError(_) => "E200",
+            Error::OnePassword(_) => "E201",
+            Error::NotFound(_) => "E300",
+            Error::InvalidInput(_) => "E400",
+            Error::Io(_) => "E500",
+            Error::Other(_) => "E900",
+            Error::Provider { error, .. } => error.code(),
         }
     }
+
+    fn message(&self) -> String {
+        match self {
+            Error::ProviderError(msg)
+            | Error::CredentialError(msg)
+            | Error::NotFound(msg)
+            | Error::InvalidInput(msg)
+            | Error::Other(msg) => redact(msg),
+            Error::Http(e) => redact(&e.to_string()),
+            Error::OnePassword(e) => redact(&e.to_string()),
+            Error::Io(e) => redact(&e.to_string()),
+            Error::Provider { error, .. } => error.message(),
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variant = match self {
+            Error::ProviderError(_) => "ProviderError",
+            Error::CredentialError(_) => "CredentialError",
+            Error::NotFound(_) => "NotFound",
+            Error::InvalidInput(_) => "InvalidInput",
+            Error::Other(_) => "Other",
+            Error::Http(_) => "Http",
+            Error::OnePassword(_) => "OnePassword",
+            Error::Io(_) => "Io",
+            Error::Provider { .. } => "Provider",
+        };
+        f.debug_tuple(variant).field(&self.message()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_converted_errors_keep_their_source() {
+        let e: Error = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file").into();
+        assert_eq!(e.to_string(), "I/O error: no such file");
+        assert_eq!(e.source().unwrap().to_string(), "no such file");
+        assert_eq!((e.category(), e.code()), ("other", "E500"));
+
+        let e: Error = OnePasswordError::Cli("not signed in".to_string()).into();
+        assert_eq!(e.category(), "credential");
+        assert!(e.source().is_some());
+        assert!(Error::NotFound("nas".to_string()).source().is_none());
+    }
 }
@@ -0,0 +1,149 @@
+//! dnscontrol configurations, as printed by `dnscontrol print-ir`: the
+//! `dnsconfig.js` evaluated into JSON, with every domain and its records.
+//!
+//! ```json
+//! {"domains": [{"name": "example.com", "records": [
+//!   {"type": "A", "name": "@", "target": "203.0.113.10", "ttl": 300},
+//!   {"type": "CNAME", "name": "www", "target": "example.com."}
+//! ]}]}
+//! ```
+
+use serde::Deserialize;
+
+use super::{Imported, qualify};
+use crate::core::rdata::{Naptr, Sshfp};
+
+#[derive(Deserialize)]
+struct Config {
+    domains: Vec<Domain>,
+}
+
+#[derive(Deserialize)]
+struct Domain {
+    name: String,
+    #[serde(default)]
+    records: Vec<Record>,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    #[serde(rename = "type")]
+    record_type: String,
+    name: String,
+    /// The full name, in recent versions
+    #[serde(default)]
+    namefqdn: Option<String>,
+    #[serde(default)]
+    target: String,
+    ttl: Option<u32>,
+    /// TXT data in older versions, which leave `target` empty
+    #[serde(default)]
+    txtstrings: Vec<String>,
+    #[serde(default)]
+    sshfpalgorithm: u8,
+    /// The fingerprint type of SSHFP records, whose `target` is the fingerprint
+    #[serde(default)]
+    sshfpfingerprint: u8,
+    #[serde(default)]
+    naptrorder: u16,
+    #[serde(default)]
+    naptrpreference: u16,
+    #[serde(default)]
+    naptrflags: String,
+    #[serde(default)]
+    naptrservice: String,
+    /// The regexp of NAPTR records, whose `target` is the replacement
+    #[serde(default)]
+    naptrregexp: String,
+}
+
+pub fn parse(raw: &str) -> Result<Imported, String> {
+    let config: Config = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+    let mut imported = Imported::default();
+    for domain in config.domains {
+        let zone = domain.name.trim_end_matches('.');
+        for record in domain.records {
+            let name = match record.namefqdn {
+                Some(fqdn) => fqdn.trim_end_matches('.').to_string(),
+                None => qualify(&record.name, zone),
+            };
+            let value = match record.record_type.as_str() {
+                "TXT" if record.target.is_empty() => record.txtstrings.concat(),
+                "CNAME" | "PTR" => target(&record.target, zone),
+                "SSHFP" => Sshfp {
+                    algorithm: record.sshfpalgorithm,
+                    fingerprint_type: record.sshfpfingerprint,
+                    fingerprint: record.target,
+                }
+                .to_string(),
+                "NAPTR" => Naptr {
+                    order: record.naptrorder,
+                    preference: record.naptrpreference,
+                    flags: record.naptrflags,
+                    service: record.naptrservice,
+                    regexp: record.naptrregexp,
+                    replacement: match record.target.as_str() {
+                        "" | "." => ".".to_string(),
+                        replacement => target(replacement, zone),
+                    },
+                }
+                .to_string(),
+                _ => record.target,
+            };
+            imported.push(&record.record_type, &name, value, record.ttl);
+        }
+    }
+    Ok(imported)
+}
+
+// Host name targets may be relative to the domain, unless they end in a dot
+fn target(target: &str, zone: &str) -> String {
+    if target.ends_with('.') {
+        target.to_string()
+    } else {
+        qualify(target, zone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecord;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_parse_print_ir_output() {
+        let raw = r#"{
+  "registrars": [],
+  "dns_providers": [],
+  "domains": [
+    {
+      "name": "example.com",
+      "registrar": "none",
+      "records": [
+        {"type": "A", "name": "@", "target": "203.0.113.10", "ttl": 300},
+        {"type": "CNAME", "name": "www", "namefqdn": "www.example.com",
+         "target": "@"},
+        {"type": "CNAME", "name": "docs", "target": "pages.example.net."},
+        {"type": "TXT", "name": "@", "txtstrings": ["v=spf1 ", "-all"]},
+        {"type": "MX", "name": "@", "target": "mail.example.com.", "mxpreference": 10}
+      ]
+    }
+  ]
+}"#;
+        let imported = parse(raw).unwrap();
+        assert_eq!(
+            imported.records,
+            [
+                DNSRecord::a("example.com", Ipv4Addr::new(203, 0, 113, 10)).ttl(300),
+                DNSRecord::cname("www.example.com", "example.com"),
+                DNSRecord::cname("docs.example.com", "pages.example.net"),
+                DNSRecord::txt("example.com", "v=spf1 -all"),
+            ]
+        );
+        assert_eq!(
+            imported.skipped,
+            ["example.com MX: unsupported record type"]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Desired records kept in the formats of other DNS-as-code tools, so teams
+//! using octodns, dnscontrol or Terraform can sync the same records to the
+//! providers those tools lack, like NextDNS.
+//!
+//! The format of a records file is chosen by its extension: `.yaml` and
+//! `.yml` files are octodns zone files, `.json` files are dnscontrol
+//! `print-ir` output, `.tfstate` files are Terraform state, and anything
+//! else is a rewrites file.
+
+mod dnscontrol;
+mod octodns;
+mod terraform;
+
+use std::path::Path;
+
+use crate::core::rdata::{Naptr, Sshfp};
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    OctoDns,
+    DnsControl,
+    Terraform,
+}
+
+impl Format {
+    /// The format of the file at `path`, or `None` for a rewrites file. An
+    /// `.age` suffix is looked past, as in `example.com.yaml.age`.
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let name = name.strip_suffix(".age").unwrap_or(name);
+        match name.rsplit_once('.')?.1 {
+            "yaml" | "yml" => Some(Format::OctoDns),
+            "json" => Some(Format::DnsControl),
+            "tfstate" => Some(Format::Terraform),
+            _ => None,
+        }
+    }
+}
+
+/// The records of an imported file, and those that cannot be synced.
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub records: Vec<DNSRecord>,
+    /// Records that cannot be synced, like MX records, each described as
+    /// `name TYPE: reason`
+    pub skipped: Vec<String>,
+}
+
+impl Imported {
+    fn skip(&mut self, name: &str, record_type: &str, reason: &str) {
+        self.skipped.push(format!("{name} {record_type}: {reason}"));
+    }
+
+    // Add a record of `record_type` at `name`, or skip it when the type is
+    // not one of ours
+    fn push(&mut self, type_name: &str, name: &str, value: String, ttl: Option<u32>) {
+        let Ok(record_type) = type_name.parse::<DNSRecordType>() else {
+            return self.skip(name, type_name, "unsupported record type");
+        };
+        // Host name targets are written fully qualified, with the root dot
+        let value = match record_type {
+            DNSRecordType::CNAME | DNSRecordType::PTR => {
+                Ok(value.trim_end_matches('.').to_string())
+            }
+            DNSRecordType::NAPTR => value.parse::<Naptr>().map(|naptr| naptr.to_string()),
+            DNSRecordType::SSHFP => value.parse::<Sshfp>().map(|sshfp| sshfp.to_string()),
+            _ => Ok(value),
+        };
+        match value {
+            Ok(value) => {
+                let record = DNSRecord::new(record_type, name, value).ttl(ttl);
+                self.records.push(record);
+            }
+            Err(e) => self.skip(name, type_name, &e),
+        }
+    }
+}
+
+/// Read the records of the file at `path`, holding `raw`, in `format`.
+pub fn parse(format: Format, path: &Path, raw: &str) -> Result<Imported, Error> {
+    let imported = match format {
+        Format::OctoDns => octodns::parse(&octodns::zone(path)?, raw),
+        Format::DnsControl => dnscontrol::parse(raw),
+        Format::Terraform => terraform::parse(raw),
+    };
+    imported.map_err(|e| Error::InvalidInput(format!("{}: {e}", path.display())))
+}
+
+// `name` in `zone`, where `@` and the empty name stand for the apex
+fn qualify(name: &str, zone: &str) -> String {
+    match name {
+        "" | "@" => zone.to_string(),
+        name => format!("{name}.{zone}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_for_path() {
+        let format = |path: &str| Format::for_path(Path::new(path));
+        assert_eq!(format("zones/example.com.yaml"), Some(Format::OctoDns));
+        assert_eq!(format("example.com.yml.age"), Some(Format::OctoDns));
+        assert_eq!(format("dnsconfig.json"), Some(Format::DnsControl));
+        assert_eq!(format("terraform.tfstate"), Some(Format::Terraform));
+        assert_eq!(format("records.txt"), None);
+        assert_eq!(format("records"), None);
+    }
+}
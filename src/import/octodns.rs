@@ -0,0 +1,243 @@
+//! octodns zone files: one YAML file per zone, named after it, mapping
+//! names relative to the zone to a record or a list of records.
+//!
+//! ```yaml
+//! '':
+//!   - type: A
+//!     values: [203.0.113.10]
+//!   - type: TXT
+//!     value: v=spf1 -all
+//! www:
+//!   type: CNAME
+//!   value: example.com.
+//! ```
+
+use serde::Deserialize;
+use serde_yaml_ng::{Mapping, Value};
+use std::path::Path;
+
+use super::{Imported, qualify};
+use crate::core::rdata::{Naptr, Sshfp};
+use crate::core::record::DNSRecordType;
+use crate::error::Error;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Records {
+    One(Record),
+    Many(Vec<Record>),
+}
+
+#[derive(Deserialize)]
+struct Record {
+    #[serde(rename = "type")]
+    record_type: String,
+    ttl: Option<u32>,
+    value: Option<Value>,
+    #[serde(default)]
+    values: Vec<Value>,
+}
+
+/// The zone of the file at `path`, from its name, e.g. `example.com` for
+/// `zones/example.com.yaml`.
+pub fn zone(path: &Path) -> Result<String, Error> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let name = name.strip_suffix(".age").unwrap_or(name);
+    match name.rsplit_once('.') {
+        Some((zone, _)) if !zone.is_empty() => Ok(zone.to_string()),
+        _ => Err(Error::InvalidInput(format!(
+            "{}: octodns files are named after their zone",
+            path.display()
+        ))),
+    }
+}
+
+pub fn parse(zone: &str, raw: &str) -> Result<Imported, String> {
+    let names: Mapping = serde_yaml_ng::from_str(raw).map_err(|e| e.to_string())?;
+    let mut imported = Imported::default();
+    for (name, records) in names {
+        // Names like `1` of reverse zones are read as numbers
+        let name = match name {
+            Value::Null => String::new(),
+            Value::String(name) => name,
+            Value::Number(name) => name.to_string(),
+            other => return Err(format!("unexpected name: {other:?}")),
+        };
+        let fqdn = qualify(&name, zone);
+        let records = match serde_yaml_ng::from_value(records) {
+            Ok(Records::One(record)) => vec![record],
+            Ok(Records::Many(records)) => records,
+            Err(e) => return Err(format!("{fqdn}: {e}")),
+        };
+        for record in records {
+            if record.record_type.parse::<DNSRecordType>().is_err() {
+                imported.skip(&fqdn, &record.record_type, "unsupported record type");
+                continue;
+            }
+            for value in record.value.into_iter().chain(record.values) {
+                let value = match value {
+                    Value::String(value) => value.replace("\\;", ";"),
+                    Value::Number(value) => value.to_string(),
+                    Value::Mapping(fields) => match fields_value(&record.record_type, fields) {
+                        Ok(value) => value,
+                        Err(e) => return Err(format!("{fqdn}: {e}")),
+                    },
+                    other => return Err(format!("{fqdn}: unexpected value: {other:?}")),
+                };
+                imported.push(&record.record_type, &fqdn, value, record.ttl);
+            }
+        }
+    }
+    Ok(imported)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SshfpFields {
+    algorithm: u8,
+    fingerprint_type: u8,
+    fingerprint: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NaptrFields {
+    order: u16,
+    preference: u16,
+    flags: String,
+    service: String,
+    regexp: String,
+    replacement: String,
+}
+
+// The presentation format of a value given as fields, like the
+// `algorithm`, `fingerprint_type` and `fingerprint` of SSHFP records
+fn fields_value(record_type: &str, fields: Mapping) -> Result<String, String> {
+    let fields = Value::Mapping(fields);
+    match record_type {
+        "SSHFP" => {
+            let f: SshfpFields = serde_yaml_ng::from_value(fields).map_err(|e| e.to_string())?;
+            Ok(Sshfp {
+                algorithm: f.algorithm,
+                fingerprint_type: f.fingerprint_type,
+                fingerprint: f.fingerprint,
+            }
+            .to_string())
+        }
+        "NAPTR" => {
+            let f: NaptrFields = serde_yaml_ng::from_value(fields).map_err(|e| e.to_string())?;
+            Ok(Naptr {
+                order: f.order,
+                preference: f.preference,
+                flags: f.flags,
+                service: f.service,
+                regexp: f.regexp,
+                replacement: f.replacement,
+            }
+            .to_string())
+        }
+        _ => Err(format!("unexpected value: {fields:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecord;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_parse_zone_file() {
+        let raw = r#"
+'':
+  - type: A
+    ttl: 600
+    values:
+      - 203.0.113.10
+      - 203.0.113.11
+  - type: TXT
+    value: v=spf1 include:_spf.example.com \; -all
+  - type: MX
+    values:
+      - exchange: mail.example.com.
+        preference: 10
+www:
+  type: CNAME
+  value: example.com.
+"#;
+        let imported = parse("example.com", raw).unwrap();
+        assert_eq!(
+            imported.records,
+            [
+                DNSRecord::a("example.com", Ipv4Addr::new(203, 0, 113, 10)).ttl(600),
+                DNSRecord::a("example.com", Ipv4Addr::new(203, 0, 113, 11)).ttl(600),
+                DNSRecord::txt("example.com", "v=spf1 include:_spf.example.com ; -all"),
+                DNSRecord::cname("www.example.com", "example.com"),
+            ]
+        );
+        assert_eq!(
+            imported.skipped,
+            ["example.com MX: unsupported record type"]
+        );
+    }
+
+    #[test]
+    fn test_parse_field_values() {
+        let raw = r#"
+nas:
+  - type: SSHFP
+    values:
+      - algorithm: 4
+        fingerprint_type: 2
+        fingerprint: 9D9A8E7B6C5D4E3F2A1B0C9D8E7F6A5B4C3D2E1F0A9B8C7D6E5F4A3B2C1D0E9F
+      - algorithm: 1
+        fingerprint_type: 1
+        fingerprint: abcd
+sip:
+  type: NAPTR
+  value:
+    order: 10
+    preference: 0
+    flags: S
+    service: SIP+D2U
+    regexp: ''
+    replacement: _sip._udp.example.com.
+"#;
+        let imported = parse("example.com", raw).unwrap();
+        assert_eq!(
+            imported.records,
+            [
+                DNSRecord::new(
+                    DNSRecordType::SSHFP,
+                    "nas.example.com",
+                    "4 2 9d9a8e7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f"
+                ),
+                DNSRecord::new(
+                    DNSRecordType::NAPTR,
+                    "sip.example.com",
+                    r#"10 0 "s" "SIP+D2U" "" _sip._udp.example.com."#
+                ),
+            ]
+        );
+        assert_eq!(
+            imported.skipped,
+            ["nas.example.com SSHFP: SSHFP fingerprint of type 1 has 40 hex digits, not 4"]
+        );
+    }
+
+    #[test]
+    fn test_zone_from_file_name() {
+        assert_eq!(
+            zone(Path::new("zones/example.com.yaml")).unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            zone(Path::new("lab.example.com.yml.age")).unwrap(),
+            "lab.example.com"
+        );
+        assert!(zone(Path::new(".yaml")).is_err());
+    }
+}
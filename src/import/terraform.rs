@@ -0,0 +1,148 @@
+//! Terraform state files, for records managed as `cloudflare_record` or
+//! `aws_route53_record` resources. Other resources are ignored.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::Imported;
+
+#[derive(Deserialize)]
+struct State {
+    version: u32,
+    #[serde(default)]
+    resources: Vec<Resource>,
+}
+
+#[derive(Deserialize)]
+struct Resource {
+    /// `managed`, or `data` for data sources
+    mode: String,
+    #[serde(rename = "type")]
+    resource_type: String,
+    #[serde(default)]
+    instances: Vec<Instance>,
+}
+
+#[derive(Deserialize)]
+struct Instance {
+    attributes: Value,
+}
+
+pub fn parse(raw: &str) -> Result<Imported, String> {
+    let state: State = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+    if state.version != 4 {
+        return Err(format!(
+            "unsupported state version {}, expected 4",
+            state.version
+        ));
+    }
+    let mut imported = Imported::default();
+    for resource in state.resources.iter().filter(|r| r.mode == "managed") {
+        for instance in &resource.instances {
+            let attributes = &instance.attributes;
+            match resource.resource_type.as_str() {
+                "cloudflare_record" => cloudflare(attributes, &mut imported),
+                "aws_route53_record" => route53(attributes, &mut imported),
+                _ => {}
+            }
+        }
+    }
+    Ok(imported)
+}
+
+fn text<'a>(attributes: &'a Value, key: &str) -> &'a str {
+    attributes.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+fn ttl(attributes: &Value) -> Option<u32> {
+    attributes
+        .get("ttl")
+        .and_then(Value::as_u64)
+        .and_then(|ttl| u32::try_from(ttl).ok())
+}
+
+// `hostname` holds the full name, `name` may be relative to the zone.
+// Version 4 of the provider renamed `value` to `content`; a TTL of 1
+// stands for automatic.
+fn cloudflare(attributes: &Value, imported: &mut Imported) {
+    let name = match text(attributes, "hostname") {
+        "" => text(attributes, "name"),
+        hostname => hostname,
+    };
+    let value = match text(attributes, "content") {
+        "" => text(attributes, "value"),
+        content => content,
+    };
+    let ttl = ttl(attributes).filter(|&ttl| ttl != 1);
+    imported.push(text(attributes, "type"), name, value.to_string(), ttl);
+}
+
+// Alias records point at AWS resources rather than holding values
+fn route53(attributes: &Value, imported: &mut Imported) {
+    let name = text(attributes, "name").trim_end_matches('.');
+    let record_type = text(attributes, "type");
+    let values = attributes.get("records").and_then(Value::as_array);
+    match values {
+        Some(values) if !values.is_empty() => {
+            for value in values.iter().filter_map(Value::as_str) {
+                imported.push(record_type, name, value.to_string(), ttl(attributes));
+            }
+        }
+        _ => imported.skip(name, record_type, "alias records are not supported"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecord;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_parse_state() {
+        let raw = r#"{
+  "version": 4,
+  "terraform_version": "1.9.0",
+  "resources": [
+    {"mode": "managed", "type": "cloudflare_record", "name": "www", "instances": [
+      {"attributes": {"name": "www", "hostname": "www.example.com", "type": "CNAME",
+                      "content": "example.com", "ttl": 1, "proxied": false}}
+    ]},
+    {"mode": "managed", "type": "cloudflare_record", "name": "mx", "instances": [
+      {"attributes": {"name": "example.com", "type": "MX", "value": "mail.example.com", "ttl": 300}}
+    ]},
+    {"mode": "managed", "type": "aws_route53_record", "name": "nas", "instances": [
+      {"attributes": {"name": "nas.lab.example.com.", "type": "A", "ttl": 300,
+                      "records": ["10.0.0.2", "10.0.0.3"], "alias": []}}
+    ]},
+    {"mode": "managed", "type": "aws_route53_record", "name": "apex", "instances": [
+      {"attributes": {"name": "example.com", "type": "A", "ttl": null, "records": null,
+                      "alias": [{"name": "d111.cloudfront.net", "zone_id": "Z2FDTNDATAQYW2"}]}}
+    ]},
+    {"mode": "data", "type": "cloudflare_record", "name": "lookup", "instances": [
+      {"attributes": {"hostname": "ignored.example.com", "type": "A", "content": "10.9.9.9"}}
+    ]},
+    {"mode": "managed", "type": "cloudflare_zone", "name": "zone", "instances": [
+      {"attributes": {"zone": "example.com"}}
+    ]}
+  ]
+}"#;
+        let imported = parse(raw).unwrap();
+        assert_eq!(
+            imported.records,
+            [
+                DNSRecord::cname("www.example.com", "example.com"),
+                DNSRecord::a("nas.lab.example.com", Ipv4Addr::new(10, 0, 0, 2)).ttl(300),
+                DNSRecord::a("nas.lab.example.com", Ipv4Addr::new(10, 0, 0, 3)).ttl(300),
+            ]
+        );
+        assert_eq!(
+            imported.skipped,
+            [
+                "example.com MX: unsupported record type",
+                "example.com A: alias records are not supported"
+            ]
+        );
+        assert!(parse(r#"{"version": 3, "modules": []}"#).is_err());
+    }
+}
@@ -1,159 +1,2473 @@
 use crate::core::provider::DNSProvider;
 // Module declarations for binary crate
 mod auth;
+mod cli;
+mod config;
+mod context;
 mod core;
+mod daemon;
+mod encrypted;
 mod error;
+mod import;
 mod onepassword;
+mod progress;
 mod providers;
-use std::env;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
+mod report;
+mod schema;
+mod secret;
+mod service;
+mod signature;
+#[cfg(test)]
+mod testing;
+mod webhook;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, IsTerminal};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::auth::credentials::{CredentialManager, OnePasswordCredentialManager};
-use crate::core::record::{DNSRecord, DNSRecordType};
+use clap::Parser;
+use futures::future;
+use tracing_subscriber::EnvFilter;
+
+use crate::auth::credentials::{
+    CredentialManager, EnvCredentialManager, FallbackCredentialManager,
+    OnePasswordCredentialManager,
+};
+use crate::cli::{
+    AcmeCommand, Challenge, Cli, Command, ConfigCommand, DaemonArgs, ProvidersCommand,
+    RecordsCommand, ServiceCommand, TagFilter,
+};
+use crate::config::{Config, ProviderConfig};
+use crate::context::Context;
+use crate::core::acme::{self, TxtLookup};
+use crate::core::cache::{CachedProvider, RecordCache};
+use crate::core::canary::{self, CanarySize, RecordLookup};
+use crate::core::checkpoint::{Checkpoint, CheckpointFile, fingerprint};
+use crate::core::compare;
+use crate::core::correlation;
+use crate::core::deadline::{Cancelled, Deadline};
+use crate::core::diff::{self, Change, Plan};
+use crate::core::flatten::{self, AddressLookup};
+use crate::core::http::HttpOptions;
+use crate::core::interfaces;
+use crate::core::lock::RunLock;
+use crate::core::migrate;
+use crate::core::ownership::Owned;
+use crate::core::planfile::{PlanFile, ProviderPlan};
+use crate::core::record::{DNSRecord, DNSRecordType, is_valid_hostname};
+use crate::core::record_set::{self, SetOperation};
+use crate::core::registry::ProviderRegistry;
+use crate::core::removal::{self, RemovalCheck};
+use crate::core::reverse;
+use crate::core::scope::Scope;
+use crate::core::stats::{SyncOutcome, SyncStats};
+use crate::core::template::{self, PublicIp};
+use crate::daemon::discovery;
+use crate::daemon::journal::Journal;
+use crate::daemon::mdns::Advertiser;
+use crate::daemon::nginx_proxy_manager::NginxProxyManager;
+use crate::daemon::{DaemonOptions, DaemonState};
+use crate::error::Error;
 use crate::onepassword::OnePasswordClient;
-use crate::providers::nextdns::{NextDNSConfig, NextDNSProvider};
+use crate::progress::{Progress, Status};
+use crate::report::{DiffFormat, ReportFormat};
+use crate::secret::redact;
+use crate::service::{Service, ServiceManager, ServiceSpec};
+use crate::signature::SignatureConfig;
+use crate::webhook::Webhook;
+
+/// Runs expected to take at least this long at a provider's rate limit
+/// announce how long they will take.
+const ETA_NOTICE: Duration = Duration::from_secs(30);
 
 #[tokio::main]
-async fn main() {
-    // Parse optional file argument
-    let args: Vec<String> = env::args().collect();
-    let file_arg = args.get(1);
-
-    // 1Password client and credential manager
-    let op_client = Arc::new(OnePasswordClient::new("Applications"));
-    let creds = Arc::new(OnePasswordCredentialManager::new(op_client.clone()));
-
-    // Load config from 1Password
-    let config = match creds.get("nextdns_profile_id") {
-        Ok(profile_id) => NextDNSConfig {
-            profile_id,
-            api_url: "https://api.nextdns.io".to_string(),
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // `RUST_LOG=dns_update=debug` logs how records were compared
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(io::stderr)
+        .init();
+
+    // Printed without a configuration, which may be the file being written
+    if matches!(
+        cli.command,
+        Command::Config {
+            command: ConfigCommand::Schema
+        }
+    ) {
+        println!("{:#}", schema::config_schema());
+        return ExitCode::SUCCESS;
+    }
+
+    let config = match (&cli.config, &cli.profile) {
+        (Some(path), profile) => match Config::load(path, profile.as_deref()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Failed to load config: {e}");
+                return ExitCode::FAILURE;
+            }
         },
-        Err(e) => {
-            eprintln!("Failed to load NextDNS profile ID: {e}");
-            return;
+        (None, Some(_)) => {
+            eprintln!("--profile needs a configuration file");
+            return ExitCode::FAILURE;
+        }
+        (None, None) => Config::default(),
+    };
+
+    let result = match cli.command {
+        Command::Update {
+            file,
+            provider,
+            tags,
+            timeout,
+            force_unlock,
+            allow_large,
+            allow_empty,
+            report,
+            quiet,
+            canary,
+            removals,
+        } => {
+            let removals = match removals.check_removals {
+                Some(check) => {
+                    match RecordLookup::new(&removals.removal_resolver, &config.resolver) {
+                        Ok(lookup) => Some(RemovalGuard { check, lookup }),
+                        Err(e) => {
+                            eprintln!("{e}");
+                            return ExitCode::FAILURE;
+                        }
+                    }
+                }
+                None => None,
+            };
+            let canary = match canary.canary {
+                Some(size) => match RecordLookup::new(&canary.canary_resolver, &config.resolver) {
+                    Ok(lookup) => Some(Canary {
+                        size,
+                        lookup,
+                        timeout: canary.canary_timeout,
+                    }),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => None,
+            };
+            let options = UpdateOptions {
+                timeout,
+                force_unlock,
+                allow_large,
+                allow_empty,
+                report,
+                quiet,
+                canary,
+                removals,
+            };
+            update(&config, file, provider, &tags, options).await
         }
+        Command::Plan {
+            file,
+            provider,
+            tags,
+            timeout,
+            allow_empty,
+            diff_format,
+            no_color,
+            out,
+        } => {
+            let color =
+                !no_color && std::env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+            let options = PlanOptions {
+                timeout,
+                allow_empty,
+                format: diff_format,
+                color,
+                out,
+            };
+            plan(&config, file, provider, &tags, options).await
+        }
+        Command::Apply {
+            plan,
+            timeout,
+            force_unlock,
+            allow_large,
+            report,
+            quiet,
+        } => {
+            let options = UpdateOptions {
+                timeout,
+                force_unlock,
+                allow_large,
+                allow_empty: false,
+                report,
+                quiet,
+                canary: None,
+                removals: None,
+            };
+            apply(&config, &plan, options).await
+        }
+        Command::Ddns { ip, ip_from, name } => match interfaces::addresses_in(&ip_from) {
+            Ok(local) => ddns(&config, &[ip, local].concat(), name).await,
+            Err(e) => Err(e.to_string()),
+        },
+        Command::Acme { command } => acme(&config, command).await,
+        Command::Compare { from, to, timeout } => {
+            compare(&config, &from, &to, Deadline::after(timeout)).await
+        }
+        Command::Migrate {
+            from,
+            to,
+            names,
+            types,
+            delete_source,
+            dry_run,
+            timeout,
+            force_unlock,
+            allow_large,
+            report,
+            quiet,
+        } => match Scope::new(names) {
+            Ok(names) => {
+                let migration = Migration {
+                    from,
+                    to,
+                    filter: migrate::Filter { names, types },
+                    delete_source,
+                    dry_run,
+                };
+                let options = UpdateOptions {
+                    timeout,
+                    force_unlock,
+                    allow_large,
+                    allow_empty: false,
+                    report,
+                    quiet,
+                    canary: None,
+                    removals: None,
+                };
+                migrate(&config, migration, options).await
+            }
+            Err(e) => Err(e.to_string()),
+        },
+        Command::Records { command } => records(&config, command).await,
+        Command::Daemon(args) => daemon(&config, args).await,
+        Command::Service { command } => {
+            service(cli.config.as_deref(), cli.profile.as_deref(), command)
+        }
+        Command::Webhook { provider, listen } => serve_webhook(&config, &provider, listen).await,
+        Command::Providers {
+            command: ProvidersCommand::List,
+        } => {
+            for (name, provider) in &config.providers {
+                println!("{name}\t{}", provider.kind());
+            }
+            for (name, ddns) in &config.ddns {
+                println!("{name}\t{} (ddns)", ddns.kind());
+            }
+            Ok(())
+        }
+        Command::Config { command } => match (command, &cli.config) {
+            (ConfigCommand::Check, Some(path)) => {
+                println!("{}: OK", path.display());
+                Ok(())
+            }
+            (ConfigCommand::Check, None) => {
+                Err("config check needs a configuration file".to_string())
+            }
+            (ConfigCommand::Schema, _) => unreachable!("printed before loading the configuration"),
+        },
     };
 
-    // Create provider
-    let provider = match NextDNSProvider::new(config, creds.clone()).await {
-        Ok(p) => p,
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
         Err(e) => {
-            eprintln!("Failed to create provider: {e:?}");
-            return;
+            eprintln!("{}", redact(&e));
+            ExitCode::FAILURE
         }
+    }
+}
+
+// How `update` runs and reports
+struct UpdateOptions {
+    timeout: Option<Duration>,
+    /// Take over locks whose run is gone
+    force_unlock: bool,
+    /// Apply changes exceeding the limits
+    allow_large: bool,
+    allow_empty: bool,
+    report: ReportFormat,
+    quiet: bool,
+    canary: Option<Canary>,
+    removals: Option<RemovalGuard>,
+}
+
+// Removals looked up for managed CNAMEs still resolving through them
+struct RemovalGuard {
+    check: RemovalCheck,
+    lookup: RecordLookup,
+}
+
+// The first changes of each provider, applied and checked before the rest
+struct Canary {
+    size: CanarySize,
+    lookup: RecordLookup,
+    timeout: Duration,
+}
+
+async fn update(
+    config: &Config,
+    file: Option<PathBuf>,
+    only: Option<String>,
+    tags: &TagFilter,
+    options: UpdateOptions,
+) -> Result<(), String> {
+    let started = Instant::now();
+    correlation::start_run();
+    let deadline = Deadline::after(options.timeout);
+    // Locked before listing, so the listings cannot go stale under us
+    let names = selected_providers(config, only.as_deref())?;
+    let _locks = RunLock::acquire_all(&config.lock_dir(), &names, options.force_unlock)
+        .map_err(|e| e.to_string())?;
+    let prepared = prepare(
+        config,
+        Arc::new(provider_registry(config)?),
+        file,
+        only,
+        tags,
+        deadline,
+        options.allow_empty,
+    )
+    .await?;
+    let prepare_time = started.elapsed();
+
+    let outcomes = sync_all(
+        config,
+        prepared,
+        deadline,
+        options.quiet,
+        options.allow_large,
+        options.canary.as_ref(),
+        options.removals.as_ref(),
+    )
+    .await;
+    finish_run(&outcomes, started, prepare_time, &options)
+}
+
+// Print the report of a run and fail it if any provider failed
+fn finish_run(
+    outcomes: &BTreeMap<String, SyncOutcome>,
+    started: Instant,
+    prepare_time: Duration,
+    options: &UpdateOptions,
+) -> Result<(), String> {
+    let timings = report::RunTimings {
+        prepare: prepare_time,
+        total: started.elapsed(),
     };
+    // A quiet run still prints a report asked for by scripts
+    if !options.quiet || options.report != ReportFormat::Text {
+        let run_id = correlation::run_id();
+        print!(
+            "{}",
+            report::render_run(outcomes, timings, run_id.as_deref(), options.report)
+        );
+    }
 
-    // Read rewrites
-    let desired_records: Vec<DNSRecord> = if let Some(file_path) = file_arg {
-        match read_rewrites_from_file(file_path) {
-            Ok(records) => records,
-            Err(e) => {
-                eprintln!("Failed to read rewrites from file: {e}");
-                return;
+    let failed: Vec<&str> = outcomes
+        .iter()
+        .filter(|(_, outcome)| !outcome.is_ok())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to sync {}", failed.join(", ")))
+    }
+}
+
+// Apply the changes of a saved plan to providers whose records are still
+// the ones it was planned against
+async fn apply(config: &Config, path: &Path, options: UpdateOptions) -> Result<(), String> {
+    let started = Instant::now();
+    correlation::start_run();
+    let saved = PlanFile::load(path).with_context(|| format!("loading {}", path.display()))?;
+    let deadline = Deadline::after(options.timeout);
+    let names: Vec<String> = saved.providers.keys().cloned().collect();
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !config.providers.contains_key(*name))
+    {
+        return Err(format!("Unknown provider: {unknown}"));
+    }
+    let _locks = RunLock::acquire_all(&config.lock_dir(), &names, options.force_unlock)
+        .map_err(|e| e.to_string())?;
+    let registry = provider_registry(config)?;
+    init_providers(&registry, &names, deadline).await?;
+    let mut snapshot = Snapshot::default();
+    snapshot.refresh(&registry, &names, deadline).await;
+    let prepare_time = started.elapsed();
+
+    let mut outcomes = BTreeMap::new();
+    for (name, planned) in saved.providers {
+        let Some(provider) = registry.get(&name) else {
+            continue;
+        };
+        let mut outcome = SyncOutcome::default();
+        let mut progress = Progress::new(options.quiet);
+        let result = match snapshot.get(&name) {
+            Ok(listing) => {
+                let (current, _) = config.scope.partition(&listing.records);
+                outcome.stats.list_time = listing.elapsed;
+                outcome.stats.examined = current.len();
+                if config.providers[&name].read_only() {
+                    Err(format!("{name} is read-only"))
+                } else if let Some(reason) = config
+                    .limits
+                    .exceeded(&planned.changes, current.len())
+                    .filter(|_| !options.allow_large)
+                {
+                    Err(format!(
+                        "Not applying: {reason}; pass --allow-large to apply anyway"
+                    ))
+                } else if planned.matches(&current) {
+                    apply_changes(
+                        provider.as_ref(),
+                        planned.changes,
+                        &[],
+                        deadline,
+                        None,
+                        &mut outcome.stats,
+                        &mut progress,
+                    )
+                    .await
+                } else {
+                    Err("Records changed since the plan was made; plan again".to_string())
+                }
             }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            progress.error(&format!("Failed to apply the plan of {name}: {e}"));
+            outcome.error = Some(e);
         }
-    } else {
-        // Read rewrites from 1Password
-        match op_client.get_dns_rewrites().await {
-            Ok(raw) => match parse_rewrites_from_str(&raw) {
-                Ok(records) => records,
-                Err(e) => {
-                    eprintln!("Failed to parse rewrites from 1Password: {e}");
-                    return;
+        outcomes.insert(name, outcome);
+    }
+    finish_run(&outcomes, started, prepare_time, &options)
+}
+
+// How `plan` prints and saves the changes
+struct PlanOptions {
+    timeout: Option<Duration>,
+    allow_empty: bool,
+    format: DiffFormat,
+    color: bool,
+    out: Option<PathBuf>,
+}
+
+async fn plan(
+    config: &Config,
+    file: Option<PathBuf>,
+    only: Option<String>,
+    tags: &TagFilter,
+    options: PlanOptions,
+) -> Result<(), String> {
+    correlation::start_run();
+    let deadline = Deadline::after(options.timeout);
+    let Prepared {
+        desired: desired_records,
+        registry,
+        snapshot,
+    } = prepare(
+        config,
+        Arc::new(provider_registry(config)?),
+        file,
+        only,
+        tags,
+        deadline,
+        options.allow_empty,
+    )
+    .await?;
+
+    let mut plans = Vec::new();
+    let mut saved = PlanFile::default();
+    let mut failed = false;
+    for name in registry.list() {
+        let Some(provider) = registry.get(&name) else {
+            continue;
+        };
+        let desired = desired_for(config, &name, &desired_records);
+        let mut stats = SyncStats::default();
+        let listing = snapshot.get(&name);
+        let progress = Progress::new(false);
+        match listing.and_then(|listing| {
+            let (current, _) = config.scope.partition(&listing.records);
+            let plan = plan_changes(
+                provider.as_ref(),
+                listing,
+                &desired,
+                config,
+                &mut stats,
+                &progress,
+            )?;
+            Ok((current, plan))
+        }) {
+            Ok((current, mut plan)) => {
+                match deadline.run(provider.plan_settings()).await {
+                    Ok(Ok(settings)) => plan.settings = settings,
+                    Ok(Err(e)) => {
+                        eprintln!("Failed to plan the settings of {name}: {e}");
+                        failed = true;
+                    }
+                    Err(Cancelled) => {
+                        eprintln!("Cancelled: planning the settings of {name}");
+                        failed = true;
+                    }
+                }
+                // Shown as drift, but never applied
+                if config.providers[&name].read_only() {
+                    if !plan.is_empty() || !plan.settings.is_empty() {
+                        eprintln!("{name} is read-only: its changes will not be applied");
+                    }
+                } else {
+                    if let Some(reason) = config.limits.exceeded(&plan.changes(), current.len()) {
+                        eprintln!("{name}: {reason}; applying needs --allow-large");
+                    }
+                    let planned = ProviderPlan::new(&current, plan.changes());
+                    saved.providers.insert(name.clone(), planned);
                 }
-            },
+                plans.push((name, plan));
+            }
             Err(e) => {
-                eprintln!("Failed to read rewrites from 1Password: {e}");
-                return;
+                eprintln!("Failed to plan {name}: {}", redact(&e));
+                failed = true;
+            }
+        }
+    }
+    print!("{}", report::render(&plans, options.format, options.color));
+
+    if failed {
+        return Err("One or more providers failed to plan".to_string());
+    }
+    if let Some(out) = &options.out {
+        saved.save(out).map_err(|e| e.to_string())?;
+        eprintln!("Saved the plan to {}", out.display());
+    }
+    Ok(())
+}
+
+// Synchronize every provider in the registry, reporting each outcome
+async fn sync_all(
+    config: &Config,
+    prepared: Prepared,
+    deadline: Deadline,
+    quiet: bool,
+    allow_large: bool,
+    canary: Option<&Canary>,
+    removals: Option<&RemovalGuard>,
+) -> BTreeMap<String, SyncOutcome> {
+    let Prepared {
+        desired: desired_records,
+        registry,
+        snapshot,
+    } = prepared;
+    let checkpoints = config.checkpoint.as_ref().map(CheckpointFile::new);
+    let mut outcomes = BTreeMap::new();
+    for name in registry.list() {
+        let Some(provider) = registry.get(&name) else {
+            continue;
+        };
+        let mut outcome = SyncOutcome::default();
+        let mut progress = Progress::new(quiet);
+        if deadline.expired() {
+            progress.error(&format!("Cancelled: sync of {name}"));
+            outcome.error = Some("Cancelled".to_string());
+        } else {
+            let desired = desired_for(config, &name, &desired_records);
+            let read_only = config.providers.get(&name).is_some_and(|p| p.read_only());
+            let checkpoint = checkpoints
+                .as_ref()
+                .filter(|_| !read_only)
+                .map(|file| (file, name.as_str()));
+            let changes = changes_to_apply(
+                provider.as_ref(),
+                snapshot.get(&name),
+                &desired,
+                config,
+                checkpoint,
+                &mut outcome.stats,
+                &progress,
+            );
+            let changes = match (changes, removals) {
+                (Ok(mut changes), Some(guard)) if !read_only => check_removals(
+                    guard,
+                    &mut changes,
+                    &desired,
+                    deadline,
+                    &mut outcome.stats,
+                    &progress,
+                )
+                .await
+                .map(|()| changes),
+                (changes, _) => changes,
+            };
+            let result = match changes {
+                Ok(changes) if read_only => {
+                    report_drift(
+                        provider.as_ref(),
+                        &changes,
+                        deadline,
+                        &mut outcome.stats,
+                        &progress,
+                    )
+                    .await
+                }
+                Ok(changes)
+                    if let Some(reason) = config
+                        .limits
+                        .exceeded(&changes, outcome.stats.examined)
+                        .filter(|_| !allow_large) =>
+                {
+                    Err(format!(
+                        "Not applying: {reason}; pass --allow-large to apply anyway"
+                    ))
+                }
+                Ok(mut changes) => {
+                    // With a canary, its changes go first and the rest waits
+                    // until they resolve
+                    let rest = match canary {
+                        Some(canary) if !changes.is_empty() => {
+                            changes.split_off(canary.size.of(changes.len()))
+                        }
+                        _ => Vec::new(),
+                    };
+                    let checked = canary::checked_records(&changes);
+                    let mut result = apply_changes(
+                        provider.as_ref(),
+                        changes,
+                        &desired,
+                        deadline,
+                        checkpoint,
+                        &mut outcome.stats,
+                        &mut progress,
+                    )
+                    .await;
+                    if let (Ok(()), Some(canary)) = (&result, canary)
+                        && !rest.is_empty()
+                    {
+                        result = check_canary(canary, &checked, deadline, &progress).await;
+                        if result.is_ok() {
+                            result = apply_changes(
+                                provider.as_ref(),
+                                rest,
+                                &desired,
+                                deadline,
+                                checkpoint,
+                                &mut outcome.stats,
+                                &mut progress,
+                            )
+                            .await;
+                        } else {
+                            progress.error(&format!(
+                                "Not applying the other {} change(s) to {name}",
+                                rest.len()
+                            ));
+                        }
+                    }
+                    if result.is_ok() {
+                        result = sync_settings(provider.as_ref(), deadline, &progress).await;
+                    }
+                    result
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                progress.error(&format!("Failed to sync {name}: {e}"));
+                outcome.error = Some(e);
             }
         }
+        outcomes.insert(name, outcome);
+    }
+    outcomes
+}
+
+// Bring the provider's managed settings in line, once its records are
+async fn sync_settings(
+    provider: &dyn DNSProvider,
+    deadline: Deadline,
+    progress: &Progress,
+) -> Result<(), String> {
+    let changes = match deadline.run(provider.plan_settings()).await {
+        Ok(result) => result.map_err(|e| e.to_string())?,
+        Err(Cancelled) => return Err("Cancelled while planning settings".to_string()),
     };
+    for change in changes {
+        match deadline.run(provider.apply_setting(&change)).await {
+            Ok(Ok(())) => progress.note(&format!("{}: {change}", provider.name())),
+            Ok(Err(e)) => return Err(format!("Failed to {change}: {e}")),
+            Err(Cancelled) => return Err("Cancelled while applying settings".to_string()),
+        }
+    }
+    Ok(())
+}
 
-    // Fetch current records
-    let current_records = match provider.list_records().await {
-        Ok(records) => records,
-        Err(e) => {
-            eprintln!("Failed to list current records: {e:?}");
-            return;
+// What applying `change` would do, for changes that are not applied
+fn pending(change: &Change) -> &'static str {
+    match change {
+        Change::Add(_) => "would be created",
+        Change::Update(_) => "would be updated",
+        Change::Remove(_) => "would be deleted",
+    }
+}
+
+// Report what a sync would change at a read-only provider, leaving it as
+// it is
+async fn report_drift(
+    provider: &dyn DNSProvider,
+    changes: &[Change],
+    deadline: Deadline,
+    stats: &mut SyncStats,
+    progress: &Progress,
+) -> Result<(), String> {
+    for change in changes {
+        progress.record(Status::Drift, change.record(), Some(pending(change)));
+    }
+    let settings = match deadline.run(provider.plan_settings()).await {
+        Ok(result) => result.map_err(|e| e.to_string())?,
+        Err(Cancelled) => return Err("Cancelled while planning settings".to_string()),
+    };
+    for change in &settings {
+        progress.note(&format!(
+            "{:<9} {}: would {change}",
+            Status::Drift,
+            provider.name()
+        ));
+    }
+    stats.drift = changes.len() + settings.len();
+    Ok(())
+}
+
+// Look up whether the removals among `changes` would leave managed CNAMEs
+// dangling, and report them, or take them out of `changes` when blocking
+async fn check_removals(
+    guard: &RemovalGuard,
+    changes: &mut Vec<Change>,
+    desired: &[DNSRecord],
+    deadline: Deadline,
+    stats: &mut SyncStats,
+    progress: &Progress,
+) -> Result<(), String> {
+    let values = |record: DNSRecord| async move { guard.lookup.values(&record).await };
+    let found = match deadline
+        .run(removal::live_dependencies(changes, desired, values))
+        .await
+    {
+        Ok(found) => found,
+        Err(Cancelled) => return Err("Cancelled while checking removals".to_string()),
+    };
+    for dependency in &found {
+        let detail = format!("{} resolves through it", dependency.dependent);
+        match guard.check {
+            RemovalCheck::Warn => progress.note(&format!(
+                "Warning: deleting {} breaks {detail}",
+                dependency.removed
+            )),
+            RemovalCheck::Block => {
+                progress.record(Status::Blocked, &dependency.removed, Some(&detail))
+            }
+        }
+    }
+    if guard.check == RemovalCheck::Block {
+        let before = changes.len();
+        changes.retain(
+            |change| !matches!(change, Change::Remove(r) if found.iter().any(|d| &d.removed == r)),
+        );
+        stats.blocked += before - changes.len();
+    }
+    Ok(())
+}
+
+// Wait for the records applied as a canary to resolve
+async fn check_canary(
+    canary: &Canary,
+    records: &[DNSRecord],
+    deadline: Deadline,
+    progress: &Progress,
+) -> Result<(), String> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    progress.note(&format!(
+        "Waiting for {} canary record(s) to resolve",
+        records.len()
+    ));
+    let values = |record: DNSRecord| async move { canary.lookup.values(&record).await };
+    let verified = canary::verify(records, values, canary.timeout);
+    match deadline.run(verified).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(Cancelled) => Err("Cancelled while checking the canary".to_string()),
+    }
+}
+
+// The desired records plus PTR records for the provider's reverse prefixes
+fn desired_for(config: &Config, name: &str, desired_records: &[DNSRecord]) -> Vec<DNSRecord> {
+    match config.providers.get(name) {
+        Some(provider) => reverse::with_ptr_records(desired_records, provider.reverse()),
+        None => desired_records.to_vec(),
+    }
+}
+
+// Credentials from 1Password, then from the environment
+fn credentials(op_client: Arc<OnePasswordClient>) -> Arc<dyn CredentialManager> {
+    Arc::new(FallbackCredentialManager::new(vec![
+        Arc::new(OnePasswordCredentialManager::new(op_client)),
+        Arc::new(EnvCredentialManager),
+    ]))
+}
+
+// Read rewrites from 1Password, keeping a copy to fall back on when it is
+// unavailable
+async fn read_rewrites_from_1password(
+    config: &Config,
+    op_client: &OnePasswordClient,
+    tags: &TagFilter,
+    deadline: Deadline,
+) -> Result<Vec<DNSRecord>, String> {
+    let fetched = match deadline.run(op_client.get_dns_rewrites()).await {
+        Ok(Ok(raw)) => Ok(raw),
+        Ok(Err(e)) => Err(format!("Failed to read rewrites from 1Password: {e}")),
+        Err(Cancelled) => Err("Timed out while reading rewrites from 1Password".to_string()),
+    };
+    match (fetched, &config.rewrites_fallback) {
+        (Ok(raw), fallback) => {
+            // Checked before the fallback copy is refreshed, so it only ever
+            // holds signed rewrites
+            let signed = match &config.signature {
+                Some(signature) => {
+                    Some(verify_1password_rewrites(signature, op_client, &raw, deadline).await?)
+                }
+                None => None,
+            };
+            let rendered = fill_placeholders(config, &raw, deadline).await?;
+            let records = parse_rewrites_from_str(&rendered, tags)
+                .map_err(|e| format!("Failed to parse rewrites from 1Password: {e}"))?;
+            if let Some(fallback) = fallback {
+                save_fallback(config, fallback, &raw, signed.as_deref());
+            }
+            Ok(records)
+        }
+        (Err(e), Some(fallback)) => {
+            eprintln!("{e}; using {}", fallback.display());
+            read_rewrites_fallback(config, fallback, tags, deadline).await
+        }
+        (Err(e), None) => Err(e),
+    }
+}
+
+// Read the saved copy of the 1Password rewrites, held to the same
+// signature as 1Password itself
+async fn read_rewrites_fallback(
+    config: &Config,
+    fallback: &Path,
+    tags: &TagFilter,
+    deadline: Deadline,
+) -> Result<Vec<DNSRecord>, String> {
+    let raw = std::fs::read_to_string(fallback)
+        .map_err(|e| format!("Failed to read rewrites from file: {e}"))?;
+    if let Some(signature) = &config.signature {
+        signature
+            .verify_file(fallback, raw.as_bytes())
+            .await
+            .map_err(|e| format!("Refusing fallback rewrites: {e}"))?;
+    }
+    let raw = fill_placeholders(config, &raw, deadline).await?;
+    parse_rewrites_from_str(&raw, tags)
+        .with_context(|| format!("reading fallback rewrites from {}", fallback.display()))
+}
+
+// Save the rewrites read from 1Password, with their detached signature
+// next to them when they are signed
+fn save_fallback(config: &Config, fallback: &Path, raw: &str, signed: Option<&str>) {
+    if let Err(e) = std::fs::write(fallback, raw) {
+        eprintln!("Failed to save rewrites to {}: {e}", fallback.display());
+    }
+    if let (Some(signature), Some(signed)) = (&config.signature, signed) {
+        let path = &signature.signature_paths(fallback)[0];
+        if let Err(e) = std::fs::write(path, signed) {
+            eprintln!(
+                "Failed to save the rewrites signature to {}: {e}",
+                path.display()
+            );
+        }
+    }
+}
+
+// Check the rewrites against the signature in the same 1Password item,
+// returning the signature
+async fn verify_1password_rewrites(
+    signature: &SignatureConfig,
+    op_client: &OnePasswordClient,
+    raw: &str,
+    deadline: Deadline,
+) -> Result<String, String> {
+    let signed = match deadline.run(op_client.get_rewrites_signature()).await {
+        Ok(Ok(signed)) => signed,
+        Ok(Err(e)) => {
+            return Err(format!(
+                "Refusing rewrites from 1Password: no signature: {e}"
+            ));
+        }
+        Err(Cancelled) => {
+            return Err("Timed out while reading the rewrites signature".to_string());
         }
     };
+    signature
+        .verify(raw.as_bytes(), signed.as_bytes())
+        .await
+        .map_err(|e| format!("Refusing rewrites from 1Password: {e}"))?;
+    Ok(signed)
+}
 
-    // Compute changes
-    let to_add: Vec<_> = desired_records
-        .iter()
-        .filter(|r| !current_records.contains(r))
-        .cloned()
-        .collect();
-    let to_remove: Vec<_> = current_records
+/// Current records of a provider, listed ahead of planning.
+struct Listing {
+    records: Vec<DNSRecord>,
+    owned: Option<Owned>,
+    elapsed: Duration,
+}
+
+/// What a run needs before it can plan: the desired records, the selected
+/// providers and what they hold.
+struct Prepared {
+    desired: Vec<DNSRecord>,
+    registry: Arc<ProviderRegistry>,
+    snapshot: Snapshot,
+}
+
+/// The records of each provider as last listed. The phases of a run all
+/// work from one snapshot instead of listing again, which is slow for large
+/// zones and counts against rate limits; a provider is only listed anew
+/// when it is refreshed.
+#[derive(Default)]
+struct Snapshot {
+    listings: BTreeMap<String, Result<Listing, String>>,
+}
+
+impl Snapshot {
+    // List the named providers at once, replacing what they held before
+    async fn refresh(&mut self, registry: &ProviderRegistry, names: &[String], deadline: Deadline) {
+        let listings = names.iter().filter_map(|name| {
+            let provider = registry.get(name)?;
+            Some(async move {
+                let listing = list_current(provider.as_ref(), deadline).await;
+                (name.clone(), listing)
+            })
+        });
+        self.listings.extend(future::join_all(listings).await);
+    }
+
+    fn get(&self, name: &str) -> Result<&Listing, String> {
+        match self.listings.get(name) {
+            Some(Ok(listing)) => Ok(listing),
+            Some(Err(e)) => Err(e.clone()),
+            None => Err(format!("No records were listed for {name}")),
+        }
+    }
+}
+
+// The names of the providers a run works on
+fn selected_providers(config: &Config, only: Option<&str>) -> Result<Vec<String>, String> {
+    match only {
+        Some(only) if !config.providers.contains_key(only) => {
+            Err(format!("Unknown provider: {only}"))
+        }
+        Some(only) => Ok(vec![only.to_string()]),
+        None => Ok(config.providers.keys().cloned().collect()),
+    }
+}
+
+// `raw` with its placeholders filled in, looking up only the public
+// addresses it uses
+async fn fill_placeholders(
+    config: &Config,
+    raw: &str,
+    deadline: Deadline,
+) -> Result<String, String> {
+    let used = template::placeholders(raw).context("reading placeholders")?;
+    if used.is_empty() {
+        return Ok(raw.to_string());
+    }
+    let http = HttpOptions::from_config(&config.http).map_err(|e| e.to_string())?;
+    let public_ip = PublicIp::new(&config.public_ip, &http).map_err(|e| e.to_string())?;
+    let lookups = used.into_iter().map(|placeholder| {
+        let public_ip = &public_ip;
+        async move {
+            let address = deadline
+                .run(public_ip.lookup(placeholder))
+                .await
+                .map_err(|_| "Timed out while looking up the public address".to_string())?
+                .map_err(|e| format!("Failed to look up the public address: {e}"))?;
+            Ok::<_, String>((placeholder, address))
+        }
+    });
+    let values = future::try_join_all(lookups).await?.into_iter().collect();
+    template::render(raw, &values).context("filling in placeholders")
+}
+
+// Read the records of a rewrites file, or of a file in one of the imported
+// formats, checking its signature and decrypting it as configured
+async fn read_records_file(
+    config: &Config,
+    file_path: &Path,
+    tags: &TagFilter,
+    deadline: Deadline,
+) -> Result<Vec<DNSRecord>, String> {
+    let contents =
+        std::fs::read(file_path).map_err(|e| format!("Failed to read rewrites from file: {e}"))?;
+    if let Some(signature) = &config.signature {
+        signature
+            .verify_file(file_path, &contents)
+            .await
+            .map_err(|e| format!("Refusing rewrites from file: {e}"))?;
+    }
+    let raw = match deadline
+        .run(encrypted::decrypt(file_path, contents, &config.decryption))
+        .await
+    {
+        Ok(raw) => raw.map_err(|e| format!("Failed to read rewrites from file: {e}"))?,
+        Err(Cancelled) => return Err("Timed out while reading rewrites".to_string()),
+    };
+    let raw = fill_placeholders(config, &raw, deadline).await?;
+    match import::Format::for_path(file_path) {
+        Some(format) => {
+            let imported = import::parse(format, file_path, &raw)
+                .map_err(|e| format!("Failed to import records: {e}"))?;
+            for skipped in &imported.skipped {
+                eprintln!("Skipping {skipped}");
+            }
+            // Imported records carry no tags
+            if tags.selects(&[]) {
+                Ok(imported.records)
+            } else {
+                Ok(Vec::new())
+            }
+        }
+        None => parse_rewrites_from_str(&raw, tags),
+    }
+}
+
+// Read the desired records, from a file or 1Password, flattening CNAMEs if
+// configured
+async fn read_desired(
+    config: &Config,
+    file: Option<PathBuf>,
+    op_client: &OnePasswordClient,
+    tags: &TagFilter,
+    deadline: Deadline,
+    allow_empty: bool,
+) -> Result<Vec<DNSRecord>, String> {
+    let desired_records: Vec<DNSRecord> = if let Some(file_path) = file {
+        read_records_file(config, &file_path, tags, deadline)
+            .await
+            .with_context(|| format!("reading records from {}", file_path.display()))?
+    } else {
+        read_rewrites_from_1password(config, op_client, tags, deadline).await?
+    };
+    // An empty source is nearly always a failed fetch, not a wish to delete
+    // every record
+    if desired_records.is_empty() && !allow_empty {
+        return Err(
+            "Refusing to sync: the desired records are empty, which would \
+             delete every record in scope; pass --allow-empty if that is intended"
+                .to_string(),
+        );
+    }
+    let desired_records = config
+        .normalize
+        .apply_all(desired_records)
+        .context("normalizing the desired records")?;
+    match &config.flatten {
+        Some(names) => {
+            let lookup = AddressLookup::new(&[], &config.resolver).map_err(|e| e.to_string())?;
+            let flattened = flatten::flatten(desired_records, names, |name| lookup.addresses(name));
+            deadline
+                .run(flattened)
+                .await
+                .map_err(|_| "Timed out while flattening CNAME records".to_string())
+        }
+        None => Ok(desired_records),
+    }
+}
+
+/// What the registry's factories share to create the configured providers.
+struct ProviderContext {
+    creds: Arc<dyn CredentialManager>,
+    http: HttpOptions,
+    scope: Scope,
+    /// Record cache put in front of each provider, and the age of the
+    /// listings it serves
+    cache: Option<(RecordCache, Duration)>,
+}
+
+impl ProviderContext {
+    async fn create(
+        &self,
+        name: &str,
+        provider_config: &ProviderConfig,
+    ) -> Result<Arc<dyn DNSProvider>, Error> {
+        let provider =
+            providers::build(provider_config, self.creds.clone(), &self.http, &self.scope).await?;
+        Ok(match &self.cache {
+            Some((cache, max_age)) => {
+                Arc::new(CachedProvider::new(name, provider, cache.clone(), *max_age))
+            }
+            None => provider,
+        })
+    }
+}
+
+// A registry of the configured providers, each created when a run first
+// uses it
+fn provider_registry(config: &Config) -> Result<ProviderRegistry, String> {
+    let cache = match &config.cache {
+        Some(cache) => Some((
+            RecordCache::open(&cache.path).map_err(|e| e.to_string())?,
+            Duration::from_secs(cache.max_age),
+        )),
+        None => None,
+    };
+    let context = Arc::new(ProviderContext {
+        creds: credentials(Arc::new(OnePasswordClient::new(&config.vault))),
+        http: HttpOptions::from_config(&config.http).map_err(|e| e.to_string())?,
+        scope: config.scope.clone(),
+        cache,
+    });
+
+    let mut registry = ProviderRegistry::new();
+    for (name, provider_config) in &config.providers {
+        let name = Arc::new(name.clone());
+        let provider_config = Arc::new(provider_config.clone());
+        let context = context.clone();
+        registry.register_lazy(
+            &name.clone(),
+            Box::new(move || {
+                let (name, provider_config, context) =
+                    (name.clone(), provider_config.clone(), context.clone());
+                Box::pin(async move { context.create(&name, &provider_config).await })
+            }),
+        );
+    }
+    Ok(registry)
+}
+
+// Create the named providers that have not been yet, logging in to all of
+// them at once
+async fn init_providers(
+    registry: &ProviderRegistry,
+    names: &[String],
+    deadline: Deadline,
+) -> Result<(), String> {
+    future::try_join_all(names.iter().map(|name| async move {
+        deadline
+            .run(registry.get_or_init(name))
+            .await
+            .map_err(|_| format!("Timed out while creating provider {name}"))?
+            .map_err(|e| format!("Failed to create provider {name}: {e}"))
+    }))
+    .await?;
+    Ok(())
+}
+
+// Read the desired records while creating the selected providers and
+// listing their current records, as neither depends on the other
+async fn prepare(
+    config: &Config,
+    registry: Arc<ProviderRegistry>,
+    file: Option<PathBuf>,
+    only: Option<String>,
+    tags: &TagFilter,
+    deadline: Deadline,
+    allow_empty: bool,
+) -> Result<Prepared, String> {
+    let names = selected_providers(config, only.as_deref())?;
+    let op_client = OnePasswordClient::new(&config.vault);
+
+    let providers = async {
+        init_providers(&registry, &names, deadline).await?;
+        let mut snapshot = Snapshot::default();
+        snapshot.refresh(&registry, &names, deadline).await;
+        Ok((registry, snapshot))
+    };
+    let (desired, (registry, snapshot)) = tokio::try_join!(
+        read_desired(config, file, &op_client, tags, deadline, allow_empty),
+        providers
+    )?;
+
+    Ok(Prepared {
+        desired,
+        registry,
+        snapshot,
+    })
+}
+
+// Create a single provider, for commands that work on one
+async fn build_provider(config: &Config, name: &str) -> Result<Arc<dyn DNSProvider>, String> {
+    let provider_config = config
+        .providers
+        .get(name)
+        .ok_or_else(|| format!("Unknown provider: {name}"))?;
+    let http = HttpOptions::from_config(&config.http).map_err(|e| e.to_string())?;
+    let creds = credentials(Arc::new(OnePasswordClient::new(&config.vault)));
+    providers::build(provider_config, creds, &http, &config.scope)
+        .await
+        .map_err(|e| format!("Failed to create provider {name}: {e}"))
+}
+
+async fn daemon(config: &Config, args: DaemonArgs) -> Result<(), String> {
+    if let Some(only) = &args.provider
+        && !config.providers.contains_key(only)
+    {
+        return Err(format!("Unknown provider: {only}"));
+    }
+    let state = match &args.journal {
+        Some(path) => DaemonState::with_journal(Journal::open(path).map_err(|e| e.to_string())?),
+        None => DaemonState::default(),
+    };
+    if args.replay {
+        let replayed = state.replay().map_err(|e| e.to_string())?;
+        println!(
+            "Replayed {replayed} event(s), {} host(s) added",
+            state.added().len()
+        );
+    }
+    let state = Arc::new(state);
+    if let Some(control) = args.control {
+        // Anyone reaching the API can publish records through it
+        if !control.ip().is_loopback() && args.control_token.is_none() {
+            return Err(format!(
+                "Refusing to serve the control API on {control} without a token; \
+                 bind a loopback address or set --control-token"
+            ));
+        }
+        let listener = tokio::net::TcpListener::bind(control)
+            .await
+            .map_err(|e| format!("Failed to listen on {control}: {e}"))?;
+        println!("Serving control API on {control}");
+        let state = state.clone();
+        let token = args.control_token.clone();
+        tokio::spawn(async move {
+            if let Err(e) = daemon::control::serve(listener, state, token.as_deref()).await {
+                eprintln!("Control API failed: {e}");
+            }
+        });
+    }
+    let http = HttpOptions::from_config(&config.http).map_err(|e| e.to_string())?;
+    if let Some(traefik) = &config.traefik {
+        println!("Discovering hosts from Traefik at {}", traefik.api_url);
+        tokio::spawn(discovery::watch(
+            traefik.clone(),
+            http.clone(),
+            state.clone(),
+        ));
+    }
+    if let Some(caddy) = &config.caddy {
+        println!("Discovering hosts from Caddy at {}", caddy.admin_url);
+        tokio::spawn(discovery::watch(caddy.clone(), http.clone(), state.clone()));
+    }
+    if let Some(npm) = &config.nginx_proxy_manager {
+        let creds = credentials(Arc::new(OnePasswordClient::new(&config.vault)));
+        let npm = NginxProxyManager {
+            config: npm.clone(),
+            email: creds.get("npm_email").map_err(|e| e.to_string())?,
+            password: creds.get("npm_password").map_err(|e| e.to_string())?,
+        };
+        println!(
+            "Discovering hosts from Nginx Proxy Manager at {}",
+            npm.config.api_url
+        );
+        tokio::spawn(discovery::watch(npm, http, state.clone()));
+    }
+    let options = DaemonOptions {
+        file: args.file,
+        only: args.provider,
+        tags: args.tags,
+        interval: args.interval,
+        timeout: args.timeout,
+        allow_empty: args.allow_empty,
+        providers: Arc::new(provider_registry(config)?),
+        mdns: config
+            .mdns
+            .as_ref()
+            .map(Advertiser::new)
+            .transpose()
+            .map_err(|e| e.to_string())?,
+    };
+    daemon::run(config, options, state).await;
+    Ok(())
+}
+
+fn service(
+    config: Option<&Path>,
+    profile: Option<&str>,
+    command: ServiceCommand,
+) -> Result<(), String> {
+    let target = match &command {
+        ServiceCommand::Install { target, .. } | ServiceCommand::Uninstall { target } => target,
+    };
+    let manager = match target.manager {
+        Some(manager) => manager,
+        None => ServiceManager::detect().map_err(|e| e.to_string())?,
+    };
+    let service = Service {
+        manager,
+        user: target.user,
+    };
+    match command {
+        ServiceCommand::Install {
+            env, print, daemon, ..
+        } => {
+            // The service does not start in this directory, so paths must be absolute
+            let absolute = |path: &Path| {
+                std::path::absolute(path)
+                    .map(|path| path.display().to_string())
+                    .map_err(|e| format!("{}: {e}", path.display()))
+            };
+            let mut args = Vec::new();
+            if let Some(config) = config {
+                args.extend(["--config".to_string(), absolute(config)?]);
+            }
+            if let Some(profile) = profile {
+                args.extend(["--profile".to_string(), profile.to_string()]);
+            }
+            let absolute_path = |path: Option<PathBuf>| {
+                path.as_deref()
+                    .map(absolute)
+                    .transpose()
+                    .map(|path| path.map(PathBuf::from))
+            };
+            let daemon = DaemonArgs {
+                file: absolute_path(daemon.file.clone())?,
+                journal: absolute_path(daemon.journal.clone())?,
+                ..*daemon
+            };
+            args.push("daemon".to_string());
+            args.extend(daemon.to_args());
+
+            // PATH so the service finds the 1Password CLI
+            let mut vars = vec!["PATH".to_string()];
+            vars.extend(env);
+            let env = vars
+                .into_iter()
+                .map(|name| match std::env::var(&name) {
+                    Ok(value) => Ok((name, value)),
+                    Err(_) => Err(format!("Environment variable {name} is not set")),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+
+            let program = std::env::current_exe().map_err(|e| e.to_string())?;
+            let spec = ServiceSpec { program, args, env };
+            if print {
+                print!("{}", service.render(&spec));
+                return Ok(());
+            }
+            let path = service.install(&spec).map_err(|e| e.to_string())?;
+            println!("Installed {}", path.display());
+            Ok(())
+        }
+        ServiceCommand::Uninstall { .. } => {
+            let path = service.uninstall().map_err(|e| e.to_string())?;
+            println!("Removed {}", path.display());
+            Ok(())
+        }
+    }
+}
+
+async fn serve_webhook(config: &Config, name: &str, listen: SocketAddr) -> Result<(), String> {
+    let provider = build_provider(config, name).await?;
+    let webhook = Arc::new(Webhook::new(provider, config.scope.clone()));
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .map_err(|e| format!("Failed to listen on {listen}: {e}"))?;
+    println!("Serving external-dns webhook for {name} on {listen}");
+    webhook::serve(listener, webhook)
+        .await
+        .map_err(|e| format!("Webhook server failed: {e}"))
+}
+
+// Print the records in scope that differ between two providers, failing
+// when any do
+async fn compare(config: &Config, from: &str, to: &str, deadline: Deadline) -> Result<(), String> {
+    let list = async |name: &str| {
+        let provider = build_provider(config, name).await?;
+        let listing = list_current(provider.as_ref(), deadline)
+            .await
+            .with_context(|| format!("listing records of {name}"))?;
+        Ok::<_, String>(config.scope.partition(&listing.records).0)
+    };
+    let (a, b) = tokio::try_join!(list(from), list(to))?;
+
+    let comparison = compare::compare(&a, &b);
+    for line in comparison.lines(from, to) {
+        println!("{line}");
+    }
+    if comparison.is_empty() {
+        println!("{from} and {to} hold the same {} records", a.len());
+        Ok(())
+    } else {
+        Err(format!(
+            "{from} and {to} differ: {} only in {from}, {} only in {to}, {} with other values",
+            comparison.only_from.len(),
+            comparison.only_to.len(),
+            comparison.different.len()
+        ))
+    }
+}
+
+// What `migrate` copies, and whether it empties the source
+struct Migration {
+    from: String,
+    to: String,
+    filter: migrate::Filter,
+    delete_source: bool,
+    dry_run: bool,
+}
+
+// Copy the selected records of one provider to another, then delete them
+// from the source if asked to
+async fn migrate(
+    config: &Config,
+    migration: Migration,
+    options: UpdateOptions,
+) -> Result<(), String> {
+    let started = Instant::now();
+    correlation::start_run();
+    let deadline = Deadline::after(options.timeout);
+    let Migration { from, to, .. } = &migration;
+    if from == to {
+        return Err("--from and --to name the same provider".to_string());
+    }
+    let changed = if migration.delete_source {
+        vec![to, from]
+    } else {
+        vec![to]
+    };
+    if let Some(name) = changed
         .iter()
-        .filter(|r| !desired_records.contains(r))
-        .cloned()
+        .find(|name| config.providers.get(**name).is_some_and(|p| p.read_only()))
+    {
+        return Err(format!("{name} is read-only"));
+    }
+    let _locks = if migration.dry_run {
+        None
+    } else {
+        let names = [from.clone(), to.clone()];
+        Some(
+            RunLock::acquire_all(&config.lock_dir(), &names, options.force_unlock)
+                .map_err(|e| e.to_string())?,
+        )
+    };
+
+    let list = async |name: &str| {
+        let provider = build_provider(config, name).await?;
+        let listing = list_current(provider.as_ref(), deadline)
+            .await
+            .with_context(|| format!("listing records of {name}"))?;
+        Ok::<_, String>((provider, listing))
+    };
+    let ((source, source_listing), (target, target_listing)) =
+        tokio::try_join!(list(from), list(to))?;
+    let (records, _) = config.scope.partition(&source_listing.records);
+    let records: Vec<DNSRecord> = records
+        .into_iter()
+        .filter(|record| migration.filter.selects(record))
         .collect();
+    let prepare_time = started.elapsed();
+
+    let mut progress = Progress::new(options.quiet);
+    let mut copied = SyncOutcome::default();
+    let mut plan = plan_changes(
+        target.as_ref(),
+        &target_listing,
+        &records,
+        config,
+        &mut copied.stats,
+        &progress,
+    )
+    .map_err(|e| format!("Failed to plan {to}: {e}"))?;
+    migrate::keep_target_records(&mut plan, &records);
+
+    if migration.dry_run {
+        let mut plans = vec![(to.clone(), plan)];
+        if migration.delete_source {
+            let emptied = Plan {
+                to_remove: records,
+                ..Plan::default()
+            };
+            plans.push((from.clone(), emptied));
+        }
+        let color = std::env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+        print!("{}", report::render(&plans, DiffFormat::default(), color));
+        return Ok(());
+    }
+
+    let mut outcomes = BTreeMap::new();
+    let changes = plan.changes();
+    let result = match config
+        .limits
+        .exceeded(&changes, copied.stats.examined)
+        .filter(|_| !options.allow_large)
+    {
+        Some(reason) => Err(format!(
+            "Not applying: {reason}; pass --allow-large to apply anyway"
+        )),
+        None => {
+            apply_changes(
+                target.as_ref(),
+                changes,
+                &[],
+                deadline,
+                None,
+                &mut copied.stats,
+                &mut progress,
+            )
+            .await
+        }
+    };
+    if let Err(e) = result {
+        progress.error(&format!("Failed to copy records to {to}: {e}"));
+        copied.error = Some(e);
+    }
+    outcomes.insert(to.clone(), copied);
+
+    // Even after a partial copy, the records that did reach the target can go
+    if migration.delete_source {
+        let mut emptied = SyncOutcome::default();
+        emptied.stats.list_time = source_listing.elapsed;
+        emptied.stats.examined = config.scope.partition(&source_listing.records).0.len();
+        let result = match list_current(target.as_ref(), deadline).await {
+            Ok(listing) => {
+                let changes = migrate::delete_copied(&records, &listing.records);
+                match config
+                    .limits
+                    .exceeded(&changes, emptied.stats.examined)
+                    .filter(|_| !options.allow_large)
+                {
+                    Some(reason) => Err(format!(
+                        "Not deleting: {reason}; pass --allow-large to delete anyway"
+                    )),
+                    None => {
+                        apply_changes(
+                            source.as_ref(),
+                            changes,
+                            &[],
+                            deadline,
+                            None,
+                            &mut emptied.stats,
+                            &mut progress,
+                        )
+                        .await
+                    }
+                }
+            }
+            Err(e) => Err(format!("{to}: {e}")),
+        };
+        if let Err(e) = result {
+            progress.error(&format!(
+                "Failed to delete the copied records from {from}: {e}"
+            ));
+            emptied.error = Some(e);
+        }
+        outcomes.insert(from.clone(), emptied);
+    }
+    finish_run(&outcomes, started, prepare_time, &options)
+}
+
+// Combine two records files into a new rewrites file
+async fn records(config: &Config, command: RecordsCommand) -> Result<(), String> {
+    let (operation, files) = match command {
+        RecordsCommand::Union(files) => (SetOperation::Union, files),
+        RecordsCommand::Intersect(files) => (SetOperation::Intersect, files),
+        RecordsCommand::Subtract(files) => (SetOperation::Subtract, files),
+    };
+    let deadline = Deadline::default();
+    let read = async |path: &Path| {
+        read_records_file(config, path, &files.tags, deadline)
+            .await
+            .with_context(|| format!("reading records from {}", path.display()))
+    };
+    let (first, second) = tokio::try_join!(read(&files.first), read(&files.second))?;
+    let combined = format_rewrites(&record_set::combine(operation, &first, &second))?;
+    match &files.out {
+        Some(out) => std::fs::write(out, combined)
+            .map_err(|e| format!("Failed to write {}: {e}", out.display())),
+        None => {
+            print!("{combined}");
+            Ok(())
+        }
+    }
+}
+
+async fn acme(config: &Config, command: AcmeCommand) -> Result<(), String> {
+    let challenge: &Challenge = match &command {
+        AcmeCommand::Present { challenge, .. } | AcmeCommand::Cleanup { challenge } => challenge,
+    };
+    let name = &challenge.provider;
+    let provider = build_provider(config, name).await?;
+    if !provider.capabilities().txt {
+        return Err(format!("Provider {name} cannot hold TXT records"));
+    }
+
+    let record = acme::challenge_record(&challenge.domain, &challenge.value);
+    match command {
+        AcmeCommand::Present { wait, resolver, .. } => {
+            println!("Adding: {record}");
+            provider
+                .add_record(record.clone())
+                .await
+                .map_err(|e| format!("Failed to add challenge record: {e}"))?;
+            provider
+                .flush()
+                .await
+                .map_err(|e| format!("Failed to write changes: {e}"))?;
+            if wait.is_zero() {
+                return Ok(());
+            }
+            let lookup = TxtLookup::new(&resolver, &config.resolver).map_err(|e| e.to_string())?;
+            acme::wait_for(|| lookup.values(&record.name), &record.value, wait)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        AcmeCommand::Cleanup { .. } => {
+            println!("Removing: {record}");
+            match provider.delete_record(record).await {
+                // Cleanup may run after a failed or repeated present
+                Err(e) if !matches!(e.kind(), error::Error::NotFound(_)) => {
+                    return Err(format!("Failed to remove challenge record: {e}"));
+                }
+                _ => {}
+            }
+            provider
+                .flush()
+                .await
+                .map_err(|e| format!("Failed to write changes: {e}"))
+        }
+    }
+}
+
+async fn ddns(config: &Config, addresses: &[IpAddr], only: Option<String>) -> Result<(), String> {
+    if let Some(only) = &only
+        && !config.ddns.contains_key(only)
+    {
+        return Err(format!("Unknown dynamic DNS entry: {only}"));
+    }
+    let http = HttpOptions::from_config(&config.http).map_err(|e| e.to_string())?;
+
+    let creds = credentials(Arc::new(OnePasswordClient::new(&config.vault)));
+
+    let mut failed = false;
+    for (name, ddns_config) in &config.ddns {
+        if only.as_ref().is_some_and(|only| only != name) {
+            continue;
+        }
+        let updater = match providers::build_ddns(ddns_config, creds.clone(), &http) {
+            Ok(updater) => updater,
+            Err(e) => {
+                eprintln!("Failed to create {name}: {e}");
+                failed = true;
+                continue;
+            }
+        };
+        for address in addresses {
+            println!("Updating {name}: {address}");
+            if let Err(e) = updater.set_address(*address).await {
+                eprintln!("Failed to update {name}: {e}");
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        Err("One or more dynamic DNS updates failed".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// Fetch the current and owned records of a provider
+async fn list_current(provider: &dyn DNSProvider, deadline: Deadline) -> Result<Listing, String> {
+    let listing = Instant::now();
+    let (records, owned) = deadline
+        .run(async { tokio::join!(provider.list_records(), provider.owned_records()) })
+        .await
+        .map_err(|_| "Timed out while listing records".to_string())?;
+    Ok(Listing {
+        records: records.map_err(|e| format!("Failed to list current records: {e}"))?,
+        owned: owned.map_err(|e| format!("Failed to list owned records: {e}"))?,
+        elapsed: listing.elapsed(),
+    })
+}
 
-    // Apply changes
-    for record in &to_add {
-        println!("Adding: {record:?}");
-        if let Err(e) = provider.add_record(record.clone()).await {
-            eprintln!("Failed to add record: {e:?}");
+// Compute the changes that bring the provider in line with the desired records
+fn plan_changes(
+    provider: &dyn DNSProvider,
+    listing: &Listing,
+    desired_records: &[DNSRecord],
+    config: &Config,
+    stats: &mut SyncStats,
+    progress: &Progress,
+) -> Result<Plan, String> {
+    // Records outside of the scope are neither added nor removed
+    let scope = &config.scope;
+    let (desired_records, out_of_scope) = scope.partition(desired_records);
+    for record in &out_of_scope {
+        progress.record(
+            Status::Skipped,
+            record,
+            Some("outside of the configured scope"),
+        );
+    }
+    let (current_records, _) = scope.partition(&listing.records);
+    stats.list_time = listing.elapsed;
+    stats.examined = current_records.len();
+
+    // Compute changes
+    let mut plan = diff::plan(
+        &desired_records,
+        &current_records,
+        &provider.capabilities(),
+        listing.owned.as_ref(),
+        &config.conflicts,
+    );
+    config.protect.block(&mut plan);
+    for change in &plan.blocked {
+        let detail = format!("protected, {}", pending(change));
+        progress.record(Status::Blocked, change.record(), Some(&detail));
+    }
+    stats.blocked = plan.blocked.len();
+    for skipped in &plan.skipped {
+        progress.record(Status::Skipped, &skipped.record, Some(&skipped.reason));
+    }
+    stats.skipped = out_of_scope.len() + plan.skipped.len();
+    if !plan.conflicts.is_empty() {
+        for conflict in &plan.conflicts {
+            progress.record(Status::Failed, &conflict.record, Some(&conflict.reason));
         }
+        return Err(format!(
+            "{} desired record(s) conflict with records of another type",
+            plan.conflicts.len()
+        ));
     }
-    for record in &to_remove {
-        println!("Removing: {record:?}");
-        if let Err(e) = provider.delete_record(record.clone()).await {
-            eprintln!("Failed to remove record: {e:?}");
+    Ok(plan)
+}
+
+// The changes to apply: those left by an interrupted run of the same
+// desired records, or a fresh plan
+fn changes_to_apply(
+    provider: &dyn DNSProvider,
+    listing: Result<&Listing, String>,
+    desired_records: &[DNSRecord],
+    config: &Config,
+    checkpoint: Option<(&CheckpointFile, &str)>,
+    stats: &mut SyncStats,
+    progress: &Progress,
+) -> Result<Vec<Change>, String> {
+    if let Some((file, name)) = checkpoint {
+        match file.load(name) {
+            Ok(Some(saved)) if saved.desired == fingerprint(desired_records) => {
+                progress.note(&format!(
+                    "Resuming {name}: {} change(s) left by an interrupted run",
+                    saved.remaining.len()
+                ));
+                return Ok(saved.remaining);
+            }
+            Ok(Some(_)) => {
+                progress.note(&format!(
+                    "Discarding the checkpoint of {name}: the desired records changed"
+                ));
+                if let Err(e) = file.save(name, None) {
+                    progress.error(&format!("Failed to clear checkpoint: {e}"));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => progress.error(&format!("Failed to read checkpoint: {e}")),
         }
     }
+    let plan = plan_changes(provider, listing?, desired_records, config, stats, progress)?;
+    Ok(plan.changes())
 }
 
-// Parse rewrite file lines into DNSRecord
-fn read_rewrites_from_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<DNSRecord>> {
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
-    use std::iter::Iterator;
-    parse_rewrites_from_iter(reader.lines().map_while(Result::ok))
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+async fn apply_changes(
+    provider: &dyn DNSProvider,
+    changes: Vec<Change>,
+    desired_records: &[DNSRecord],
+    deadline: Deadline,
+    checkpoint: Option<(&CheckpointFile, &str)>,
+    stats: &mut SyncStats,
+    progress: &mut Progress,
+) -> Result<(), String> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(eta) = provider.estimate(changes.len()).await
+        && eta >= ETA_NOTICE
+    {
+        progress.note(&format!(
+            "Applying {} change(s) to {}; about {}s at the API rate limit",
+            changes.len(),
+            provider.name(),
+            eta.as_secs()
+        ));
+    }
+    progress.start(provider.name(), changes.len());
+
+    // Apply changes, flushing and recording progress after each chunk
+    let applying = Instant::now();
+    let chunk_size = checkpoint.map_or(usize::MAX, |(file, _)| file.chunk_size());
+    let mut changes = VecDeque::from(changes);
+    let mut cancelled = Vec::new();
+    let mut flushed = Ok(());
+    while !changes.is_empty() && cancelled.is_empty() {
+        let chunk: Vec<Change> = changes.drain(..chunk_size.min(changes.len())).collect();
+        for change in chunk {
+            if !cancelled.is_empty() {
+                cancelled.push(change);
+                continue;
+            }
+            let change_id = correlation::new_id();
+            let (status, applied) = match &change {
+                Change::Add(record) => (Status::Created, provider.add_record(record.clone())),
+                Change::Update(record) => (Status::Updated, provider.update_record(record.clone())),
+                Change::Remove(record) => (Status::Deleted, provider.delete_record(record.clone())),
+            };
+            let result = correlation::for_change(change_id.clone(), deadline.run(applied)).await;
+            match result {
+                Ok(Ok(())) => {
+                    progress.record(status, change.record(), None);
+                    match change {
+                        Change::Add(_) => stats.created += 1,
+                        Change::Update(_) => stats.updated += 1,
+                        Change::Remove(_) => stats.deleted += 1,
+                    }
+                }
+                Ok(Err(e)) => {
+                    let detail = format!("{e} [{}] (request {change_id})", e.code());
+                    progress.record(Status::Failed, change.record(), Some(&detail));
+                    stats.fail(e.category());
+                }
+                Err(Cancelled) => cancelled.push(change),
+            }
+        }
+
+        // Keep whatever was applied before the deadline
+        flushed = provider.flush().await;
+        if flushed.is_err() {
+            break;
+        }
+        if let Some((file, name)) = checkpoint {
+            let remaining: Vec<Change> = cancelled.iter().chain(&changes).cloned().collect();
+            let saved = Checkpoint {
+                desired: fingerprint(desired_records),
+                remaining,
+            };
+            if let Err(e) = file.save(name, Some(saved)) {
+                progress.error(&format!("Failed to save checkpoint: {e}"));
+            }
+        }
+    }
+    stats.apply_time += applying.elapsed();
+    progress.finish();
+    cancelled.extend(changes);
+    if let Err(e) = flushed {
+        return Err(format!("Failed to write changes: {e}"));
+    }
+    if !cancelled.is_empty() {
+        for change in &cancelled {
+            progress.record(Status::Cancelled, change.record(), None);
+            stats.fail("cancelled");
+        }
+        return Err(format!(
+            "Timed out with {} change(s) not applied",
+            cancelled.len()
+        ));
+    }
+    Ok(())
 }
 
 // Parse DNS rewrites from a string (1Password)
-fn parse_rewrites_from_str(s: &str) -> Result<Vec<DNSRecord>, String> {
-    let lines = s
-        .lines()
-        .map(str::trim)
-        .filter(|l| !l.is_empty() && !l.starts_with('#'));
-    parse_rewrites_from_iter(lines).map_err(|e| format!("Failed to parse rewrites: {e}"))
+fn parse_rewrites_from_str(s: &str, tags: &TagFilter) -> Result<Vec<DNSRecord>, String> {
+    parse_rewrites_from_iter(s.lines(), tags).map_err(|e| format!("Failed to parse rewrites: {e}"))
 }
 
 // Shared parser for lines
-fn parse_rewrites_from_iter<I>(lines: I) -> Result<Vec<DNSRecord>, String>
+fn parse_rewrites_from_iter<I>(lines: I, selected: &TagFilter) -> Result<Vec<DNSRecord>, String>
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
     let mut records = Vec::new();
-    for line in lines {
-        let line = line.as_ref();
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() != 2 {
-            continue;
+    for (index, line) in lines.into_iter().enumerate() {
+        if let Some(record) = parse_rewrite_line(line.as_ref(), selected)
+            .with_context(|| format!("parsing line {}", index + 1))?
+        {
+            records.push(record);
         }
-        let (value, name) = (parts[0], parts[1]);
-        let record_type = if value.parse::<std::net::Ipv4Addr>().is_ok() {
-            DNSRecordType::A
-        } else if value.parse::<std::net::Ipv6Addr>().is_ok() {
-            DNSRecordType::AAAA
-        } else {
-            DNSRecordType::CNAME
-        };
-        records.push(DNSRecord {
-            record_type,
-            name: name.to_string(),
-            value: value.to_string(),
-            ttl: Some(300),
-        });
     }
     Ok(records)
 }
+
+// The record of one line, or `None` for blank and comment lines and lines
+// of other networks
+fn parse_rewrite_line(line: &str, selected: &TagFilter) -> Result<Option<DNSRecord>, String> {
+    // A trailing `# text` becomes the record's comment
+    let (line, comment) = match line.split_once('#') {
+        Some((line, comment)) => (line, Some(comment.trim()).filter(|c| !c.is_empty())),
+        None => (line, None),
+    };
+    // `@home @vpn` tags the record for those networks, while a lone `@`
+    // is the apex name
+    let (tags, parts): (Vec<&str>, Vec<&str>) = line
+        .split_whitespace()
+        .partition(|p| p.len() > 1 && p.starts_with('@'));
+    if parts.len() != 2 {
+        return Ok(None);
+    }
+    let tags: Vec<&str> = tags.iter().map(|t| &t[1..]).collect();
+    if !selected.selects(&tags) {
+        return Ok(None);
+    }
+    let (value, name) = (parts[0], parts[1]);
+    let record = DNSRecord::new(DNSRecordType::for_value(value), name, value)
+        .ttl(300)
+        .comment(comment.map(str::to_string));
+    // Unicode names are sent to providers as punycode
+    let record = record.to_ascii().map_err(|e| e.to_string())?;
+    // Rather than turning a mistyped address into a CNAME. `@` stands
+    // for the zone apex, filled in by normalization.
+    if record.name != "@" && !is_valid_hostname(&record.name) {
+        return Err(format!("invalid name: {name}"));
+    }
+    if record.record_type == DNSRecordType::CNAME
+        && (!is_valid_hostname(&record.value) || record.value.starts_with("*."))
+    {
+        return Err(format!("invalid address or host name: {value}"));
+    }
+    Ok(Some(record))
+}
+
+// Write records as rewrites lines, the reverse of `parse_rewrites_from_str`.
+// Only A, AAAA and CNAME records can be written, as the type of a line
+// follows from its value.
+fn format_rewrites(records: &[DNSRecord]) -> Result<String, String> {
+    let mut out = String::new();
+    for record in records {
+        if record.record_type != DNSRecordType::for_value(&record.value) {
+            return Err(format!("{record}: not expressible as a rewrite"));
+        }
+        out.push_str(&format!("{} {}", record.value, record.name));
+        if let Some(comment) = &record.comment {
+            out.push_str(&format!(" # {comment}"));
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rewrites_with_comments() {
+        let records = parse_rewrites_from_str(
+            "# homelab\n\
+             10.0.0.2 nas.lab.example.com # rack 2, from ansible\n\
+             nas.lab.example.com files.lab.example.com\n\
+             10.0.0.3 # missing name\n",
+            &TagFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].name, "nas.lab.example.com");
+        assert_eq!(records[0].comment.as_deref(), Some("rack 2, from ansible"));
+        assert_eq!(records[1].record_type, DNSRecordType::CNAME);
+        assert_eq!(records[1].comment, None);
+    }
+
+    #[test]
+    fn test_parse_rewrites_with_tags() {
+        let rewrites = "10.0.0.2 nas.lab.example.com\n\
+                        10.0.1.2 vpn.example.com @vpn\n\
+                        192.168.1.2 printer.example.com @home @office\n";
+        let names = |tags: &[&str]| -> Vec<String> {
+            let tags = TagFilter {
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                ..TagFilter::default()
+            };
+            parse_rewrites_from_str(rewrites, &tags)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.name)
+                .collect()
+        };
+        assert_eq!(names(&[]).len(), 3);
+        assert_eq!(
+            names(&["home"]),
+            ["nas.lab.example.com", "printer.example.com"]
+        );
+        assert_eq!(names(&["vpn"]), ["nas.lab.example.com", "vpn.example.com"]);
+        assert_eq!(names(&["lab"]), ["nas.lab.example.com"]);
+    }
+
+    #[test]
+    fn test_parse_rewrites_apex() {
+        let all = TagFilter::default();
+        let records = parse_rewrites_from_str("10.0.0.1 @\n", &all).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "@");
+        assert_eq!(records[0].record_type, DNSRecordType::A);
+        let home = TagFilter {
+            tags: vec!["home".to_string()],
+            ..TagFilter::default()
+        };
+        let records = parse_rewrites_from_str("10.0.0.1 @ @home\n", &home).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "@");
+        let vpn = TagFilter {
+            tags: vec!["vpn".to_string()],
+            ..TagFilter::default()
+        };
+        assert!(
+            parse_rewrites_from_str("10.0.0.1 @ @home\n", &vpn)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_rewrites_converts_idn() {
+        let records =
+            parse_rewrites_from_str("bücher.example www.bücher.example\n", &TagFilter::default())
+                .unwrap();
+        assert_eq!(records[0].name, "www.xn--bcher-kva.example");
+        assert_eq!(records[0].value, "xn--bcher-kva.example");
+        assert!(
+            parse_rewrites_from_str("10.0.0.1 bad<name>.bücher.example\n", &TagFilter::default())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let raw = "# lab\n\n10.0.0.2 nas.lab.example.com\n10.0.0.3 bad<name>.example.com\n";
+        let e = parse_rewrites_from_str(raw, &TagFilter::default()).unwrap_err();
+        assert_eq!(
+            e,
+            "Failed to parse rewrites: invalid name: bad<name>.example.com\n  while parsing line 4"
+        );
+    }
+
+    #[test]
+    fn test_format_rewrites_round_trips() {
+        let raw = "10.0.0.2 nas.lab.example.com # rack 2\n\
+                   nas.lab.example.com files.lab.example.com\n";
+        let records = parse_rewrites_from_str(raw, &TagFilter::default()).unwrap();
+        assert_eq!(format_rewrites(&records).unwrap(), raw);
+        let txt = DNSRecord::txt("example.com", "v=spf1 -all");
+        assert!(format_rewrites(&[txt]).is_err());
+    }
+
+    #[test]
+    fn test_parse_rewrites_rejects_malformed_values() {
+        for line in [
+            "10.0.0.256 nas.lab.example.com",
+            "1.2.3 nas.lab.example.com",
+            "10.0.0.2 bad/name.example.com",
+            "*.example.com www.example.com",
+        ] {
+            assert!(
+                parse_rewrites_from_str(line, &TagFilter::default()).is_err(),
+                "{line}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_desired_records_need_allow_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("rewrites.txt");
+        std::fs::write(&file, "# nothing here yet\n").unwrap();
+        let config = Config::default();
+        let op_client = OnePasswordClient::new(&config.vault);
+        let tags = TagFilter::default();
+        let read = |allow_empty| {
+            read_desired(
+                &config,
+                Some(file.clone()),
+                &op_client,
+                &tags,
+                Deadline::default(),
+                allow_empty,
+            )
+        };
+        let refused = read(false).await.unwrap_err();
+        assert!(refused.contains("--allow-empty"), "{refused}");
+        assert_eq!(read(true).await.unwrap(), []);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_rewrites_need_a_signature() {
+        let dir = tempfile::tempdir().unwrap();
+        let fallback = dir.path().join("rewrites.txt");
+        std::fs::write(&fallback, "10.0.0.2 nas.lab.example.com\n").unwrap();
+        let tags = TagFilter::default();
+        let read = |config: Config| {
+            let (fallback, tags) = (&fallback, &tags);
+            async move { read_rewrites_fallback(&config, fallback, tags, Deadline::default()).await }
+        };
+        assert_eq!(read(Config::default()).await.unwrap().len(), 1);
+
+        let signed = || {
+            Config::parse(
+                "[signature]\nminisign_key = \"RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\"",
+            )
+            .unwrap()
+        };
+        let refused = read(signed()).await.unwrap_err();
+        assert!(refused.contains("is not signed"), "{refused}");
+        std::fs::write(dir.path().join("rewrites.txt.minisig"), "forged").unwrap();
+        let refused = read(signed()).await.unwrap_err();
+        assert!(
+            refused.starts_with("Refusing fallback rewrites"),
+            "{refused}"
+        );
+    }
+
+    mod sync {
+        use super::*;
+        use crate::core::checkpoint::CheckpointConfig;
+        use crate::core::provider::ProviderCapabilities;
+        use crate::testing::{InMemoryProvider, Operation};
+        use std::net::Ipv4Addr;
+
+        fn a(name: &str, last: u8) -> DNSRecord {
+            DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, last))
+        }
+
+        // Plan or resume, then apply, as a run does
+        async fn sync(
+            provider: &InMemoryProvider,
+            listing: Result<Listing, String>,
+            desired: &[DNSRecord],
+            deadline: Deadline,
+            checkpoint: Option<(&CheckpointFile, &str)>,
+            stats: &mut SyncStats,
+        ) -> Result<(), String> {
+            let mut progress = Progress::new(true);
+            let changes = changes_to_apply(
+                provider,
+                listing.as_ref().map_err(String::clone),
+                desired,
+                &Config::default(),
+                checkpoint,
+                stats,
+                &progress,
+            )?;
+            apply_changes(
+                provider,
+                changes,
+                desired,
+                deadline,
+                checkpoint,
+                stats,
+                &mut progress,
+            )
+            .await
+        }
+
+        // List, then sync
+        async fn run(
+            provider: &InMemoryProvider,
+            desired: &[DNSRecord],
+            deadline: Deadline,
+            stats: &mut SyncStats,
+        ) -> Result<(), String> {
+            let listing = list_current(provider, deadline).await;
+            sync(provider, listing, desired, deadline, None, stats).await
+        }
+
+        #[tokio::test]
+        async fn test_adds_are_applied_before_removes() {
+            let provider = InMemoryProvider::new(vec![a("nas.example.com", 1)]);
+            let desired = [a("nas.example.com", 2)];
+            let mut stats = SyncStats::default();
+            run(&provider, &desired, Deadline::default(), &mut stats)
+                .await
+                .unwrap();
+            assert_eq!(
+                provider.applied(),
+                vec![
+                    (Operation::Add, a("nas.example.com", 2)),
+                    (Operation::Delete, a("nas.example.com", 1)),
+                ]
+            );
+            assert_eq!(provider.calls().last().unwrap().operation, Operation::Flush);
+            assert_eq!(provider.records(), desired);
+            assert_eq!((stats.examined, stats.created, stats.deleted), (1, 1, 1));
+        }
+
+        #[tokio::test]
+        async fn test_values_are_updated_in_place_when_supported() {
+            let provider = InMemoryProvider::new(vec![a("nas.example.com", 1)]).with_capabilities(
+                ProviderCapabilities {
+                    update_in_place: true,
+                    ..ProviderCapabilities::default()
+                },
+            );
+            let desired = [a("nas.example.com", 2)];
+            let mut stats = SyncStats::default();
+            run(&provider, &desired, Deadline::default(), &mut stats)
+                .await
+                .unwrap();
+            assert_eq!(
+                provider.applied(),
+                vec![(Operation::Update, a("nas.example.com", 2))]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_failed_change_does_not_stop_the_others() {
+            let provider =
+                InMemoryProvider::new(vec![a("old.example.com", 9)]).failing_for("bad.example.com");
+            let desired = [a("bad.example.com", 1), a("good.example.com", 2)];
+            let mut stats = SyncStats::default();
+            let _ = run(&provider, &desired, Deadline::default(), &mut stats).await;
+            assert_eq!(provider.records(), vec![a("good.example.com", 2)]);
+            assert_eq!((stats.created, stats.deleted), (1, 1));
+            assert_eq!(stats.failures["provider"], 1);
+        }
+
+        #[tokio::test]
+        async fn test_read_only_providers_only_report_drift() {
+            let provider = InMemoryProvider::new(vec![a("old.example.com", 9)]);
+            let desired = [a("nas.example.com", 2)];
+            let mut stats = SyncStats::default();
+            let listing = list_current(&provider, Deadline::default()).await.unwrap();
+            let progress = Progress::new(true);
+            let changes = changes_to_apply(
+                &provider,
+                Ok(&listing),
+                &desired,
+                &Config::default(),
+                None,
+                &mut stats,
+                &progress,
+            )
+            .unwrap();
+            report_drift(
+                &provider,
+                &changes,
+                Deadline::default(),
+                &mut stats,
+                &progress,
+            )
+            .await
+            .unwrap();
+            assert_eq!(stats.drift, 2);
+            assert_eq!((stats.created, stats.deleted), (0, 0));
+            assert!(provider.applied().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_listing_failure_fails_the_sync() {
+            let provider = InMemoryProvider::default();
+            provider.fail_next(Operation::List, 1);
+            let desired = [a("nas.example.com", 2)];
+            let mut stats = SyncStats::default();
+            assert!(
+                run(&provider, &desired, Deadline::default(), &mut stats)
+                    .await
+                    .is_err()
+            );
+            assert!(provider.applied().is_empty());
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_interrupted_apply_resumes_from_checkpoint() {
+            let dir = tempfile::tempdir().unwrap();
+            let file = CheckpointFile::new(&CheckpointConfig {
+                path: dir.path().join("checkpoint.json"),
+                chunk_size: 1,
+            });
+            let checkpoint = Some((&file, "lan"));
+            let provider = InMemoryProvider::default().with_latency(Duration::from_secs(10));
+            let desired = [
+                a("a.example.com", 1),
+                a("b.example.com", 2),
+                a("c.example.com", 3),
+            ];
+
+            // Listing and the first chunk fit in the budget, the rest doesn't
+            let deadline = Deadline::after(Some(Duration::from_secs(25)));
+            let listing = list_current(&provider, deadline).await;
+            let mut stats = SyncStats::default();
+            let result = sync(
+                &provider, listing, &desired, deadline, checkpoint, &mut stats,
+            )
+            .await;
+            assert!(result.is_err());
+            assert_eq!(file.load("lan").unwrap().unwrap().remaining.len(), 2);
+
+            // The next run applies what is left without planning again
+            let listing = Err("not listed".to_string());
+            let mut stats = SyncStats::default();
+            sync(
+                &provider,
+                listing,
+                &desired,
+                Deadline::default(),
+                checkpoint,
+                &mut stats,
+            )
+            .await
+            .unwrap();
+            assert_eq!(stats.created, 2);
+            assert_eq!(provider.records(), desired);
+            assert_eq!(file.load("lan").unwrap(), None);
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_providers_are_listed_at_once() {
+            let mut registry = ProviderRegistry::new();
+            for name in ["lan", "wan"] {
+                let provider = InMemoryProvider::new(vec![a("nas.example.com", 1)])
+                    .with_latency(Duration::from_secs(10));
+                registry.register(name, Arc::new(provider));
+            }
+            let names = registry.list();
+            let started = tokio::time::Instant::now();
+            let mut snapshot = Snapshot::default();
+            snapshot
+                .refresh(&registry, &names, Deadline::default())
+                .await;
+            assert_eq!(started.elapsed(), Duration::from_secs(10));
+            assert!(
+                names
+                    .iter()
+                    .all(|name| snapshot.get(name).unwrap().records.len() == 1)
+            );
+            assert!(snapshot.get("dmz").is_err());
+        }
+
+        #[tokio::test]
+        async fn test_snapshot_is_only_listed_again_when_refreshed() {
+            let lan = Arc::new(InMemoryProvider::new(vec![a("nas.example.com", 1)]));
+            let mut registry = ProviderRegistry::new();
+            registry.register("lan", lan.clone());
+            let names = registry.list();
+            let mut snapshot = Snapshot::default();
+            snapshot
+                .refresh(&registry, &names, Deadline::default())
+                .await;
+
+            lan.add_record(a("nas.example.com", 2)).await.unwrap();
+            assert_eq!(snapshot.get("lan").unwrap().records.len(), 1);
+            snapshot
+                .refresh(&registry, &names, Deadline::default())
+                .await;
+            assert_eq!(snapshot.get("lan").unwrap().records.len(), 2);
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_changes_past_the_deadline_are_cancelled() {
+            let provider = InMemoryProvider::default().with_latency(Duration::from_secs(10));
+            let desired = [
+                a("a.example.com", 1),
+                a("b.example.com", 2),
+                a("c.example.com", 3),
+            ];
+            // Listing and the first add fit in the budget, the second add doesn't
+            let deadline = Deadline::after(Some(Duration::from_secs(25)));
+            let mut stats = SyncStats::default();
+            let result = run(&provider, &desired, deadline, &mut stats).await;
+            assert_eq!(
+                result,
+                Err("Timed out with 2 change(s) not applied".to_string())
+            );
+            assert_eq!(provider.records(), vec![a("a.example.com", 1)]);
+            assert_eq!(stats.failures["cancelled"], 2);
+        }
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        fn hostname() -> impl Strategy<Value = String> {
+            "[a-z0-9]([a-z0-9-]{0,10}[a-z0-9])?(\\.[a-z0-9]([a-z0-9-]{0,10}[a-z0-9])?){0,3}\\.[a-z]{2,6}"
+        }
+
+        fn record() -> impl Strategy<Value = DNSRecord> {
+            let value = prop_oneof![
+                any::<Ipv4Addr>().prop_map(|a| a.to_string()),
+                any::<Ipv6Addr>().prop_map(|a| a.to_string()),
+                hostname(),
+            ];
+            // Surrounding whitespace of comments is not kept
+            let comment = proptest::option::of("[a-zA-Z0-9]([a-zA-Z0-9,.:/ -]{0,20}[a-zA-Z0-9])?");
+            (value, hostname(), comment).prop_map(|(value, name, comment)| {
+                DNSRecord::new(DNSRecordType::for_value(&value), name, value)
+                    .ttl(300)
+                    .comment(comment)
+            })
+        }
+
+        // The rewrites format of a record, as written by hand
+        fn format(record: &DNSRecord, tags: &[String]) -> String {
+            let mut line = format!("{} {}", record.value, record.name);
+            for tag in tags {
+                line.push_str(&format!(" @{tag}"));
+            }
+            if let Some(comment) = &record.comment {
+                line.push_str(&format!(" # {comment}"));
+            }
+            line
+        }
+
+        proptest! {
+            #[test]
+            fn parse_format_round_trip(
+                records in proptest::collection::vec(record(), 0..20),
+                tags in proptest::collection::vec("[a-z]{1,8}", 0..3),
+            ) {
+                let text: Vec<String> = records.iter().map(|r| format(r, &tags)).collect();
+                let parsed = parse_rewrites_from_str(&text.join("\n"), &TagFilter::default()).unwrap();
+                prop_assert_eq!(&parsed, &records);
+
+                // Formatting the parsed records again gives the same records
+                let again: Vec<String> = parsed.iter().map(|r| format(r, &[])).collect();
+                prop_assert_eq!(parse_rewrites_from_str(&again.join("\n"), &TagFilter::default()).unwrap(), records);
+            }
+
+            #[test]
+            fn parsed_types_match_values(input in "(\\PC{0,40}\n){0,8}") {
+                // Arbitrary input may be rejected, but never panics or
+                // yields a record whose value contradicts its type
+                if let Ok(records) = parse_rewrites_from_str(&input, &TagFilter::default()) {
+                    for record in records {
+                        match record.record_type {
+                            DNSRecordType::A => prop_assert!(record.value.parse::<Ipv4Addr>().is_ok()),
+                            DNSRecordType::AAAA => prop_assert!(record.value.parse::<Ipv6Addr>().is_ok()),
+                            _ => prop_assert!(is_valid_hostname(&record.value)),
+                        }
+                        prop_assert!(is_valid_hostname(&record.name));
+                    }
+                }
+            }
+        }
+    }
+}
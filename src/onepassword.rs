@@ -6,6 +6,8 @@ use std::process::Stdio;
 use thiserror::Error;
 use tokio::process::Command;
 
+use crate::secret::{Secret, redact};
+
 #[derive(Error, Debug)]
 pub enum OnePasswordError {
     #[error("1Password CLI error: {0}")]
@@ -54,9 +56,9 @@ impl OnePasswordClient {
             .map_err(|e| OnePasswordError::Cli(e.to_string()))?;
 
         if !output.status.success() {
-            return Err(OnePasswordError::Cli(
-                String::from_utf8_lossy(&output.stderr).to_string(),
-            ));
+            return Err(OnePasswordError::Cli(redact(&String::from_utf8_lossy(
+                &output.stderr,
+            ))));
         }
 
         #[derive(Deserialize)]
@@ -89,9 +91,9 @@ impl OnePasswordClient {
             .map_err(|e| OnePasswordError::Cli(e.to_string()))?;
 
         if !output.status.success() {
-            return Err(OnePasswordError::Cli(
-                String::from_utf8_lossy(&output.stderr).to_string(),
-            ));
+            return Err(OnePasswordError::Cli(redact(&String::from_utf8_lossy(
+                &output.stderr,
+            ))));
         }
 
         #[derive(Deserialize)]
@@ -112,6 +114,12 @@ impl OnePasswordClient {
         self.get_field("DNS Rewrites", "notesPlain").await
     }
 
+    /// Get the detached signature of the DNS rewrites, from the "signature"
+    /// field of the "DNS Rewrites" item.
+    pub async fn get_rewrites_signature(&self) -> Result<String, OnePasswordError> {
+        self.get_field("DNS Rewrites", "signature").await
+    }
+
     /// Get NextDNS credentials from the "NextDNS" item.
     pub async fn get_nextdns_credentials(&self) -> Result<NextDnsCredentials, OnePasswordError> {
         let fields = self
@@ -129,6 +137,7 @@ impl OnePasswordClient {
             password: fields
                 .get("password")
                 .cloned()
+                .map(Secret::new)
                 .ok_or_else(|| OnePasswordError::MissingField("password".into()))?,
         })
     }
@@ -138,7 +147,7 @@ impl OnePasswordClient {
 pub struct NextDnsCredentials {
     pub id: String,
     pub email: String,
-    pub password: String,
+    pub password: Secret,
 }
 
 fn strip_formatting(value: &str) -> String {
@@ -193,7 +202,7 @@ mod tests {
         let out = result.unwrap();
         assert_eq!(out.id, "profileid");
         assert_eq!(out.email, "user@example.com");
-        assert_eq!(out.password, "secret");
+        assert_eq!(out.password.expose(), "secret");
     }
 
     #[test]
@@ -0,0 +1,154 @@
+//! Progress output while `dns-update update` applies changes.
+//!
+//! On a terminal, a progress bar per provider counts the applied changes
+//! and the status of each record is printed above it. Elsewhere, like in
+//! logs, only the status lines are printed. Everything goes to stderr,
+//! leaving stdout to the report. `--quiet` keeps just the failures, so
+//! cron only sends mail when something went wrong.
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::fmt;
+
+use crate::core::record::DNSRecord;
+use crate::secret::redact;
+
+/// What happened to one record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Created,
+    Updated,
+    Deleted,
+    Skipped,
+    /// Would change, at a read-only provider
+    Drift,
+    /// Would change, but is protected
+    Blocked,
+    Failed,
+    Cancelled,
+}
+
+impl Status {
+    fn is_failure(self) -> bool {
+        matches!(self, Status::Failed | Status::Cancelled)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `pad`, so status lines can align the label
+        f.pad(match self {
+            Status::Created => "created",
+            Status::Updated => "updated",
+            Status::Deleted => "deleted",
+            Status::Skipped => "skipped",
+            Status::Drift => "drift",
+            Status::Blocked => "blocked",
+            Status::Failed => "failed",
+            Status::Cancelled => "cancelled",
+        })
+    }
+}
+
+// One status line, padded so the records line up
+fn status_line(status: Status, record: &DNSRecord, detail: Option<&str>) -> String {
+    match detail {
+        Some(detail) => format!("{status:<9} {record}: {detail}"),
+        None => format!("{status:<9} {record}"),
+    }
+}
+
+/// Output of one provider's sync.
+pub struct Progress {
+    bar: ProgressBar,
+    quiet: bool,
+}
+
+impl Progress {
+    pub fn new(quiet: bool) -> Self {
+        Self {
+            bar: ProgressBar::hidden(),
+            quiet,
+        }
+    }
+
+    /// Show a bar counting `total` changes to `name`. It stays hidden when
+    /// quiet or when stderr is not a terminal.
+    pub fn start(&mut self, name: &str, total: usize) {
+        if self.quiet {
+            return;
+        }
+        let bar = ProgressBar::with_draw_target(Some(total as u64), ProgressDrawTarget::stderr());
+        bar.set_style(
+            ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len} ETA {eta}")
+                .expect("valid template")
+                .progress_chars("=> "),
+        );
+        bar.set_prefix(name.to_string());
+        self.bar = bar;
+    }
+
+    /// Informational output, left out when quiet.
+    pub fn note(&self, message: &str) {
+        if !self.quiet {
+            self.print(message);
+        }
+    }
+
+    /// A problem that is not tied to one record.
+    pub fn error(&self, message: &str) {
+        self.print(message);
+    }
+
+    /// Report the status of a record, counting it towards the bar unless
+    /// it was skipped before applying.
+    pub fn record(&self, status: Status, record: &DNSRecord, detail: Option<&str>) {
+        if status != Status::Skipped {
+            self.bar.inc(1);
+        }
+        if self.quiet && !status.is_failure() {
+            return;
+        }
+        self.print(&status_line(status, record, detail));
+    }
+
+    /// Remove the bar; later output is printed plainly.
+    pub fn finish(&mut self) {
+        self.bar.finish_and_clear();
+        self.bar = ProgressBar::hidden();
+    }
+
+    // Print above the bar, or plainly when there is none
+    fn print(&self, message: &str) {
+        let message = redact(message);
+        if self.bar.is_hidden() {
+            eprintln!("{message}");
+        } else {
+            self.bar.println(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+
+    #[test]
+    fn test_status_line() {
+        let record = DNSRecord::new(
+            DNSRecordType::A,
+            "nas.lab.example.com".to_string(),
+            "10.0.0.5".to_string(),
+        );
+        assert_eq!(
+            status_line(Status::Created, &record, None),
+            "created   nas.lab.example.com A 10.0.0.5"
+        );
+        assert_eq!(
+            status_line(Status::Failed, &record, Some("Rate limited")),
+            "failed    nas.lab.example.com A 10.0.0.5: Rate limited"
+        );
+        assert!(Status::Cancelled.is_failure());
+        assert!(!Status::Skipped.is_failure());
+    }
+}
@@ -0,0 +1,138 @@
+//! dnsmasq configuration snippet output (`address=` and `cname=` lines).
+
+use std::net::IpAddr;
+
+use crate::core::provider::ProviderCapabilities;
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::providers::file::FileFormat;
+
+pub struct DnsmasqFormat;
+
+impl FileFormat for DnsmasqFormat {
+    fn name(&self) -> &str {
+        "dnsmasq"
+    }
+
+    fn supports(&self, record: &DNSRecord) -> bool {
+        record.record_type != DNSRecordType::TXT
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            record_types: vec![
+                DNSRecordType::A,
+                DNSRecordType::AAAA,
+                DNSRecordType::CNAME,
+                DNSRecordType::PTR,
+            ],
+            ttl: false,
+            wildcards: false,
+            update_in_place: true,
+            txt: false,
+        }
+    }
+
+    fn parse(&self, lines: &[&str]) -> Vec<DNSRecord> {
+        let mut records = Vec::new();
+        // dnsmasq only allows whole-line comments, so a record's comment
+        // is the line above it
+        let mut comment = None;
+        for line in lines {
+            let line = line.trim();
+            if let Some(text) = line.strip_prefix('#') {
+                comment = Some(text.trim().to_string()).filter(|c| !c.is_empty());
+                continue;
+            }
+            let comment = comment.take();
+            if let Some(rest) = line.strip_prefix("address=/") {
+                // address=/name/ip
+                let Some((name, address)) = rest.split_once('/') else {
+                    continue;
+                };
+                let record = match address.parse() {
+                    Ok(IpAddr::V4(v4)) => DNSRecord::a(name, v4),
+                    Ok(IpAddr::V6(v6)) => DNSRecord::aaaa(name, v6),
+                    Err(_) => continue,
+                };
+                records.push(record.comment(comment));
+            } else if let Some(rest) = line.strip_prefix("ptr-record=") {
+                // ptr-record=reverse-name,target
+                let Some((name, target)) = rest.split_once(',') else {
+                    continue;
+                };
+                records.push(DNSRecord::ptr(name, target).comment(comment));
+            } else if let Some(rest) = line.strip_prefix("cname=") {
+                // cname=alias,target
+                let Some((name, target)) = rest.split_once(',') else {
+                    continue;
+                };
+                records.push(DNSRecord::cname(name, target).comment(comment));
+            }
+        }
+        records
+    }
+
+    fn render(&self, records: &[DNSRecord]) -> Vec<String> {
+        let mut records: Vec<_> = records.iter().filter(|r| self.supports(r)).collect();
+        records.sort_by(|a, b| (&a.name, &a.value).cmp(&(&b.name, &b.value)));
+        let mut lines = Vec::new();
+        for r in records {
+            if let Some(comment) = &r.comment {
+                lines.push(format!("# {}", comment.replace(['\r', '\n'], " ")));
+            }
+            lines.push(match r.record_type {
+                DNSRecordType::A | DNSRecordType::AAAA => {
+                    format!("address=/{}/{}", r.name, r.value)
+                }
+                DNSRecordType::CNAME => format!("cname={},{}", r.name, r.value),
+                DNSRecordType::PTR => format!("ptr-record={},{}", r.name, r.value),
+                DNSRecordType::NAPTR | DNSRecordType::SSHFP | DNSRecordType::TXT => continue,
+            });
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let records = vec![
+            DNSRecord {
+                record_type: DNSRecordType::A,
+                name: "a.example.com".into(),
+                value: "1.2.3.4".into(),
+                ttl: None,
+                comment: None,
+            },
+            DNSRecord {
+                record_type: DNSRecordType::AAAA,
+                name: "b.example.com".into(),
+                value: "2001:db8::1".into(),
+                ttl: None,
+                comment: Some("printer".into()),
+            },
+            DNSRecord {
+                record_type: DNSRecordType::CNAME,
+                name: "c.example.com".into(),
+                value: "a.example.com".into(),
+                ttl: None,
+                comment: None,
+            },
+        ];
+        let lines = DnsmasqFormat.render(&records);
+        assert_eq!(
+            lines,
+            vec![
+                "address=/a.example.com/1.2.3.4",
+                "# printer",
+                "address=/b.example.com/2001:db8::1",
+                "cname=c.example.com,a.example.com",
+            ]
+        );
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(DnsmasqFormat.parse(&lines), records);
+    }
+}
@@ -0,0 +1,257 @@
+//! Dynamic DNS services that only support "set the IP for this hostname".
+
+use async_trait::async_trait;
+use reqwest::Client;
+use std::net::IpAddr;
+
+use crate::core::dyndns::DynamicDNSUpdater;
+use crate::core::http::HttpOptions;
+use crate::error::Error;
+use crate::secret::Secret;
+
+pub const DUCKDNS_UPDATE_URL: &str = "https://www.duckdns.org/update";
+pub const NOIP_UPDATE_URL: &str = "https://dynupdate.no-ip.com/nic/update";
+pub const DYNU_UPDATE_URL: &str = "https://api.dynu.com/nic/update";
+
+fn http_client(http: &HttpOptions) -> Result<Client, Error> {
+    http.builder()
+        .build()
+        .map_err(|e| Error::ProviderError(e.to_string()))
+}
+
+/// DuckDNS, authenticated by an account token.
+pub struct DuckDNSUpdater {
+    client: Client,
+    update_url: String,
+    /// Subdomain without the `.duckdns.org` suffix
+    domain: String,
+    token: Secret,
+}
+
+impl DuckDNSUpdater {
+    pub fn new(
+        update_url: &str,
+        hostname: &str,
+        token: Secret,
+        http: &HttpOptions,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            client: http_client(http)?,
+            update_url: update_url.to_string(),
+            domain: hostname.trim_end_matches(".duckdns.org").to_string(),
+            token,
+        })
+    }
+}
+
+#[async_trait]
+impl DynamicDNSUpdater for DuckDNSUpdater {
+    fn name(&self) -> &str {
+        "duckdns"
+    }
+
+    async fn set_address(&self, address: IpAddr) -> Result<(), Error> {
+        let ip_param = match address {
+            IpAddr::V4(_) => "ip",
+            IpAddr::V6(_) => "ipv6",
+        };
+        let body = self
+            .client
+            .get(&self.update_url)
+            .query(&[
+                ("domains", self.domain.as_str()),
+                ("token", self.token.expose()),
+                (ip_param, &address.to_string()),
+            ])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| Error::ProviderError(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| Error::ProviderError(e.to_string()))?;
+
+        // DuckDNS answers KO for both a bad token and an unknown domain
+        match body.lines().next().unwrap_or_default().trim() {
+            "OK" => Ok(()),
+            _ => Err(Error::ProviderError(format!(
+                "DuckDNS rejected update for {}",
+                self.domain
+            ))),
+        }
+    }
+}
+
+/// Services speaking the dyndns2 protocol, such as No-IP and Dynu.
+pub struct Dyndns2Updater {
+    name: String,
+    client: Client,
+    update_url: String,
+    hostname: String,
+    username: String,
+    password: Secret,
+}
+
+impl Dyndns2Updater {
+    pub fn new(
+        name: &str,
+        update_url: &str,
+        hostname: &str,
+        username: String,
+        password: Secret,
+        http: &HttpOptions,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            name: name.to_string(),
+            client: http_client(http)?,
+            update_url: update_url.to_string(),
+            hostname: hostname.to_string(),
+            username,
+            password,
+        })
+    }
+}
+
+#[async_trait]
+impl DynamicDNSUpdater for Dyndns2Updater {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn set_address(&self, address: IpAddr) -> Result<(), Error> {
+        let ip_param = match address {
+            IpAddr::V4(_) => "myip",
+            IpAddr::V6(_) => "myipv6",
+        };
+        let body = self
+            .client
+            .get(&self.update_url)
+            .basic_auth(&self.username, Some(self.password.expose()))
+            .query(&[
+                ("hostname", self.hostname.as_str()),
+                (ip_param, &address.to_string()),
+            ])
+            .send()
+            .await
+            .map_err(|e| Error::ProviderError(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| Error::ProviderError(e.to_string()))?;
+        parse_dyndns2_response(&body)
+    }
+}
+
+/// Map a dyndns2 return code to a result.
+fn parse_dyndns2_response(body: &str) -> Result<(), Error> {
+    let code = body.split_whitespace().next().unwrap_or_default();
+    match code {
+        "good" | "nochg" => Ok(()),
+        "badauth" | "!donator" => Err(Error::CredentialError(code.to_string())),
+        "nohost" | "notfqdn" | "numhost" => Err(Error::NotFound(code.to_string())),
+        _ => Err(Error::ProviderError(body.trim().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    #[test]
+    fn test_parse_dyndns2_response() {
+        assert!(parse_dyndns2_response("good 1.2.3.4").is_ok());
+        assert!(parse_dyndns2_response("nochg 1.2.3.4\n").is_ok());
+        assert!(matches!(
+            parse_dyndns2_response("badauth"),
+            Err(Error::CredentialError(_))
+        ));
+        assert!(matches!(
+            parse_dyndns2_response("nohost"),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            parse_dyndns2_response("911"),
+            Err(Error::ProviderError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_duckdns_update() {
+        let server = MockServer::start_async().await;
+        let ok = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/update")
+                    .query_param("domains", "myhome")
+                    .query_param("token", "secret")
+                    .query_param("ipv6", "2001:db8::1");
+                then.status(200).body("OK");
+            })
+            .await;
+        let updater = DuckDNSUpdater::new(
+            &server.url("/update"),
+            "myhome.duckdns.org",
+            "secret".into(),
+            &HttpOptions::default(),
+        )
+        .unwrap();
+        updater
+            .set_address("2001:db8::1".parse().unwrap())
+            .await
+            .unwrap();
+        ok.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_duckdns_rejected() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/update");
+                then.status(200).body("KO");
+            })
+            .await;
+        let updater = DuckDNSUpdater::new(
+            &server.url("/update"),
+            "myhome",
+            "bad".into(),
+            &HttpOptions::default(),
+        )
+        .unwrap();
+        assert!(
+            updater
+                .set_address("1.2.3.4".parse().unwrap())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dyndns2_update() {
+        let server = MockServer::start_async().await;
+        let good = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/nic/update")
+                    .header("Authorization", "Basic dXNlcjpwYXNz")
+                    .query_param("hostname", "home.example.net")
+                    .query_param("myip", "1.2.3.4");
+                then.status(200).body("good 1.2.3.4");
+            })
+            .await;
+        let updater = Dyndns2Updater::new(
+            "noip",
+            &server.url("/nic/update"),
+            "home.example.net",
+            "user".into(),
+            "pass".into(),
+            &HttpOptions::default(),
+        )
+        .unwrap();
+        updater
+            .set_address("1.2.3.4".parse().unwrap())
+            .await
+            .unwrap();
+        good.assert_async().await;
+    }
+}
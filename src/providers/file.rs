@@ -0,0 +1,406 @@
+//! Providers that render records into local files instead of calling an API.
+//!
+//! Records are kept between `# BEGIN dns-update` / `# END dns-update` markers
+//! so the rest of the file (for example the stock `/etc/hosts` entries) is
+//! left untouched. Changes are buffered in memory and written atomically
+//! when the provider is flushed at the end of a run, optionally followed by
+//! a reload of the resolver that reads the file.
+
+use async_trait::async_trait;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+use crate::config::ReloadConfig;
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
+use crate::core::record::DNSRecord;
+use crate::error::Error;
+
+pub const BEGIN_MARKER: &str = "# BEGIN dns-update";
+pub const END_MARKER: &str = "# END dns-update";
+
+/// A line-oriented file format that can hold DNS records.
+pub trait FileFormat: Send + Sync {
+    /// Name reported through `DNSProvider::name`.
+    fn name(&self) -> &str;
+
+    /// Whether the format can represent the given record.
+    fn supports(&self, record: &DNSRecord) -> bool;
+
+    /// What the format can hold. Files carry no TTLs or wildcards unless the
+    /// format says otherwise.
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            ttl: false,
+            wildcards: false,
+            update_in_place: true,
+            ..Default::default()
+        }
+    }
+
+    /// Parse the lines of the managed block into records.
+    fn parse(&self, lines: &[&str]) -> Vec<DNSRecord>;
+
+    /// Render records into the lines of the managed block.
+    fn render(&self, records: &[DNSRecord]) -> Vec<String>;
+
+    /// Extract records from the full file contents.
+    fn read(&self, contents: &str) -> Vec<DNSRecord> {
+        self.parse(&managed_lines(contents))
+    }
+
+    /// Produce the new file contents from the previous contents and records.
+    fn write(&self, contents: &str, records: &[DNSRecord]) -> String {
+        replace_managed_block(contents, &self.render(records))
+    }
+}
+
+pub struct FileProvider<F> {
+    path: PathBuf,
+    format: F,
+    reload: Option<ReloadConfig>,
+    state: Mutex<FileState>,
+}
+
+struct FileState {
+    records: Vec<DNSRecord>,
+    dirty: bool,
+}
+
+impl<F: FileFormat> FileProvider<F> {
+    /// Load the managed block of `path`; a missing file starts out empty.
+    pub fn new(path: impl Into<PathBuf>, format: F) -> Result<Self, Error> {
+        let path = path.into();
+        let contents = read_to_string_or_empty(&path)?;
+        let records = format.read(&contents);
+        Ok(Self {
+            path,
+            format,
+            reload: None,
+            state: Mutex::new(FileState {
+                records,
+                dirty: false,
+            }),
+        })
+    }
+
+    /// Reload the consuming resolver after each write.
+    pub fn with_reload(mut self, reload: Option<ReloadConfig>) -> Self {
+        self.reload = reload;
+        self
+    }
+
+    fn check_supported(&self, record: &DNSRecord) -> Result<(), Error> {
+        if self.format.supports(record) {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "{} file cannot hold {:?} record {}",
+                self.format.name(),
+                record.record_type,
+                record.name
+            )))
+        }
+    }
+}
+
+#[async_trait]
+impl<F: FileFormat> DNSProvider for FileProvider<F> {
+    fn name(&self) -> &str {
+        self.format.name()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.format.capabilities()
+    }
+
+    async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+        let mut state = self.state.lock().await;
+        // Read again, as the file may have been edited since, e.g. by a
+        // manual run while a daemon keeps this provider; changes not
+        // flushed yet are kept
+        if !state.dirty {
+            state.records = self.format.read(&read_to_string_or_empty(&self.path)?);
+        }
+        Ok(state.records.clone())
+    }
+
+    async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.check_supported(&record)?;
+        let mut state = self.state.lock().await;
+        if !state.records.iter().any(|r| same_entry(r, &record)) {
+            state.records.push(record);
+            state.dirty = true;
+        }
+        Ok(())
+    }
+
+    async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.check_supported(&record)?;
+        let mut state = self.state.lock().await;
+        match state
+            .records
+            .iter_mut()
+            .find(|r| r.record_type == record.record_type && r.name == record.name)
+        {
+            Some(existing) => {
+                *existing = record;
+                state.dirty = true;
+                Ok(())
+            }
+            None => Err(Error::NotFound("Record not found".to_string())),
+        }
+    }
+
+    async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let mut state = self.state.lock().await;
+        let before = state.records.len();
+        state.records.retain(|r| !same_entry(r, &record));
+        if state.records.len() == before {
+            return Err(Error::NotFound("Record not found".to_string()));
+        }
+        state.dirty = true;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().await;
+        if !state.dirty {
+            return Ok(());
+        }
+        let contents = read_to_string_or_empty(&self.path)?;
+        let contents = self.format.write(&contents, &state.records);
+        write_atomic(&self.path, &contents)
+            .map_err(|e| Error::ProviderError(format!("{}: {e}", self.path.display())))?;
+        state.dirty = false;
+
+        if let Some(reload) = &self.reload {
+            run_reload(reload).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Records in files have no TTL, so entries are compared by type, name and value.
+fn same_entry(a: &DNSRecord, b: &DNSRecord) -> bool {
+    a.record_type == b.record_type && a.name == b.name && a.value == b.value
+}
+
+/// Split a trailing comment introduced by `marker` off a line. Markers in
+/// quoted strings, like the `;` of DKIM keys in zone files, are kept.
+pub fn split_comment(line: &str, marker: char) -> (&str, Option<String>) {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == marker && !quoted => {
+                let comment = line[i + c.len_utf8()..].trim();
+                return (
+                    &line[..i],
+                    (!comment.is_empty()).then(|| comment.to_string()),
+                );
+            }
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+/// Append the record's comment, if any, to a rendered line.
+pub fn with_comment(line: String, record: &DNSRecord, marker: char) -> String {
+    match &record.comment {
+        Some(comment) => format!("{line}\t{marker} {}", comment.replace(['\r', '\n'], " ")),
+        None => line,
+    }
+}
+
+async fn run_reload(reload: &ReloadConfig) -> Result<(), Error> {
+    let mut command = match reload {
+        ReloadConfig::Signal { pid_file } => {
+            let pid = std::fs::read_to_string(pid_file)
+                .map_err(|e| Error::ProviderError(format!("{}: {e}", pid_file.display())))?;
+            let mut command = tokio::process::Command::new("kill");
+            command.arg("-HUP").arg(pid.trim());
+            command
+        }
+        ReloadConfig::Command { command: args } => {
+            let (program, args) = args
+                .split_first()
+                .ok_or_else(|| Error::InvalidInput("empty reload command".to_string()))?;
+            let mut command = tokio::process::Command::new(program);
+            command.args(args);
+            command
+        }
+    };
+
+    let output = command
+        .output()
+        .await
+        .map_err(|e| Error::ProviderError(format!("reload failed: {e}")))?;
+    if !output.status.success() {
+        return Err(Error::ProviderError(format!(
+            "reload failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn read_to_string_or_empty(path: &Path) -> Result<String, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(Error::ProviderError(format!("{}: {e}", path.display()))),
+    }
+}
+
+/// Lines between the managed-block markers, excluding the markers themselves.
+pub fn managed_lines(contents: &str) -> Vec<&str> {
+    contents
+        .lines()
+        .skip_while(|l| l.trim() != BEGIN_MARKER)
+        .skip(1)
+        .take_while(|l| l.trim() != END_MARKER)
+        .collect()
+}
+
+/// Replace the managed block in `contents`, appending one if none exists.
+pub fn replace_managed_block(contents: &str, block: &[String]) -> String {
+    let mut out = String::new();
+    let mut lines = contents.lines();
+    let mut replaced = false;
+
+    for line in lines.by_ref() {
+        if line.trim() == BEGIN_MARKER {
+            push_block(&mut out, block);
+            replaced = true;
+            break;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    if replaced {
+        // Drop the old block, keep whatever follows it
+        for line in lines.by_ref() {
+            if line.trim() == END_MARKER {
+                break;
+            }
+        }
+        for line in lines {
+            out.push_str(line);
+            out.push('\n');
+        }
+    } else {
+        push_block(&mut out, block);
+    }
+    out
+}
+
+fn push_block(out: &mut String, block: &[String]) {
+    out.push_str(BEGIN_MARKER);
+    out.push('\n');
+    for line in block {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(END_MARKER);
+    out.push('\n');
+}
+
+/// Write through a temporary sibling file and rename it into place, so
+/// readers never observe a partially written file.
+pub fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".dns-update.tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        std::fs::set_permissions(&tmp_path, metadata.permissions())?;
+    }
+    std::fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_comment_skips_quoted_markers() {
+        assert_eq!(
+            split_comment("a 60 IN A 10.0.0.1 ; nas", ';'),
+            ("a 60 IN A 10.0.0.1 ", Some("nas".to_string()))
+        );
+        let txt = r#"k 60 IN TXT "v=DKIM1; k=rsa; p=\"x;\"" "#;
+        assert_eq!(
+            split_comment(&format!("{txt};dkim"), ';'),
+            (txt, Some("dkim".to_string()))
+        );
+        assert_eq!(
+            split_comment("10.0.0.1 nas #", '#'),
+            ("10.0.0.1 nas ", None)
+        );
+    }
+
+    #[test]
+    fn test_managed_lines() {
+        let contents = "127.0.0.1 localhost\n# BEGIN dns-update\n1.2.3.4 a.com\n# END dns-update\n";
+        assert_eq!(managed_lines(contents), vec!["1.2.3.4 a.com"]);
+        assert!(managed_lines("127.0.0.1 localhost\n").is_empty());
+    }
+
+    #[test]
+    fn test_replace_managed_block_preserves_surroundings() {
+        let contents =
+            "127.0.0.1 localhost\n# BEGIN dns-update\nold\n# END dns-update\n::1 localhost\n";
+        let out = replace_managed_block(contents, &["new".to_string()]);
+        assert_eq!(
+            out,
+            "127.0.0.1 localhost\n# BEGIN dns-update\nnew\n# END dns-update\n::1 localhost\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_managed_block_appends() {
+        let out = replace_managed_block("127.0.0.1 localhost\n", &["new".to_string()]);
+        assert_eq!(
+            out,
+            "127.0.0.1 localhost\n# BEGIN dns-update\nnew\n# END dns-update\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_command_failure() {
+        let ok = ReloadConfig::Command {
+            command: vec!["true".to_string()],
+        };
+        assert!(run_reload(&ok).await.is_ok());
+        let fail = ReloadConfig::Command {
+            command: vec!["false".to_string()],
+        };
+        assert!(matches!(
+            run_reload(&fail).await,
+            Err(Error::ProviderError(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts");
+        write_atomic(&path, "first\n").unwrap();
+        write_atomic(&path, "second\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
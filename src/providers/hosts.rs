@@ -0,0 +1,167 @@
+//! hosts(5) file output, also usable as a dnsmasq `addn-hosts` file.
+
+use crate::core::provider::ProviderCapabilities;
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::providers::file::{FileFormat, split_comment, with_comment};
+
+pub struct HostsFormat;
+
+impl FileFormat for HostsFormat {
+    fn name(&self) -> &str {
+        "hosts"
+    }
+
+    fn supports(&self, record: &DNSRecord) -> bool {
+        matches!(record.record_type, DNSRecordType::A | DNSRecordType::AAAA)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            record_types: vec![DNSRecordType::A, DNSRecordType::AAAA],
+            ttl: false,
+            wildcards: false,
+            update_in_place: true,
+            txt: false,
+        }
+    }
+
+    fn parse(&self, lines: &[&str]) -> Vec<DNSRecord> {
+        let mut records = Vec::new();
+        for line in lines {
+            let (line, comment) = split_comment(line, '#');
+            let mut parts = line.split_whitespace();
+            let Some(address) = parts.next() else {
+                continue;
+            };
+            let record_type = if address.parse::<std::net::Ipv4Addr>().is_ok() {
+                DNSRecordType::A
+            } else if address.parse::<std::net::Ipv6Addr>().is_ok() {
+                DNSRecordType::AAAA
+            } else {
+                continue;
+            };
+            for name in parts {
+                records.push(DNSRecord {
+                    record_type: record_type.clone(),
+                    name: name.to_string(),
+                    value: address.to_string(),
+                    ttl: None,
+                    comment: comment.clone(),
+                });
+            }
+        }
+        records
+    }
+
+    fn render(&self, records: &[DNSRecord]) -> Vec<String> {
+        let mut records: Vec<_> = records.iter().filter(|r| self.supports(r)).collect();
+        records.sort_by(|a, b| (&a.name, &a.value).cmp(&(&b.name, &b.value)));
+        records
+            .into_iter()
+            .map(|r| with_comment(format!("{}\t{}", r.value, r.name), r, '#'))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::provider::DNSProvider;
+    use crate::providers::file::FileProvider;
+
+    fn record(record_type: DNSRecordType, name: &str, value: &str) -> DNSRecord {
+        DNSRecord {
+            record_type,
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_hosts_lines() {
+        let lines = [
+            "1.2.3.4 a.example.com b.example.com # comment",
+            "",
+            "2001:db8::1\tipv6.example.com",
+            "not-an-ip broken.example.com",
+        ];
+        let records = HostsFormat.parse(&lines);
+        assert_eq!(
+            records,
+            vec![
+                commented(record(DNSRecordType::A, "a.example.com", "1.2.3.4")),
+                commented(record(DNSRecordType::A, "b.example.com", "1.2.3.4")),
+                record(DNSRecordType::AAAA, "ipv6.example.com", "2001:db8::1"),
+            ]
+        );
+    }
+
+    fn commented(record: DNSRecord) -> DNSRecord {
+        DNSRecord {
+            comment: Some("comment".to_string()),
+            ..record
+        }
+    }
+
+    #[test]
+    fn test_render_comments() {
+        let records = [commented(record(
+            DNSRecordType::A,
+            "a.example.com",
+            "1.2.3.4",
+        ))];
+        let lines = HostsFormat.render(&records);
+        assert_eq!(lines, vec!["1.2.3.4\ta.example.com\t# comment"]);
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(HostsFormat.parse(&lines), records);
+    }
+
+    #[test]
+    fn test_render_is_sorted_and_skips_cname() {
+        let records = [
+            record(DNSRecordType::A, "b.example.com", "1.2.3.4"),
+            record(DNSRecordType::CNAME, "c.example.com", "b.example.com"),
+            record(DNSRecordType::A, "a.example.com", "1.2.3.4"),
+        ];
+        assert_eq!(
+            HostsFormat.render(&records),
+            vec!["1.2.3.4\ta.example.com", "1.2.3.4\tb.example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts");
+        std::fs::write(&path, "127.0.0.1 localhost\n").unwrap();
+
+        let provider = FileProvider::new(&path, HostsFormat).unwrap();
+        provider
+            .add_record(record(DNSRecordType::A, "a.example.com", "1.2.3.4"))
+            .await
+            .unwrap();
+        let cname = record(DNSRecordType::CNAME, "c.example.com", "a.example.com");
+        assert!(provider.add_record(cname).await.is_err());
+        provider.flush().await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "127.0.0.1 localhost\n# BEGIN dns-update\n1.2.3.4\ta.example.com\n# END dns-update\n"
+        );
+
+        let reloaded = FileProvider::new(&path, HostsFormat).unwrap();
+        let records = reloaded.list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![record(DNSRecordType::A, "a.example.com", "1.2.3.4")]
+        );
+        reloaded.delete_record(records[0].clone()).await.unwrap();
+        reloaded.flush().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "127.0.0.1 localhost\n# BEGIN dns-update\n# END dns-update\n"
+        );
+    }
+}
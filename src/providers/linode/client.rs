@@ -0,0 +1,588 @@
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+
+use crate::auth::credentials::CredentialManager;
+use crate::core::http::{HttpOptions, with_request_id};
+use crate::core::ownership::{self, Owned, Ownership};
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
+use crate::core::record::DNSRecord;
+use crate::core::scope::Scope;
+use crate::error::Error;
+use crate::providers::linode::error::{LinodeProviderError, map_error};
+use crate::providers::linode::types::*;
+use crate::secret::Secret;
+use async_trait::async_trait;
+
+const PAGE_SIZE: u32 = 500;
+
+pub struct LinodeConfig {
+    pub api_url: String,
+    /// Zone name, e.g. `example.com`
+    pub domain: String,
+    pub ownership: Ownership,
+    /// Names a sync may touch, so listings can leave out the rest
+    pub scope: Scope,
+}
+
+pub struct LinodeProvider {
+    config: LinodeConfig,
+    client: Client,
+    token: Secret,
+    domain_id: u64,
+    /// The zone as listed for the additions of a run, until `flush`
+    listing: Mutex<Option<Vec<LinodeRecord>>>,
+}
+
+impl LinodeProvider {
+    /// Create the provider and resolve the domain ID for the configured zone.
+    pub async fn new(
+        config: LinodeConfig,
+        credentials: Arc<dyn CredentialManager>,
+        http: &HttpOptions,
+    ) -> Result<Self, LinodeProviderError> {
+        let token = credentials
+            .get("linode_token")
+            .map_err(|e| LinodeProviderError::Credential(e.to_string()))?;
+        let client = http.builder().build()?;
+
+        let mut provider = Self {
+            config,
+            client,
+            token,
+            domain_id: 0,
+            listing: Mutex::new(None),
+        };
+        provider.domain_id = provider.resolve_domain_id().await?;
+        Ok(provider)
+    }
+
+    async fn resolve_domain_id(&self) -> Result<u64, LinodeProviderError> {
+        let url = format!("{}/domains", self.config.api_url);
+        let domains: Vec<LinodeDomain> = self.get_all(&url, None).await?;
+        domains
+            .into_iter()
+            .find(|d| d.domain.eq_ignore_ascii_case(&self.config.domain))
+            .map(|d| d.id)
+            .ok_or_else(|| LinodeProviderError::NotFound(format!("domain {}", self.config.domain)))
+    }
+
+    fn request(&self, builder: RequestBuilder) -> RequestBuilder {
+        with_request_id(builder).bearer_auth(self.token.expose())
+    }
+
+    async fn handle_request<T: DeserializeOwned>(
+        &self,
+        builder: RequestBuilder,
+    ) -> Result<T, LinodeProviderError> {
+        let response = self.request(builder).send().await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Err(LinodeProviderError::NotFound(
+                response.url().path().to_string(),
+            ))
+        } else {
+            let status = response.status();
+            let errors: LinodeErrors = response.json().await.unwrap_or(LinodeErrors {
+                errors: vec![LinodeError {
+                    field: None,
+                    reason: format!("HTTP {status}"),
+                }],
+            });
+            Err(errors.into())
+        }
+    }
+
+    /// Fetch every page of a paginated collection, only the items matching
+    /// `filter` if given.
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        filter: Option<&Value>,
+    ) -> Result<Vec<T>, LinodeProviderError> {
+        let mut items = Vec::new();
+        let mut page = 1;
+        loop {
+            let mut builder = self
+                .client
+                .get(url)
+                .query(&[("page", page), ("page_size", PAGE_SIZE)]);
+            if let Some(filter) = filter {
+                builder = builder.header("X-Filter", filter.to_string());
+            }
+            let result: Page<T> = self.handle_request(builder).await?;
+            items.extend(result.data);
+            if result.page >= result.pages {
+                return Ok(items);
+            }
+            page = result.page + 1;
+        }
+    }
+
+    fn records_url(&self) -> String {
+        format!("{}/domains/{}/records", self.config.api_url, self.domain_id)
+    }
+
+    pub async fn list_linode_records(&self) -> Result<Vec<LinodeRecord>, LinodeProviderError> {
+        self.get_all(&self.records_url(), None).await
+    }
+
+    fn to_request(&self, record: &DNSRecord) -> Result<RecordRequest, Error> {
+        to_record_request(record, &self.config.domain).ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "{} is outside of {}",
+                record.name, self.config.domain
+            ))
+        })
+    }
+
+    /// Find the Linode record matching `matches`, for operations that need an ID.
+    async fn find_record<F>(&self, matches: F) -> Result<LinodeRecord, Error>
+    where
+        F: Fn(&DNSRecord) -> bool,
+    {
+        self.list_linode_records()
+            .await
+            .map_err(map_error)?
+            .into_iter()
+            .find(|r| to_dns_record(r, &self.config.domain).is_some_and(|d| matches(&d)))
+            .ok_or_else(|| Error::NotFound("Record not found".to_string()))
+    }
+
+    fn is_same(&self, existing: &LinodeRecord, record: &DNSRecord) -> bool {
+        to_dns_record(existing, &self.config.domain).is_some_and(|d| d.same_data(record))
+    }
+
+    /// The zone as listed by the first addition of a run, and kept up to
+    /// date by the following ones, so each does not list it again.
+    async fn listing(&self) -> Result<MappedMutexGuard<'_, Vec<LinodeRecord>>, Error> {
+        let mut listing = self.listing.lock().await;
+        if listing.is_none() {
+            *listing = Some(self.list_linode_records().await.map_err(map_error)?);
+        }
+        Ok(MutexGuard::map(listing, |l| l.get_or_insert_default()))
+    }
+
+    fn is_marker(&self, record: &LinodeRecord, (name, value): &(String, String)) -> bool {
+        record.record_type == "TXT"
+            && fqdn(&record.name, &self.config.domain) == *name
+            && record.target == *value
+    }
+
+    /// Publish the ownership marker for `record` unless `records` has it.
+    async fn claim(
+        &self,
+        record: &DNSRecord,
+        records: &mut Vec<LinodeRecord>,
+    ) -> Result<(), Error> {
+        if !self.config.ownership.marks(record) {
+            return Ok(());
+        }
+        let marker = ownership::marker(record);
+        if records.iter().any(|r| self.is_marker(r, &marker)) {
+            return Ok(());
+        }
+        let req = RecordRequest {
+            record_type: "TXT".to_string(),
+            name: relative_name(&marker.0, &self.config.domain).unwrap_or_default(),
+            target: marker.1,
+            ttl_sec: None,
+        };
+        let builder = self.client.post(self.records_url()).json(&req);
+        let created = self.handle_request(builder).await.map_err(map_error)?;
+        records.push(created);
+        Ok(())
+    }
+
+    /// Remove the ownership marker for `record` once no value is left under it.
+    async fn release(&self, record: &DNSRecord) -> Result<(), Error> {
+        if !self.config.ownership.marks(record) {
+            return Ok(());
+        }
+        let marker = ownership::marker(record);
+        let records = self.list_linode_records().await.map_err(map_error)?;
+        let in_use = records
+            .iter()
+            .any(|r| to_dns_record(r, &self.config.domain).is_some_and(|d| d.same_name(record)));
+        let Some(existing) = records.iter().find(|r| self.is_marker(r, &marker)) else {
+            return Ok(());
+        };
+        if in_use {
+            return Ok(());
+        }
+        let url = format!("{}/{}", self.records_url(), existing.id);
+        self.handle_request::<serde_json::Value>(self.client.delete(url))
+            .await
+            .map(|_| ())
+            .map_err(map_error)
+    }
+}
+
+#[async_trait]
+impl DNSProvider for LinodeProvider {
+    fn name(&self) -> &str {
+        "linode"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            update_in_place: true,
+            txt: true,
+            ..Default::default()
+        }
+    }
+
+    async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+        // Filtered by Linode, so large zones are not listed in full
+        let filter = list_filter(&self.config.domain, &self.config.scope);
+        let records: Vec<LinodeRecord> = self
+            .get_all(&self.records_url(), Some(&filter))
+            .await
+            .map_err(map_error)?;
+        Ok(records
+            .iter()
+            .filter_map(|r| to_dns_record(r, &self.config.domain))
+            .collect())
+    }
+
+    async fn owned_records(&self) -> Result<Option<Owned>, Error> {
+        if self.config.ownership != Ownership::Txt {
+            return Ok(None);
+        }
+        let filter = json!({ "type": "TXT" });
+        let records: Vec<LinodeRecord> = self
+            .get_all(&self.records_url(), Some(&filter))
+            .await
+            .map_err(map_error)?;
+        Ok(Some(
+            records
+                .iter()
+                .filter_map(|r| {
+                    ownership::parse_marker(&fqdn(&r.name, &self.config.domain), &r.target)
+                })
+                .collect(),
+        ))
+    }
+
+    async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let req = self.to_request(&record)?;
+        // Linode accepts duplicate records, so an add that already went
+        // through, e.g. before an interrupted run, must not be repeated
+        let mut records = self.listing().await?;
+        if !records.iter().any(|r| self.is_same(r, &record)) {
+            let builder = self.client.post(self.records_url()).json(&req);
+            let created = self.handle_request(builder).await.map_err(map_error)?;
+            records.push(created);
+        }
+        self.claim(&record, &mut records).await
+    }
+
+    async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let req = self.to_request(&record)?;
+        *self.listing.lock().await = None;
+        let existing = self.find_record(|r| r.same_name(&record)).await?;
+        let url = format!("{}/{}", self.records_url(), existing.id);
+        let builder = self.client.put(url).json(&req);
+        self.handle_request::<LinodeRecord>(builder)
+            .await
+            .map(|_| ())
+            .map_err(map_error)
+    }
+
+    async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+        *self.listing.lock().await = None;
+        let existing = self.find_record(|r| r.same_data(&record)).await?;
+        let url = format!("{}/{}", self.records_url(), existing.id);
+        self.handle_request::<serde_json::Value>(self.client.delete(url))
+            .await
+            .map_err(map_error)?;
+        self.release(&record).await
+    }
+
+    async fn flush(&self) -> Result<(), Error> {
+        // The next run lists the zone afresh
+        *self.listing.lock().await = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    struct FakeCredentialManager;
+
+    impl CredentialManager for FakeCredentialManager {
+        fn get(&self, key: &str) -> Result<Secret, Error> {
+            match key {
+                "linode_token" => Ok("token".into()),
+                _ => Err(Error::CredentialError("missing".into())),
+            }
+        }
+    }
+
+    async fn mock_domains(server: &MockServer) {
+        server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/domains")
+                    .header("Authorization", "Bearer token");
+                then.status(200).json_body(json!({
+                    "data": [
+                        { "id": 1, "domain": "other.com" },
+                        { "id": 42, "domain": "example.com" }
+                    ],
+                    "page": 1, "pages": 1, "results": 2
+                }));
+            })
+            .await;
+    }
+
+    async fn provider_with(
+        server: &MockServer,
+        ownership: Ownership,
+    ) -> Result<LinodeProvider, LinodeProviderError> {
+        let config = LinodeConfig {
+            api_url: server.url(""),
+            domain: "example.com".into(),
+            ownership,
+            scope: Scope::default(),
+        };
+        LinodeProvider::new(
+            config,
+            Arc::new(FakeCredentialManager),
+            &HttpOptions::default(),
+        )
+        .await
+    }
+
+    async fn provider(server: &MockServer) -> Result<LinodeProvider, LinodeProviderError> {
+        provider_with(server, Ownership::Zone).await
+    }
+
+    #[tokio::test]
+    async fn test_resolves_domain_and_lists_all_pages() {
+        let server = MockServer::start_async().await;
+        mock_domains(&server).await;
+        let page1 = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/domains/42/records")
+                    .query_param("page", "1");
+                then.status(200).json_body(json!({
+                    "data": [{ "id": 7, "type": "A", "name": "www", "target": "1.2.3.4", "ttl_sec": 300 }],
+                    "page": 1, "pages": 2, "results": 2
+                }));
+            })
+            .await;
+        let page2 = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/domains/42/records")
+                    .query_param("page", "2");
+                then.status(200).json_body(json!({
+                    "data": [{ "id": 8, "type": "MX", "name": "", "target": "mail.example.com", "ttl_sec": 0 }],
+                    "page": 2, "pages": 2, "results": 2
+                }));
+            })
+            .await;
+
+        let provider = provider(&server).await.unwrap();
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "www.example.com");
+        assert_eq!(records[0].ttl, Some(300));
+        page1.assert_async().await;
+        page2.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_listing_is_filtered_by_linode() {
+        let server = MockServer::start_async().await;
+        mock_domains(&server).await;
+        let filter = list_filter("example.com", &Scope::default()).to_string();
+        let listing = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/domains/42/records")
+                    .header("X-Filter", filter);
+                then.status(200).json_body(json!({
+                    "data": [{ "id": 7, "type": "A", "name": "www", "target": "1.2.3.4", "ttl_sec": 300 }],
+                    "page": 1, "pages": 1, "results": 1
+                }));
+            })
+            .await;
+
+        let provider = provider(&server).await.unwrap();
+        assert_eq!(provider.list_records().await.unwrap().len(), 1);
+        listing.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_unknown_domain() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/domains");
+                then.status(200)
+                    .json_body(json!({ "data": [], "page": 1, "pages": 1, "results": 0 }));
+            })
+            .await;
+        assert!(matches!(
+            provider(&server).await,
+            Err(LinodeProviderError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_and_delete_record() {
+        let server = MockServer::start_async().await;
+        mock_domains(&server).await;
+        let create = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/domains/42/records").json_body(
+                    json!({ "type": "A", "name": "new", "target": "1.2.3.5", "ttl_sec": 300 }),
+                );
+                then.status(200).json_body(json!(
+                    { "id": 9, "type": "A", "name": "new", "target": "1.2.3.5", "ttl_sec": 300 }
+                ));
+            })
+            .await;
+        let listing = server
+            .mock_async(|when, then| {
+                when.method(GET).path("/domains/42/records");
+                then.status(200).json_body(json!({
+                    "data": [{ "id": 7, "type": "A", "name": "www", "target": "1.2.3.4", "ttl_sec": 300 }],
+                    "page": 1, "pages": 1, "results": 1
+                }));
+            })
+            .await;
+        let delete = server
+            .mock_async(|when, then| {
+                when.method(DELETE).path("/domains/42/records/7");
+                then.status(200).json_body(json!({}));
+            })
+            .await;
+
+        let provider = provider(&server).await.unwrap();
+        let existing = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "www.example.com".into(),
+            value: "1.2.3.4".into(),
+            ttl: Some(300),
+            comment: None,
+        };
+        let new = DNSRecord {
+            name: "new.example.com".into(),
+            value: "1.2.3.5".into(),
+            ..existing.clone()
+        };
+        // Already present, however it is spelled, so not created again
+        provider.add_record(existing.clone()).await.unwrap();
+        let spelled = DNSRecord {
+            name: "WWW.example.com.".into(),
+            ..existing.clone()
+        };
+        provider.add_record(spelled).await.unwrap();
+        provider.add_record(new).await.unwrap();
+        // The additions share one listing
+        listing.assert_hits_async(1).await;
+        provider.delete_record(existing).await.unwrap();
+        create.assert_async().await;
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_api_errors_surface_reason() {
+        let server = MockServer::start_async().await;
+        mock_domains(&server).await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/domains/42/records");
+                then.status(200)
+                    .json_body(json!({ "data": [], "page": 1, "pages": 1, "results": 0 }));
+            })
+            .await;
+        server
+            .mock_async(|when, then| {
+                when.method(POST).path("/domains/42/records");
+                then.status(400).json_body(json!({
+                    "errors": [{ "field": "target", "reason": "Invalid IPv4 address" }]
+                }));
+            })
+            .await;
+
+        let provider = provider(&server).await.unwrap();
+        let record = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "www.example.com".into(),
+            value: "nope".into(),
+            ttl: None,
+            comment: None,
+        };
+        let err = provider.add_record(record).await.unwrap_err();
+        assert!(matches!(err.kind(), Error::ProviderError(msg) if msg.contains("Invalid IPv4")));
+    }
+
+    #[tokio::test]
+    async fn test_txt_ownership() {
+        let server = MockServer::start_async().await;
+        mock_domains(&server).await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/domains/42/records");
+                then.status(200).json_body(json!({
+                    "data": [
+                        { "id": 7, "type": "A", "name": "www", "target": "1.2.3.4", "ttl_sec": 300 },
+                        { "id": 8, "type": "A", "name": "manual", "target": "1.2.3.5", "ttl_sec": 300 },
+                        { "id": 9, "type": "TXT", "name": "_dns-update.www", "target": "heritage=dns-update,type=A", "ttl_sec": 0 }
+                    ],
+                    "page": 1, "pages": 1, "results": 3
+                }));
+            })
+            .await;
+        let create_marker = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/domains/42/records").json_body(json!({
+                    "type": "TXT", "name": "_dns-update.new", "target": "heritage=dns-update,type=A"
+                }));
+                then.status(200).json_body(json!(
+                    { "id": 10, "type": "TXT", "name": "_dns-update.new", "target": "heritage=dns-update,type=A", "ttl_sec": 0 }
+                ));
+            })
+            .await;
+        server
+            .mock_async(|when, then| {
+                when.method(POST).path("/domains/42/records").json_body(
+                    json!({ "type": "A", "name": "new", "target": "1.2.3.6", "ttl_sec": 300 }),
+                );
+                then.status(200).json_body(json!(
+                    { "id": 11, "type": "A", "name": "new", "target": "1.2.3.6", "ttl_sec": 300 }
+                ));
+            })
+            .await;
+
+        let provider = provider_with(&server, Ownership::Txt).await.unwrap();
+        let owned = provider.owned_records().await.unwrap().unwrap();
+        assert_eq!(
+            owned,
+            [("www.example.com".to_string(), DNSRecordType::A)].into()
+        );
+
+        let record = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "new.example.com".into(),
+            value: "1.2.3.6".into(),
+            ttl: Some(300),
+            comment: None,
+        };
+        provider.add_record(record).await.unwrap();
+        create_marker.assert_async().await;
+    }
+}
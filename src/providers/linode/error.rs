@@ -0,0 +1,89 @@
+use crate::providers::linode::types::LinodeErrors;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LinodeProviderError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Credential error: {0}")]
+    Credential(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Provider error: {0}")]
+    Provider(String),
+}
+
+impl From<LinodeErrors> for LinodeProviderError {
+    fn from(err: LinodeErrors) -> Self {
+        let message = err
+            .errors
+            .into_iter()
+            .map(|e| match e.field {
+                Some(field) => format!("{field}: {}", e.reason),
+                None => e.reason,
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        LinodeProviderError::Provider(message)
+    }
+}
+
+use crate::error::Error;
+
+pub fn map_error(e: LinodeProviderError) -> Error {
+    use LinodeProviderError::*;
+    match e {
+        Http(err) => Error::Http(err),
+        Credential(ref msg) => Error::with_source(Error::CredentialError(msg.clone()), e),
+        NotFound(ref msg) => Error::with_source(Error::NotFound(msg.clone()), e),
+        Provider(ref msg) => Error::with_source(Error::ProviderError(msg.clone()), e),
+    }
+}
+
+impl From<LinodeProviderError> for Error {
+    fn from(e: LinodeProviderError) -> Self {
+        map_error(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::linode::types::LinodeError;
+
+    #[test]
+    fn test_map_error_variants() {
+        use LinodeProviderError::*;
+
+        let err = map_error(Credential("fail".to_string()));
+        assert!(matches!(err.kind(), Error::CredentialError(_)));
+        let err = map_error(NotFound("missing".to_string()));
+        assert!(matches!(err.kind(), Error::NotFound(_)));
+        let err = map_error(Provider("fail".to_string()));
+        assert!(matches!(err.kind(), Error::ProviderError(_)));
+    }
+
+    #[test]
+    fn test_api_errors_are_joined() {
+        let errors = LinodeErrors {
+            errors: vec![
+                LinodeError {
+                    field: Some("target".into()),
+                    reason: "Invalid IPv4".into(),
+                },
+                LinodeError {
+                    field: None,
+                    reason: "Try again".into(),
+                },
+            ],
+        };
+        let err: LinodeProviderError = errors.into();
+        assert_eq!(
+            err.to_string(),
+            "Provider error: target: Invalid IPv4; Try again"
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Linode Domains provider implementation
+
+pub mod client;
+pub mod error;
+pub mod types;
+
+pub use client::{LinodeConfig, LinodeProvider};
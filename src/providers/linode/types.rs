@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+use crate::core::scope::Scope;
+
+/// Record types read from Linode; others are never listed.
+const LISTED_TYPES: [&str; 4] = ["A", "AAAA", "CNAME", "TXT"];
+
+#[derive(Deserialize, Debug)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub page: u32,
+    pub pages: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LinodeDomain {
+    pub id: u64,
+    pub domain: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LinodeRecord {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    /// Name relative to the domain, empty for the apex
+    pub name: String,
+    pub target: String,
+    /// Zero means the domain default
+    #[serde(default)]
+    pub ttl_sec: u32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RecordRequest {
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub name: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_sec: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LinodeErrors {
+    pub errors: Vec<LinodeError>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LinodeError {
+    pub field: Option<String>,
+    pub reason: String,
+}
+
+/// Fully qualified name for a record name relative to `domain`.
+pub fn fqdn(name: &str, domain: &str) -> String {
+    if name.is_empty() {
+        domain.to_string()
+    } else {
+        format!("{name}.{domain}")
+    }
+}
+
+/// Record name relative to `domain`, or `None` if `name` is outside it.
+pub fn relative_name(name: &str, domain: &str) -> Option<String> {
+    if name == domain {
+        Some(String::new())
+    } else {
+        name.strip_suffix(&format!(".{domain}")).map(str::to_string)
+    }
+}
+
+pub fn to_dns_record(record: &LinodeRecord, domain: &str) -> Option<DNSRecord> {
+    let record_type = match record.record_type.as_str() {
+        "A" => DNSRecordType::A,
+        "AAAA" => DNSRecordType::AAAA,
+        "CNAME" => DNSRecordType::CNAME,
+        "TXT" => DNSRecordType::TXT,
+        _ => return None,
+    };
+    let value = match record_type {
+        DNSRecordType::TXT => record.target.clone(),
+        _ => record.target.trim_end_matches('.').to_string(),
+    };
+    Some(DNSRecord {
+        record_type,
+        name: fqdn(&record.name, domain),
+        value,
+        ttl: (record.ttl_sec != 0).then_some(record.ttl_sec),
+        comment: None,
+    })
+}
+
+/// `X-Filter` of the records worth listing in `domain`: those of the types
+/// read from Linode, at names that may be in `scope`. Names are matched by
+/// substring, as Linode cannot match suffixes, so the listing may hold more
+/// than the scope, which is applied to it afterwards.
+pub fn list_filter(domain: &str, scope: &Scope) -> Value {
+    let types: Vec<Value> = LISTED_TYPES.iter().map(|t| json!({ "type": t })).collect();
+    let types = json!({ "+or": types });
+    match scoped_names(domain, scope) {
+        Some(names) => {
+            let names: Vec<Value> = names
+                .iter()
+                .map(|name| json!({ "name": { "+contains": name } }))
+                .collect();
+            json!({ "+and": [types, { "+or": names }] })
+        }
+        None => types,
+    }
+}
+
+// The names relative to `domain` that the scope is limited to, or `None`
+// if it may cover any name of the zone
+fn scoped_names(domain: &str, scope: &Scope) -> Option<Vec<String>> {
+    if !scope.covered_by_domains() {
+        return None;
+    }
+    let mut names = Vec::new();
+    for scoped in scope.domains() {
+        if scoped == domain || domain.ends_with(&format!(".{scoped}")) {
+            return None;
+        }
+        names.extend(relative_name(&scoped, domain));
+    }
+    (!names.is_empty()).then_some(names)
+}
+
+pub fn to_record_request(record: &DNSRecord, domain: &str) -> Option<RecordRequest> {
+    Some(RecordRequest {
+        record_type: record.record_type.as_str().to_string(),
+        // However the name is spelled, e.g. with the root dot
+        name: relative_name(
+            &normalize_hostname(&record.name),
+            &normalize_hostname(domain),
+        )?,
+        target: record.value.clone(),
+        ttl_sec: record.ttl,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_conversion() {
+        let record = LinodeRecord {
+            id: 1,
+            record_type: "A".into(),
+            name: "www".into(),
+            target: "1.2.3.4".into(),
+            ttl_sec: 0,
+        };
+        let dns = to_dns_record(&record, "example.com").unwrap();
+        assert_eq!(dns.name, "www.example.com");
+        assert_eq!(dns.value, "1.2.3.4");
+        assert_eq!(dns.ttl, None);
+
+        let req = to_record_request(&dns, "example.com").unwrap();
+        assert_eq!(req.name, "www");
+        assert_eq!(req.record_type, "A");
+
+        let apex = DNSRecord {
+            name: "example.com".into(),
+            ..dns
+        };
+        assert_eq!(to_record_request(&apex, "example.com").unwrap().name, "");
+    }
+
+    #[test]
+    fn test_unmanaged_types_skipped() {
+        let record = LinodeRecord {
+            id: 2,
+            record_type: "MX".into(),
+            name: "".into(),
+            target: "mail.example.com".into(),
+            ttl_sec: 300,
+        };
+        assert!(to_dns_record(&record, "example.com").is_none());
+    }
+
+    #[test]
+    fn test_list_filter() {
+        let scope = |patterns: &[&str]| {
+            Scope::new(patterns.iter().map(|p| p.to_string()).collect()).unwrap()
+        };
+        let types = json!({ "+or": [
+            { "type": "A" }, { "type": "AAAA" }, { "type": "CNAME" }, { "type": "TXT" }
+        ] });
+        assert_eq!(
+            list_filter(
+                "example.com",
+                &scope(&["*.lab.example.com", "router.example.com", "www.other.com"])
+            ),
+            json!({ "+and": [types, { "+or": [
+                { "name": { "+contains": "lab" } },
+                { "name": { "+contains": "router" } }
+            ] }] })
+        );
+        assert_eq!(list_filter("example.com", &Scope::default()), types);
+        assert_eq!(
+            list_filter("example.com", &scope(&["*.example.com"])),
+            types
+        );
+        assert_eq!(
+            list_filter("example.com", &scope(&["host-*.example.com"])),
+            types
+        );
+    }
+}
@@ -1,3 +1,197 @@
 //! Provider implementations module
 
+pub mod dnsmasq;
+pub mod dyndns;
+pub mod file;
+pub mod hosts;
+pub mod linode;
+pub mod namecheap;
 pub mod nextdns;
+pub mod unbound;
+pub mod vultr;
+pub mod zone;
+
+use std::sync::Arc;
+
+use crate::auth::credentials::CredentialManager;
+use crate::config::{DdnsConfig, ProviderConfig};
+use crate::core::dyndns::DynamicDNSUpdater;
+use crate::core::http::HttpOptions;
+use crate::core::provider::DNSProvider;
+use crate::core::read_only::ReadOnlyProvider;
+use crate::core::scope::Scope;
+use crate::error::Error;
+use crate::providers::dnsmasq::DnsmasqFormat;
+use crate::providers::dyndns::{DuckDNSUpdater, Dyndns2Updater};
+use crate::providers::file::FileProvider;
+use crate::providers::hosts::HostsFormat;
+use crate::providers::linode::{LinodeConfig, LinodeProvider};
+use crate::providers::namecheap::{NamecheapConfig, NamecheapProvider};
+use crate::providers::nextdns::{NextDNSConfig, NextDNSProvider};
+use crate::providers::unbound::UnboundFormat;
+use crate::providers::vultr::{VultrConfig, VultrProvider};
+use crate::providers::zone::ZoneFormat;
+
+/// Construct the provider described by a configuration entry. Providers
+/// whose API can filter listings only list the records `scope` may cover.
+pub async fn build(
+    config: &ProviderConfig,
+    credentials: Arc<dyn CredentialManager>,
+    http: &HttpOptions,
+    scope: &Scope,
+) -> Result<Arc<dyn DNSProvider>, Error> {
+    let provider = build_kind(config, credentials, http, scope).await?;
+    if config.read_only() {
+        Ok(Arc::new(ReadOnlyProvider::new(provider)))
+    } else {
+        Ok(provider)
+    }
+}
+
+async fn build_kind(
+    config: &ProviderConfig,
+    credentials: Arc<dyn CredentialManager>,
+    http: &HttpOptions,
+    scope: &Scope,
+) -> Result<Arc<dyn DNSProvider>, Error> {
+    match config {
+        ProviderConfig::NextDNS {
+            profile_id,
+            api_url,
+            requests_per_minute,
+            settings,
+            ..
+        } => {
+            let profile_id = match profile_id {
+                Some(id) => id.clone(),
+                None => credentials.get("nextdns_profile_id")?.expose().to_string(),
+            };
+            let config = NextDNSConfig {
+                profile_id,
+                api_url: api_url.clone(),
+                requests_per_minute: *requests_per_minute,
+                settings: settings.clone(),
+            };
+            let provider = NextDNSProvider::new(config, credentials, http)
+                .await
+                .map_err(nextdns::error::map_error)?;
+            Ok(Arc::new(provider))
+        }
+        ProviderConfig::Namecheap {
+            domain,
+            api_user,
+            username,
+            client_ip,
+            api_url,
+            ownership,
+            ..
+        } => {
+            let config = NamecheapConfig {
+                api_url: api_url.clone(),
+                api_user: api_user.clone(),
+                username: username.clone().unwrap_or_else(|| api_user.clone()),
+                client_ip: client_ip.clone(),
+                domain: domain.clone(),
+                ownership: *ownership,
+            };
+            let provider = NamecheapProvider::new(config, credentials, http)
+                .map_err(namecheap::error::map_error)?;
+            Ok(Arc::new(provider))
+        }
+        ProviderConfig::Linode {
+            domain,
+            api_url,
+            ownership,
+            ..
+        } => {
+            let config = LinodeConfig {
+                api_url: api_url.clone(),
+                domain: domain.clone(),
+                ownership: *ownership,
+                scope: scope.clone(),
+            };
+            let provider = LinodeProvider::new(config, credentials, http)
+                .await
+                .map_err(linode::error::map_error)?;
+            Ok(Arc::new(provider))
+        }
+        ProviderConfig::Vultr {
+            domain,
+            api_url,
+            ownership,
+            ..
+        } => {
+            let config = VultrConfig {
+                api_url: api_url.clone(),
+                domain: domain.clone(),
+                ownership: *ownership,
+            };
+            let provider =
+                VultrProvider::new(config, credentials, http).map_err(vultr::error::map_error)?;
+            Ok(Arc::new(provider))
+        }
+        ProviderConfig::Hosts { path, reload, .. } => Ok(Arc::new(
+            FileProvider::new(path, HostsFormat)?.with_reload(reload.clone()),
+        )),
+        ProviderConfig::Dnsmasq { path, reload, .. } => Ok(Arc::new(
+            FileProvider::new(path, DnsmasqFormat)?.with_reload(reload.clone()),
+        )),
+        ProviderConfig::Unbound { path, reload, .. } => Ok(Arc::new(
+            FileProvider::new(path, UnboundFormat)?.with_reload(reload.clone()),
+        )),
+        ProviderConfig::Zone {
+            path,
+            zone,
+            nameserver,
+            ttl,
+            reload,
+            ..
+        } => {
+            let format = ZoneFormat::new(zone, nameserver.as_deref(), *ttl);
+            Ok(Arc::new(
+                FileProvider::new(path, format)?.with_reload(reload.clone()),
+            ))
+        }
+    }
+}
+
+/// Construct the dynamic DNS updater described by a configuration entry.
+pub fn build_ddns(
+    config: &DdnsConfig,
+    credentials: Arc<dyn CredentialManager>,
+    http: &HttpOptions,
+) -> Result<Arc<dyn DynamicDNSUpdater>, Error> {
+    match config {
+        DdnsConfig::DuckDNS {
+            hostname,
+            update_url,
+        } => {
+            let token = credentials.get("duckdns_token")?;
+            Ok(Arc::new(DuckDNSUpdater::new(
+                update_url, hostname, token, http,
+            )?))
+        }
+        DdnsConfig::NoIP {
+            hostname,
+            update_url,
+        } => Ok(Arc::new(Dyndns2Updater::new(
+            "noip",
+            update_url,
+            hostname,
+            credentials.get("noip_username")?.expose().to_string(),
+            credentials.get("noip_password")?,
+            http,
+        )?)),
+        DdnsConfig::Dynu {
+            hostname,
+            update_url,
+        } => Ok(Arc::new(Dyndns2Updater::new(
+            "dynu",
+            update_url,
+            hostname,
+            credentials.get("dynu_username")?.expose().to_string(),
+            credentials.get("dynu_password")?,
+            http,
+        )?)),
+    }
+}
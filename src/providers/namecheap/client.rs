@@ -0,0 +1,457 @@
+use reqwest::Client;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::auth::credentials::CredentialManager;
+use crate::core::http::{HttpOptions, with_request_id};
+use crate::core::ownership::{self, Owned, Ownership};
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
+use crate::core::record::DNSRecord;
+use crate::error::Error;
+use crate::providers::namecheap::error::{NamecheapProviderError, map_error};
+use crate::providers::namecheap::types::*;
+use crate::secret::Secret;
+use async_trait::async_trait;
+
+pub struct NamecheapConfig {
+    pub api_url: String,
+    pub api_user: String,
+    /// Account the domain belongs to; usually the same as `api_user`
+    pub username: String,
+    /// Whitelisted IP address the requests are made from
+    pub client_ip: String,
+    /// Registered domain, e.g. `example.com`
+    pub domain: String,
+    pub ownership: Ownership,
+}
+
+pub struct NamecheapProvider {
+    config: NamecheapConfig,
+    client: Client,
+    api_key: Secret,
+    // setHosts replaces the whole host list, so read-modify-write cycles
+    // must not interleave
+    write_lock: Mutex<()>,
+}
+
+struct HostList {
+    email_type: Option<String>,
+    hosts: Vec<NamecheapHost>,
+}
+
+impl NamecheapProvider {
+    pub fn new(
+        config: NamecheapConfig,
+        credentials: Arc<dyn CredentialManager>,
+        http: &HttpOptions,
+    ) -> Result<Self, NamecheapProviderError> {
+        let api_key = credentials
+            .get("namecheap_api_key")
+            .map_err(|e| NamecheapProviderError::Credential(e.to_string()))?;
+        let client = http.builder().build()?;
+        Ok(Self {
+            config,
+            client,
+            api_key,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    /// Second-level domain and TLD, e.g. `example` and `co.uk`.
+    fn sld_tld(&self) -> Result<(&str, &str), NamecheapProviderError> {
+        self.config.domain.split_once('.').ok_or_else(|| {
+            NamecheapProviderError::Provider(format!("invalid domain: {}", self.config.domain))
+        })
+    }
+
+    fn base_params(&self, command: &str) -> Result<Vec<(String, String)>, NamecheapProviderError> {
+        let (sld, tld) = self.sld_tld()?;
+        Ok(vec![
+            ("ApiUser".into(), self.config.api_user.clone()),
+            ("ApiKey".into(), self.api_key.expose().to_string()),
+            ("UserName".into(), self.config.username.clone()),
+            ("ClientIp".into(), self.config.client_ip.clone()),
+            ("Command".into(), command.into()),
+            ("SLD".into(), sld.into()),
+            ("TLD".into(), tld.into()),
+        ])
+    }
+
+    async fn handle_response(
+        &self,
+        response: reqwest::Response,
+    ) -> Result<ApiResponse, NamecheapProviderError> {
+        let body = response.error_for_status()?.text().await?;
+        let parsed: ApiResponse = quick_xml::de::from_str(&body)?;
+        if parsed.status != "OK" {
+            let message = parsed
+                .errors
+                .unwrap_or_default()
+                .errors
+                .into_iter()
+                .map(|e| format!("{} ({})", e.message.trim(), e.number))
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(NamecheapProviderError::Provider(message));
+        }
+        Ok(parsed)
+    }
+
+    async fn get_hosts(&self) -> Result<HostList, NamecheapProviderError> {
+        let params = self.base_params("namecheap.domains.dns.getHosts")?;
+        let response = with_request_id(self.client.get(&self.config.api_url))
+            .query(&params)
+            .send()
+            .await?;
+        let result = self
+            .handle_response(response)
+            .await?
+            .command_response
+            .and_then(|c| c.hosts_result)
+            .ok_or_else(|| NamecheapProviderError::Provider("missing host list".to_string()))?;
+        Ok(HostList {
+            email_type: result.email_type,
+            hosts: result.hosts,
+        })
+    }
+
+    async fn set_hosts(&self, list: &HostList) -> Result<(), NamecheapProviderError> {
+        let mut params = self.base_params("namecheap.domains.dns.setHosts")?;
+        if let Some(email_type) = &list.email_type {
+            params.push(("EmailType".into(), email_type.clone()));
+        }
+        for (i, host) in list.hosts.iter().enumerate() {
+            let n = i + 1;
+            params.push((format!("HostName{n}"), host.name.clone()));
+            params.push((format!("RecordType{n}"), host.record_type.clone()));
+            params.push((format!("Address{n}"), host.address.clone()));
+            if let Some(mx_pref) = &host.mx_pref {
+                params.push((format!("MXPref{n}"), mx_pref.clone()));
+            }
+            if let Some(ttl) = host.ttl {
+                params.push((format!("TTL{n}"), ttl.to_string()));
+            }
+        }
+        let response = with_request_id(self.client.post(&self.config.api_url))
+            .form(&params)
+            .send()
+            .await?;
+        self.handle_response(response).await.map(|_| ())
+    }
+
+    /// Fetch the full host list, apply `change`, and write it back. Hosts the
+    /// change does not touch, including record types this tool does not
+    /// manage, are sent back exactly as they were read.
+    async fn modify_hosts<F>(&self, change: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Vec<NamecheapHost>) -> Result<bool, Error>,
+    {
+        let _guard = self.write_lock.lock().await;
+        let mut list = self.get_hosts().await.map_err(map_error)?;
+        if change(&mut list.hosts)? {
+            self.set_hosts(&list).await.map_err(map_error)?;
+        }
+        Ok(())
+    }
+
+    fn to_host(&self, record: &DNSRecord) -> Result<NamecheapHost, Error> {
+        to_namecheap_host(record, &self.config.domain).ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "{} is outside of {}",
+                record.name, self.config.domain
+            ))
+        })
+    }
+
+    /// The ownership marker host for `record`, if markers are in use.
+    fn marker_host(&self, record: &DNSRecord) -> Option<NamecheapHost> {
+        if !self.config.ownership.marks(record) {
+            return None;
+        }
+        let (name, value) = ownership::marker(record);
+        Some(NamecheapHost {
+            name: relative_name(&name, &self.config.domain)?,
+            record_type: "TXT".to_string(),
+            address: value,
+            mx_pref: None,
+            ttl: None,
+        })
+    }
+}
+
+fn same_host(a: &NamecheapHost, b: &NamecheapHost) -> bool {
+    a.name == b.name
+        && a.record_type == b.record_type
+        && a.address.trim_end_matches('.') == b.address.trim_end_matches('.')
+}
+
+#[async_trait]
+impl DNSProvider for NamecheapProvider {
+    fn name(&self) -> &str {
+        "namecheap"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            update_in_place: true,
+            txt: true,
+            ..Default::default()
+        }
+    }
+
+    async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+        let list = self.get_hosts().await.map_err(map_error)?;
+        Ok(list
+            .hosts
+            .iter()
+            .filter_map(|h| to_dns_record(h, &self.config.domain))
+            .collect())
+    }
+
+    async fn owned_records(&self) -> Result<Option<Owned>, Error> {
+        if self.config.ownership != Ownership::Txt {
+            return Ok(None);
+        }
+        let list = self.get_hosts().await.map_err(map_error)?;
+        Ok(Some(
+            list.hosts
+                .iter()
+                .filter(|h| h.record_type == "TXT")
+                .filter_map(|h| {
+                    ownership::parse_marker(&fqdn(&h.name, &self.config.domain), &h.address)
+                })
+                .collect(),
+        ))
+    }
+
+    async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let host = self.to_host(&record)?;
+        let marker = self.marker_host(&record);
+        self.modify_hosts(|hosts| {
+            let mut changed = false;
+            if !hosts.iter().any(|h| same_host(h, &host)) {
+                hosts.push(host);
+                changed = true;
+            }
+            if let Some(marker) = marker
+                && !hosts.iter().any(|h| same_host(h, &marker))
+            {
+                hosts.push(marker);
+                changed = true;
+            }
+            Ok(changed)
+        })
+        .await
+    }
+
+    async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let host = self.to_host(&record)?;
+        self.modify_hosts(|hosts| {
+            let existing = hosts
+                .iter_mut()
+                .find(|h| h.name == host.name && h.record_type == host.record_type)
+                .ok_or_else(|| Error::NotFound("Record not found".to_string()))?;
+            existing.address = host.address;
+            existing.ttl = host.ttl;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let host = self.to_host(&record)?;
+        let marker = self.marker_host(&record);
+        self.modify_hosts(|hosts| {
+            let before = hosts.len();
+            hosts.retain(|h| !same_host(h, &host));
+            if hosts.len() == before {
+                return Err(Error::NotFound("Record not found".to_string()));
+            }
+            // Release the name once no value is left under it
+            if let Some(marker) = marker
+                && !hosts
+                    .iter()
+                    .any(|h| h.name == host.name && h.record_type == host.record_type)
+            {
+                hosts.retain(|h| !same_host(h, &marker));
+            }
+            Ok(true)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+    use httpmock::prelude::*;
+
+    struct FakeCredentialManager;
+
+    impl CredentialManager for FakeCredentialManager {
+        fn get(&self, key: &str) -> Result<Secret, Error> {
+            match key {
+                "namecheap_api_key" => Ok("key".into()),
+                _ => Err(Error::CredentialError("missing".into())),
+            }
+        }
+    }
+
+    const HOSTS: &str = r#"<ApiResponse Status="OK">
+  <CommandResponse Type="namecheap.domains.dns.getHosts">
+    <DomainDNSGetHostsResult Domain="example.com" EmailType="MX">
+      <host Name="www" Type="A" Address="1.2.3.4" MXPref="10" TTL="1800" />
+      <host Name="@" Type="MX" Address="mail.example.com." MXPref="10" TTL="1800" />
+    </DomainDNSGetHostsResult>
+  </CommandResponse>
+</ApiResponse>"#;
+
+    const SET_OK: &str = r#"<ApiResponse Status="OK">
+  <CommandResponse Type="namecheap.domains.dns.setHosts">
+    <DomainDNSSetHostsResult Domain="example.com" IsSuccess="true" />
+  </CommandResponse>
+</ApiResponse>"#;
+
+    fn provider(server: &MockServer) -> NamecheapProvider {
+        provider_with(server, Ownership::Zone)
+    }
+
+    fn provider_with(server: &MockServer, ownership: Ownership) -> NamecheapProvider {
+        let config = NamecheapConfig {
+            api_url: server.url("/xml.response"),
+            api_user: "user".into(),
+            username: "user".into(),
+            client_ip: "203.0.113.10".into(),
+            domain: "example.com".into(),
+            ownership,
+        };
+        NamecheapProvider::new(
+            config,
+            Arc::new(FakeCredentialManager),
+            &HttpOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_list_records_skips_unmanaged_types() {
+        let server = MockServer::start_async().await;
+        let get_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/xml.response")
+                    .query_param("Command", "namecheap.domains.dns.getHosts")
+                    .query_param("SLD", "example")
+                    .query_param("TLD", "com");
+                then.status(200).body(HOSTS);
+            })
+            .await;
+
+        let records = provider(&server).list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "www.example.com");
+        get_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_record_preserves_unmanaged_hosts() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/xml.response");
+                then.status(200).body(HOSTS);
+            })
+            .await;
+        let set_mock = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/xml.response")
+                    .x_www_form_urlencoded_tuple("Command", "namecheap.domains.dns.setHosts")
+                    .x_www_form_urlencoded_tuple("EmailType", "MX")
+                    .x_www_form_urlencoded_tuple("HostName1", "www")
+                    .x_www_form_urlencoded_tuple("RecordType2", "MX")
+                    .x_www_form_urlencoded_tuple("Address2", "mail.example.com.")
+                    .x_www_form_urlencoded_tuple("HostName3", "new")
+                    .x_www_form_urlencoded_tuple("Address3", "5.6.7.8");
+                then.status(200).body(SET_OK);
+            })
+            .await;
+
+        let record = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "new.example.com".into(),
+            value: "5.6.7.8".into(),
+            ttl: Some(300),
+            comment: None,
+        };
+        provider(&server).add_record(record).await.unwrap();
+        set_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_api_error_is_reported() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/xml.response");
+                then.status(200).body(
+                    r#"<ApiResponse Status="ERROR"><Errors><Error Number="2019166">Domain not found</Error></Errors></ApiResponse>"#,
+                );
+            })
+            .await;
+
+        let err = provider(&server).list_records().await.unwrap_err();
+        assert!(
+            matches!(err.kind(), Error::ProviderError(msg) if msg.contains("Domain not found"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_records_outside_domain() {
+        let server = MockServer::start_async().await;
+        let record = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "www.example.org".into(),
+            value: "5.6.7.8".into(),
+            ttl: None,
+            comment: None,
+        };
+        let err = provider(&server).add_record(record).await.unwrap_err();
+        assert!(matches!(err.kind(), Error::InvalidInput(_)));
+    }
+
+    #[tokio::test]
+    async fn test_add_record_claims_name() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/xml.response");
+                then.status(200).body(HOSTS);
+            })
+            .await;
+        let set_mock = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/xml.response")
+                    .x_www_form_urlencoded_tuple("HostName3", "new")
+                    .x_www_form_urlencoded_tuple("HostName4", "_dns-update.new")
+                    .x_www_form_urlencoded_tuple("RecordType4", "TXT")
+                    .x_www_form_urlencoded_tuple("Address4", "heritage=dns-update,type=A");
+                then.status(200).body(SET_OK);
+            })
+            .await;
+
+        let provider = provider_with(&server, Ownership::Txt);
+        assert_eq!(provider.owned_records().await.unwrap(), Some(Owned::new()));
+        let record = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "new.example.com".into(),
+            value: "5.6.7.8".into(),
+            ttl: None,
+            comment: None,
+        };
+        provider.add_record(record).await.unwrap();
+        set_mock.assert_async().await;
+    }
+}
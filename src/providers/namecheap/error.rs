@@ -0,0 +1,49 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum NamecheapProviderError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("XML parse error: {0}")]
+    Xml(#[from] quick_xml::DeError),
+
+    #[error("Credential error: {0}")]
+    Credential(String),
+
+    #[error("Provider error: {0}")]
+    Provider(String),
+}
+
+use crate::error::Error;
+
+pub fn map_error(e: NamecheapProviderError) -> Error {
+    use NamecheapProviderError::*;
+    match e {
+        Http(err) => Error::Http(err),
+        Xml(ref err) => Error::with_source(Error::ProviderError(err.to_string()), e),
+        Credential(ref msg) => Error::with_source(Error::CredentialError(msg.clone()), e),
+        Provider(ref msg) => Error::with_source(Error::ProviderError(msg.clone()), e),
+    }
+}
+
+impl From<NamecheapProviderError> for Error {
+    fn from(e: NamecheapProviderError) -> Self {
+        map_error(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_error_variants() {
+        use NamecheapProviderError::*;
+
+        let err = map_error(Credential("fail".to_string()));
+        assert!(matches!(err.kind(), Error::CredentialError(_)));
+        let err = map_error(Provider("fail".to_string()));
+        assert!(matches!(err.kind(), Error::ProviderError(_)));
+    }
+}
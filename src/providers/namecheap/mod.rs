@@ -0,0 +1,7 @@
+//! Namecheap provider implementation
+
+pub mod client;
+pub mod error;
+pub mod types;
+
+pub use client::{NamecheapConfig, NamecheapProvider};
@@ -0,0 +1,159 @@
+use serde::Deserialize;
+
+use crate::core::record::{DNSRecord, DNSRecordType};
+
+#[derive(Deserialize, Debug)]
+pub struct ApiResponse {
+    #[serde(rename = "@Status")]
+    pub status: String,
+    #[serde(rename = "Errors", default)]
+    pub errors: Option<ApiErrors>,
+    #[serde(rename = "CommandResponse", default)]
+    pub command_response: Option<CommandResponse>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ApiErrors {
+    #[serde(rename = "Error", default)]
+    pub errors: Vec<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiError {
+    #[serde(rename = "@Number", default)]
+    pub number: String,
+    #[serde(rename = "$text", default)]
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CommandResponse {
+    #[serde(rename = "DomainDNSGetHostsResult", default)]
+    pub hosts_result: Option<HostsResult>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HostsResult {
+    #[serde(rename = "@EmailType", default)]
+    pub email_type: Option<String>,
+    #[serde(rename = "host", default)]
+    pub hosts: Vec<NamecheapHost>,
+}
+
+/// A host entry as returned by getHosts. Every field is kept so that hosts
+/// this tool does not manage can be written back unchanged.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct NamecheapHost {
+    #[serde(rename = "@Name")]
+    pub name: String,
+    #[serde(rename = "@Type")]
+    pub record_type: String,
+    #[serde(rename = "@Address")]
+    pub address: String,
+    #[serde(rename = "@MXPref", default)]
+    pub mx_pref: Option<String>,
+    #[serde(rename = "@TTL", default)]
+    pub ttl: Option<u32>,
+}
+
+/// Fully qualified name for a host relative to `domain` (`@` is the apex).
+pub fn fqdn(host: &str, domain: &str) -> String {
+    if host == "@" || host.is_empty() {
+        domain.to_string()
+    } else {
+        format!("{host}.{domain}")
+    }
+}
+
+/// Host name relative to `domain`, or `None` if `name` is outside it.
+pub fn relative_name(name: &str, domain: &str) -> Option<String> {
+    if name == domain {
+        Some("@".to_string())
+    } else {
+        name.strip_suffix(&format!(".{domain}")).map(str::to_string)
+    }
+}
+
+pub fn to_dns_record(host: &NamecheapHost, domain: &str) -> Option<DNSRecord> {
+    let record_type = match host.record_type.as_str() {
+        "A" => DNSRecordType::A,
+        "AAAA" => DNSRecordType::AAAA,
+        "CNAME" => DNSRecordType::CNAME,
+        "TXT" => DNSRecordType::TXT,
+        _ => return None,
+    };
+    let value = match record_type {
+        DNSRecordType::TXT => host.address.clone(),
+        _ => host.address.trim_end_matches('.').to_string(),
+    };
+    Some(DNSRecord {
+        record_type,
+        name: fqdn(&host.name, domain),
+        value,
+        ttl: host.ttl,
+        comment: None,
+    })
+}
+
+pub fn to_namecheap_host(record: &DNSRecord, domain: &str) -> Option<NamecheapHost> {
+    Some(NamecheapHost {
+        name: relative_name(&record.name, domain)?,
+        record_type: record.record_type.as_str().to_string(),
+        address: record.value.clone(),
+        mx_pref: None,
+        ttl: record.ttl,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_get_hosts_response() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<ApiResponse Status="OK" xmlns="http://api.namecheap.com/xml.response">
+  <Errors />
+  <CommandResponse Type="namecheap.domains.dns.getHosts">
+    <DomainDNSGetHostsResult Domain="example.com" EmailType="MX" IsUsingOurDNS="true">
+      <host HostId="1" Name="@" Type="A" Address="1.2.3.4" MXPref="10" TTL="1800" />
+      <host HostId="2" Name="@" Type="MX" Address="mail.example.com." MXPref="10" TTL="1800" />
+    </DomainDNSGetHostsResult>
+  </CommandResponse>
+</ApiResponse>"#;
+        let response: ApiResponse = quick_xml::de::from_str(xml).unwrap();
+        assert_eq!(response.status, "OK");
+        let result = response.command_response.unwrap().hosts_result.unwrap();
+        assert_eq!(result.email_type.as_deref(), Some("MX"));
+        assert_eq!(result.hosts.len(), 2);
+
+        let record = to_dns_record(&result.hosts[0], "example.com").unwrap();
+        assert_eq!(record.name, "example.com");
+        assert_eq!(record.value, "1.2.3.4");
+        assert_eq!(record.ttl, Some(1800));
+        assert!(to_dns_record(&result.hosts[1], "example.com").is_none());
+    }
+
+    #[test]
+    fn test_parse_error_response() {
+        let xml = r#"<ApiResponse Status="ERROR">
+  <Errors><Error Number="1011102">API Key is invalid or API access has not been enabled</Error></Errors>
+</ApiResponse>"#;
+        let response: ApiResponse = quick_xml::de::from_str(xml).unwrap();
+        assert_eq!(response.status, "ERROR");
+        let errors = response.errors.unwrap().errors;
+        assert_eq!(errors[0].number, "1011102");
+        assert!(errors[0].message.starts_with("API Key is invalid"));
+    }
+
+    #[test]
+    fn test_relative_names() {
+        assert_eq!(relative_name("example.com", "example.com").unwrap(), "@");
+        assert_eq!(
+            relative_name("www.example.com", "example.com").unwrap(),
+            "www"
+        );
+        assert!(relative_name("www.example.org", "example.com").is_none());
+        assert_eq!(fqdn("www", "example.com"), "www.example.com");
+    }
+}
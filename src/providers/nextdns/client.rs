@@ -1,11 +1,15 @@
-use reqwest::{Client, StatusCode};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{debug, info};
 
 use crate::auth::credentials::CredentialManager;
-use crate::core::provider::DNSProvider;
+use crate::core::http::{HttpOptions, with_request_id};
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
 use crate::core::record::DNSRecord;
+use crate::core::settings::{self, SettingChange};
 use crate::error::Error;
 use crate::providers::nextdns::error::{NextDNSProviderError, map_error};
 use crate::providers::nextdns::types::*;
@@ -14,66 +18,214 @@ use async_trait::async_trait;
 pub struct NextDNSConfig {
     pub profile_id: String,
     pub api_url: String,
+    pub requests_per_minute: u32,
+    pub settings: NextDNSSettings,
 }
 
+const MAX_ATTEMPTS: u32 = 3;
+
 pub struct NextDNSProvider {
     config: NextDNSConfig,
     client: Client,
     credentials: Arc<dyn CredentialManager>,
     rate_limiter: RateLimiter,
+    /// Backoff before a retry, multiplied by the attempt number
+    retry_delay: Duration,
+    /// Time limit of each request, on top of the client's own
+    timeout: Option<Duration>,
+    /// Set once logged in, which sets the session cookie of `client`;
+    /// cleared when the API refuses the session
+    logged_in: Mutex<bool>,
+}
+
+/// Builds a [`NextDNSProvider`] from a configuration, for callers that
+/// bring their own HTTP client or tune how it talks to the API.
+pub struct NextDNSProviderBuilder {
+    config: NextDNSConfig,
+    credentials: Arc<dyn CredentialManager>,
+    client: Option<Client>,
+    http: HttpOptions,
+    timeout: Option<Duration>,
+    retry_delay: Duration,
+    eager: bool,
+}
+
+// The binary only sets the HTTP options; the rest are for embedding and tests
+#[allow(dead_code)]
+impl NextDNSProviderBuilder {
+    /// Send requests through `client` instead of one built from the HTTP
+    /// options. The API keeps the session in a cookie, so the client needs
+    /// a cookie store.
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Build the client from `http`, unless one is given.
+    pub fn http(mut self, http: &HttpOptions) -> Self {
+        self.http = http.clone();
+        self
+    }
+
+    /// Limit the time of each request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn requests_per_minute(mut self, requests_per_minute: u32) -> Self {
+        self.config.requests_per_minute = requests_per_minute;
+        self
+    }
+
+    /// Wait before retrying a failed request, longer with each attempt.
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Log in with the first request rather than when building, so the
+    /// provider can be built without reaching the API.
+    pub fn lazy_login(mut self) -> Self {
+        self.eager = false;
+        self
+    }
+
+    pub async fn build(self) -> Result<NextDNSProvider, NextDNSProviderError> {
+        let client = match self.client {
+            Some(client) => client,
+            None => self.http.builder().cookie_store(true).build()?,
+        };
+        let provider = NextDNSProvider {
+            rate_limiter: RateLimiter::new(self.config.requests_per_minute),
+            config: self.config,
+            client,
+            credentials: self.credentials,
+            retry_delay: self.retry_delay,
+            timeout: self.timeout,
+            logged_in: Mutex::new(false),
+        };
+        if self.eager {
+            provider.login().await?;
+        }
+        Ok(provider)
+    }
 }
 
+/// Waits of at least this long are logged at info level, so a throttled
+/// run does not look stuck; shorter ones at debug level.
+const ANNOUNCE_WAIT: Duration = Duration::from_secs(5);
+
+/// Schedules requests against a per-minute budget shared by every endpoint
+/// of the API: up to a minute's worth of requests go through at once, then
+/// one per `interval` as the budget refills. Built on tokio's clock, so
+/// tests can run it with paused time instead of real sleeps.
 #[derive(Clone)]
 struct RateLimiter {
-    last_request: Arc<Mutex<Instant>>,
-    min_delay: Duration,
+    /// When the budget is fully refilled, or `None` before the first
+    /// request; each request moves it forward by `interval`
+    refilled_at: Arc<Mutex<Option<Instant>>>,
+    interval: Duration,
+    /// How far ahead of `refilled_at` requests are let through
+    burst: Duration,
 }
 
 impl RateLimiter {
+    fn new(requests_per_minute: u32) -> Self {
+        let per_minute = Duration::from_secs(60);
+        let interval = per_minute / requests_per_minute.max(1);
+        Self {
+            refilled_at: Arc::new(Mutex::new(None)),
+            interval,
+            burst: per_minute - interval,
+        }
+    }
+
+    /// Reserve the next slot in the budget and wait for it. The lock is
+    /// released before sleeping, so concurrent callers queue up behind
+    /// each other instead of all waking at once.
     async fn wait(&self) {
-        let mut last = self.last_request.lock().await;
         let now = Instant::now();
-        let elapsed = now.duration_since(*last);
-
-        if elapsed < self.min_delay {
-            tokio::time::sleep(self.min_delay - elapsed).await;
+        let at = {
+            let mut refilled_at = self.refilled_at.lock().await;
+            let base = refilled_at.map_or(now, |at| at.max(now));
+            *refilled_at = Some(base + self.interval);
+            base.checked_sub(self.burst).unwrap_or(now).max(now)
+        };
+        let wait = at - now;
+        if wait >= ANNOUNCE_WAIT {
+            info!(wait_secs = wait.as_secs(), "NextDNS request budget used up");
+        } else if !wait.is_zero() {
+            debug!(wait_ms = wait.as_millis(), "spacing out NextDNS requests");
         }
+        tokio::time::sleep_until(at).await;
+    }
 
-        *last = Instant::now();
+    /// Hold back every request until `delay` has passed, after the API
+    /// asked to slow down.
+    async fn pause(&self, delay: Duration) {
+        let until = Instant::now() + delay + self.burst;
+        let mut refilled_at = self.refilled_at.lock().await;
+        *refilled_at = Some(refilled_at.map_or(until, |at| at.max(until)));
+    }
+
+    /// How long until `requests` more requests have been let through.
+    async fn eta(&self, requests: u32) -> Duration {
+        if requests == 0 {
+            return Duration::ZERO;
+        }
+        let now = Instant::now();
+        let base = self.refilled_at.lock().await.map_or(now, |at| at.max(now));
+        let last = base + self.interval * (requests - 1);
+        last.checked_sub(self.burst)
+            .map_or(Duration::ZERO, |at| at.saturating_duration_since(now))
     }
 }
 
 impl NextDNSProvider {
+    /// Build a provider with the client described by `http`, logging in
+    /// right away.
     pub async fn new(
         config: NextDNSConfig,
         credentials: Arc<dyn CredentialManager>,
+        http: &HttpOptions,
     ) -> Result<Self, NextDNSProviderError> {
-        let client = Client::builder()
-            .cookie_store(true)
-            .timeout(Duration::from_secs(30))
-            .build()?;
-
-        let rate_limiter = RateLimiter {
-            last_request: Arc::new(Mutex::new(Instant::now())),
-            min_delay: Duration::from_millis(500),
-        };
+        Self::builder(config, credentials).http(http).build().await
+    }
 
-        let provider = Self {
+    pub fn builder(
+        config: NextDNSConfig,
+        credentials: Arc<dyn CredentialManager>,
+    ) -> NextDNSProviderBuilder {
+        NextDNSProviderBuilder {
             config,
-            client,
             credentials,
-            rate_limiter,
-        };
+            client: None,
+            http: HttpOptions::default(),
+            timeout: None,
+            retry_delay: Duration::from_secs(1),
+            eager: true,
+        }
+    }
 
-        provider.authenticate().await?;
-        Ok(provider)
+    // Log in unless already logged in; a failed login is tried again with
+    // the next request
+    async fn login(&self) -> Result<(), NextDNSProviderError> {
+        let mut logged_in = self.logged_in.lock().await;
+        if !*logged_in {
+            self.authenticate().await?;
+            *logged_in = true;
+        }
+        Ok(())
     }
 
     async fn authenticate(&self) -> Result<(), NextDNSProviderError> {
         let email = self
             .credentials
             .get("nextdns_email")
-            .map_err(|e| NextDNSProviderError::Credential(e.to_string()))?;
+            .map_err(|e| NextDNSProviderError::Credential(e.to_string()))?
+            .expose()
+            .to_string();
         let password = self
             .credentials
             .get("nextdns_password")
@@ -81,55 +233,110 @@ impl NextDNSProvider {
 
         let login = LoginRequest { email, password };
 
-        let res = self
+        let mut request = self
             .client
             .post(format!("{}/auth/login", self.config.api_url))
-            .json(&login)
-            .send()
-            .await?;
+            .json(&login);
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let res = request.send().await?;
 
         res.error_for_status_ref()?;
         Ok(())
     }
 
-    async fn handle_request<T, F>(&self, fut: F) -> Result<T, NextDNSProviderError>
-    where
-        F: std::future::Future<Output = Result<reqwest::Response, reqwest::Error>>,
-        T: serde::de::DeserializeOwned,
-    {
-        let response = fut.await?;
-
-        match response.status() {
-            StatusCode::OK => Ok(response.json().await?),
-            StatusCode::TOO_MANY_REQUESTS => {
-                let retry_after = response
-                    .headers()
-                    .get("Retry-After")
-                    .and_then(|h| h.to_str().ok())
-                    .and_then(|s| s.parse::<u64>().ok())
-                    .unwrap_or(5);
-
-                tokio::time::sleep(Duration::from_secs(retry_after)).await;
-                Err(NextDNSProviderError::RateLimited)
+    /// Send a request and check the status, logging in again once if the
+    /// session was refused.
+    async fn send(&self, builder: RequestBuilder) -> Result<Response, NextDNSProviderError> {
+        self.login().await?;
+        let mut request = with_request_id(builder).build()?;
+        if let Some(timeout) = self.timeout {
+            *request.timeout_mut() = Some(timeout);
+        }
+        match self.execute(&request).await {
+            // The session expired, as it does in a long-running daemon; a
+            // refused request changed nothing, so it is safe to send again
+            Err(e) if e.is_auth_failure() => {
+                debug!("NextDNS refused the session, logging in again");
+                *self.logged_in.lock().await = false;
+                self.login().await?;
+                self.execute(&request).await
             }
-            _ => {
-                let error: NextDNSError = response.json().await.unwrap_or(NextDNSError {
-                    code: "unknown".to_string(),
-                    message: "Unknown error".to_string(),
-                });
-                Err(error.into())
+            result => result,
+        }
+    }
+
+    /// Send a request, retrying failures that may go away. Creations are
+    /// only retried when the API asked to slow down, as other failures may
+    /// have created the rewrite anyway.
+    async fn execute(&self, request: &Request) -> Result<Response, NextDNSProviderError> {
+        let idempotent = request.method() != Method::POST;
+        let mut attempt = 1;
+        loop {
+            self.rate_limiter.wait().await;
+            let attempt_request = request.try_clone().ok_or_else(|| {
+                NextDNSProviderError::Provider("request cannot be retried".to_string())
+            })?;
+            let result = match self.client.execute(attempt_request).await {
+                Ok(response) => self.check_status(response).await,
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Err(e)
+                    if attempt < MAX_ATTEMPTS
+                        && e.is_retryable()
+                        && (idempotent || matches!(e, NextDNSProviderError::RateLimited)) =>
+                {
+                    // The rate limiter already holds back a request the API
+                    // asked to slow down for
+                    if !matches!(e, NextDNSProviderError::RateLimited) {
+                        tokio::time::sleep(self.retry_delay * attempt).await;
+                    }
+                    attempt += 1;
+                }
+                result => return result,
             }
         }
     }
 
+    async fn check_status(&self, response: Response) -> Result<Response, NextDNSProviderError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get("Retry-After")
+                .and_then(|h| h.to_str().ok())
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(5);
+
+            // Requests on other endpoints share the quota, so they wait too
+            self.rate_limiter
+                .pause(Duration::from_secs(retry_after))
+                .await;
+            return Err(NextDNSProviderError::RateLimited);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(NextDNSProviderError::from_response(status, &body))
+    }
+
+    async fn handle_request<T>(&self, builder: RequestBuilder) -> Result<T, NextDNSProviderError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Ok(self.send(builder).await?.json().await?)
+    }
+
     // Example: List DNS rewrites
     pub async fn list_rewrites(&self) -> Result<Vec<NextDNSRecord>, NextDNSProviderError> {
-        self.rate_limiter.wait().await;
         let url = format!(
             "{}/profiles/{}/dns/rewrites",
             self.config.api_url, self.config.profile_id
         );
-        self.handle_request(self.client.get(url).send()).await
+        self.handle_request(self.client.get(url)).await
     }
 
     // Example: Create DNS rewrite
@@ -137,13 +344,11 @@ impl NextDNSProvider {
         &self,
         req: &CreateRecordRequest,
     ) -> Result<NextDNSRecord, NextDNSProviderError> {
-        self.rate_limiter.wait().await;
         let url = format!(
             "{}/profiles/{}/dns/rewrites",
             self.config.api_url, self.config.profile_id
         );
-        self.handle_request(self.client.post(url).json(req).send())
-            .await
+        self.handle_request(self.client.post(url).json(req)).await
     }
 
     // Example: Update DNS rewrite
@@ -152,33 +357,60 @@ impl NextDNSProvider {
         id: &str,
         req: &CreateRecordRequest,
     ) -> Result<NextDNSRecord, NextDNSProviderError> {
-        self.rate_limiter.wait().await;
         let url = format!(
             "{}/profiles/{}/dns/rewrites/{}",
             self.config.api_url, self.config.profile_id, id
         );
-        self.handle_request(self.client.put(url).json(req).send())
-            .await
+        self.handle_request(self.client.put(url).json(req)).await
     }
 
     // Example: Delete DNS rewrite
     pub async fn delete_rewrite(&self, id: &str) -> Result<(), NextDNSProviderError> {
-        self.rate_limiter.wait().await;
         let url = format!(
             "{}/profiles/{}/dns/rewrites/{}",
             self.config.api_url, self.config.profile_id, id
         );
-        let response = self.client.delete(url).send().await?;
-        match response.status() {
-            StatusCode::NO_CONTENT | StatusCode::OK => Ok(()),
-            _ => {
-                let error: NextDNSError = response.json().await.unwrap_or(NextDNSError {
-                    code: "unknown".to_string(),
-                    message: "Unknown error".to_string(),
-                });
-                Err(error.into())
-            }
-        }
+        self.send(self.client.delete(url)).await.map(|_| ())
+    }
+
+    /// The enabled items of the profile setting at `path`, e.g.
+    /// `privacy/blocklists`.
+    pub async fn list_setting(&self, path: &str) -> Result<Vec<String>, NextDNSProviderError> {
+        let url = format!(
+            "{}/profiles/{}/{path}",
+            self.config.api_url, self.config.profile_id
+        );
+        let items: SettingItems = self.handle_request(self.client.get(url)).await?;
+        Ok(items
+            .data
+            .into_iter()
+            .filter(|item| item.active)
+            .map(|item| item.id)
+            .collect())
+    }
+
+    pub async fn enable_setting(&self, path: &str, id: &str) -> Result<(), NextDNSProviderError> {
+        let url = format!(
+            "{}/profiles/{}/{path}",
+            self.config.api_url, self.config.profile_id
+        );
+        // Categories are only blocked while active
+        let body = if path == "parentalControl/categories" {
+            serde_json::json!({ "id": id, "active": true })
+        } else {
+            serde_json::json!({ "id": id })
+        };
+        self.send(self.client.post(url).json(&body))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn disable_setting(&self, path: &str, id: &str) -> Result<(), NextDNSProviderError> {
+        let url = format!(
+            "{}/profiles/{}/{path}/{id}",
+            self.config.api_url, self.config.profile_id
+        );
+        self.send(self.client.delete(url)).await.map(|_| ())
     }
 }
 
@@ -188,6 +420,21 @@ impl DNSProvider for NextDNSProvider {
         "nextdns"
     }
 
+    // Rewrites carry no TTL, and `update_record` looks records up by their
+    // value, so a changed value has to be added and removed instead
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            ttl: false,
+            ..Default::default()
+        }
+    }
+
+    // Each change lists the rewrites to find its ID, then writes
+    async fn estimate(&self, changes: usize) -> Option<Duration> {
+        let requests = u32::try_from(changes.saturating_mul(2)).unwrap_or(u32::MAX);
+        Some(self.rate_limiter.eta(requests).await)
+    }
+
     async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
         self.list_rewrites()
             .await
@@ -196,25 +443,28 @@ impl DNSProvider for NextDNSProvider {
     }
 
     async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        // NextDNS accepts duplicate rewrites, so an add that already went
+        // through, e.g. before an interrupted run, must not be repeated
+        let records = self.list_rewrites().await.map_err(map_error)?;
+        if records.iter().any(|r| to_dns_record(r).same_data(&record)) {
+            return Ok(());
+        }
         let req = to_nextdns_record(&record);
         self.create_rewrite(&req)
             .await
             .map(|_| ())
-            .map_err(map_error)
+            .map_err(|e| map_error(e.for_record(&record)))
     }
 
     async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
         // NextDNS needs record id, so we must fetch all and match
         let records = self.list_rewrites().await.map_err(map_error)?;
-        if let Some(existing) = records
-            .iter()
-            .find(|r| r.domain == record.name && r.value == record.value)
-        {
+        if let Some(existing) = records.iter().find(|r| to_dns_record(r).same_data(&record)) {
             let req = to_nextdns_record(&record);
             self.update_rewrite(&existing.id, &req)
                 .await
                 .map(|_| ())
-                .map_err(map_error)
+                .map_err(|e| map_error(e.for_record(&record)))
         } else {
             Err(Error::NotFound("Record not found".to_string()))
         }
@@ -222,21 +472,47 @@ impl DNSProvider for NextDNSProvider {
 
     async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
         let records = self.list_rewrites().await.map_err(map_error)?;
-        if let Some(existing) = records
-            .iter()
-            .find(|r| r.domain == record.name && r.value == record.value)
-        {
-            self.delete_rewrite(&existing.id).await.map_err(map_error)
+        if let Some(existing) = records.iter().find(|r| to_dns_record(r).same_data(&record)) {
+            self.delete_rewrite(&existing.id)
+                .await
+                .map_err(|e| map_error(e.for_record(&record)))
         } else {
             Err(Error::NotFound("Record not found".to_string()))
         }
     }
+
+    async fn plan_settings(&self) -> Result<Vec<SettingChange>, Error> {
+        let mut changes = Vec::new();
+        for (setting, path, desired) in self.config.settings.managed() {
+            let current = self.list_setting(path).await.map_err(map_error)?;
+            changes.extend(settings::diff(setting, &current, desired));
+        }
+        Ok(changes)
+    }
+
+    async fn apply_setting(&self, change: &SettingChange) -> Result<(), Error> {
+        let (_, path, _) = self
+            .config
+            .settings
+            .managed()
+            .into_iter()
+            .find(|(setting, _, _)| *setting == change.setting)
+            .ok_or_else(|| Error::InvalidInput(format!("unmanaged setting: {change}")))?;
+        let result = if change.enable {
+            self.enable_setting(path, &change.item).await
+        } else {
+            self.disable_setting(path, &change.item).await
+        };
+        result.map_err(map_error)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::auth::credentials::CredentialManager;
+    use crate::config::DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE;
+    use crate::secret::Secret;
     use httpmock::prelude::*;
     use mockall::predicate::*;
     use std::sync::Arc;
@@ -248,18 +524,52 @@ mod tests {
 
     use crate::error::Error;
     impl CredentialManager for FakeCredentialManager {
-        fn get(&self, key: &str) -> Result<String, Error> {
+        fn get(&self, key: &str) -> Result<Secret, Error> {
             if self.fail {
                 Err(Error::CredentialError("invalid credentials".into()))
             } else {
                 self.creds
                     .get(key)
-                    .cloned()
+                    .map(Secret::new)
                     .ok_or(Error::CredentialError("missing".into()))
             }
         }
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_budget() {
+        let limiter = RateLimiter::new(3);
+        let started = Instant::now();
+        assert_eq!(limiter.eta(4).await, Duration::from_secs(20));
+
+        // A minute's budget goes through at once, then it refills
+        for _ in 0..3 {
+            limiter.wait().await;
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        limiter.wait().await;
+        assert_eq!(started.elapsed(), Duration::from_secs(20));
+
+        // The budget refills while idle
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        let resumed = Instant::now();
+        assert_eq!(limiter.eta(3).await, Duration::ZERO);
+        limiter.wait().await;
+        assert_eq!(resumed.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_pause() {
+        let limiter = RateLimiter::new(60);
+        let started = Instant::now();
+        limiter.pause(Duration::from_secs(5)).await;
+        assert_eq!(limiter.eta(2).await, Duration::from_secs(6));
+        limiter.wait().await;
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+        limiter.wait().await;
+        assert_eq!(started.elapsed(), Duration::from_secs(6));
+    }
+
     #[tokio::test]
     async fn test_full_workflow_success() {
         let server = MockServer::start_async().await;
@@ -297,8 +607,10 @@ mod tests {
         let config = NextDNSConfig {
             profile_id: profile_id.into(),
             api_url: api_url.clone(),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            settings: NextDNSSettings::default(),
         };
-        let provider = NextDNSProvider::new(config, Arc::new(creds)).await;
+        let provider = NextDNSProvider::new(config, Arc::new(creds), &HttpOptions::default()).await;
         assert!(provider.is_ok());
         // Actually call list_rewrites to trigger both mocks
         let provider = provider.unwrap();
@@ -307,6 +619,128 @@ mod tests {
         list_mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn test_lazy_login_with_given_client() {
+        let server = MockServer::start_async().await;
+        let profile_id = "profileid";
+        let login_mock = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/auth/login");
+                then.status(200)
+                    .json_body_obj(&serde_json::json!({ "success": true }));
+            })
+            .await;
+        let list_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/profiles/{profile_id}/dns/rewrites"))
+                    .header("user-agent", "test-agent");
+                then.status(200)
+                    .json_body_obj::<Vec<serde_json::Value>>(&vec![]);
+            })
+            .await;
+
+        let creds = FakeCredentialManager {
+            creds: [
+                ("nextdns_email".into(), "user@example.com".into()),
+                ("nextdns_password".into(), "secret".into()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            fail: false,
+        };
+        let config = NextDNSConfig {
+            profile_id: profile_id.into(),
+            api_url: server.url(""),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            settings: NextDNSSettings::default(),
+        };
+        let client = Client::builder()
+            .user_agent("test-agent")
+            .cookie_store(true)
+            .build()
+            .unwrap();
+        let provider = NextDNSProvider::builder(config, Arc::new(creds))
+            .client(client)
+            .timeout(Duration::from_secs(5))
+            .lazy_login()
+            .build()
+            .await
+            .unwrap();
+        login_mock.assert_hits_async(0).await;
+
+        provider.list_rewrites().await.unwrap();
+        provider.list_rewrites().await.unwrap();
+        login_mock.assert_hits_async(1).await;
+        list_mock.assert_hits_async(2).await;
+    }
+
+    #[tokio::test]
+    async fn test_expired_session_logs_in_again() {
+        let server = MockServer::start_async().await;
+        let profile_id = "profileid";
+        let first_login = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/auth/login");
+                then.status(200).header("Set-Cookie", "sid=old; Path=/");
+            })
+            .await;
+        let expired = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/profiles/{profile_id}/dns/rewrites"))
+                    .cookie("sid", "old");
+                then.status(401).json_body(serde_json::json!({
+                    "errors": [{ "code": "unauthorized" }]
+                }));
+            })
+            .await;
+        let list_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/profiles/{profile_id}/dns/rewrites"))
+                    .cookie("sid", "new");
+                then.status(200)
+                    .json_body_obj::<Vec<serde_json::Value>>(&vec![]);
+            })
+            .await;
+
+        let creds = FakeCredentialManager {
+            creds: [
+                ("nextdns_email".into(), "user@example.com".into()),
+                ("nextdns_password".into(), "secret".into()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            fail: false,
+        };
+        let config = NextDNSConfig {
+            profile_id: profile_id.into(),
+            api_url: server.url(""),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            settings: NextDNSSettings::default(),
+        };
+        let provider = NextDNSProvider::new(config, Arc::new(creds), &HttpOptions::default())
+            .await
+            .unwrap();
+        first_login.assert_async().await;
+
+        // The session the API hands out from now on
+        first_login.delete_async().await;
+        let second_login = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/auth/login");
+                then.status(200).header("Set-Cookie", "sid=new; Path=/");
+            })
+            .await;
+        provider.list_rewrites().await.unwrap();
+        expired.assert_async().await;
+        second_login.assert_async().await;
+        list_mock.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_workflow_with_invalid_credentials() {
         let server = MockServer::start_async().await;
@@ -335,8 +769,10 @@ mod tests {
         let config = NextDNSConfig {
             profile_id: profile_id.into(),
             api_url: api_url.clone(),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            settings: NextDNSSettings::default(),
         };
-        let provider = NextDNSProvider::new(config, Arc::new(creds)).await;
+        let provider = NextDNSProvider::new(config, Arc::new(creds), &HttpOptions::default()).await;
         assert!(provider.is_err());
         login_mock.assert_async().await;
     }
@@ -378,12 +814,232 @@ mod tests {
         let config = NextDNSConfig {
             profile_id: profile_id.into(),
             api_url: api_url.clone(),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            settings: NextDNSSettings::default(),
         };
-        let provider = NextDNSProvider::new(config, Arc::new(creds)).await.unwrap();
+        let provider = NextDNSProvider::builder(config, Arc::new(creds))
+            .retry_delay(Duration::ZERO)
+            .build()
+            .await
+            .unwrap();
         let result = provider.list_rewrites().await;
         assert!(result.is_err());
         login_mock.assert_async().await;
-        list_mock.assert_async().await;
+        // Server errors are retried for reads
+        list_mock.assert_hits_async(MAX_ATTEMPTS as usize).await;
+    }
+
+    #[tokio::test]
+    async fn test_add_existing_rewrite_is_skipped() {
+        let server = MockServer::start_async().await;
+        let profile_id = "profileid";
+        server
+            .mock_async(|when, then| {
+                when.method(POST).path("/auth/login");
+                then.status(200)
+                    .json_body_obj(&serde_json::json!({ "success": true }));
+            })
+            .await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/profiles/{profile_id}/dns/rewrites"));
+                then.status(200).json_body_obj(&serde_json::json!([
+                    { "id": "1", "domain": "nas.lan", "type": "A", "value": "10.0.0.2" }
+                ]));
+            })
+            .await;
+        let create_mock = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path(format!("/profiles/{profile_id}/dns/rewrites"));
+                then.status(200);
+            })
+            .await;
+
+        let creds = FakeCredentialManager {
+            creds: [
+                ("nextdns_email".into(), "user@example.com".into()),
+                ("nextdns_password".into(), "secret".into()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            fail: false,
+        };
+        let config = NextDNSConfig {
+            profile_id: profile_id.into(),
+            api_url: server.url(""),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            settings: NextDNSSettings::default(),
+        };
+        let provider = NextDNSProvider::new(config, Arc::new(creds), &HttpOptions::default())
+            .await
+            .unwrap();
+        let record = DNSRecord {
+            record_type: crate::core::record::DNSRecordType::A,
+            name: "nas.lan".into(),
+            value: "10.0.0.2".into(),
+            ttl: None,
+            comment: None,
+        };
+        provider.add_record(record.clone()).await.unwrap();
+        // Names are compared like everywhere else, whatever their case and
+        // trailing dot
+        let spelled = DNSRecord {
+            name: "NAS.lan.".into(),
+            ..record
+        };
+        provider.add_record(spelled).await.unwrap();
+        create_mock.assert_hits_async(0).await;
+    }
+
+    #[tokio::test]
+    async fn test_settings_are_planned_and_applied() {
+        let server = MockServer::start_async().await;
+        let profile_id = "profileid";
+        server
+            .mock_async(|when, then| {
+                when.method(POST).path("/auth/login");
+                then.status(200)
+                    .json_body_obj(&serde_json::json!({ "success": true }));
+            })
+            .await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/profiles/{profile_id}/privacy/blocklists"));
+                then.status(200).json_body_obj(&serde_json::json!({
+                    "data": [{ "id": "nextdns-recommended" }, { "id": "oisd" }]
+                }));
+            })
+            .await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/profiles/{profile_id}/parentalControl/categories"));
+                then.status(200).json_body_obj(&serde_json::json!({
+                    "data": [{ "id": "gambling", "active": false }]
+                }));
+            })
+            .await;
+        let enable_mock = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path(format!("/profiles/{profile_id}/parentalControl/categories"))
+                    .json_body_obj(&serde_json::json!({ "id": "gambling", "active": true }));
+                then.status(204);
+            })
+            .await;
+        let disable_mock = server
+            .mock_async(|when, then| {
+                when.method(DELETE).path(format!(
+                    "/profiles/{profile_id}/privacy/blocklists/nextdns-recommended"
+                ));
+                then.status(204);
+            })
+            .await;
+
+        let creds = FakeCredentialManager {
+            creds: [
+                ("nextdns_email".into(), "user@example.com".into()),
+                ("nextdns_password".into(), "secret".into()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            fail: false,
+        };
+        let config = NextDNSConfig {
+            profile_id: profile_id.into(),
+            api_url: server.url(""),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            // Blocked TLDs are not managed, so they are not even listed
+            settings: NextDNSSettings {
+                blocklists: Some(vec!["oisd".to_string()]),
+                blocked_tlds: None,
+                parental_control_categories: Some(vec!["gambling".to_string()]),
+            },
+        };
+        let provider = NextDNSProvider::new(config, Arc::new(creds), &HttpOptions::default())
+            .await
+            .unwrap();
+        let changes = provider.plan_settings().await.unwrap();
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "disable blocklists nextdns-recommended",
+                "enable parental_control_categories gambling"
+            ]
+        );
+        for change in &changes {
+            provider.apply_setting(change).await.unwrap();
+        }
+        enable_mock.assert_async().await;
+        disable_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rejected_create_is_not_retried() {
+        let server = MockServer::start_async().await;
+        let profile_id = "profileid";
+        server
+            .mock_async(|when, then| {
+                when.method(POST).path("/auth/login");
+                then.status(200)
+                    .json_body_obj(&serde_json::json!({ "success": true }));
+            })
+            .await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/profiles/{profile_id}/dns/rewrites"));
+                then.status(200)
+                    .json_body_obj::<Vec<serde_json::Value>>(&vec![]);
+            })
+            .await;
+        let create_mock = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path(format!("/profiles/{profile_id}/dns/rewrites"));
+                then.status(400).json_body_obj(&serde_json::json!({
+                    "errors": [{ "code": "invalid", "detail": "Invalid content", "source": { "pointer": "/content" } }]
+                }));
+            })
+            .await;
+
+        let creds = FakeCredentialManager {
+            creds: [
+                ("nextdns_email".into(), "user@example.com".into()),
+                ("nextdns_password".into(), "secret".into()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            fail: false,
+        };
+        let config = NextDNSConfig {
+            profile_id: profile_id.into(),
+            api_url: server.url(""),
+            requests_per_minute: DEFAULT_NEXTDNS_REQUESTS_PER_MINUTE,
+            settings: NextDNSSettings::default(),
+        };
+        let provider = NextDNSProvider::new(config, Arc::new(creds), &HttpOptions::default())
+            .await
+            .unwrap();
+        let record = DNSRecord {
+            record_type: crate::core::record::DNSRecordType::A,
+            name: "nas.lan".into(),
+            value: "10.0.0.300".into(),
+            ttl: None,
+            comment: None,
+        };
+        let err = provider.add_record(record).await.unwrap_err();
+        assert!(
+            matches!(err.kind(), Error::InvalidInput(msg) if msg.starts_with("nas.lan A 10.0.0.300: invalid")),
+            "{err:?}"
+        );
+        create_mock.assert_async().await;
     }
 
     // Additional integration tests can be added here with HTTP mocking as needed.
@@ -1,4 +1,6 @@
-use crate::providers::nextdns::types::NextDNSError;
+use crate::core::record::DNSRecord;
+use crate::providers::nextdns::types::{NextDNSApiError, NextDNSError, NextDNSErrors};
+use reqwest::StatusCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -21,6 +23,20 @@ pub enum NextDNSProviderError {
     #[error("Rate limited")]
     RateLimited,
 
+    /// Structured errors returned by the API
+    #[error("{}", describe_api_errors(*.status, .errors))]
+    Api {
+        status: u16,
+        errors: Vec<NextDNSApiError>,
+    },
+
+    /// An error caused by a specific record
+    #[error("{} {:?} {}: {source}", .record.name, .record.record_type, .record.value)]
+    Record {
+        record: Box<DNSRecord>,
+        source: Box<NextDNSProviderError>,
+    },
+
     #[allow(dead_code)]
     #[error("Unknown error: {0}")]
     Unknown(String),
@@ -38,25 +54,128 @@ impl From<NextDNSError> for NextDNSProviderError {
     }
 }
 
+impl NextDNSProviderError {
+    /// Build the error for a failed response from its status and body.
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        if let Ok(parsed) = serde_json::from_str::<NextDNSErrors>(body)
+            && !parsed.errors.is_empty()
+        {
+            return NextDNSProviderError::Api {
+                status: status.as_u16(),
+                errors: parsed.errors,
+            };
+        }
+        if let Ok(legacy) = serde_json::from_str::<NextDNSError>(body) {
+            return legacy.into();
+        }
+        NextDNSProviderError::Api {
+            status: status.as_u16(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Whether the API refused the session, e.g. once it expired.
+    pub fn is_auth_failure(&self) -> bool {
+        use NextDNSProviderError::*;
+        match self {
+            Credential(_) => true,
+            Api { status, .. } => matches!(status, 401 | 403),
+            _ => false,
+        }
+    }
+
+    /// Whether sending the same request again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        use NextDNSProviderError::*;
+        match self {
+            Http(err) => err.is_timeout() || err.is_connect(),
+            RateLimited => true,
+            Api { status, .. } => matches!(status, 500 | 502 | 503 | 504),
+            Record { source, .. } => source.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// The crate error variant for structured and record errors.
+    fn category(&self) -> fn(String) -> Error {
+        use NextDNSProviderError::*;
+        match self {
+            Credential(_) => Error::CredentialError,
+            NotFound(_) => Error::NotFound,
+            InvalidInput(_) => Error::InvalidInput,
+            Unknown(_) => Error::Other,
+            Api { status, .. } => match status {
+                401 | 403 => Error::CredentialError,
+                404 => Error::NotFound,
+                400 | 409 | 422 => Error::InvalidInput,
+                _ => Error::ProviderError,
+            },
+            Record { source, .. } => source.category(),
+            _ => Error::ProviderError,
+        }
+    }
+
+    /// Attach the record that caused the error.
+    pub fn for_record(self, record: &DNSRecord) -> Self {
+        NextDNSProviderError::Record {
+            record: Box::new(record.clone()),
+            source: Box::new(self),
+        }
+    }
+}
+
+fn describe_api_errors(status: u16, errors: &[NextDNSApiError]) -> String {
+    if errors.is_empty() {
+        return format!("HTTP {status}");
+    }
+    errors
+        .iter()
+        .map(|e| {
+            let mut message = e.code.clone();
+            if let Some(detail) = &e.detail {
+                message.push_str(&format!(": {detail}"));
+            }
+            if let Some(at) = e
+                .source
+                .as_ref()
+                .and_then(|s| s.pointer.as_ref().or(s.parameter.as_ref()))
+            {
+                message.push_str(&format!(" (at {at})"));
+            }
+            message
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 use crate::error::Error;
 
 pub fn map_error(e: NextDNSProviderError) -> Error {
     use NextDNSProviderError::*;
     match e {
-        Http(err) => Error::ProviderError(err.to_string()),
+        Http(err) => Error::Http(err),
         Credential(msg) => Error::CredentialError(msg),
         NotFound(msg) => Error::NotFound(msg),
         InvalidInput(msg) => Error::InvalidInput(msg),
         Provider(msg) => Error::ProviderError(msg),
         RateLimited => Error::ProviderError("Rate limited".to_string()),
+        Api { .. } | Record { .. } => Error::with_source(e.category()(e.to_string()), e),
         Unknown(msg) => Error::Other(msg),
     }
 }
 
+impl From<NextDNSProviderError> for Error {
+    fn from(e: NextDNSProviderError) -> Self {
+        map_error(e)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::record::DNSRecordType;
     use crate::error::Error;
+    use std::error::Error as _;
 
     // --- Error Mapping Tests ---
     #[test]
@@ -64,16 +183,77 @@ mod tests {
         use NextDNSProviderError::*;
 
         let err = map_error(Credential("fail".to_string()));
-        assert!(matches!(err, Error::CredentialError(_)));
+        assert!(matches!(err.kind(), Error::CredentialError(_)));
         let err = map_error(NotFound("not found".to_string()));
-        assert!(matches!(err, Error::NotFound(_)));
+        assert!(matches!(err.kind(), Error::NotFound(_)));
         let err = map_error(InvalidInput("bad".to_string()));
-        assert!(matches!(err, Error::InvalidInput(_)));
+        assert!(matches!(err.kind(), Error::InvalidInput(_)));
         let err = map_error(Provider("fail".to_string()));
-        assert!(matches!(err, Error::ProviderError(_)));
+        assert!(matches!(err.kind(), Error::ProviderError(_)));
         let err = map_error(RateLimited);
-        assert!(matches!(err, Error::ProviderError(_)));
+        assert!(matches!(err.kind(), Error::ProviderError(_)));
         let err = map_error(Unknown("fail".to_string()));
-        assert!(matches!(err, Error::Other(_)));
+        assert!(matches!(err.kind(), Error::Other(_)));
+    }
+
+    // --- Response Parsing Tests ---
+    #[test]
+    fn test_from_response_parses_errors_array() {
+        let body = r#"{"errors":[{"code":"invalid","detail":"Invalid IP address","source":{"pointer":"/content"}}]}"#;
+        let err = NextDNSProviderError::from_response(StatusCode::BAD_REQUEST, body);
+        assert_eq!(err.to_string(), "invalid: Invalid IP address (at /content)");
+        assert!(!err.is_retryable());
+        assert!(matches!(map_error(err).kind(), Error::InvalidInput(_)));
+    }
+
+    #[test]
+    fn test_from_response_fallbacks() {
+        let legacy = r#"{"code":"unauthorized","message":"bad token"}"#;
+        let err = NextDNSProviderError::from_response(StatusCode::UNAUTHORIZED, legacy);
+        assert!(matches!(err, NextDNSProviderError::Credential(_)));
+
+        let err = NextDNSProviderError::from_response(StatusCode::BAD_GATEWAY, "<html>");
+        assert_eq!(err.to_string(), "HTTP 502");
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_record_context() {
+        let record = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "nas.lan".to_string(),
+            value: "10.0.0.300".to_string(),
+            ttl: None,
+            comment: None,
+        };
+        let body = r#"{"errors":[{"code":"invalid"}]}"#;
+        let err =
+            NextDNSProviderError::from_response(StatusCode::BAD_REQUEST, body).for_record(&record);
+        let err = map_error(err);
+        match err.kind() {
+            Error::InvalidInput(msg) => assert_eq!(msg, "nas.lan A 10.0.0.300: invalid"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert_eq!(
+            err.to_string(),
+            "Invalid input: nas.lan A 10.0.0.300: invalid"
+        );
+
+        // The structured errors are kept as sources, down to the API's
+        let source = err.source().unwrap();
+        let Some(NextDNSProviderError::Record { record, .. }) = source.downcast_ref() else {
+            panic!("unexpected source: {source:?}");
+        };
+        assert_eq!(record.name, "nas.lan");
+        // Held boxed by the record error
+        let api = source
+            .source()
+            .unwrap()
+            .downcast_ref::<Box<NextDNSProviderError>>()
+            .map(|api| &**api);
+        assert!(
+            matches!(api, Some(NextDNSProviderError::Api { status: 400, .. })),
+            "{api:?}"
+        );
     }
 }
@@ -1,9 +1,12 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::secret::Secret;
+
 #[derive(Serialize)]
 pub struct LoginRequest {
     pub email: String,
-    pub password: String,
+    pub password: Secret,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -26,12 +29,86 @@ pub struct CreateRecordRequest {
     pub ttl: Option<u32>,
 }
 
+/// Profile settings kept in line with the configuration; each list is
+/// left as it is when omitted.
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NextDNSSettings {
+    /// Privacy blocklists to enable, e.g. `nextdns-recommended` or `oisd`
+    pub blocklists: Option<Vec<String>>,
+    /// Top-level domains to block, e.g. `zip`
+    pub blocked_tlds: Option<Vec<String>>,
+    /// Parental control categories to block, e.g. `gambling`
+    pub parental_control_categories: Option<Vec<String>>,
+}
+
+impl NextDNSSettings {
+    /// The managed settings: name, API path under the profile and the
+    /// desired items.
+    pub fn managed(&self) -> Vec<(&'static str, &'static str, &[String])> {
+        [
+            ("blocklists", "privacy/blocklists", &self.blocklists),
+            ("blocked_tlds", "security/tlds", &self.blocked_tlds),
+            (
+                "parental_control_categories",
+                "parentalControl/categories",
+                &self.parental_control_categories,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(setting, path, items)| Some((setting, path, items.as_deref()?)))
+        .collect()
+    }
+}
+
+/// Listing of a profile setting: `{"data": [{"id": ...}]}`.
+#[derive(Deserialize, Debug)]
+pub struct SettingItems {
+    pub data: Vec<SettingItem>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SettingItem {
+    pub id: String,
+    /// Parental control categories can be listed but inactive
+    #[serde(default = "active")]
+    pub active: bool,
+}
+
+fn active() -> bool {
+    true
+}
+
 #[derive(Deserialize, Debug)]
 pub struct NextDNSError {
     pub code: String,
     pub message: String,
 }
 
+/// Error body of the NextDNS API: `{"errors": [{"code": ..., "detail": ...}]}`.
+#[derive(Deserialize, Debug)]
+pub struct NextDNSErrors {
+    #[serde(default)]
+    pub errors: Vec<NextDNSApiError>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct NextDNSApiError {
+    pub code: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+    #[serde(default)]
+    pub source: Option<ErrorSource>,
+}
+
+/// The part of the request an error refers to.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorSource {
+    /// JSON pointer into the request body, e.g. `/content`
+    pub pointer: Option<String>,
+    pub parameter: Option<String>,
+}
+
 use crate::core::record::{DNSRecord, DNSRecordType};
 
 pub fn to_dns_record(nr: &NextDNSRecord) -> DNSRecord {
@@ -45,17 +122,14 @@ pub fn to_dns_record(nr: &NextDNSRecord) -> DNSRecord {
         name: nr.domain.clone(),
         value: nr.value.clone(),
         ttl: nr.ttl,
+        comment: None,
     }
 }
 
 pub fn to_nextdns_record(rec: &DNSRecord) -> CreateRecordRequest {
     CreateRecordRequest {
         domain: rec.name.clone(),
-        record_type: match rec.record_type {
-            DNSRecordType::A => "A".to_string(),
-            DNSRecordType::AAAA => "AAAA".to_string(),
-            DNSRecordType::CNAME => "CNAME".to_string(),
-        },
+        record_type: rec.record_type.as_str().to_string(),
         value: rec.value.clone(),
         ttl: rec.ttl,
     }
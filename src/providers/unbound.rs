@@ -0,0 +1,136 @@
+//! Unbound `local-data:` snippet output.
+
+use crate::core::provider::ProviderCapabilities;
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::providers::file::{FileFormat, split_comment, with_comment};
+
+pub struct UnboundFormat;
+
+impl FileFormat for UnboundFormat {
+    fn name(&self) -> &str {
+        "unbound"
+    }
+
+    fn supports(&self, record: &DNSRecord) -> bool {
+        matches!(
+            record.record_type,
+            DNSRecordType::A | DNSRecordType::AAAA | DNSRecordType::CNAME | DNSRecordType::PTR
+        )
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Wildcards need local-zone redirects, which are not generated
+        ProviderCapabilities {
+            record_types: vec![
+                DNSRecordType::A,
+                DNSRecordType::AAAA,
+                DNSRecordType::CNAME,
+                DNSRecordType::PTR,
+            ],
+            wildcards: false,
+            update_in_place: true,
+            ..Default::default()
+        }
+    }
+
+    fn parse(&self, lines: &[&str]) -> Vec<DNSRecord> {
+        let mut records = Vec::new();
+        for line in lines {
+            // local-data: "name. [ttl] IN TYPE value"
+            let (line, comment) = split_comment(line, '#');
+            let Some(data) = line.trim().strip_prefix("local-data:") else {
+                continue;
+            };
+            let data = data.trim().trim_matches('"');
+            let parts: Vec<&str> = data.split_whitespace().collect();
+            let (name, ttl, rest) = match parts.as_slice() {
+                [name, ttl, rest @ ..] if ttl.parse::<u32>().is_ok() => {
+                    (*name, ttl.parse().ok(), rest)
+                }
+                [name, rest @ ..] => (*name, None, rest),
+                [] => continue,
+            };
+            let [class, record_type, value] = rest else {
+                continue;
+            };
+            if !class.eq_ignore_ascii_case("IN") {
+                continue;
+            }
+            let record_type = match record_type.to_ascii_uppercase().as_str() {
+                "A" => DNSRecordType::A,
+                "AAAA" => DNSRecordType::AAAA,
+                "CNAME" => DNSRecordType::CNAME,
+                "PTR" => DNSRecordType::PTR,
+                _ => continue,
+            };
+            records.push(DNSRecord {
+                record_type,
+                name: name.trim_end_matches('.').to_string(),
+                value: value.trim_end_matches('.').to_string(),
+                ttl,
+                comment,
+            });
+        }
+        records
+    }
+
+    fn render(&self, records: &[DNSRecord]) -> Vec<String> {
+        let mut records: Vec<_> = records.iter().collect();
+        records.sort_by(|a, b| (&a.name, &a.value).cmp(&(&b.name, &b.value)));
+
+        // Clauses may repeat, so the block can open its own server: section
+        let mut lines = vec!["server:".to_string()];
+        lines.extend(records.into_iter().filter_map(|r| {
+            let ttl = r.ttl.map(|t| format!(" {t}")).unwrap_or_default();
+            let (record_type, value) = match r.record_type {
+                DNSRecordType::A => ("A", r.value.clone()),
+                DNSRecordType::AAAA => ("AAAA", r.value.clone()),
+                DNSRecordType::CNAME => ("CNAME", format!("{}.", r.value)),
+                DNSRecordType::PTR => ("PTR", format!("{}.", r.value)),
+                DNSRecordType::NAPTR | DNSRecordType::SSHFP | DNSRecordType::TXT => return None,
+            };
+            let line = format!(
+                "    local-data: \"{}.{ttl} IN {record_type} {value}\"",
+                r.name
+            );
+            Some(with_comment(line, r, '#'))
+        }));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let records = vec![
+            DNSRecord {
+                record_type: DNSRecordType::A,
+                name: "a.example.com".into(),
+                value: "1.2.3.4".into(),
+                ttl: Some(300),
+                comment: None,
+            },
+            DNSRecord {
+                record_type: DNSRecordType::CNAME,
+                name: "c.example.com".into(),
+                value: "a.example.com".into(),
+                ttl: None,
+                comment: None,
+            },
+        ];
+        let lines = UnboundFormat.render(&records);
+        assert_eq!(
+            lines,
+            vec![
+                "server:",
+                "    local-data: \"a.example.com. 300 IN A 1.2.3.4\"",
+                "    local-data: \"c.example.com. IN CNAME a.example.com.\"",
+            ]
+        );
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(UnboundFormat.parse(&lines), records);
+    }
+}
@@ -0,0 +1,472 @@
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+
+use crate::auth::credentials::CredentialManager;
+use crate::core::http::{HttpOptions, with_request_id};
+use crate::core::ownership::{self, Owned, Ownership};
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
+use crate::core::record::DNSRecord;
+use crate::error::Error;
+use crate::providers::vultr::error::{VultrProviderError, map_error};
+use crate::providers::vultr::types::*;
+use crate::secret::Secret;
+use async_trait::async_trait;
+
+const PAGE_SIZE: u32 = 500;
+
+pub struct VultrConfig {
+    pub api_url: String,
+    /// Zone name, e.g. `example.com`
+    pub domain: String,
+    pub ownership: Ownership,
+}
+
+pub struct VultrProvider {
+    config: VultrConfig,
+    client: Client,
+    api_key: Secret,
+    /// The zone as listed for the additions of a run, until `flush`
+    listing: Mutex<Option<Vec<VultrRecord>>>,
+}
+
+impl VultrProvider {
+    pub fn new(
+        config: VultrConfig,
+        credentials: Arc<dyn CredentialManager>,
+        http: &HttpOptions,
+    ) -> Result<Self, VultrProviderError> {
+        let api_key = credentials
+            .get("vultr_api_key")
+            .map_err(|e| VultrProviderError::Credential(e.to_string()))?;
+        let client = http.builder().build()?;
+        Ok(Self {
+            config,
+            client,
+            api_key,
+            listing: Mutex::new(None),
+        })
+    }
+
+    fn records_url(&self) -> String {
+        format!(
+            "{}/domains/{}/records",
+            self.config.api_url, self.config.domain
+        )
+    }
+
+    /// Send a request and check the status, returning the response for the
+    /// caller to decode (mutations answer with 204 No Content).
+    async fn send(&self, builder: RequestBuilder) -> Result<reqwest::Response, VultrProviderError> {
+        let response = with_request_id(builder)
+            .bearer_auth(self.api_key.expose())
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let error: VultrError = response.json().await.unwrap_or(VultrError {
+            error: format!("HTTP {status}"),
+            status: Some(status.as_u16()),
+        });
+        Err(VultrError {
+            status: error.status.or(Some(status.as_u16())),
+            ..error
+        }
+        .into())
+    }
+
+    async fn handle_request<T: DeserializeOwned>(
+        &self,
+        builder: RequestBuilder,
+    ) -> Result<T, VultrProviderError> {
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    pub async fn list_vultr_records(&self) -> Result<Vec<VultrRecord>, VultrProviderError> {
+        let mut records = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let mut builder = self
+                .client
+                .get(self.records_url())
+                .query(&[("per_page", PAGE_SIZE.to_string())]);
+            if !cursor.is_empty() {
+                builder = builder.query(&[("cursor", &cursor)]);
+            }
+            let page: RecordList = self.handle_request(builder).await?;
+            records.extend(page.records);
+            if page.meta.links.next.is_empty() {
+                return Ok(records);
+            }
+            cursor = page.meta.links.next;
+        }
+    }
+
+    fn to_request(&self, record: &DNSRecord) -> Result<RecordRequest, Error> {
+        to_record_request(record, &self.config.domain).ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "{} is outside of {}",
+                record.name, self.config.domain
+            ))
+        })
+    }
+
+    /// Find the Vultr record matching `matches`, for operations that need an ID.
+    async fn find_record<F>(&self, matches: F) -> Result<VultrRecord, Error>
+    where
+        F: Fn(&DNSRecord) -> bool,
+    {
+        self.list_vultr_records()
+            .await
+            .map_err(map_error)?
+            .into_iter()
+            .find(|r| to_dns_record(r, &self.config.domain).is_some_and(|d| matches(&d)))
+            .ok_or_else(|| Error::NotFound("Record not found".to_string()))
+    }
+
+    fn is_same(&self, existing: &VultrRecord, record: &DNSRecord) -> bool {
+        to_dns_record(existing, &self.config.domain).is_some_and(|d| d.same_data(record))
+    }
+
+    /// The zone as listed by the first addition of a run, and kept up to
+    /// date by the following ones, so each does not list it again.
+    async fn listing(&self) -> Result<MappedMutexGuard<'_, Vec<VultrRecord>>, Error> {
+        let mut listing = self.listing.lock().await;
+        if listing.is_none() {
+            *listing = Some(self.list_vultr_records().await.map_err(map_error)?);
+        }
+        Ok(MutexGuard::map(listing, |l| l.get_or_insert_default()))
+    }
+
+    /// Create a record, returning it as Vultr stored it.
+    async fn create(&self, req: &RecordRequest) -> Result<VultrRecord, Error> {
+        let builder = self.client.post(self.records_url()).json(req);
+        let created: CreatedRecord = self.handle_request(builder).await.map_err(map_error)?;
+        Ok(created.record)
+    }
+
+    fn is_marker(&self, record: &VultrRecord, (name, value): &(String, String)) -> bool {
+        record.record_type == "TXT"
+            && fqdn(&record.name, &self.config.domain) == *name
+            && record.data.trim_matches('"') == value
+    }
+
+    /// Publish the ownership marker for `record` unless `records` has it.
+    async fn claim(&self, record: &DNSRecord, records: &mut Vec<VultrRecord>) -> Result<(), Error> {
+        if !self.config.ownership.marks(record) {
+            return Ok(());
+        }
+        let marker = ownership::marker(record);
+        if records.iter().any(|r| self.is_marker(r, &marker)) {
+            return Ok(());
+        }
+        let req = RecordRequest {
+            record_type: "TXT".to_string(),
+            name: relative_name(&marker.0, &self.config.domain).unwrap_or_default(),
+            data: format!("\"{}\"", marker.1),
+            ttl: None,
+        };
+        records.push(self.create(&req).await?);
+        Ok(())
+    }
+
+    /// Remove the ownership marker for `record` once no value is left under it.
+    async fn release(&self, record: &DNSRecord) -> Result<(), Error> {
+        if !self.config.ownership.marks(record) {
+            return Ok(());
+        }
+        let marker = ownership::marker(record);
+        let records = self.list_vultr_records().await.map_err(map_error)?;
+        let in_use = records
+            .iter()
+            .any(|r| to_dns_record(r, &self.config.domain).is_some_and(|d| d.same_name(record)));
+        let Some(existing) = records.iter().find(|r| self.is_marker(r, &marker)) else {
+            return Ok(());
+        };
+        if in_use {
+            return Ok(());
+        }
+        let url = format!("{}/{}", self.records_url(), existing.id);
+        self.send(self.client.delete(url))
+            .await
+            .map(|_| ())
+            .map_err(map_error)
+    }
+}
+
+#[async_trait]
+impl DNSProvider for VultrProvider {
+    fn name(&self) -> &str {
+        "vultr"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            update_in_place: true,
+            txt: true,
+            ..Default::default()
+        }
+    }
+
+    async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+        let records = self.list_vultr_records().await.map_err(map_error)?;
+        Ok(records
+            .iter()
+            .filter_map(|r| to_dns_record(r, &self.config.domain))
+            .collect())
+    }
+
+    async fn owned_records(&self) -> Result<Option<Owned>, Error> {
+        if self.config.ownership != Ownership::Txt {
+            return Ok(None);
+        }
+        let records = self.list_vultr_records().await.map_err(map_error)?;
+        Ok(Some(
+            records
+                .iter()
+                .filter(|r| r.record_type == "TXT")
+                .filter_map(|r| {
+                    ownership::parse_marker(&fqdn(&r.name, &self.config.domain), &r.data)
+                })
+                .collect(),
+        ))
+    }
+
+    async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let req = self.to_request(&record)?;
+        // Vultr accepts duplicate records, so an add that already went
+        // through, e.g. before an interrupted run, must not be repeated
+        let mut records = self.listing().await?;
+        if !records.iter().any(|r| self.is_same(r, &record)) {
+            let created = self.create(&req).await?;
+            records.push(created);
+        }
+        self.claim(&record, &mut records).await
+    }
+
+    async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
+        let req = self.to_request(&record)?;
+        *self.listing.lock().await = None;
+        let existing = self.find_record(|r| r.same_name(&record)).await?;
+        let url = format!("{}/{}", self.records_url(), existing.id);
+        let builder = self.client.patch(url).json(&req);
+        self.send(builder).await.map(|_| ()).map_err(map_error)
+    }
+
+    async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+        *self.listing.lock().await = None;
+        let existing = self.find_record(|r| r.same_data(&record)).await?;
+        let url = format!("{}/{}", self.records_url(), existing.id);
+        let response = self
+            .send(self.client.delete(url))
+            .await
+            .map_err(map_error)?;
+        match response.status() {
+            StatusCode::NO_CONTENT | StatusCode::OK => self.release(&record).await,
+            status => Err(Error::ProviderError(format!("unexpected status {status}"))),
+        }
+    }
+
+    async fn flush(&self) -> Result<(), Error> {
+        // The next run lists the zone afresh
+        *self.listing.lock().await = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecordType;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    struct FakeCredentialManager;
+
+    impl CredentialManager for FakeCredentialManager {
+        fn get(&self, key: &str) -> Result<Secret, Error> {
+            match key {
+                "vultr_api_key" => Ok("key".into()),
+                _ => Err(Error::CredentialError("missing".into())),
+            }
+        }
+    }
+
+    fn provider_with(server: &MockServer, ownership: Ownership) -> VultrProvider {
+        let config = VultrConfig {
+            api_url: server.url(""),
+            domain: "example.com".into(),
+            ownership,
+        };
+        VultrProvider::new(
+            config,
+            Arc::new(FakeCredentialManager),
+            &HttpOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn provider(server: &MockServer) -> VultrProvider {
+        provider_with(server, Ownership::Zone)
+    }
+
+    #[tokio::test]
+    async fn test_list_records_follows_cursor() {
+        let server = MockServer::start_async().await;
+        let first = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/domains/example.com/records")
+                    .header("Authorization", "Bearer key")
+                    .matches(|req| {
+                        !req.query_params
+                            .as_ref()
+                            .is_some_and(|q| q.iter().any(|(k, _)| k == "cursor"))
+                    });
+                then.status(200).json_body(json!({
+                    "records": [{ "id": "a", "type": "A", "name": "www", "data": "1.2.3.4", "priority": -1, "ttl": 300 }],
+                    "meta": { "total": 2, "links": { "next": "bmV4dA==", "prev": "" } }
+                }));
+            })
+            .await;
+        let second = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/domains/example.com/records")
+                    .query_param("cursor", "bmV4dA==");
+                then.status(200).json_body(json!({
+                    "records": [{ "id": "b", "type": "TXT", "name": "", "data": "\"v=spf1\"", "priority": -1, "ttl": 300 }],
+                    "meta": { "total": 2, "links": { "next": "", "prev": "" } }
+                }));
+            })
+            .await;
+
+        let records = provider(&server).list_records().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].name, "www.example.com");
+        assert_eq!(records[1].record_type, DNSRecordType::TXT);
+        assert_eq!(records[1].value, "v=spf1");
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_and_delete_record() {
+        let server = MockServer::start_async().await;
+        let create = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/domains/example.com/records")
+                    .json_body(json!({ "type": "A", "name": "new", "data": "1.2.3.5", "ttl": 300 }));
+                then.status(201).json_body(json!({
+                    "record": { "id": "b", "type": "A", "name": "new", "data": "1.2.3.5", "priority": -1, "ttl": 300 }
+                }));
+            })
+            .await;
+        let listing = server
+            .mock_async(|when, then| {
+                when.method(GET).path("/domains/example.com/records");
+                then.status(200).json_body(json!({
+                    "records": [{ "id": "a", "type": "A", "name": "www", "data": "1.2.3.4", "priority": -1, "ttl": 300 }],
+                    "meta": { "total": 1, "links": { "next": "", "prev": "" } }
+                }));
+            })
+            .await;
+        let delete = server
+            .mock_async(|when, then| {
+                when.method(DELETE).path("/domains/example.com/records/a");
+                then.status(204);
+            })
+            .await;
+
+        let provider = provider(&server);
+        let existing = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "www.example.com".into(),
+            value: "1.2.3.4".into(),
+            ttl: Some(300),
+            comment: None,
+        };
+        let new = DNSRecord {
+            name: "new.example.com".into(),
+            value: "1.2.3.5".into(),
+            ..existing.clone()
+        };
+        // Already present, however it is spelled, so not created again
+        provider.add_record(existing.clone()).await.unwrap();
+        let spelled = DNSRecord {
+            name: "WWW.example.com.".into(),
+            ..existing.clone()
+        };
+        provider.add_record(spelled).await.unwrap();
+        provider.add_record(new).await.unwrap();
+        // The additions share one listing
+        listing.assert_hits_async(1).await;
+        provider.delete_record(existing).await.unwrap();
+        create.assert_async().await;
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_invalid_token() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/domains/example.com/records");
+                then.status(401)
+                    .json_body(json!({ "error": "Invalid API token.", "status": 401 }));
+            })
+            .await;
+
+        let err = provider(&server).list_records().await.unwrap_err();
+        assert!(matches!(err.kind(), Error::CredentialError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_marker_kept_while_records_remain() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/domains/example.com/records");
+                then.status(200).json_body(json!({
+                    "records": [
+                        { "id": "a", "type": "A", "name": "www", "data": "1.2.3.4", "priority": -1, "ttl": 300 },
+                        { "id": "m", "type": "TXT", "name": "_dns-update.www", "data": "\"heritage=dns-update,type=A\"", "priority": -1, "ttl": 300 }
+                    ],
+                    "meta": { "total": 2, "links": { "next": "", "prev": "" } }
+                }));
+            })
+            .await;
+        let delete = server
+            .mock_async(|when, then| {
+                when.method(DELETE).path("/domains/example.com/records/a");
+                then.status(204);
+            })
+            .await;
+        let delete_marker = server
+            .mock_async(|when, then| {
+                when.method(DELETE).path("/domains/example.com/records/m");
+                then.status(204);
+            })
+            .await;
+
+        let provider = provider_with(&server, Ownership::Txt);
+        let owned = provider.owned_records().await.unwrap().unwrap();
+        assert!(owned.contains(&("www.example.com".to_string(), DNSRecordType::A)));
+
+        // The mocked listing still shows the A record, so the marker stays
+        let record = DNSRecord {
+            record_type: DNSRecordType::A,
+            name: "www.example.com".into(),
+            value: "1.2.3.4".into(),
+            ttl: Some(300),
+            comment: None,
+        };
+        provider.delete_record(record).await.unwrap();
+        delete.assert_async().await;
+        delete_marker.assert_hits_async(0).await;
+    }
+}
@@ -0,0 +1,78 @@
+use crate::providers::vultr::types::VultrError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VultrProviderError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Credential error: {0}")]
+    Credential(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Provider error: {0}")]
+    Provider(String),
+}
+
+impl From<VultrError> for VultrProviderError {
+    fn from(err: VultrError) -> Self {
+        match err.status {
+            Some(401) | Some(403) => VultrProviderError::Credential(err.error),
+            Some(404) => VultrProviderError::NotFound(err.error),
+            _ => VultrProviderError::Provider(err.error),
+        }
+    }
+}
+
+use crate::error::Error;
+
+pub fn map_error(e: VultrProviderError) -> Error {
+    use VultrProviderError::*;
+    match e {
+        Http(err) => Error::Http(err),
+        Credential(ref msg) => Error::with_source(Error::CredentialError(msg.clone()), e),
+        NotFound(ref msg) => Error::with_source(Error::NotFound(msg.clone()), e),
+        Provider(ref msg) => Error::with_source(Error::ProviderError(msg.clone()), e),
+    }
+}
+
+impl From<VultrProviderError> for Error {
+    fn from(e: VultrProviderError) -> Self {
+        map_error(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_error_variants() {
+        use VultrProviderError::*;
+
+        let err = map_error(Credential("fail".to_string()));
+        assert!(matches!(err.kind(), Error::CredentialError(_)));
+        let err = map_error(NotFound("missing".to_string()));
+        assert!(matches!(err.kind(), Error::NotFound(_)));
+        let err = map_error(Provider("fail".to_string()));
+        assert!(matches!(err.kind(), Error::ProviderError(_)));
+    }
+
+    #[test]
+    fn test_api_error_classification() {
+        let err: VultrProviderError = VultrError {
+            error: "Invalid API token.".into(),
+            status: Some(401),
+        }
+        .into();
+        assert!(matches!(err, VultrProviderError::Credential(_)));
+        let err: VultrProviderError = VultrError {
+            error: "Invalid data".into(),
+            status: Some(400),
+        }
+        .into();
+        assert!(matches!(err, VultrProviderError::Provider(_)));
+    }
+}
@@ -0,0 +1,7 @@
+//! Vultr DNS provider implementation
+
+pub mod client;
+pub mod error;
+pub mod types;
+
+pub use client::{VultrConfig, VultrProvider};
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::rdata::{join_txt, txt_strings};
+use crate::core::record::{DNSRecord, DNSRecordType, normalize_hostname};
+
+#[derive(Deserialize, Debug)]
+pub struct RecordList {
+    pub records: Vec<VultrRecord>,
+    pub meta: Meta,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Meta {
+    pub links: Links,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Links {
+    /// Cursor for the next page, empty on the last page
+    #[serde(default)]
+    pub next: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VultrRecord {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    /// Name relative to the domain, empty for the apex
+    pub name: String,
+    pub data: String,
+    pub ttl: Option<u32>,
+}
+
+/// The answer to a record creation.
+#[derive(Deserialize, Debug)]
+pub struct CreatedRecord {
+    pub record: VultrRecord,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RecordRequest {
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub name: String,
+    pub data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VultrError {
+    pub error: String,
+    pub status: Option<u16>,
+}
+
+/// Fully qualified name for a record name relative to `domain`.
+pub fn fqdn(name: &str, domain: &str) -> String {
+    if name.is_empty() {
+        domain.to_string()
+    } else {
+        format!("{name}.{domain}")
+    }
+}
+
+/// Record name relative to `domain`, or `None` if `name` is outside it.
+pub fn relative_name(name: &str, domain: &str) -> Option<String> {
+    if name == domain {
+        Some(String::new())
+    } else {
+        name.strip_suffix(&format!(".{domain}")).map(str::to_string)
+    }
+}
+
+pub fn to_dns_record(record: &VultrRecord, domain: &str) -> Option<DNSRecord> {
+    let record_type = match record.record_type.as_str() {
+        "A" => DNSRecordType::A,
+        "AAAA" => DNSRecordType::AAAA,
+        "CNAME" => DNSRecordType::CNAME,
+        "TXT" => DNSRecordType::TXT,
+        _ => return None,
+    };
+    let value = match record_type {
+        DNSRecordType::TXT => join_txt(&record.data),
+        _ => record.data.trim_end_matches('.').to_string(),
+    };
+    Some(DNSRecord {
+        record_type,
+        name: fqdn(&record.name, domain),
+        value,
+        ttl: record.ttl,
+        comment: None,
+    })
+}
+
+pub fn to_record_request(record: &DNSRecord, domain: &str) -> Option<RecordRequest> {
+    Some(RecordRequest {
+        record_type: record.record_type.as_str().to_string(),
+        // However the name is spelled, e.g. with the root dot
+        name: relative_name(
+            &normalize_hostname(&record.name),
+            &normalize_hostname(domain),
+        )?,
+        data: match record.record_type {
+            DNSRecordType::TXT => txt_strings(&record.value),
+            _ => record.value.clone(),
+        },
+        ttl: record.ttl,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_conversion() {
+        let record = VultrRecord {
+            id: "cb676a46".into(),
+            record_type: "CNAME".into(),
+            name: "www".into(),
+            data: "example.com".into(),
+            ttl: Some(300),
+        };
+        let dns = to_dns_record(&record, "example.com").unwrap();
+        assert_eq!(dns.record_type, DNSRecordType::CNAME);
+        assert_eq!(dns.name, "www.example.com");
+        assert_eq!(dns.value, "example.com");
+
+        let req = to_record_request(&dns, "example.com").unwrap();
+        assert_eq!(req.name, "www");
+        assert_eq!(req.data, "example.com");
+        assert!(to_record_request(&dns, "example.org").is_none());
+
+        let record = VultrRecord {
+            record_type: "TXT".into(),
+            name: "mail._domainkey".into(),
+            data: r#""v=DKIM1; p=MIIB" "IjAN""#.into(),
+            ..record
+        };
+        let dns = to_dns_record(&record, "example.com").unwrap();
+        assert_eq!(dns.value, "v=DKIM1; p=MIIBIjAN");
+        let req = to_record_request(&dns, "example.com").unwrap();
+        assert_eq!(req.data, r#""v=DKIM1; p=MIIBIjAN""#);
+    }
+}
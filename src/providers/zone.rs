@@ -0,0 +1,309 @@
+//! RFC 1035 zone file output for CoreDNS's `file` plugin.
+//!
+//! The whole file is generated, including the SOA and NS records. The SOA
+//! serial is bumped on every write so CoreDNS notices the change and reloads
+//! the zone.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::core::provider::ProviderCapabilities;
+use crate::core::rdata::{join_txt, txt_strings};
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::providers::file::{FileFormat, split_comment, with_comment};
+
+pub const DEFAULT_ZONE_TTL: u32 = 300;
+
+pub struct ZoneFormat {
+    zone: String,
+    nameserver: String,
+    ttl: u32,
+}
+
+impl ZoneFormat {
+    pub fn new(zone: &str, nameserver: Option<&str>, ttl: Option<u32>) -> Self {
+        let zone = zone.trim_end_matches('.').to_string();
+        let nameserver = nameserver
+            .map(|ns| ns.trim_end_matches('.').to_string())
+            .unwrap_or_else(|| format!("ns.{zone}"));
+        Self {
+            zone,
+            nameserver,
+            ttl: ttl.unwrap_or(DEFAULT_ZONE_TTL),
+        }
+    }
+
+    fn in_zone(&self, name: &str) -> bool {
+        name == self.zone || name.ends_with(&format!(".{}", self.zone))
+    }
+
+    /// The next serial: one past the previous serial, or the current Unix
+    /// time if that is larger, so serials also increase across rebuilds.
+    fn next_serial(&self, contents: &str) -> u32 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or_default();
+        match parse_serial(contents) {
+            Some(previous) => now.max(previous.wrapping_add(1)),
+            None => now,
+        }
+    }
+}
+
+impl FileFormat for ZoneFormat {
+    fn name(&self) -> &str {
+        "zone"
+    }
+
+    fn supports(&self, record: &DNSRecord) -> bool {
+        self.in_zone(&record.name)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            record_types: vec![
+                DNSRecordType::A,
+                DNSRecordType::AAAA,
+                DNSRecordType::CNAME,
+                DNSRecordType::NAPTR,
+                DNSRecordType::PTR,
+                DNSRecordType::SSHFP,
+            ],
+            update_in_place: true,
+            txt: true,
+            ..Default::default()
+        }
+    }
+
+    fn parse(&self, lines: &[&str]) -> Vec<DNSRecord> {
+        let mut records = Vec::new();
+        for line in lines {
+            let (line, comment) = split_comment(line, ';');
+            if line.trim_start().starts_with('$') {
+                continue;
+            }
+            // The value is the rest of the line, as it may have several fields
+            let Some((name, rest)) = next_field(line) else {
+                continue;
+            };
+            let Some((ttl, rest)) = next_field(rest) else {
+                continue;
+            };
+            let Some((class, rest)) = next_field(rest) else {
+                continue;
+            };
+            let Some((record_type, value)) = next_field(rest) else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            if !class.eq_ignore_ascii_case("IN") {
+                continue;
+            }
+            let record_type = match record_type.to_ascii_uppercase().as_str() {
+                "A" => DNSRecordType::A,
+                "AAAA" => DNSRecordType::AAAA,
+                "CNAME" => DNSRecordType::CNAME,
+                "NAPTR" => DNSRecordType::NAPTR,
+                "PTR" => DNSRecordType::PTR,
+                "SSHFP" => DNSRecordType::SSHFP,
+                "TXT" => DNSRecordType::TXT,
+                // SOA and NS are generated, anything else is not managed
+                _ => continue,
+            };
+            let value = match record_type {
+                DNSRecordType::TXT => join_txt(value),
+                DNSRecordType::NAPTR | DNSRecordType::SSHFP => {
+                    value.split_whitespace().collect::<Vec<_>>().join(" ")
+                }
+                _ => value.trim_end_matches('.').to_string(),
+            };
+            records.push(DNSRecord {
+                record_type,
+                name: name.trim_end_matches('.').to_string(),
+                value,
+                ttl: ttl.parse().ok(),
+                comment,
+            });
+        }
+        records
+    }
+
+    fn render(&self, records: &[DNSRecord]) -> Vec<String> {
+        let mut records: Vec<_> = records.iter().filter(|r| self.supports(r)).collect();
+        records.sort_by(|a, b| (&a.name, &a.value).cmp(&(&b.name, &b.value)));
+        records
+            .into_iter()
+            .map(|r| {
+                let (record_type, value) = match r.record_type {
+                    DNSRecordType::A => ("A", r.value.clone()),
+                    DNSRecordType::AAAA => ("AAAA", r.value.clone()),
+                    DNSRecordType::CNAME => ("CNAME", format!("{}.", r.value)),
+                    DNSRecordType::PTR => ("PTR", format!("{}.", r.value)),
+                    // Canonical, with the NAPTR replacement fully qualified
+                    DNSRecordType::NAPTR => ("NAPTR", r.normalized_value()),
+                    DNSRecordType::SSHFP => ("SSHFP", r.normalized_value()),
+                    DNSRecordType::TXT => ("TXT", txt_strings(&r.value)),
+                };
+                let line = format!(
+                    "{}.\t{}\tIN\t{record_type}\t{value}",
+                    r.name,
+                    r.ttl.unwrap_or(self.ttl)
+                );
+                with_comment(line, r, ';')
+            })
+            .collect()
+    }
+
+    fn read(&self, contents: &str) -> Vec<DNSRecord> {
+        let lines: Vec<&str> = contents.lines().collect();
+        self.parse(&lines)
+    }
+
+    fn write(&self, contents: &str, records: &[DNSRecord]) -> String {
+        let zone = &self.zone;
+        let ttl = self.ttl;
+        let mut out = format!(
+            "; Generated by dns-update, do not edit\n\
+             $ORIGIN {zone}.\n\
+             $TTL {ttl}\n\
+             {zone}.\t{ttl}\tIN\tSOA\t{ns}. hostmaster.{zone}. {serial} 7200 3600 1209600 {ttl}\n\
+             {zone}.\t{ttl}\tIN\tNS\t{ns}.\n",
+            ns = self.nameserver,
+            serial = self.next_serial(contents),
+        );
+        for line in self.render(records) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// The first whitespace separated field of `line`, and the rest
+fn next_field(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let end = line.find(char::is_whitespace).unwrap_or(line.len());
+    (end > 0).then(|| line.split_at(end))
+}
+
+fn parse_serial(contents: &str) -> Option<u32> {
+    contents.lines().find_map(|line| {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let soa = parts.iter().position(|p| p.eq_ignore_ascii_case("SOA"))?;
+        parts.get(soa + 3)?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::provider::DNSProvider;
+    use crate::providers::file::FileProvider;
+
+    fn record(record_type: DNSRecordType, name: &str, value: &str) -> DNSRecord {
+        DNSRecord {
+            record_type,
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl: Some(60),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_round_trip() {
+        let format = ZoneFormat::new("lab.example.com.", None, None);
+        let records = vec![
+            record(DNSRecordType::A, "a.lab.example.com", "10.0.0.1"),
+            record(
+                DNSRecordType::CNAME,
+                "b.lab.example.com",
+                "a.lab.example.com",
+            ),
+        ];
+        let contents = format.write("", &records);
+        assert!(contents.contains("$ORIGIN lab.example.com.\n"));
+        assert!(contents.contains("IN\tNS\tns.lab.example.com.\n"));
+        assert!(contents.contains("a.lab.example.com.\t60\tIN\tA\t10.0.0.1\n"));
+        assert!(contents.contains("b.lab.example.com.\t60\tIN\tCNAME\ta.lab.example.com.\n"));
+        assert_eq!(format.read(&contents), records);
+    }
+
+    #[test]
+    fn test_reverse_zone_round_trip() {
+        let format = ZoneFormat::new("0.0.10.in-addr.arpa", Some("ns.lab.example.com"), None);
+        let records = vec![record(
+            DNSRecordType::PTR,
+            "2.0.0.10.in-addr.arpa",
+            "nas.lab.example.com",
+        )];
+        let contents = format.write("", &records);
+        assert!(contents.contains("2.0.0.10.in-addr.arpa.\t60\tIN\tPTR\tnas.lab.example.com.\n"));
+        assert_eq!(format.read(&contents), records);
+    }
+
+    #[test]
+    fn test_multi_field_values_round_trip() {
+        let format = ZoneFormat::new("example.com", None, None);
+        let records = vec![
+            record(
+                DNSRecordType::SSHFP,
+                "nas.example.com",
+                "4 1 0123456789abcdef0123456789abcdef01234567",
+            ),
+            record(
+                DNSRecordType::NAPTR,
+                "sip.example.com",
+                r#"10 0 "s" "SIP+D2U" "" _sip._udp.example.com."#,
+            ),
+        ];
+        let contents = format.write("", &records);
+        assert!(contents.contains(
+            "nas.example.com.\t60\tIN\tSSHFP\t4 1 0123456789abcdef0123456789abcdef01234567\n"
+        ));
+        assert_eq!(format.read(&contents), records);
+    }
+
+    #[test]
+    fn test_long_txt_values_are_chunked() {
+        let format = ZoneFormat::new("example.com", None, None);
+        let dkim = format!("v=DKIM1; k=rsa; p={}", "MIIBIjANBgkq".repeat(30));
+        let records = vec![record(
+            DNSRecordType::TXT,
+            "mail._domainkey.example.com",
+            &dkim,
+        )];
+        let contents = format.write("", &records);
+        let line = contents.lines().find(|l| l.contains("TXT")).unwrap();
+        assert!(line.ends_with(&format!("\"{}\"", &dkim[255..])));
+        assert_eq!(format.read(&contents), records);
+    }
+
+    #[test]
+    fn test_serial_is_bumped() {
+        let format = ZoneFormat::new("lab.example.com", None, None);
+        let future = u32::MAX - 10;
+        let previous = format!(
+            "lab.example.com.\t300\tIN\tSOA\tns.lab.example.com. hostmaster.lab.example.com. {future} 7200 3600 1209600 300\n"
+        );
+        let contents = format.write(&previous, &[]);
+        assert_eq!(parse_serial(&contents), Some(future + 1));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_out_of_zone_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = FileProvider::new(
+            dir.path().join("db.lab"),
+            ZoneFormat::new("lab.example.com", None, None),
+        )
+        .unwrap();
+        let outside = record(DNSRecordType::A, "example.org", "10.0.0.1");
+        assert!(provider.add_record(outside).await.is_err());
+        let inside = record(DNSRecordType::A, "lab.example.com", "10.0.0.1");
+        assert!(provider.add_record(inside).await.is_ok());
+    }
+}
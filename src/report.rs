@@ -0,0 +1,625 @@
+//! Rendering plans for `dns-update plan` and the report at the end of
+//! `dns-update update`.
+
+use clap::ValueEnum;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::core::diff::{Change, Plan};
+use crate::core::record::{DNSRecordType, to_unicode_hostname};
+use crate::core::settings::SettingChange;
+use crate::core::stats::SyncOutcome;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    /// `+`/`~`/`-` lines grouped by provider and record type
+    #[default]
+    Unified,
+    /// One aligned row per change
+    Table,
+    /// A JSON array of changes
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// A table of counts and timings per provider
+    #[default]
+    Text,
+    /// A JSON object, for scripts and monitoring
+    Json,
+}
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// One change of one provider, flattened for rendering
+struct Row<'a> {
+    provider: &'a str,
+    change: Change,
+    /// Refused, as the record is protected
+    blocked: bool,
+}
+
+impl Row<'_> {
+    fn action(&self) -> &'static str {
+        match self.change {
+            Change::Add(_) => "add",
+            Change::Update(_) => "update",
+            Change::Remove(_) => "remove",
+        }
+    }
+
+    fn sign(&self) -> char {
+        if self.blocked {
+            return '!';
+        }
+        match self.change {
+            Change::Add(_) => '+',
+            Change::Update(_) => '~',
+            Change::Remove(_) => '-',
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self.change {
+            Change::Add(_) => GREEN,
+            Change::Update(_) => YELLOW,
+            Change::Remove(_) => RED,
+        }
+    }
+
+    fn record_type(&self) -> &DNSRecordType {
+        &self.change.record().record_type
+    }
+
+    // Names are shown in Unicode; providers hold them as punycode
+    fn name(&self) -> String {
+        to_unicode_hostname(&self.change.record().name)
+    }
+
+    fn value(&self) -> String {
+        let record = self.change.record();
+        match record.record_type {
+            DNSRecordType::CNAME | DNSRecordType::PTR => to_unicode_hostname(&record.value),
+            _ => record.value.clone(),
+        }
+    }
+
+    fn ttl(&self) -> String {
+        self.change
+            .record()
+            .ttl
+            .map(|ttl| ttl.to_string())
+            .unwrap_or_default()
+    }
+}
+
+fn rows<'a>(plans: &'a [(String, Plan)]) -> Vec<Row<'a>> {
+    let mut rows: Vec<Row> = plans
+        .iter()
+        .flat_map(|(provider, plan)| {
+            let blocked = plan.blocked.iter().cloned().map(|change| (change, true));
+            plan.changes()
+                .into_iter()
+                .map(|change| (change, false))
+                .chain(blocked)
+                .map(move |(change, blocked)| Row {
+                    provider: provider.as_str(),
+                    change,
+                    blocked,
+                })
+        })
+        .collect();
+    // Stable, so changes of one name keep the add/update/remove order
+    rows.sort_by(|a, b| {
+        (
+            a.provider,
+            a.record_type().as_str(),
+            &a.change.record().name,
+        )
+            .cmp(&(
+                b.provider,
+                b.record_type().as_str(),
+                &b.change.record().name,
+            ))
+    });
+    rows
+}
+
+// Setting changes of each provider, after its record changes
+fn setting_rows(plans: &[(String, Plan)]) -> Vec<(&str, &SettingChange)> {
+    plans
+        .iter()
+        .flat_map(|(provider, plan)| {
+            plan.settings
+                .iter()
+                .map(move |change| (provider.as_str(), change))
+        })
+        .collect()
+}
+
+fn setting_color(change: &SettingChange) -> &'static str {
+    if change.enable { GREEN } else { RED }
+}
+
+fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{color}{text}{RESET}")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Render the plans of the named providers.
+pub fn render(plans: &[(String, Plan)], format: DiffFormat, color: bool) -> String {
+    let rows = rows(plans);
+    let settings = setting_rows(plans);
+    match format {
+        DiffFormat::Json => json_output(&rows, &settings),
+        _ if rows.is_empty() && settings.is_empty() => "No changes\n".to_string(),
+        DiffFormat::Unified => unified(&rows, &settings, color),
+        DiffFormat::Table => table(&rows, &settings, color),
+    }
+}
+
+fn unified(rows: &[Row], settings: &[(&str, &SettingChange)], color: bool) -> String {
+    let name_width = rows.iter().map(|r| r.name().chars().count()).max();
+    let value_width = rows.iter().map(|r| r.value().chars().count()).max();
+    let (name_width, value_width) = (name_width.unwrap_or(0), value_width.unwrap_or(0));
+
+    let mut out = String::new();
+    let mut group = None;
+    for row in rows {
+        let key = (row.provider, row.record_type());
+        if group != Some(key) {
+            let header = format!("--- {} {}", row.provider, row.record_type().as_str());
+            out.push_str(&paint(&header, BOLD, color));
+            out.push('\n');
+            group = Some(key);
+        }
+        let mut line = format!(
+            "{} {:name_width$}  {:value_width$}  {}",
+            row.sign(),
+            row.name(),
+            row.value(),
+            row.ttl()
+        );
+        if row.blocked {
+            line = format!("{}  (blocked {})", line.trim_end(), row.action());
+        }
+        out.push_str(&paint(line.trim_end(), row.color(), color));
+        out.push('\n');
+    }
+    let mut group = None;
+    for (provider, change) in settings {
+        if group != Some(provider) {
+            let header = format!("--- {provider} settings");
+            out.push_str(&paint(&header, BOLD, color));
+            out.push('\n');
+            group = Some(provider);
+        }
+        let sign = if change.enable { '+' } else { '-' };
+        let line = format!("{sign} {}  {}", change.setting, change.item);
+        out.push_str(&paint(&line, setting_color(change), color));
+        out.push('\n');
+    }
+    out
+}
+
+// Setting changes are listed after the records, with the setting as the
+// type and the item as the name
+fn table(rows: &[Row], settings: &[(&str, &SettingChange)], color: bool) -> String {
+    let header = ["ACTION", "PROVIDER", "TYPE", "NAME", "VALUE", "TTL"];
+    let record_cells = rows.iter().map(|row| {
+        let action = match row.blocked {
+            true => format!("blocked {}", row.action()),
+            false => row.action().to_string(),
+        };
+        [
+            action,
+            row.provider.to_string(),
+            row.record_type().as_str().to_string(),
+            row.name(),
+            row.value(),
+            row.ttl(),
+        ]
+    });
+    let setting_cells = settings.iter().map(|(provider, change)| {
+        [
+            change.action().to_string(),
+            provider.to_string(),
+            change.setting.to_string(),
+            change.item.clone(),
+            String::new(),
+            String::new(),
+        ]
+    });
+    let cells: Vec<[String; 6]> = record_cells.chain(setting_cells).collect();
+    let lines = aligned(&header.map(String::from), &cells);
+    let colors = rows
+        .iter()
+        .map(Row::color)
+        .chain(settings.iter().map(|(_, change)| setting_color(change)));
+
+    let mut out = paint(&lines[0], BOLD, color);
+    out.push('\n');
+    for (row_color, line) in colors.zip(&lines[1..]) {
+        out.push_str(&paint(line, row_color, color));
+        out.push('\n');
+    }
+    out
+}
+
+// The header and rows as lines of columns padded to a common width
+fn aligned<const N: usize>(header: &[String; N], rows: &[[String; N]]) -> Vec<String> {
+    let mut widths = header.each_ref().map(|cell| cell.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    std::iter::once(header)
+        .chain(rows)
+        .map(|cells| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+fn json_output(rows: &[Row], settings: &[(&str, &SettingChange)]) -> String {
+    let records = rows.iter().map(|row| {
+        let record = row.change.record();
+        json!({
+            "provider": row.provider,
+            "action": row.action(),
+            "type": row.record_type().as_str(),
+            "name": record.name,
+            "value": record.value,
+            "ttl": record.ttl,
+            "blocked": row.blocked,
+        })
+    });
+    let settings = settings.iter().map(|(provider, change)| {
+        json!({
+            "provider": provider,
+            "action": change.action(),
+            "setting": change.setting,
+            "item": change.item,
+        })
+    });
+    let changes: Vec<_> = records.chain(settings).collect();
+    let mut out = serde_json::to_string_pretty(&changes).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+/// Wall-clock time of the phases shared by all providers.
+#[derive(Debug, Clone, Copy)]
+pub struct RunTimings {
+    /// Fetching the desired records, and logging in to and listing the
+    /// providers alongside
+    pub prepare: Duration,
+    pub total: Duration,
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.2}s", duration.as_secs_f64())
+}
+
+/// Render the statistics of the run with ID `run_id`.
+pub fn render_run(
+    outcomes: &BTreeMap<String, SyncOutcome>,
+    timings: RunTimings,
+    run_id: Option<&str>,
+    format: ReportFormat,
+) -> String {
+    match format {
+        ReportFormat::Text => run_text(outcomes, timings, run_id),
+        ReportFormat::Json => {
+            let report = json!({
+                "run_id": run_id,
+                "prepare_ms": timings.prepare.as_millis() as u64,
+                "total_ms": timings.total.as_millis() as u64,
+                "providers": outcomes,
+            });
+            let mut out = serde_json::to_string_pretty(&report).unwrap_or_default();
+            out.push('\n');
+            out
+        }
+    }
+}
+
+fn run_text(
+    outcomes: &BTreeMap<String, SyncOutcome>,
+    timings: RunTimings,
+    run_id: Option<&str>,
+) -> String {
+    let header = [
+        "PROVIDER", "EXAMINED", "CREATED", "UPDATED", "DELETED", "SKIPPED", "FAILED", "LIST",
+        "APPLY",
+    ];
+    let cells: Vec<[String; 9]> = outcomes
+        .iter()
+        .map(|(name, outcome)| {
+            let stats = &outcome.stats;
+            [
+                name.clone(),
+                stats.examined.to_string(),
+                stats.created.to_string(),
+                stats.updated.to_string(),
+                stats.deleted.to_string(),
+                stats.skipped.to_string(),
+                stats.failed().to_string(),
+                seconds(stats.list_time),
+                seconds(stats.apply_time),
+            ]
+        })
+        .collect();
+    let mut out = String::new();
+    for line in aligned(&header.map(String::from), &cells) {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    for (name, outcome) in outcomes {
+        if outcome.stats.blocked > 0 {
+            out.push_str(&format!(
+                "{name} blocked: {} change(s) to protected records\n",
+                outcome.stats.blocked
+            ));
+        }
+        if outcome.stats.drift > 0 {
+            out.push_str(&format!(
+                "{name} drift: {} change(s) not applied, read-only\n",
+                outcome.stats.drift
+            ));
+        }
+        if !outcome.stats.failures.is_empty() {
+            let failures: Vec<String> = outcome
+                .stats
+                .failures
+                .iter()
+                .map(|(category, count)| format!("{category} {count}"))
+                .collect();
+            out.push_str(&format!("{name} failures: {}\n", failures.join(", ")));
+        }
+    }
+    out.push_str(&format!(
+        "Prepared in {}, finished in {}\n",
+        seconds(timings.prepare),
+        seconds(timings.total)
+    ));
+    if let Some(run_id) = run_id {
+        out.push_str(&format!("Run {run_id}\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::record::DNSRecord;
+
+    fn record(record_type: DNSRecordType, name: &str, value: &str, ttl: Option<u32>) -> DNSRecord {
+        DNSRecord {
+            record_type,
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl,
+            comment: None,
+        }
+    }
+
+    fn plans() -> Vec<(String, Plan)> {
+        let plan = Plan {
+            to_add: vec![
+                record(DNSRecordType::A, "nas.lab", "10.0.0.2", Some(300)),
+                record(DNSRecordType::CNAME, "www.lab", "nas.lab", None),
+            ],
+            to_update: vec![record(DNSRecordType::A, "router.lab", "10.0.0.1", None)],
+            to_remove: vec![record(DNSRecordType::A, "old.lab", "10.0.0.9", None)],
+            ..Plan::default()
+        };
+        vec![("lan".to_string(), plan)]
+    }
+
+    #[test]
+    fn test_unified_groups_by_provider_and_type() {
+        let out = render(&plans(), DiffFormat::Unified, false);
+        assert_eq!(
+            out,
+            "--- lan A\n\
+             + nas.lab     10.0.0.2  300\n\
+             - old.lab     10.0.0.9\n\
+             ~ router.lab  10.0.0.1\n\
+             --- lan CNAME\n\
+             + www.lab     nas.lab\n"
+        );
+    }
+
+    #[test]
+    fn test_color_is_optional() {
+        let colored = render(&plans(), DiffFormat::Unified, true);
+        assert!(colored.contains(&format!("{GREEN}+ nas.lab")));
+        assert!(colored.contains(&format!("{RED}- old.lab")));
+        assert!(!render(&plans(), DiffFormat::Table, false).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_table_aligns_columns() {
+        let out = render(&plans(), DiffFormat::Table, false);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "ACTION  PROVIDER  TYPE   NAME        VALUE     TTL"
+        );
+        assert_eq!(
+            lines[1],
+            "add     lan       A      nas.lab     10.0.0.2  300"
+        );
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn test_json_lists_changes() {
+        let out = render(&plans(), DiffFormat::Json, false);
+        let changes: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(changes.as_array().unwrap().len(), 4);
+        assert_eq!(changes[0]["action"], "add");
+        assert_eq!(changes[0]["ttl"], 300);
+        assert_eq!(changes[3]["type"], "CNAME");
+    }
+
+    #[test]
+    fn test_idn_names_are_shown_in_unicode() {
+        let plan = Plan {
+            to_add: vec![record(
+                DNSRecordType::CNAME,
+                "www.xn--bcher-kva.example",
+                "xn--bcher-kva.example",
+                None,
+            )],
+            ..Plan::default()
+        };
+        let plans = vec![("lan".to_string(), plan)];
+        assert_eq!(
+            render(&plans, DiffFormat::Unified, false),
+            "--- lan CNAME\n+ www.bücher.example  bücher.example\n"
+        );
+        let json = render(&plans, DiffFormat::Json, false);
+        assert!(json.contains("\"www.xn--bcher-kva.example\""));
+    }
+
+    #[test]
+    fn test_setting_changes_follow_the_records() {
+        let plan = Plan {
+            settings: vec![
+                SettingChange {
+                    setting: "blocklists",
+                    item: "oisd".to_string(),
+                    enable: true,
+                },
+                SettingChange {
+                    setting: "blocked_tlds",
+                    item: "zip".to_string(),
+                    enable: false,
+                },
+            ],
+            ..Plan::default()
+        };
+        let plans = vec![("nextdns".to_string(), plan)];
+        assert_eq!(
+            render(&plans, DiffFormat::Unified, false),
+            "--- nextdns settings\n+ blocklists  oisd\n- blocked_tlds  zip\n"
+        );
+        let table = render(&plans, DiffFormat::Table, false);
+        assert_eq!(
+            table.lines().nth(1),
+            Some("enable   nextdns   blocklists    oisd")
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&plans, DiffFormat::Json, false)).unwrap();
+        assert_eq!(json[1]["action"], "disable");
+        assert_eq!(json[1]["setting"], "blocked_tlds");
+    }
+
+    #[test]
+    fn test_blocked_changes_are_marked() {
+        let plan = Plan {
+            to_add: vec![record(
+                DNSRecordType::A,
+                "nas.example.com",
+                "10.0.0.2",
+                None,
+            )],
+            blocked: vec![Change::Remove(record(
+                DNSRecordType::A,
+                "example.com",
+                "10.0.0.1",
+                None,
+            ))],
+            ..Plan::default()
+        };
+        let plans = vec![("lan".to_string(), plan)];
+        assert_eq!(
+            render(&plans, DiffFormat::Unified, false),
+            "--- lan A\n! example.com      10.0.0.1  (blocked remove)\n+ nas.example.com  10.0.0.2\n"
+        );
+        let table = render(&plans, DiffFormat::Table, false);
+        assert!(
+            table
+                .lines()
+                .nth(1)
+                .unwrap()
+                .starts_with("blocked remove  lan")
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&plans, DiffFormat::Json, false)).unwrap();
+        assert_eq!(json[0]["blocked"], true);
+        assert_eq!(json[1]["blocked"], false);
+    }
+
+    fn outcomes() -> BTreeMap<String, SyncOutcome> {
+        let mut outcome = SyncOutcome::default();
+        outcome.stats.examined = 12;
+        outcome.stats.created = 2;
+        outcome.stats.fail("provider");
+        outcome.stats.list_time = Duration::from_millis(250);
+        outcome.error = Some("1 change failed".to_string());
+        BTreeMap::from([("lan".to_string(), outcome)])
+    }
+
+    const RUN_ID: &str = "6f1c2a9e-3b4d-4e8f-9a0b-1c2d3e4f5a6b";
+
+    fn timings() -> RunTimings {
+        RunTimings {
+            prepare: Duration::from_millis(800),
+            total: Duration::from_millis(1500),
+        }
+    }
+
+    #[test]
+    fn test_run_report_text() {
+        let out = render_run(&outcomes(), timings(), Some(RUN_ID), ReportFormat::Text);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "PROVIDER  EXAMINED  CREATED  UPDATED  DELETED  SKIPPED  FAILED  LIST   APPLY"
+        );
+        assert_eq!(
+            lines[1],
+            "lan       12        2        0        0        0        1       0.25s  0.00s"
+        );
+        assert_eq!(lines[2], "lan failures: provider 1");
+        assert_eq!(lines[3], "Prepared in 0.80s, finished in 1.50s");
+        assert_eq!(lines[4], format!("Run {RUN_ID}"));
+    }
+
+    #[test]
+    fn test_run_report_json() {
+        let out = render_run(&outcomes(), timings(), Some(RUN_ID), ReportFormat::Json);
+        let report: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(report["run_id"], RUN_ID);
+        assert_eq!(report["prepare_ms"], 800);
+        assert_eq!(report["providers"]["lan"]["created"], 2);
+        assert_eq!(report["providers"]["lan"]["failures"]["provider"], 1);
+        assert_eq!(report["providers"]["lan"]["list_ms"], 250);
+        assert_eq!(report["providers"]["lan"]["error"], "1 change failed");
+    }
+
+    #[test]
+    fn test_empty_plan() {
+        let empty = vec![("lan".to_string(), Plan::default())];
+        assert_eq!(render(&empty, DiffFormat::Unified, false), "No changes\n");
+        assert_eq!(render(&empty, DiffFormat::Json, false), "[]\n");
+    }
+}
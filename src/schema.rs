@@ -0,0 +1,217 @@
+//! JSON Schema of the configuration file, and checking files against it.
+//!
+//! The schema is printed by `dns-update config schema` for editors and CI.
+//! Configuration files are checked against it before they are parsed: the
+//! parser stops at the first mistake, while the schema reports every one,
+//! each at its line and column.
+
+use jsonschema::ValidationError;
+use jsonschema::error::ValidationErrorKind;
+use serde_json::Value;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+use crate::config::Config;
+use crate::error::Error;
+
+/// The JSON Schema of the configuration file.
+pub fn config_schema() -> Value {
+    schemars::schema_for!(Config).to_value()
+}
+
+/// Check the configuration file `raw` against the schema, listing every
+/// mistake in it.
+pub fn validate(raw: &str) -> Result<(), Error> {
+    // Syntax errors are left to the parser, which points at them already
+    let (Ok(instance), Ok(document)) = (toml::from_str::<Value>(raw), DeTable::parse(raw)) else {
+        return Ok(());
+    };
+    let validator = jsonschema::validator_for(&config_schema())
+        .map_err(|e| Error::Other(format!("config schema: {e}")))?;
+    let (span, table) = (document.span(), document.into_inner());
+    let document = Spanned::new(span, DeValue::Table(table));
+
+    let errors: Vec<ValidationError> = validator.iter_errors(&instance).collect();
+    let mut problems = Vec::new();
+    for error in &errors {
+        closest(error, &mut problems);
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let mut problems: Vec<((usize, usize), String)> = problems
+        .iter()
+        .map(|error| describe(raw, &document, error))
+        .collect();
+    problems.sort();
+    let problems: Vec<String> = problems.into_iter().map(|(_, problem)| problem).collect();
+    Err(Error::InvalidInput(problems.join("\n")))
+}
+
+// A value matching none of the variants of an enum is reported by the
+// mistakes of the variant it comes closest to, rather than as a whole: the
+// variant whose tag it has, else the one it breaks the fewest rules of
+fn closest<'e>(error: &'e ValidationError<'e>, problems: &mut Vec<&'e ValidationError<'e>>) {
+    let context = match error.kind() {
+        ValidationErrorKind::AnyOf { context } | ValidationErrorKind::OneOfNotValid { context } => {
+            context
+        }
+        _ => {
+            problems.push(error);
+            return;
+        }
+    };
+    let is_tag = |e: &ValidationError| matches!(e.kind(), ValidationErrorKind::Constant { .. });
+    let best = context
+        .iter()
+        .filter(|errors| !errors.iter().any(is_tag))
+        .min_by_key(|errors| errors.len());
+    match best {
+        Some(errors) if !errors.is_empty() => {
+            for e in errors {
+                closest(e, problems);
+            }
+        }
+        _ => problems.push(error),
+    }
+}
+
+// `line L, column C: <where>: <mistake>`, with the line and column
+fn describe(
+    raw: &str,
+    document: &Spanned<DeValue>,
+    error: &ValidationError,
+) -> ((usize, usize), String) {
+    let mut path: Vec<String> = error
+        .instance_path()
+        .iter()
+        .map(|segment| segment.to_string())
+        .collect();
+    // Point at the first unexpected key rather than the table holding it
+    if let ValidationErrorKind::AdditionalProperties { unexpected } = error.kind()
+        && let Some(key) = unexpected.first()
+    {
+        path.push(key.clone());
+    }
+    let (line, column) = position(raw, document, &path);
+    let name = if path.is_empty() {
+        "top level".to_string()
+    } else {
+        path.join(".")
+    };
+    let problem = format!("line {line}, column {column}: {name}: {}", message(error));
+    ((line, column), problem)
+}
+
+fn message(error: &ValidationError) -> String {
+    match error.kind() {
+        ValidationErrorKind::AdditionalProperties { unexpected } => {
+            format!("unknown field `{}`", unexpected.join("`, `"))
+        }
+        ValidationErrorKind::Required { property } => {
+            format!("missing field `{}`", property.as_str().unwrap_or_default())
+        }
+        // The variants of an enum of names, each a constant
+        ValidationErrorKind::AnyOf { context } | ValidationErrorKind::OneOfNotValid { context } => {
+            let names: Option<Vec<String>> = context
+                .iter()
+                .map(|errors| match errors.as_slice() {
+                    [error] => match error.kind() {
+                        ValidationErrorKind::Constant { expected_value } => {
+                            Some(expected_value.to_string())
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect();
+            match names {
+                Some(names) => format!("expected one of {}", names.join(", ")),
+                None => error.to_string(),
+            }
+        }
+        _ => error.to_string(),
+    }
+}
+
+// Line and column, from 1, of the deepest value along `path` found in the
+// document
+fn position(raw: &str, document: &Spanned<DeValue>, path: &[String]) -> (usize, usize) {
+    let mut value = document;
+    for segment in path {
+        let next = match segment.parse::<usize>() {
+            Ok(index) if value.get_ref().is_array() => value.get_ref().get(index),
+            _ => value.get_ref().get(segment.as_str()),
+        };
+        match next {
+            Some(next) => value = next,
+            None => break,
+        }
+    }
+    let offset = value.span().start.min(raw.len());
+    let before = &raw[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |i| i + 1)..]
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_mistake_is_listed_at_its_line() {
+        let raw = r#"vault = "Apps"
+colour = true
+
+[providers.home]
+type = "namecheap"
+domain = "example.com"
+api_user = "me"
+client_ip = 5
+
+[providers.edge]
+type = "linode"
+domain = "example.com"
+ownership = "everything"
+"#;
+        let Err(Error::InvalidInput(problems)) = validate(raw) else {
+            panic!("mistakes were not reported");
+        };
+        assert_eq!(
+            problems.lines().collect::<Vec<_>>(),
+            [
+                "line 2, column 10: colour: unknown field `colour`",
+                "line 8, column 13: providers.home.client_ip: 5 is not of type \"string\"",
+                "line 13, column 13: providers.edge.ownership: expected one of \"txt\", \"zone\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_valid_files_pass() {
+        let raw = r#"
+scope = ["*.lab.example.com"]
+
+[providers.lan]
+type = "dnsmasq"
+path = "/etc/dnsmasq.d/lab.conf"
+reverse = ["10.0.0.0/24"]
+reload = { pid_file = "/run/dnsmasq.pid" }
+
+[providers.nextdns.settings]
+blocklists = ["oisd"]
+
+[providers.nextdns]
+type = "nextdns"
+
+[profile.staging]
+vault = "Staging"
+"#;
+        assert!(validate(raw).is_ok());
+        assert!(validate("not toml = ").is_ok(), "left to the parser");
+    }
+}
@@ -0,0 +1,120 @@
+//! Keeping passwords and tokens out of output.
+//!
+//! Credentials are held as [`Secret`]s, which print as `***` when
+//! Debug-formatted. Their values can still end up in messages written by
+//! others, like a reqwest error quoting a URL with an API key in its query
+//! or 1Password CLI output, so every secret is also remembered and
+//! [`redact`] blanks them out of text before it is printed.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+/// What secrets are replaced with.
+pub const REDACTED: &str = "***";
+
+/// Shorter values are not redacted, as blanking them out would mangle
+/// unrelated text.
+const MIN_REDACTED_LEN: usize = 8;
+
+static KNOWN: LazyLock<Mutex<BTreeSet<String>>> = LazyLock::new(Default::default);
+
+/// A password, token or other credential.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        let value = value.into();
+        if value.len() >= MIN_REDACTED_LEN {
+            KNOWN
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(value.clone());
+        }
+        Self(value)
+    }
+
+    /// The value itself, for handing to an API.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({REDACTED})")
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Serialized as is, for request bodies.
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// `text` with the value of every secret seen so far replaced by `***`.
+pub fn redact(text: &str) -> String {
+    let known = KNOWN.lock().unwrap_or_else(|e| e.into_inner());
+    // Longest first, so a secret containing another is blanked out whole
+    let mut secrets: Vec<&String> = known.iter().collect();
+    secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    secrets.into_iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret.as_str(), REDACTED)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_debug_hides_the_value() {
+        let secret = Secret::new("hunter2-but-longer");
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+        assert_eq!(secret.expose(), "hunter2-but-longer");
+    }
+
+    #[test]
+    fn test_redact_known_secrets() {
+        Secret::new("9f8e7d6c5b4a");
+        Secret::new("9f8e7d6c5b4a-extended");
+        Secret::new("short");
+        let message = "error sending request for url \
+            (https://api.example.com/xml.response?ApiKey=9f8e7d6c5b4a-extended&Cmd=list)";
+        assert_eq!(
+            redact(message),
+            "error sending request for url \
+            (https://api.example.com/xml.response?ApiKey=***&Cmd=list)"
+        );
+        assert_eq!(redact("short and 9f8e7d6c5b4a"), "short and ***");
+
+        let error = Error::ProviderError("token 9f8e7d6c5b4a rejected".to_string());
+        assert_eq!(error.to_string(), "Provider error: token *** rejected");
+        assert_eq!(
+            format!("{error:?}"),
+            r#"ProviderError("token *** rejected")"#
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Registering `dns-update daemon` with systemd or launchd.
+//!
+//! The service definition is generated from the options given to
+//! `service install`, so the installed daemon runs with the same
+//! configuration file, profile and daemon options.
+
+use clap::ValueEnum;
+use std::fs::{OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::Error;
+
+pub const SYSTEMD_UNIT: &str = "dns-update.service";
+pub const LAUNCHD_LABEL: &str = "io.github.nalabelle.dns-update";
+
+/// Seconds to wait before restarting a failed daemon.
+const RESTART_DELAY_SECS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ServiceManager {
+    Systemd,
+    Launchd,
+}
+
+impl ServiceManager {
+    /// The service manager of this operating system.
+    pub fn detect() -> Result<Self, Error> {
+        if cfg!(target_os = "macos") {
+            Ok(ServiceManager::Launchd)
+        } else if cfg!(target_os = "linux") {
+            Ok(ServiceManager::Systemd)
+        } else {
+            Err(Error::InvalidInput(
+                "No supported service manager on this system; pass --manager".to_string(),
+            ))
+        }
+    }
+}
+
+/// The command a service runs and the environment it runs with.
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+/// The daemon service of one service manager, system-wide or per user.
+pub struct Service {
+    pub manager: ServiceManager,
+    pub user: bool,
+}
+
+impl Service {
+    /// Where the service definition is installed.
+    pub fn path(&self) -> Result<PathBuf, Error> {
+        let home = || {
+            std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .ok_or_else(|| Error::Other("HOME is not set".to_string()))
+        };
+        Ok(match (self.manager, self.user) {
+            (ServiceManager::Systemd, false) => {
+                PathBuf::from("/etc/systemd/system").join(SYSTEMD_UNIT)
+            }
+            (ServiceManager::Systemd, true) => {
+                home()?.join(".config/systemd/user").join(SYSTEMD_UNIT)
+            }
+            (ServiceManager::Launchd, false) => {
+                PathBuf::from(format!("/Library/LaunchDaemons/{LAUNCHD_LABEL}.plist"))
+            }
+            (ServiceManager::Launchd, true) => {
+                home()?.join(format!("Library/LaunchAgents/{LAUNCHD_LABEL}.plist"))
+            }
+        })
+    }
+
+    /// The service definition running `spec`.
+    pub fn render(&self, spec: &ServiceSpec) -> String {
+        match self.manager {
+            ServiceManager::Systemd => systemd_unit(spec, self.user),
+            ServiceManager::Launchd => launchd_plist(spec),
+        }
+    }
+
+    /// Write the service definition, then enable and start the service.
+    pub fn install(&self, spec: &ServiceSpec) -> Result<PathBuf, Error> {
+        let path = self.path()?;
+        let io_error = |e: std::io::Error| Error::Other(format!("{}: {e}", path.display()));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        // Only readable by its owner, as it holds the `--env` secrets
+        write_private(&path, &self.render(spec)).map_err(io_error)?;
+        match self.manager {
+            ServiceManager::Systemd => {
+                self.systemctl(&["daemon-reload"])?;
+                self.systemctl(&["enable", "--now", SYSTEMD_UNIT])?;
+            }
+            ServiceManager::Launchd => {
+                run("launchctl", &["load", "-w", &path.display().to_string()])?;
+            }
+        }
+        Ok(path)
+    }
+
+    /// Stop and disable the service, then remove its definition.
+    pub fn uninstall(&self) -> Result<PathBuf, Error> {
+        let path = self.path()?;
+        if !path.exists() {
+            return Err(Error::NotFound(format!(
+                "{} is not installed",
+                path.display()
+            )));
+        }
+        match self.manager {
+            ServiceManager::Systemd => self.systemctl(&["disable", "--now", SYSTEMD_UNIT])?,
+            ServiceManager::Launchd => {
+                run("launchctl", &["unload", "-w", &path.display().to_string()])?
+            }
+        }
+        std::fs::remove_file(&path)
+            .map_err(|e| Error::Other(format!("{}: {e}", path.display())))?;
+        if self.manager == ServiceManager::Systemd {
+            self.systemctl(&["daemon-reload"])?;
+        }
+        Ok(path)
+    }
+
+    fn systemctl(&self, args: &[&str]) -> Result<(), Error> {
+        let mut all = Vec::new();
+        if self.user {
+            all.push("--user");
+        }
+        all.extend(args);
+        run("systemctl", &all)
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), Error> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| Error::Other(format!("Failed to run {program}: {e}")))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::Other(format!(
+            "{program} {} failed: {status}",
+            args.join(" ")
+        )))
+    }
+}
+
+// Quote a word for a systemd command line or Environment= setting
+fn systemd_quote(word: &str) -> String {
+    let escaped = word
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    if escaped.is_empty() || escaped.contains(char::is_whitespace) || escaped != word {
+        format!("\"{escaped}\"")
+    } else {
+        escaped
+    }
+}
+
+fn systemd_unit(spec: &ServiceSpec, user: bool) -> String {
+    let command: Vec<String> = std::iter::once(spec.program.display().to_string())
+        .chain(spec.args.iter().cloned())
+        .map(|word| systemd_quote(&word))
+        .collect();
+    let mut out = String::from(
+        "[Unit]\n\
+         Description=Synchronize DNS records across providers\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n",
+    );
+    out.push_str(&format!("ExecStart={}\n", command.join(" ")));
+    for (name, value) in &spec.env {
+        out.push_str(&format!(
+            "Environment={}\n",
+            systemd_quote(&format!("{name}={value}"))
+        ));
+    }
+    out.push_str(&format!(
+        "Restart=on-failure\nRestartSec={RESTART_DELAY_SECS}\n\n[Install]\nWantedBy={}\n",
+        if user {
+            "default.target"
+        } else {
+            "multi-user.target"
+        }
+    ));
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn launchd_plist(spec: &ServiceSpec) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n",
+    );
+    out.push_str(&format!(
+        "  <key>Label</key>\n  <string>{LAUNCHD_LABEL}</string>\n"
+    ));
+    out.push_str("  <key>ProgramArguments</key>\n  <array>\n");
+    for word in std::iter::once(spec.program.display().to_string()).chain(spec.args.iter().cloned())
+    {
+        out.push_str(&format!("    <string>{}</string>\n", xml_escape(&word)));
+    }
+    out.push_str("  </array>\n");
+    if !spec.env.is_empty() {
+        out.push_str("  <key>EnvironmentVariables</key>\n  <dict>\n");
+        for (name, value) in &spec.env {
+            out.push_str(&format!(
+                "    <key>{}</key>\n    <string>{}</string>\n",
+                xml_escape(name),
+                xml_escape(value)
+            ));
+        }
+        out.push_str("  </dict>\n");
+    }
+    // Restart after failures, but not after a clean exit
+    out.push_str(&format!(
+        "  <key>RunAtLoad</key>\n  <true/>\n\
+         \x20 <key>KeepAlive</key>\n  <dict>\n    <key>SuccessfulExit</key>\n    <false/>\n  </dict>\n\
+         \x20 <key>ThrottleInterval</key>\n  <integer>{RESTART_DELAY_SECS}</integer>\n\
+         </dict>\n</plist>\n"
+    ));
+    out
+}
+
+// Write `contents` to `path`, readable and writable by its owner only.
+// The mode is only applied on creation, so it is set again in case an
+// earlier install left the file readable.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ServiceSpec {
+        ServiceSpec {
+            program: PathBuf::from("/usr/local/bin/dns-update"),
+            args: vec![
+                "--config".to_string(),
+                "/etc/dns update/config.toml".to_string(),
+                "daemon".to_string(),
+            ],
+            env: vec![("PATH".to_string(), "/usr/bin:/opt/op bin".to_string())],
+        }
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let service = Service {
+            manager: ServiceManager::Systemd,
+            user: false,
+        };
+        let unit = service.render(&spec());
+        assert!(unit.contains(
+            "ExecStart=/usr/local/bin/dns-update --config \"/etc/dns update/config.toml\" daemon\n"
+        ));
+        assert!(unit.contains("Environment=\"PATH=/usr/bin:/opt/op bin\"\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(unit.ends_with("WantedBy=multi-user.target\n"));
+        assert_eq!(systemd_quote("100%$"), "\"100%%$$\"");
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let service = Service {
+            manager: ServiceManager::Launchd,
+            user: true,
+        };
+        let plist = service.render(&spec());
+        assert!(plist.contains("<string>/etc/dns update/config.toml</string>"));
+        assert!(plist.contains("<key>PATH</key>\n    <string>/usr/bin:/opt/op bin</string>"));
+        assert!(plist.contains("<key>SuccessfulExit</key>\n    <false/>"));
+        assert_eq!(xml_escape("a<b&c"), "a&lt;b&amp;c");
+    }
+
+    #[test]
+    fn test_definition_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dns-update.service");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, "[Unit]\n").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[Unit]\n");
+    }
+}
@@ -0,0 +1,241 @@
+//! Verification of the desired records against a detached signature.
+//!
+//! With a `[signature]` section configured, records are only synchronized
+//! when they carry a valid minisign or GPG signature, so unsigned or
+//! tampered state, like a rewrites file edited on a shared host or in a
+//! shared vault, is refused instead of published.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use blake2::{Blake2b512, Digest};
+use ed25519_dalek::{Signature, VerifyingKey};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::error::Error;
+use crate::secret::redact;
+
+/// The `[signature]` configuration section; one key must be set.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SignatureConfig {
+    /// minisign public key, e.g. `RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3`
+    pub minisign_key: Option<String>,
+    /// GPG keyring holding the keys allowed to sign, checked with `gpgv`
+    pub gpg_keyring: Option<PathBuf>,
+}
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::InvalidInput(format!("signature: {message}"))
+}
+
+impl SignatureConfig {
+    /// Where the detached signature of the file at `path` is looked for:
+    /// `<path>.minisig` for minisign, `<path>.sig` or `<path>.asc` for GPG.
+    pub fn signature_paths(&self, path: &Path) -> Vec<PathBuf> {
+        let with_suffix = |suffix: &str| {
+            let mut path = path.as_os_str().to_owned();
+            path.push(suffix);
+            PathBuf::from(path)
+        };
+        match self.minisign_key {
+            Some(_) => vec![with_suffix(".minisig")],
+            None => vec![with_suffix(".sig"), with_suffix(".asc")],
+        }
+    }
+
+    /// Check that `signature` is a valid signature of `data` by the
+    /// configured key.
+    pub async fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), Error> {
+        match (&self.minisign_key, &self.gpg_keyring) {
+            (Some(key), None) => verify_minisign(key, data, signature),
+            (None, Some(keyring)) => verify_gpg(keyring, data, signature).await,
+            _ => Err(invalid("set one of minisign_key and gpg_keyring")),
+        }
+    }
+
+    /// Check the file contents `data` read from `path` against the detached
+    /// signature next to it.
+    pub async fn verify_file(&self, path: &Path, data: &[u8]) -> Result<(), Error> {
+        let paths = self.signature_paths(path);
+        let signature = paths
+            .iter()
+            .find_map(|path| std::fs::read(path).ok())
+            .ok_or_else(|| {
+                let names: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                invalid(format!(
+                    "{} is not signed: no {}",
+                    path.display(),
+                    names.join(" or ")
+                ))
+            })?;
+        self.verify(data, &signature).await
+    }
+}
+
+// The base64 line of a key or signature file, past its comments
+fn base64_line<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Result<Vec<u8>, Error> {
+    let line = lines
+        .find(|line| !line.trim().is_empty() && !line.starts_with("untrusted comment:"))
+        .ok_or_else(|| invalid("truncated minisign data"))?;
+    STANDARD
+        .decode(line.trim())
+        .map_err(|e| invalid(format!("malformed minisign data: {e}")))
+}
+
+fn verify_minisign(key: &str, data: &[u8], signature: &[u8]) -> Result<(), Error> {
+    // Public key: algorithm (`Ed`), key ID and the Ed25519 key
+    let key = base64_line(&mut key.lines())?;
+    if key.len() != 42 || &key[..2] != b"Ed" {
+        return Err(invalid("unsupported minisign key"));
+    }
+    let key_id = &key[2..10];
+    let public_key: [u8; 32] = key[10..].try_into().expect("checked length");
+    let public_key =
+        VerifyingKey::from_bytes(&public_key).map_err(|_| invalid("malformed minisign key"))?;
+
+    // Signature: algorithm (`Ed`, or `ED` over a BLAKE2b hash), key ID and
+    // the signature, then a trusted comment signed along with it
+    let signature = String::from_utf8_lossy(signature);
+    let mut lines = signature.lines();
+    let signed = base64_line(&mut lines)?;
+    if signed.len() != 74 {
+        return Err(invalid("malformed minisign signature"));
+    }
+    let (algorithm, signed_key_id, signature_bytes) = (&signed[..2], &signed[2..10], &signed[10..]);
+    if signed_key_id != key_id {
+        return Err(invalid("signed with another key"));
+    }
+    let message = match algorithm {
+        b"Ed" => data.to_vec(),
+        b"ED" => Blake2b512::digest(data).to_vec(),
+        _ => return Err(invalid("unsupported minisign signature")),
+    };
+    let signature_bytes: [u8; 64] = signature_bytes.try_into().expect("checked length");
+    public_key
+        .verify_strict(&message, &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| invalid("does not match the signed data"))?;
+
+    let trusted_comment = lines
+        .next()
+        .and_then(|line| line.strip_prefix("trusted comment: "))
+        .ok_or_else(|| invalid("missing minisign trusted comment"))?;
+    let global: [u8; 64] = base64_line(&mut lines)?
+        .try_into()
+        .map_err(|_| invalid("malformed minisign signature"))?;
+    let mut comment_message = signature_bytes.to_vec();
+    comment_message.extend_from_slice(trusted_comment.as_bytes());
+    public_key
+        .verify_strict(&comment_message, &Signature::from_bytes(&global))
+        .map_err(|_| invalid("trusted comment does not match"))
+}
+
+// `gpgv` takes the signature from a file and the data from stdin
+async fn verify_gpg(keyring: &Path, data: &[u8], signature: &[u8]) -> Result<(), Error> {
+    let mut signature_file = tempfile::NamedTempFile::new().map_err(invalid)?;
+    signature_file.write_all(signature).map_err(invalid)?;
+    let mut child = Command::new("gpgv")
+        .arg("--keyring")
+        .arg(keyring)
+        .arg(signature_file.path())
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| invalid(format!("failed to run gpgv: {e}")))?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    stdin.write_all(data).await.map_err(invalid)?;
+    drop(stdin);
+    let output = child.wait_with_output().await.map_err(invalid)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "gpgv: {}",
+            redact(String::from_utf8_lossy(&output.stderr).trim())
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const REWRITES: &[u8] = b"10.0.0.5 nas.lab.example.com\n";
+    const KEY_ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn minisign_key(key: &SigningKey) -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend(KEY_ID);
+        bytes.extend(key.verifying_key().as_bytes());
+        format!("untrusted comment: test key\n{}\n", STANDARD.encode(bytes))
+    }
+
+    // A prehashed signature, as `minisign -S` makes by default
+    fn minisign(key: &SigningKey, data: &[u8], comment: &str) -> String {
+        let signature = key.sign(&Blake2b512::digest(data)).to_bytes();
+        let mut signed = b"ED".to_vec();
+        signed.extend(KEY_ID);
+        signed.extend(signature);
+        let mut comment_message = signature.to_vec();
+        comment_message.extend(comment.as_bytes());
+        let global = key.sign(&comment_message).to_bytes();
+        format!(
+            "untrusted comment: signature\n{}\ntrusted comment: {comment}\n{}\n",
+            STANDARD.encode(signed),
+            STANDARD.encode(global)
+        )
+    }
+
+    fn trusting(key: &SigningKey) -> SignatureConfig {
+        SignatureConfig {
+            minisign_key: Some(minisign_key(key)),
+            gpg_keyring: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_minisign_signature() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let signature = minisign(&key, REWRITES, "timestamp:1700000000");
+        let config = trusting(&key);
+        config.verify(REWRITES, signature.as_bytes()).await.unwrap();
+
+        let tampered = b"10.0.0.66 nas.lab.example.com\n";
+        assert!(config.verify(tampered, signature.as_bytes()).await.is_err());
+        let other = trusting(&SigningKey::from_bytes(&[8; 32]));
+        assert!(other.verify(REWRITES, signature.as_bytes()).await.is_err());
+        let comment_changed = signature.replace("timestamp:1700000000", "timestamp:1");
+        assert!(
+            config
+                .verify(REWRITES, comment_changed.as_bytes())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_file_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rewrites.txt");
+        std::fs::write(&path, REWRITES).unwrap();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let config = trusting(&key);
+        let err = config.verify_file(&path, REWRITES).await.unwrap_err();
+        assert!(err.to_string().contains("is not signed"), "{err}");
+
+        std::fs::write(
+            dir.path().join("rewrites.txt.minisig"),
+            minisign(&key, REWRITES, "x"),
+        )
+        .unwrap();
+        config.verify_file(&path, REWRITES).await.unwrap();
+    }
+}
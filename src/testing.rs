@@ -0,0 +1,302 @@
+//! Test doubles for exercising sync behavior without mocking HTTP APIs.
+
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::core::provider::{DNSProvider, ProviderCapabilities};
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::error::Error;
+
+/// A provider operation, as recorded in the call journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    List,
+    Add,
+    Update,
+    Delete,
+    Flush,
+}
+
+/// One call made to an [`InMemoryProvider`] and whether it succeeded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub operation: Operation,
+    pub record: Option<DNSRecord>,
+    pub ok: bool,
+}
+
+type Key = (DNSRecordType, String, String);
+
+fn key(record: &DNSRecord) -> Key {
+    (
+        record.record_type.clone(),
+        record.name.clone(),
+        record.value.clone(),
+    )
+}
+
+struct RateLimit {
+    calls: usize,
+    window: Duration,
+    made: VecDeque<Instant>,
+}
+
+/// A HashMap-backed provider with configurable latency, injected errors and
+/// rate limiting. Every call is journaled, so tests can assert the order in
+/// which changes were applied.
+pub struct InMemoryProvider {
+    capabilities: ProviderCapabilities,
+    records: Mutex<HashMap<Key, DNSRecord>>,
+    latency: Duration,
+    fail_next: Mutex<HashMap<Operation, usize>>,
+    fail_names: Vec<String>,
+    rate_limit: Option<Mutex<RateLimit>>,
+    calls: Mutex<Vec<Call>>,
+}
+
+impl Default for InMemoryProvider {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl InMemoryProvider {
+    pub fn new(records: Vec<DNSRecord>) -> Self {
+        Self {
+            capabilities: ProviderCapabilities::default(),
+            records: Mutex::new(records.into_iter().map(|r| (key(&r), r)).collect()),
+            latency: Duration::ZERO,
+            fail_next: Mutex::new(HashMap::new()),
+            fail_names: Vec::new(),
+            rate_limit: None,
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_capabilities(self, capabilities: ProviderCapabilities) -> Self {
+        Self {
+            capabilities,
+            ..self
+        }
+    }
+
+    /// Delay every call by `latency`; use with paused tokio time.
+    pub fn with_latency(self, latency: Duration) -> Self {
+        Self { latency, ..self }
+    }
+
+    /// Fail every change to records named `name`.
+    pub fn failing_for(mut self, name: &str) -> Self {
+        self.fail_names.push(name.to_string());
+        self
+    }
+
+    /// Allow at most `calls` calls per `window`; others fail as rate limited.
+    pub fn with_rate_limit(self, calls: usize, window: Duration) -> Self {
+        let rate_limit = RateLimit {
+            calls,
+            window,
+            made: VecDeque::new(),
+        };
+        Self {
+            rate_limit: Some(Mutex::new(rate_limit)),
+            ..self
+        }
+    }
+
+    /// Fail the next `times` calls of `operation`.
+    pub fn fail_next(&self, operation: Operation, times: usize) {
+        *self.fail_next.lock().unwrap().entry(operation).or_default() += times;
+    }
+
+    /// The stored records, sorted.
+    pub fn records(&self) -> Vec<DNSRecord> {
+        let mut records: Vec<_> = self.records.lock().unwrap().values().cloned().collect();
+        records.sort();
+        records
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The successful changes, in the order they were applied.
+    pub fn applied(&self) -> Vec<(Operation, DNSRecord)> {
+        self.calls()
+            .into_iter()
+            .filter(|c| c.ok)
+            .filter_map(|c| Some((c.operation, c.record?)))
+            .collect()
+    }
+
+    fn check(&self, operation: Operation, record: Option<&DNSRecord>) -> Result<(), Error> {
+        if let Some(rate_limit) = &self.rate_limit {
+            let mut rate_limit = rate_limit.lock().unwrap();
+            let now = Instant::now();
+            let window = rate_limit.window;
+            rate_limit
+                .made
+                .retain(|&at| now.duration_since(at) < window);
+            if rate_limit.made.len() >= rate_limit.calls {
+                return Err(Error::ProviderError("Rate limited".to_string()));
+            }
+            rate_limit.made.push_back(now);
+        }
+        if let Some(remaining) = self.fail_next.lock().unwrap().get_mut(&operation)
+            && *remaining > 0
+        {
+            *remaining -= 1;
+            return Err(Error::ProviderError(format!(
+                "injected {operation:?} failure"
+            )));
+        }
+        if let Some(record) = record
+            && self.fail_names.contains(&record.name)
+        {
+            return Err(Error::ProviderError(format!(
+                "injected failure for {}",
+                record.name
+            )));
+        }
+        Ok(())
+    }
+
+    async fn call<T>(
+        &self,
+        operation: Operation,
+        record: Option<DNSRecord>,
+        apply: impl FnOnce(&mut HashMap<Key, DNSRecord>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        let result = self
+            .check(operation, record.as_ref())
+            .and_then(|()| apply(&mut self.records.lock().unwrap()));
+        self.calls.lock().unwrap().push(Call {
+            operation,
+            record,
+            ok: result.is_ok(),
+        });
+        result
+    }
+}
+
+#[async_trait]
+impl DNSProvider for InMemoryProvider {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn list_records(&self) -> Result<Vec<DNSRecord>, Error> {
+        self.call(Operation::List, None, |records| {
+            let mut records: Vec<_> = records.values().cloned().collect();
+            records.sort();
+            Ok(records)
+        })
+        .await
+    }
+
+    async fn add_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.call(Operation::Add, Some(record.clone()), |records| {
+            records.insert(key(&record), record);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.call(Operation::Update, Some(record.clone()), |records| {
+            let existing = records
+                .keys()
+                .find(|(record_type, name, _)| {
+                    *record_type == record.record_type && *name == record.name
+                })
+                .cloned()
+                .ok_or_else(|| Error::NotFound(record.name.clone()))?;
+            records.remove(&existing);
+            records.insert(key(&record), record);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_record(&self, record: DNSRecord) -> Result<(), Error> {
+        self.call(Operation::Delete, Some(record.clone()), |records| {
+            records
+                .remove(&key(&record))
+                .map(|_| ())
+                .ok_or_else(|| Error::NotFound(record.name.clone()))
+        })
+        .await
+    }
+
+    async fn flush(&self) -> Result<(), Error> {
+        self.call(Operation::Flush, None, |_| Ok(())).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn a(name: &str, last: u8) -> DNSRecord {
+        DNSRecord::a(name, Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[tokio::test]
+    async fn test_records_and_journal() {
+        let provider = InMemoryProvider::new(vec![a("old.example.com", 1)]);
+        provider.add_record(a("new.example.com", 2)).await.unwrap();
+        provider
+            .delete_record(a("old.example.com", 1))
+            .await
+            .unwrap();
+        assert!(matches!(
+            provider.delete_record(a("old.example.com", 1)).await,
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(provider.records(), vec![a("new.example.com", 2)]);
+        assert_eq!(
+            provider.applied(),
+            vec![
+                (Operation::Add, a("new.example.com", 2)),
+                (Operation::Delete, a("old.example.com", 1)),
+            ]
+        );
+        assert_eq!(provider.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_injected_failures() {
+        let provider = InMemoryProvider::default().failing_for("bad.example.com");
+        provider.fail_next(Operation::List, 1);
+        assert!(provider.list_records().await.is_err());
+        assert!(provider.list_records().await.is_ok());
+        assert!(provider.add_record(a("bad.example.com", 1)).await.is_err());
+        assert!(provider.add_record(a("good.example.com", 1)).await.is_ok());
+        assert_eq!(provider.records(), vec![a("good.example.com", 1)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_and_latency() {
+        let provider = InMemoryProvider::default()
+            .with_latency(Duration::from_millis(100))
+            .with_rate_limit(2, Duration::from_secs(1));
+        let started = Instant::now();
+        assert!(provider.list_records().await.is_ok());
+        assert!(provider.list_records().await.is_ok());
+        assert!(provider.list_records().await.is_err());
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(provider.list_records().await.is_ok());
+    }
+}
@@ -0,0 +1,300 @@
+//! external-dns webhook provider, so Kubernetes external-dns can manage
+//! records through any configured provider.
+//!
+//! See <https://kubernetes-sigs.github.io/external-dns/latest/docs/tutorials/webhook-provider/>.
+
+pub mod types;
+
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+use crate::core::provider::DNSProvider;
+use crate::core::record::{DNSRecord, DNSRecordType};
+use crate::core::scope::Scope;
+use crate::error::Error;
+use types::{Changes, DomainFilter, Endpoint, MEDIA_TYPE, to_endpoints, to_records};
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8888";
+
+pub struct Webhook {
+    provider: Arc<dyn DNSProvider>,
+    scope: Scope,
+    // external-dns applies one batch at a time, but file providers buffer
+    // changes until flushed, so batches must not interleave
+    changes: Mutex<()>,
+}
+
+impl Webhook {
+    pub fn new(provider: Arc<dyn DNSProvider>, scope: Scope) -> Self {
+        Self {
+            provider,
+            scope,
+            changes: Mutex::new(()),
+        }
+    }
+
+    fn supports(&self, record_type: &DNSRecordType) -> bool {
+        let capabilities = self.provider.capabilities();
+        capabilities.record_types.contains(record_type)
+            || (capabilities.txt && *record_type == DNSRecordType::TXT)
+    }
+
+    async fn records(&self) -> Result<Vec<Endpoint>, Error> {
+        let records: Vec<DNSRecord> = self
+            .provider
+            .list_records()
+            .await?
+            .into_iter()
+            .filter(|r| self.supports(&r.record_type) && self.scope.contains(&r.name))
+            .collect();
+        Ok(to_endpoints(&records))
+    }
+
+    fn adjust(&self, endpoints: Vec<Endpoint>) -> Vec<Endpoint> {
+        let ttl = self.provider.capabilities().ttl;
+        endpoints
+            .into_iter()
+            .filter(|e| {
+                e.record_type
+                    .parse()
+                    .is_ok_and(|record_type| self.supports(&record_type))
+            })
+            .map(|e| Endpoint {
+                record_ttl: e.record_ttl.filter(|_| ttl),
+                ..e
+            })
+            .collect()
+    }
+
+    async fn apply(&self, changes: Changes) -> Result<(), Error> {
+        let records = |endpoints: &[Endpoint]| -> Result<Vec<DNSRecord>, Error> {
+            let mut records = Vec::new();
+            for endpoint in endpoints {
+                let converted = to_records(endpoint)
+                    .filter(|_| self.scope.contains(&endpoint.dns_name))
+                    .ok_or_else(|| {
+                        Error::InvalidInput(format!(
+                            "cannot manage {} record {}",
+                            endpoint.record_type, endpoint.dns_name
+                        ))
+                    })?;
+                records.extend(converted);
+            }
+            Ok(records)
+        };
+        let old = records(&changes.update_old)?;
+        let new = records(&changes.update_new)?;
+        let mut to_add = records(&changes.create)?;
+        to_add.extend(
+            new.iter()
+                .filter(|r| !old.iter().any(|o| o.same_data(r)))
+                .cloned(),
+        );
+        let mut to_remove = records(&changes.delete)?;
+        to_remove.extend(
+            old.iter()
+                .filter(|r| !new.iter().any(|n| n.same_data(r)))
+                .cloned(),
+        );
+
+        let _batch = self.changes.lock().await;
+        for record in to_add {
+            println!("Adding: {record}");
+            self.provider.add_record(record).await?;
+        }
+        for record in to_remove {
+            println!("Removing: {record}");
+            match self.provider.delete_record(record).await {
+                Err(e) if !matches!(e.kind(), Error::NotFound(_)) => return Err(e),
+                _ => {}
+            }
+        }
+        self.provider.flush().await
+    }
+}
+
+/// A JSON body in the media type external-dns negotiated.
+fn webhook_json<T: Serialize>(value: &T) -> Response {
+    match serde_json::to_vec(value) {
+        Ok(body) => ([(header::CONTENT_TYPE, MEDIA_TYPE)], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+fn error_response(e: Error) -> Response {
+    eprintln!("{e}");
+    let status = match e.kind() {
+        Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string()).into_response()
+}
+
+async fn negotiate(State(webhook): State<Arc<Webhook>>) -> Response {
+    webhook_json(&DomainFilter {
+        include: webhook.scope.domains(),
+        exclude: Vec::new(),
+    })
+}
+
+async fn get_records(State(webhook): State<Arc<Webhook>>) -> Response {
+    match webhook.records().await {
+        Ok(endpoints) => webhook_json(&endpoints),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn post_records(
+    State(webhook): State<Arc<Webhook>>,
+    Json(changes): Json<Changes>,
+) -> Response {
+    match webhook.apply(changes).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn adjust_endpoints(
+    State(webhook): State<Arc<Webhook>>,
+    Json(endpoints): Json<Vec<Endpoint>>,
+) -> Response {
+    webhook_json(&webhook.adjust(endpoints))
+}
+
+pub fn router(webhook: Arc<Webhook>) -> Router {
+    Router::new()
+        .route("/", get(negotiate))
+        .route("/records", get(get_records).post(post_records))
+        .route("/adjustendpoints", post(adjust_endpoints))
+        .route("/healthz", get(|| async { "ok" }))
+        .with_state(webhook)
+}
+
+/// Serve the webhook API until interrupted.
+pub async fn serve(listener: TcpListener, webhook: Arc<Webhook>) -> std::io::Result<()> {
+    axum::serve(listener, router(webhook))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::InMemoryProvider;
+    use serde_json::{Value, json};
+
+    async fn start(scope: Scope) -> (String, Arc<InMemoryProvider>) {
+        let provider = Arc::new(InMemoryProvider::default());
+        let webhook = Arc::new(Webhook::new(provider.clone(), scope));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(webhook)).await });
+        (url, provider)
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_returns_domain_filter() {
+        let scope = Scope::new(vec!["*.lab.example.com".to_string()]).unwrap();
+        let (url, _) = start(scope).await;
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], MEDIA_TYPE);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["include"], json!(["lab.example.com"]));
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes_and_list() {
+        let (url, provider) = start(Scope::default()).await;
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{url}/records"))
+            .header(header::CONTENT_TYPE, MEDIA_TYPE)
+            .body(
+                json!({
+                    "Create": [{"dnsName": "www.example.com", "targets": ["10.0.0.1"], "recordType": "A"}],
+                    "UpdateOld": null,
+                    "UpdateNew": null,
+                    "Delete": null
+                })
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = client
+            .post(format!("{url}/records"))
+            .header(header::CONTENT_TYPE, MEDIA_TYPE)
+            .body(
+                json!({
+                    "UpdateOld": [{"dnsName": "www.example.com", "targets": ["10.0.0.1"], "recordType": "A"}],
+                    "UpdateNew": [{"dnsName": "www.example.com", "targets": ["10.0.0.2"], "recordType": "A"}]
+                })
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(provider.records()[0].value, "10.0.0.2");
+
+        let endpoints: Value = reqwest::get(format!("{url}/records"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            endpoints,
+            json!([{"dnsName": "www.example.com", "targets": ["10.0.0.2"], "recordType": "A"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_changes_are_rejected() {
+        let scope = Scope::new(vec!["*.lab.example.com".to_string()]).unwrap();
+        let (url, provider) = start(scope).await;
+        let response = reqwest::Client::new()
+            .post(format!("{url}/records"))
+            .header(header::CONTENT_TYPE, MEDIA_TYPE)
+            .body(
+                json!({"Create": [{"dnsName": "www.example.com", "targets": ["10.0.0.1"], "recordType": "A"}]})
+                    .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(provider.records().is_empty());
+
+        let adjusted: Value = reqwest::Client::new()
+            .post(format!("{url}/adjustendpoints"))
+            .header(header::CONTENT_TYPE, MEDIA_TYPE)
+            .body(
+                json!([
+                    {"dnsName": "a.lab.example.com", "targets": ["10.0.0.1"], "recordType": "A", "recordTTL": 60},
+                    {"dnsName": "a.lab.example.com", "targets": ["\"v=spf1\""], "recordType": "TXT"}
+                ])
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        // The default capabilities keep TTLs but cannot hold TXT records
+        assert_eq!(adjusted.as_array().unwrap().len(), 1);
+        assert_eq!(adjusted[0]["recordTTL"], 60);
+    }
+}
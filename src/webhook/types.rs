@@ -0,0 +1,181 @@
+//! external-dns webhook provider API types.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+use crate::core::record::{DNSRecord, DNSRecordType};
+
+/// Media type external-dns negotiates for every request and response.
+pub const MEDIA_TYPE: &str = "application/external.dns.webhook+json;version=1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoint {
+    pub dns_name: String,
+    #[serde(default, deserialize_with = "nullable")]
+    pub targets: Vec<String>,
+    pub record_type: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub set_identifier: String,
+    #[serde(default, rename = "recordTTL", skip_serializing_if = "Option::is_none")]
+    pub record_ttl: Option<u32>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub labels: BTreeMap<String, String>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub provider_specific: Vec<ProviderSpecificProperty>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderSpecificProperty {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Changes {
+    #[serde(
+        default,
+        rename = "Create",
+        alias = "create",
+        deserialize_with = "nullable"
+    )]
+    pub create: Vec<Endpoint>,
+    #[serde(
+        default,
+        rename = "UpdateOld",
+        alias = "updateOld",
+        deserialize_with = "nullable"
+    )]
+    pub update_old: Vec<Endpoint>,
+    #[serde(
+        default,
+        rename = "UpdateNew",
+        alias = "updateNew",
+        deserialize_with = "nullable"
+    )]
+    pub update_new: Vec<Endpoint>,
+    #[serde(
+        default,
+        rename = "Delete",
+        alias = "delete",
+        deserialize_with = "nullable"
+    )]
+    pub delete: Vec<Endpoint>,
+}
+
+/// Domains the provider is responsible for, returned on negotiation.
+#[derive(Debug, Default, Serialize)]
+pub struct DomainFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+// Go encodes empty slices and maps as `null`
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Group records sharing a name and type into one endpoint each.
+pub fn to_endpoints(records: &[DNSRecord]) -> Vec<Endpoint> {
+    let mut groups: BTreeMap<(String, &'static str), Endpoint> = BTreeMap::new();
+    for record in records {
+        let record_type = record.record_type.as_str();
+        let endpoint = groups
+            .entry((record.name.clone(), record_type))
+            .or_insert_with(|| Endpoint {
+                dns_name: record.name.clone(),
+                targets: Vec::new(),
+                record_type: record_type.to_string(),
+                set_identifier: String::new(),
+                record_ttl: record.ttl,
+                labels: BTreeMap::new(),
+                provider_specific: Vec::new(),
+            });
+        // external-dns keeps TXT targets quoted, e.g. its registry records
+        let target = match record.record_type {
+            DNSRecordType::TXT => format!("\"{}\"", record.value),
+            _ => record.value.clone(),
+        };
+        endpoint.targets.push(target);
+    }
+    groups.into_values().collect()
+}
+
+/// The records an endpoint stands for, or `None` for unknown record types.
+pub fn to_records(endpoint: &Endpoint) -> Option<Vec<DNSRecord>> {
+    let record_type: DNSRecordType = endpoint.record_type.parse().ok()?;
+    Some(
+        endpoint
+            .targets
+            .iter()
+            .map(|target| DNSRecord {
+                record_type: record_type.clone(),
+                name: endpoint.dns_name.trim_end_matches('.').to_string(),
+                value: match record_type {
+                    DNSRecordType::TXT => target.trim_matches('"').to_string(),
+                    _ => target.trim_end_matches('.').to_string(),
+                },
+                ttl: endpoint.record_ttl.filter(|&ttl| ttl > 0),
+                comment: None,
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_round_trip() {
+        let raw = r#"[
+            {"dnsName": "www.example.com", "targets": ["10.0.0.1", "10.0.0.2"], "recordType": "A", "recordTTL": 300},
+            {"dnsName": "a-www.example.com", "targets": ["\"heritage=external-dns\""], "recordType": "TXT"}
+        ]"#;
+        let endpoints: Vec<Endpoint> = serde_json::from_str(raw).unwrap();
+        let records: Vec<DNSRecord> = endpoints
+            .iter()
+            .flat_map(|e| to_records(e).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].value, "heritage=external-dns");
+        assert_eq!(records[0].ttl, Some(300));
+
+        let mut expected = endpoints.clone();
+        expected.sort_by(|a, b| a.dns_name.cmp(&b.dns_name));
+        assert_eq!(to_endpoints(&records), expected);
+    }
+
+    #[test]
+    fn test_changes_accept_either_case() {
+        let changes: Changes =
+            serde_json::from_str(r#"{"Create": [], "UpdateNew": [], "Delete": null}"#).unwrap();
+        assert!(changes.create.is_empty() && changes.delete.is_empty());
+        let changes: Changes = serde_json::from_str(
+            r#"{"create": [{"dnsName": "a.example.com", "targets": ["1.2.3.4"], "recordType": "A"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(changes.create.len(), 1);
+    }
+
+    #[test]
+    fn test_unknown_types_are_rejected() {
+        let endpoint: Endpoint = serde_json::from_str(
+            r#"{"dnsName": "example.com", "targets": ["10 mx.example.com"], "recordType": "MX"}"#,
+        )
+        .unwrap();
+        assert!(to_records(&endpoint).is_none());
+    }
+}